@@ -0,0 +1,169 @@
+//! Runtime selection between the compiled backends.
+
+use http_kit::{Endpoint, Request, Response};
+
+#[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+use super::AppleBackend;
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+use super::CurlBackend;
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+use super::HyperBackend;
+#[cfg(target_arch = "wasm32")]
+use super::WebBackend;
+use super::{Capabilities, ClientBackend, DefaultBackend};
+use crate::Client;
+
+/// Environment variable read by [`AnyBackend::from_env`].
+pub const BACKEND_ENV: &str = "ZENWAVE_BACKEND";
+
+/// Any of the backends compiled into this build, chosen at runtime.
+///
+/// Every backend already reports failures as [`crate::Error`], so code written
+/// against `AnyBackend` behaves the same whichever variant is selected.
+///
+/// ```rust,no_run
+/// # fn example() -> Result<(), zenwave::Error> {
+/// use zenwave::{Client, backend::AnyBackend};
+///
+/// // `ZENWAVE_BACKEND=curl` switches transports without recompiling.
+/// let client = AnyBackend::from_env()?.follow_redirect();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AnyBackend {
+    /// [`HyperBackend`], boxed as it is much larger than the other backends.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+    Hyper(Box<HyperBackend>),
+    /// [`CurlBackend`].
+    #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+    Curl(CurlBackend),
+    /// [`AppleBackend`].
+    #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+    Apple(AppleBackend),
+    /// [`WebBackend`].
+    #[cfg(target_arch = "wasm32")]
+    Web(WebBackend),
+}
+
+impl AnyBackend {
+    /// Select a [`HyperBackend`].
+    #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+    #[must_use]
+    pub fn hyper() -> Self {
+        Self::Hyper(Box::default())
+    }
+
+    /// Select a [`CurlBackend`].
+    #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+    #[must_use]
+    pub fn curl() -> Self {
+        Self::Curl(CurlBackend::new())
+    }
+
+    /// Select an [`AppleBackend`].
+    #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+    #[must_use]
+    pub fn apple() -> Self {
+        Self::Apple(AppleBackend::new())
+    }
+
+    /// Select the backend named by the `ZENWAVE_BACKEND` environment variable.
+    ///
+    /// Accepted names are `hyper`, `curl` and `apple` (case-insensitive), for
+    /// the backends compiled into this build. When the variable is unset or
+    /// empty, the [`DefaultBackend`] is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when the variable names a
+    /// backend that is unknown or not compiled in.
+    pub fn from_env() -> Result<Self, crate::Error> {
+        match std::env::var(BACKEND_ENV) {
+            Ok(name) if !name.trim().is_empty() => Self::from_name(name.trim()),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, crate::Error> {
+        match name.to_ascii_lowercase().as_str() {
+            #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+            "hyper" => Ok(Self::hyper()),
+            #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+            "curl" => Ok(Self::curl()),
+            #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+            "apple" => Ok(Self::apple()),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "{BACKEND_ENV}=`{name}` does not name a backend compiled into this build"
+            ))),
+        }
+    }
+}
+
+impl Default for AnyBackend {
+    fn default() -> Self {
+        DefaultBackend::default().into()
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+impl From<HyperBackend> for AnyBackend {
+    fn from(backend: HyperBackend) -> Self {
+        Self::Hyper(Box::new(backend))
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+impl From<CurlBackend> for AnyBackend {
+    fn from(backend: CurlBackend) -> Self {
+        Self::Curl(backend)
+    }
+}
+
+#[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+impl From<AppleBackend> for AnyBackend {
+    fn from(backend: AppleBackend) -> Self {
+        Self::Apple(backend)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl From<WebBackend> for AnyBackend {
+    fn from(backend: WebBackend) -> Self {
+        Self::Web(backend)
+    }
+}
+
+impl Endpoint for AnyBackend {
+    type Error = crate::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        match self {
+            #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+            Self::Hyper(backend) => backend.respond(request).await,
+            #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+            Self::Curl(backend) => backend.respond(request).await,
+            #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+            Self::Apple(backend) => backend.respond(request).await,
+            #[cfg(target_arch = "wasm32")]
+            Self::Web(backend) => backend.respond(request).await,
+        }
+    }
+}
+
+impl Client for AnyBackend {}
+
+impl ClientBackend for AnyBackend {
+    fn capabilities(&self) -> Capabilities {
+        match self {
+            #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+            Self::Hyper(backend) => backend.capabilities(),
+            #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+            Self::Curl(backend) => backend.capabilities(),
+            #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+            Self::Apple(backend) => backend.capabilities(),
+            #[cfg(target_arch = "wasm32")]
+            Self::Web(backend) => backend.capabilities(),
+        }
+    }
+}
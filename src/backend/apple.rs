@@ -11,6 +11,7 @@ use std::{
     sync::{Arc, Mutex, OnceLock},
 };
 
+use super::{Capabilities, ClientBackend};
 use crate::{Client, error::HttpErrorResponse};
 use anyhow::{Error, anyhow};
 use block::{Block, ConcreteBlock};
@@ -38,6 +39,8 @@ pub struct AppleBackend {
     _delegate: StrongPtr,
     _queue: StrongPtr,
     handle: SessionHandle,
+    error_for_status: bool,
+    error_body_limit: usize,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -153,9 +156,33 @@ impl AppleBackend {
                 _delegate: delegate,
                 _queue: queue,
                 handle: SessionHandle(session),
+                error_for_status: true,
+                error_body_limit: super::DEFAULT_ERROR_BODY_LIMIT,
             }
         }
     }
+
+    /// Choose whether 4xx/5xx responses are returned as [`crate::Error::Http`].
+    ///
+    /// Enabled by default. When disabled, every response is returned as `Ok`
+    /// so error bodies can be inspected like any other response.
+    #[must_use]
+    pub const fn with_error_for_status(mut self, enabled: bool) -> Self {
+        self.error_for_status = enabled;
+        self
+    }
+
+    /// Capture at most `limit` bytes of an error response body into
+    /// [`crate::Error::Http`]; longer bodies are cut and end with `…`.
+    ///
+    /// This keeps a huge error payload out of error messages and logs. The
+    /// response kept in the error still holds the whole body. Defaults to
+    /// 64 KiB.
+    #[must_use]
+    pub const fn with_error_body_limit(mut self, limit: usize) -> Self {
+        self.error_body_limit = limit;
+        self
+    }
 }
 
 impl Default for AppleBackend {
@@ -176,9 +203,14 @@ impl Endpoint for AppleBackend {
     type Error = crate::Error;
     async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
         let handle = self.handle;
-        send_with_url_session(handle, request)
-            .await
-            .map_err(Into::into)
+        send_with_url_session(
+            handle,
+            request,
+            self.error_for_status,
+            self.error_body_limit,
+        )
+        .await
+        .map_err(Into::into)
     }
 }
 
@@ -190,6 +222,18 @@ impl core::fmt::Debug for AppleBackend {
 
 impl Client for AppleBackend {}
 
+impl ClientBackend for AppleBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            proxy: false,
+            streaming_upload: false,
+            streaming_download: false,
+            http2: true,
+            tls_config: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct SessionResponse {
     status: StatusCode,
@@ -202,6 +246,8 @@ type CompletionSender = Arc<Mutex<Option<oneshot::Sender<Result<SessionResponse,
 async fn send_with_url_session(
     handle: SessionHandle,
     request: &mut Request,
+    error_for_status: bool,
+    error_body_limit: usize,
 ) -> Result<Response, AppleError> {
     let method = request.method().as_str().to_owned();
     let uri = request.uri().to_string();
@@ -251,13 +297,8 @@ async fn send_with_url_session(
     *http_response.status_mut() = status;
     *http_response.headers_mut() = headers;
 
-    if status.is_client_error() || status.is_server_error() {
-        let body = http_response
-            .body_mut()
-            .as_str()
-            .await
-            .ok()
-            .map(std::borrow::ToOwned::to_owned);
+    if super::is_error_status(status, error_for_status) {
+        let body = super::capture_error_body(http_response.body_mut(), error_body_limit).await;
         return Err(AppleError::Remote {
             status,
             body,
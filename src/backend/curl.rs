@@ -1,24 +1,46 @@
-use std::{mem::replace, str};
+use std::{
+    ffi::{CStr, c_char, c_long},
+    io,
+    mem::replace,
+    net::SocketAddr,
+    ptr, str,
+};
 
 use anyhow::{Context, anyhow};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use blocking::unblock;
 use curl::easy::{Easy2, Handler, List, ProxyType, ReadError, WriteError};
+use futures_channel::{mpsc, oneshot};
 use http::{
     HeaderMap, Method,
     header::{HeaderName, HeaderValue},
 };
-use http_kit::{Body, Endpoint, HttpError, Request, Response, StatusCode};
+use http_kit::{Body, Endpoint, HttpError, Request, Response, StatusCode, utils::Bytes};
 use thiserror::Error;
 
+use super::{Capabilities, ClientBackend, ConnectionInfo};
 use crate::proxy::Intercept;
-use crate::{Client, Proxy, error::HttpErrorResponse};
+use crate::{Client, Proxy, error::HttpErrorResponse, tls::TlsConfig};
 
 /// HTTP backend implemented with libcurl.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct CurlBackend {
     proxy: Option<Proxy>,
+    error_for_status: bool,
+    error_body_limit: usize,
+    tls: TlsConfig,
+}
+
+impl Default for CurlBackend {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            error_for_status: true,
+            error_body_limit: super::DEFAULT_ERROR_BODY_LIMIT,
+            tls: TlsConfig::new(),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -95,18 +117,70 @@ impl CurlBackend {
     /// Create a backend configured to use the supplied proxy matcher.
     #[must_use]
     pub const fn with_proxy(proxy: Proxy) -> Self {
-        Self { proxy: Some(proxy) }
+        Self {
+            proxy: Some(proxy),
+            error_for_status: true,
+            error_body_limit: super::DEFAULT_ERROR_BODY_LIMIT,
+            tls: TlsConfig::new(),
+        }
     }
 
     /// Replace the proxy matcher.
     #[must_use]
-    pub fn proxy(self, proxy: Proxy) -> Self {
-        Self::with_proxy(proxy)
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Choose whether 4xx/5xx responses are returned as [`crate::Error::Http`].
+    ///
+    /// Enabled by default. When disabled, every response is returned as `Ok`
+    /// so error bodies can be inspected like any other response.
+    #[must_use]
+    pub const fn with_error_for_status(mut self, enabled: bool) -> Self {
+        self.error_for_status = enabled;
+        self
+    }
+
+    /// Capture at most `limit` bytes of an error response body into
+    /// [`crate::Error::Http`]; longer bodies are cut and end with `…`.
+    ///
+    /// This keeps a huge error payload out of error messages and logs. The
+    /// response kept in the error still holds the whole body. Defaults to
+    /// 64 KiB.
+    #[must_use]
+    pub const fn with_error_body_limit(mut self, limit: usize) -> Self {
+        self.error_body_limit = limit;
+        self
+    }
+
+    /// Use `tls` for `https` requests.
+    ///
+    /// The settings map onto libcurl's TLS options. Configured roots replace
+    /// libcurl's CA bundle instead of extending it.
+    #[must_use]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
     }
 }
 
 impl Client for CurlBackend {}
 
+impl ClientBackend for CurlBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            proxy: true,
+            // libcurl runs on a blocking thread with the whole request body
+            // in memory; the response streams out of that thread.
+            streaming_upload: false,
+            streaming_download: true,
+            http2: false,
+            tls_config: true,
+        }
+    }
+}
+
 impl Endpoint for CurlBackend {
     type Error = crate::Error;
     async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
@@ -116,13 +190,25 @@ impl Endpoint for CurlBackend {
             .body(Body::empty())
             .expect("building dummy request failed");
         let request = replace(request, dummy_request);
-        execute(request, self.proxy.clone())
-            .await
-            .map_err(Into::into)
+        execute(
+            request,
+            self.proxy.clone(),
+            self.tls.clone(),
+            self.error_for_status,
+            self.error_body_limit,
+        )
+        .await
+        .map_err(Into::into)
     }
 }
 
-async fn execute(request: Request, proxy: Option<Proxy>) -> Result<Response, CurlError> {
+async fn execute(
+    request: Request,
+    proxy: Option<Proxy>,
+    tls: TlsConfig,
+    error_for_status: bool,
+    error_body_limit: usize,
+) -> Result<Response, CurlError> {
     let (parts, body) = request.into_parts();
     let mut headers = Vec::with_capacity(parts.headers.len());
     for (name, value) in &parts.headers {
@@ -148,77 +234,72 @@ async fn execute(request: Request, proxy: Option<Proxy>) -> Result<Response, Cur
         headers,
         body: body_bytes,
         proxy,
+        tls,
     };
 
-    let response = unblock(move || perform(prepared)).await?;
+    let (head, response) = oneshot::channel();
+    // The transfer outlives this call while the body streams, and stops once
+    // the body is dropped.
+    unblock(move || perform(prepared, head)).detach();
+    let mut response = response.await.unwrap_or_else(|_| {
+        Err(CurlError::bad_gateway(anyhow!(
+            "curl transfer ended without a response"
+        )))
+    })?;
+
+    if super::is_error_status(response.status(), error_for_status) {
+        let body = super::capture_error_body(response.body_mut(), error_body_limit).await;
+        return Err(CurlError::Remote {
+            status: response.status(),
+            body,
+            raw_response: Box::new(response),
+        });
+    }
 
     Ok(response)
 }
 
-fn perform(request: PreparedRequest) -> Result<Response, CurlError> {
-    let handler = CurlHandler::new(request.body);
-    let upload_len = handler.request_body_len();
+/// Run the transfer on the current (blocking) thread, handing the response
+/// to `head` as soon as its body starts.
+fn perform(request: PreparedRequest, head: oneshot::Sender<Result<Response, CurlError>>) {
+    let mut easy = Easy2::new(CurlHandler::new(request.body, head));
+    let handle = easy.raw();
+    easy.get_mut().handle = handle;
+    let result = configure(&mut easy, &request.method, &request.url, &request.headers)
+        .and_then(|()| {
+            if let Some(proxy) = &request.proxy {
+                apply_proxy(&mut easy, proxy)?;
+            }
+            request.tls.apply_to_curl(&mut easy)?;
+            easy.perform()
+        })
+        .map_err(map_curl_error);
+    easy.get_mut().finish(result);
+}
 
-    let mut easy = Easy2::new(handler);
-    easy.url(&request.url).map_err(map_curl_error)?;
-    easy.custom_request(&request.method)
-        .map_err(map_curl_error)?;
+fn configure(
+    easy: &mut Easy2<CurlHandler>,
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+) -> Result<(), curl::Error> {
+    easy.url(url)?;
+    easy.custom_request(method)?;
 
+    let upload_len = easy.get_ref().request_body_len();
     if upload_len > 0 {
-        easy.upload(true).map_err(map_curl_error)?;
-        easy.in_filesize(upload_len as u64)
-            .map_err(map_curl_error)?;
+        easy.upload(true)?;
+        easy.in_filesize(upload_len as u64)?;
     }
 
-    let header_list = if request.headers.is_empty() {
-        None
-    } else {
+    if !headers.is_empty() {
         let mut list = List::new();
-        for (name, value) in &request.headers {
-            list.append(&format!("{name}: {value}"))
-                .map_err(map_curl_error)?;
+        for (name, value) in headers {
+            list.append(&format!("{name}: {value}"))?;
         }
-        Some(easy.http_headers(list).map_err(map_curl_error)?)
-    };
-
-    if let Some(proxy) = &request.proxy {
-        apply_proxy(&mut easy, proxy).map_err(map_curl_error)?;
-    }
-
-    easy.perform().map_err(map_curl_error)?;
-
-    // Keep the header list alive until this point.
-    let _ = header_list;
-
-    let handler = easy.get_mut();
-    let response = handler.take_response().map_err(CurlError::bad_gateway)?;
-
-    let SessionResponse {
-        status,
-        headers,
-        body,
-    } = response;
-
-    let is_error = status.is_client_error() || status.is_server_error();
-    let error_body = if is_error {
-        String::from_utf8(body.clone()).ok()
-    } else {
-        None
-    };
-
-    let mut http_response = http::Response::new(Body::from(body));
-    *http_response.status_mut() = status;
-    *http_response.headers_mut() = headers;
-
-    if is_error {
-        return Err(CurlError::Remote {
-            status,
-            body: error_body,
-            raw_response: Box::new(http_response),
-        });
+        easy.http_headers(list)?;
     }
-
-    Ok(http_response)
+    Ok(())
 }
 
 fn map_curl_error(error: curl::Error) -> CurlError {
@@ -232,6 +313,7 @@ struct PreparedRequest {
     headers: Vec<(String, String)>,
     body: Vec<u8>,
     proxy: Option<ResolvedProxy>,
+    tls: TlsConfig,
 }
 #[derive(Debug)]
 struct ResolvedProxy {
@@ -329,24 +411,38 @@ fn decode_basic_auth(value: &HeaderValue) -> Option<(String, String)> {
     Some((user, pass))
 }
 
+/// Chunks buffered between the curl thread and the response body.
+const BODY_CHANNEL_CAPACITY: usize = 8;
+
+type Head = oneshot::Sender<Result<Response, CurlError>>;
+
 #[derive(Debug)]
 struct CurlHandler {
     request_body: Option<Vec<u8>>,
     offset: usize,
-    response_body: Vec<u8>,
     headers: HeaderMap,
     status: Option<StatusCode>,
+    /// Receives the response when its body starts, or when the transfer
+    /// ends without one.
+    head: Option<Head>,
+    /// Feeds the body of the response sent through `head`.
+    chunks: Option<mpsc::Sender<io::Result<Bytes>>>,
+    /// The transfer this handler belongs to, for reading connection details
+    /// from inside its callbacks.
+    handle: *mut curl_sys::CURL,
 }
 
 impl CurlHandler {
-    fn new(body: Vec<u8>) -> Self {
+    fn new(body: Vec<u8>, head: Head) -> Self {
         let request_body = if body.is_empty() { None } else { Some(body) };
         Self {
             request_body,
             offset: 0,
-            response_body: Vec::new(),
             headers: HeaderMap::new(),
             status: None,
+            head: Some(head),
+            chunks: None,
+            handle: ptr::null_mut(),
         }
     }
 
@@ -354,15 +450,62 @@ impl CurlHandler {
         self.request_body.as_ref().map_or(0, Vec::len)
     }
 
-    fn take_response(&mut self) -> anyhow::Result<SessionResponse> {
-        let status = self
-            .status
-            .ok_or_else(|| anyhow!("curl response missing HTTP status line"))?;
-        Ok(SessionResponse {
-            status,
-            headers: std::mem::take(&mut self.headers),
-            body: std::mem::take(&mut self.response_body),
-        })
+    /// Send the response, streaming its body when `streaming` is set.
+    /// Returns `false` when nobody waits for it anymore.
+    fn respond(&mut self, streaming: bool) -> bool {
+        let Some(head) = self.head.take() else {
+            return true;
+        };
+        let Some(status) = self.status else {
+            let _ = head.send(Err(CurlError::bad_gateway(anyhow!(
+                "curl response missing HTTP status line"
+            ))));
+            return false;
+        };
+        let body = if streaming {
+            let (sender, receiver) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+            self.chunks = Some(sender);
+            Body::from_stream(receiver)
+        } else {
+            Body::empty()
+        };
+        let mut response = http::Response::new(body);
+        *response.status_mut() = status;
+        *response.headers_mut() = std::mem::take(&mut self.headers);
+        response
+            .extensions_mut()
+            .insert(connection_info(self.handle));
+        head.send(Ok(response)).is_ok()
+    }
+
+    /// Hand `chunk` to the body, waiting while it is full. Returns `false`
+    /// once the body was dropped.
+    fn send_chunk(&mut self, chunk: io::Result<Bytes>) -> bool {
+        let Some(chunks) = &mut self.chunks else {
+            return false;
+        };
+        async_io::block_on(core::future::poll_fn(|cx| chunks.poll_ready(cx)))
+            .and_then(|()| chunks.start_send(chunk))
+            .is_ok()
+    }
+
+    /// Deliver the outcome of the transfer: an error before the response
+    /// fails the request, an error after it fails the body.
+    fn finish(&mut self, result: Result<(), CurlError>) {
+        match result {
+            Ok(()) => {
+                self.respond(false);
+            }
+            Err(error) => {
+                if let Some(head) = self.head.take() {
+                    let _ = head.send(Err(error));
+                } else {
+                    self.send_chunk(Err(io::Error::other(error)));
+                }
+            }
+        }
+        // Ends the body.
+        self.chunks = None;
     }
 
     fn parse_header_line(&mut self, line: &str) {
@@ -397,9 +540,49 @@ impl CurlHandler {
     }
 }
 
+/// The addresses of the connection `handle` runs on.
+///
+/// Called from the transfer's callbacks, where its `Easy2` getters cannot be
+/// reached; libcurl does not report the TLS version or ALPN protocol there.
+fn connection_info(handle: *mut curl_sys::CURL) -> ConnectionInfo {
+    let address = |ip_info, port_info| {
+        if handle.is_null() {
+            return None;
+        }
+        let mut ip: *const c_char = ptr::null();
+        let mut port: c_long = 0;
+        // SAFETY: `handle` is the transfer calling back, which libcurl allows
+        // to be queried with `curl_easy_getinfo`; the returned string lives
+        // until the handle is used again, after it is copied here.
+        let ip = unsafe {
+            if curl_sys::curl_easy_getinfo(handle, ip_info, &mut ip) != curl_sys::CURLE_OK
+                || curl_sys::curl_easy_getinfo(handle, port_info, &mut port) != curl_sys::CURLE_OK
+                || ip.is_null()
+            {
+                return None;
+            }
+            CStr::from_ptr(ip).to_str().ok()?.parse().ok()?
+        };
+        Some(SocketAddr::new(ip, u16::try_from(port).ok()?))
+    };
+    ConnectionInfo {
+        local_addr: address(curl_sys::CURLINFO_LOCAL_IP, curl_sys::CURLINFO_LOCAL_PORT),
+        peer_addr: address(
+            curl_sys::CURLINFO_PRIMARY_IP,
+            curl_sys::CURLINFO_PRIMARY_PORT,
+        ),
+        tls_version: None,
+        alpn: None,
+        reused: false,
+    }
+}
+
 impl Handler for CurlHandler {
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        self.response_body.extend_from_slice(data);
+        // Returning less than `data.len()` aborts the transfer.
+        if !self.respond(true) || !self.send_chunk(Ok(Bytes::copy_from_slice(data))) {
+            return Ok(0);
+        }
         Ok(data.len())
     }
 
@@ -425,10 +608,3 @@ impl Handler for CurlHandler {
         }
     }
 }
-
-#[derive(Debug)]
-struct SessionResponse {
-    status: StatusCode,
-    headers: HeaderMap,
-    body: Vec<u8>,
-}
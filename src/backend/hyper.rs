@@ -1,12 +1,11 @@
-use async_io::{Timer, block_on};
+use async_io::Timer;
 use async_net::TcpStream;
 use core::future::Future;
 use dns_lookup::{AddrFamily, AddrInfoHints, SockType, getaddrinfo};
-use executor_core::{AnyExecutor, Executor};
+use executor_core::Executor;
 use futures_channel::mpsc::{UnboundedReceiver, unbounded};
 use futures_io::{AsyncRead, AsyncWrite};
 use futures_util::FutureExt;
-use futures_util::TryStreamExt;
 use futures_util::future::{Either, pending, select};
 use futures_util::pin_mut;
 use futures_util::stream::{FuturesUnordered, StreamExt};
@@ -15,57 +14,741 @@ use http_body_util::BodyDataStream;
 use http_kit::{Endpoint, HttpError, Method, Request, Response};
 use hyper::http;
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{BTreeMap, HashSet, VecDeque},
     io,
     mem::replace,
     net::{IpAddr, SocketAddr},
+    ops::Deref,
     pin::Pin,
+    sync::{Arc, Mutex, OnceLock},
     task::{Context, Poll},
     thread,
     time::{Duration, Instant},
 };
 use tracing::{debug, warn};
 
-use crate::{Client, error::HttpErrorResponse};
+use super::{Capabilities, ClientBackend, ConnectionInfo, FreshConnection, RequestTrailers};
+use crate::{
+    Client,
+    cancel::{CancelToken, Cancelled},
+    error::{DnsError, HttpErrorResponse, InterruptedBody},
+    task::{Spawner, TaskSet},
+    tls::{Certificate, Identity, TlsConfig, TlsError, TlsVersion},
+};
+
+mod pool;
+#[cfg(feature = "proxy")]
+mod tunnel;
+pub use pool::PoolStats;
+use pool::{ConnectionPermit, ConnectionPool};
+#[cfg(feature = "proxy")]
+use tunnel::ProxyRoute;
+
+/// Without the `proxy` feature there is never a route, so requests
+/// always connect directly.
+#[cfg(not(feature = "proxy"))]
+type ProxyRoute = core::convert::Infallible;
 
 /// Hyper-based HTTP client backend powered by `async-io`/`async-net`.
-#[derive(Debug, Default)]
+///
+/// `https` connections offer HTTP/2 through ALPN when rustls is the TLS
+/// backend; requests to an origin that agreed to it share one multiplexed
+/// connection. Everything else uses HTTP/1.1, one connection per request,
+/// unless [`http2_prior_knowledge`](Self::http2_prior_knowledge) is set.
+/// [`max_connections_per_host`](Self::max_connections_per_host) caps the
+/// connections open to each origin.
+///
+/// Cloning is cheap: clones keep the configuration and share the open
+/// connections, the DNS caches and the tasks driving the connections, so
+/// they can send requests from several tasks at once.
+#[derive(Debug, Clone)]
 pub struct HyperBackend {
-    executor: Option<AnyExecutor>,
+    tasks: Shared<TaskSet>,
+    error_for_status: bool,
+    error_body_limit: usize,
+    connect_retries: u32,
+    dns_cache: DnsCache,
+    connect: ConnectOptions,
+    #[cfg(feature = "proxy")]
+    proxy: Option<crate::Proxy>,
+    tls: TlsConfig,
+    http1: Http1Options,
+    http2_prior_knowledge: bool,
+    /// Open HTTP/2 connections by origin, shared by the requests to it.
+    http2: Shared<Mutex<BTreeMap<String, (Http2Sender, ConnectionInfo)>>>,
+    pool: ConnectionPool,
+}
+
+type Http2Sender = hyper::client::conn::http2::SendRequest<RequestBody>;
+
+/// State shared by the clones of a [`HyperBackend`], created on first use
+/// so that its constructors can stay `const`.
+#[derive(Debug)]
+struct Shared<T>(OnceLock<Arc<T>>);
+
+impl<T: Default> Shared<T> {
+    const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+}
+
+impl<T> From<T> for Shared<T> {
+    fn from(value: T) -> Self {
+        Self(OnceLock::from(Arc::new(value)))
+    }
+}
+
+impl<T: Default> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.get_or_init(Arc::default)
+    }
+}
+
+impl<T: Default> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(OnceLock::from(Arc::clone(self.0.get_or_init(Arc::default))))
+    }
+}
+
+/// How requests are written on HTTP/1 connections.
+#[derive(Clone, Copy, Debug)]
+struct Http1Options {
+    title_case_headers: bool,
+    preserve_header_case: bool,
+    version_1_0: bool,
+}
+
+impl Default for HyperBackend {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HyperBackend {
     /// Create a new `HyperBackend`.
     #[must_use]
     pub const fn new() -> Self {
-        Self { executor: None }
+        Self {
+            tasks: Shared::new(),
+            error_for_status: true,
+            error_body_limit: super::DEFAULT_ERROR_BODY_LIMIT,
+            connect_retries: 0,
+            dns_cache: DnsCache::new(),
+            connect: ConnectOptions::new(),
+            #[cfg(feature = "proxy")]
+            proxy: None,
+            tls: TlsConfig::new(),
+            http1: Http1Options {
+                title_case_headers: false,
+                preserve_header_case: false,
+                version_1_0: false,
+            },
+            http2_prior_knowledge: false,
+            http2: Shared::new(),
+            pool: ConnectionPool::new(),
+        }
     }
 
     /// Create a `HyperBackend` that uses the provided executor for background tasks.
     #[must_use]
     pub fn with_executor(executor: impl Executor + 'static) -> Self {
         Self {
-            executor: Some(AnyExecutor::new(executor)),
+            tasks: TaskSet::with_executor(executor).into(),
+            ..Self::new()
         }
     }
 
-    fn spawn_background(&self, fut: impl Future<Output = ()> + Send + 'static) {
-        if let Some(executor) = &self.executor {
-            executor.spawn(fut).detach();
-        } else {
-            thread::spawn(move || {
-                block_on(fut);
-            });
+    /// Create a `HyperBackend` that sends requests through the proxy
+    /// `proxy` matches for them.
+    #[cfg(feature = "proxy")]
+    #[must_use]
+    pub fn with_proxy(proxy: crate::Proxy) -> Self {
+        Self::new().proxy(proxy)
+    }
+
+    /// Start a [`HyperBackendBuilder`], which checks the TLS settings
+    /// before the first connection.
+    pub const fn builder() -> HyperBackendBuilder {
+        HyperBackendBuilder {
+            backend: Self::new(),
+            tls: TlsConfig::new(),
+            error: None,
         }
     }
+
+    /// Choose whether 4xx/5xx responses are returned as [`crate::Error::Http`].
+    ///
+    /// Enabled by default. When disabled, every response is returned as `Ok`
+    /// so error bodies can be inspected like any other response.
+    #[must_use]
+    pub const fn with_error_for_status(mut self, enabled: bool) -> Self {
+        self.error_for_status = enabled;
+        self
+    }
+
+    /// Capture at most `limit` bytes of an error response body into
+    /// [`crate::Error::Http`]; longer bodies are cut and end with `…`.
+    ///
+    /// This keeps a server answering with a huge error payload from being
+    /// buffered in memory. The response kept in the error still holds the
+    /// whole body. Defaults to 64 KiB.
+    #[must_use]
+    pub const fn with_error_body_limit(mut self, limit: usize) -> Self {
+        self.error_body_limit = limit;
+        self
+    }
+
+    /// Retry the connection phase (TCP connect and TLS handshake) up to
+    /// `retries` extra times on I/O failures.
+    ///
+    /// No request bytes have been written when these attempts fail, so this is
+    /// safe for non-idempotent requests such as `POST`, unlike the
+    /// [`Retry`](crate::retry::Retry) middleware which resends the whole
+    /// request. Disabled (`0`) by default. Name resolution failures are not
+    /// retried; see [`HyperBackend::negative_ttl`].
+    #[must_use]
+    pub const fn connect_retries(mut self, retries: u32) -> Self {
+        self.connect_retries = retries;
+        self
+    }
+
+    /// Remember failed host name lookups for `ttl`.
+    ///
+    /// While a failure is remembered, requests to that host fail immediately
+    /// with the same [`DnsError`], marked as [cached](DnsError::is_cached),
+    /// instead of querying the resolver again. This keeps tight retry loops
+    /// from hammering the resolver, at the cost of noticing a DNS fix up to
+    /// `ttl` late. Disabled ([`Duration::ZERO`]) by default.
+    #[must_use]
+    pub const fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.dns_cache.negative_ttl = ttl;
+        self
+    }
+
+    /// Connect to `addr` for requests to `host`, instead of resolving it.
+    ///
+    /// Calling it again for the same host adds another address. The URL
+    /// still decides the `Host` header and the name TLS verifies, so this
+    /// can point a hostname at a staging load balancer or a local server. A
+    /// port of zero is replaced by the port of the URL.
+    #[must_use]
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        let host = host.into().to_ascii_lowercase();
+        self.dns_cache.overrides.entry(host).or_default().push(addr);
+        self
+    }
+
+    /// Resolve host names with `resolver` instead of the operating system.
+    #[must_use]
+    pub fn with_resolver(mut self, resolver: impl Resolver) -> Self {
+        self.dns_cache.custom = Some(CustomResolver(std::sync::Arc::new(resolver)));
+        self
+    }
+
+    /// Remember the addresses of a host for `ttl` after resolving it.
+    ///
+    /// Disabled ([`Duration::ZERO`]) by default: the operating system's
+    /// resolver usually caches already, honoring the TTLs of the DNS
+    /// records, which this cache cannot see.
+    #[must_use]
+    pub const fn dns_ttl(mut self, ttl: Duration) -> Self {
+        self.dns_cache.ttl = ttl;
+        self
+    }
+
+    /// Wait `delay` for a connection attempt before racing the next address
+    /// of the host against it.
+    ///
+    /// Connections follow Happy Eyeballs (RFC 8305): addresses alternate
+    /// between IPv6 and IPv4, and a new attempt starts whenever the previous
+    /// one has not connected within `delay`, so an unreachable address
+    /// family costs one delay rather than a connect timeout. Defaults to
+    /// 250 ms and is kept between 10 ms and 2 seconds.
+    #[must_use]
+    pub const fn connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.connect.attempt_delay = bounded_connection_attempt_delay(delay);
+        self
+    }
+
+    /// Connect only over the address families `family` allows.
+    ///
+    /// Addresses of the other family are dropped after resolution, and a
+    /// URL naming such an address directly fails. Defaults to
+    /// [`AddressFamily::Auto`].
+    #[must_use]
+    pub const fn with_address_family(mut self, family: AddressFamily) -> Self {
+        self.connect.family = family;
+        self
+    }
+
+    /// Disable `TCP_NODELAY` so that small writes may be coalesced
+    /// (Nagle's algorithm). It is enabled by default, as request latency
+    /// usually matters more than packet count.
+    #[must_use]
+    pub const fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.connect.socket.nodelay = enabled;
+        self
+    }
+
+    /// Send TCP keepalive probes once a connection was idle for `time`, so
+    /// that long-lived connections survive idle timeouts of middleboxes and
+    /// dead peers are noticed. Off by default.
+    #[must_use]
+    pub const fn tcp_keepalive(mut self, time: Duration) -> Self {
+        self.connect.socket.keepalive = Some(time);
+        self
+    }
+
+    /// Wait `interval` between unanswered TCP keepalive probes; see
+    /// [`tcp_keepalive`](Self::tcp_keepalive). The system default applies
+    /// otherwise, and on platforms that cannot set it.
+    #[must_use]
+    pub const fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.connect.socket.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Connect from `addr`, for hosts with several addresses or networks.
+    ///
+    /// Only addresses of the same family as `addr` can be reached; set
+    /// [`with_address_family`](Self::with_address_family) to match.
+    #[must_use]
+    pub const fn local_address(mut self, addr: IpAddr) -> Self {
+        self.connect.socket.local_address = Some(addr);
+        self
+    }
+
+    /// Bind connections to the network interface `name` (`SO_BINDTODEVICE`),
+    /// such as `eth1` or a VPN device. This usually needs `CAP_NET_RAW`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[must_use]
+    pub fn interface(mut self, name: impl Into<String>) -> Self {
+        self.connect.socket.interface = Some(name.into());
+        self
+    }
+
+    /// Send requests through the proxy `proxy` matches for them.
+    ///
+    /// Plain `http` requests are forwarded to an `http://` proxy, while
+    /// `https` requests tunnel through it with `CONNECT`, so TLS is still
+    /// verified against the origin. Credentials in the proxy URL are sent as
+    /// `Proxy-Authorization`. `socks5://` and `socks5h://` proxies need the
+    /// `socks` feature; other schemes fail the request.
+    #[cfg(feature = "proxy")]
+    #[must_use]
+    pub fn proxy(mut self, proxy: crate::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Use `tls` for `https` connections: extra roots, a client
+    /// certificate, versions and pins.
+    #[must_use]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Speak HTTP/2 to `http` URLs without negotiating it first (h2c with
+    /// prior knowledge), for cleartext servers known to support it.
+    ///
+    /// `https` connections negotiate HTTP/2 through ALPN regardless, when
+    /// the rustls TLS backend is used.
+    #[must_use]
+    pub const fn http2_prior_knowledge(mut self) -> Self {
+        self.http2_prior_knowledge = true;
+        self
+    }
+
+    /// Write header names in title case (`Content-Type`) on HTTP/1
+    /// connections, for servers that do not treat them case-insensitively.
+    ///
+    /// Header names are sent in lowercase by default. HTTP/2 always
+    /// lowercases them.
+    #[must_use]
+    pub const fn with_http1_title_case_headers(mut self, enabled: bool) -> Self {
+        self.http1.title_case_headers = enabled;
+        self
+    }
+
+    /// Keep the case of the header names in HTTP/1 responses as the server
+    /// sent them, where hyper tracks it, instead of normalizing them.
+    #[must_use]
+    pub const fn with_http1_preserve_header_case(mut self, enabled: bool) -> Self {
+        self.http1.preserve_header_case = enabled;
+        self
+    }
+
+    /// Send requests as HTTP/1.0, for legacy servers that reject HTTP/1.1.
+    ///
+    /// HTTP/2 is neither offered through ALPN nor used with
+    /// [`http2_prior_knowledge`](Self::http2_prior_knowledge). HTTP/1.0 has
+    /// no chunked encoding, so request bodies need a known length.
+    #[must_use]
+    pub const fn with_http1_0(mut self, enabled: bool) -> Self {
+        self.http1.version_1_0 = enabled;
+        self
+    }
+
+    /// Open at most `limit` connections to each origin at a time; further
+    /// requests wait until one of them ends. A limit of zero is treated as
+    /// one.
+    ///
+    /// An HTTP/1 connection ends once its response body was read to the end
+    /// or dropped. An HTTP/2 connection shared by the requests to its origin
+    /// frees its slot as soon as it is open, so a
+    /// [`FreshConnection`](crate::backend::FreshConnection) or a
+    /// replacement connection never waits for it; an unshared one holds its
+    /// slot until it is closed.
+    #[must_use]
+    pub const fn max_connections_per_host(mut self, limit: usize) -> Self {
+        self.pool.limit = Some(limit);
+        self
+    }
+
+    /// Fail requests with [`Error::Timeout`](crate::Error::Timeout) that
+    /// waited `timeout` for a connection under
+    /// [`max_connections_per_host`](Self::max_connections_per_host).
+    #[must_use]
+    pub const fn pool_acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.pool.acquire_timeout = Some(timeout);
+        self
+    }
+
+    /// How many connections are in use and how many requests wait for one,
+    /// summed over every origin.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    fn http2_connections(
+        &self,
+    ) -> std::sync::MutexGuard<'_, BTreeMap<String, (Http2Sender, ConnectionInfo)>> {
+        self.http2
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// The open HTTP/2 connection to `origin`, if there is one.
+    fn http2_sender(&self, origin: &str) -> Option<(Http2Sender, ConnectionInfo)> {
+        let (sender, info) = self.http2_connections().get(origin)?.clone();
+        if sender.is_closed() {
+            self.http2_connections().remove(origin);
+            return None;
+        }
+        Some((
+            sender,
+            ConnectionInfo {
+                reused: true,
+                ..info
+            },
+        ))
+    }
+
+    /// Forget every cached DNS lookup, failed or not.
+    pub fn clear_dns_cache(&self) {
+        self.dns_cache.clear();
+    }
+
+    /// Stop the tasks driving connections and wait up to `deadline` for
+    /// them to end; see [`TaskSet::shutdown`].
+    ///
+    /// Response bodies still being read fail once their connection is
+    /// stopped, and later requests fail too. Returns whether every task
+    /// ended before the deadline.
+    pub async fn shutdown(&self, deadline: Duration) -> bool {
+        self.tasks.shutdown(deadline).await
+    }
+
+    /// Close the backend gracefully: wait up to `deadline` for the
+    /// connections still in use to finish, then stop the rest as
+    /// [`shutdown`](Self::shutdown) does; see [`TaskSet::drain`].
+    ///
+    /// A connection finishes once the body of its response was read to the
+    /// end or dropped; idle HTTP/2 connections are closed right away.
+    /// Returns whether every connection finished before the deadline.
+    pub async fn drain(self, deadline: Duration) -> bool {
+        self.http2_connections().clear();
+        self.tasks.drain(deadline).await
+    }
+}
+
+/// Builder for a [`HyperBackend`]; see [`HyperBackend::builder`].
+///
+/// The TLS options are applied to whichever TLS implementation is compiled
+/// in. Malformed PEM documents and settings that implementation rejects are
+/// reported by [`build`](Self::build) rather than by the first `https`
+/// request.
+///
+/// ```rust,no_run
+/// # fn example() -> Result<(), zenwave::tls::TlsError> {
+/// use zenwave::{backend::HyperBackend, tls::TlsVersion};
+///
+/// let backend = HyperBackend::builder()
+///     .add_root_certificate(&std::fs::read("ca.pem").unwrap_or_default())
+///     .min_tls_version(TlsVersion::Tls13)
+///     .connect_retries(2)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct HyperBackendBuilder {
+    backend: HyperBackend,
+    tls: TlsConfig,
+    /// The first option that could not be applied.
+    error: Option<TlsError>,
+}
+
+impl HyperBackendBuilder {
+    /// Start from `tls`, replacing the TLS options set so far.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Trust every certificate of the PEM document `pem` as a root, in
+    /// addition to the system roots.
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        match Certificate::from_pem_bundle(pem) {
+            Ok(roots) if roots.is_empty() => {
+                self.fail(TlsError::Pem("no CERTIFICATE block".into()));
+            }
+            Ok(roots) => {
+                return self.map_tls(|tls| {
+                    roots
+                        .into_iter()
+                        .fold(tls, TlsConfig::with_root_certificate)
+                });
+            }
+            Err(error) => self.fail(error),
+        }
+        self
+    }
+
+    /// Present a client certificate to servers that ask for one (mutual
+    /// TLS). `pem` holds the certificate chain, leaf first, and its PKCS#8
+    /// private key (`BEGIN PRIVATE KEY`).
+    pub fn identity(mut self, pem: &[u8]) -> Self {
+        match Identity::from_pem(pem, pem) {
+            Ok(identity) => self.map_tls(|tls| tls.with_identity(identity)),
+            Err(error) => {
+                self.fail(error);
+                self
+            }
+        }
+    }
+
+    /// Accept any server certificate; see
+    /// [`TlsConfig::danger_accept_invalid_certs`].
+    pub fn danger_accept_invalid_certs(self, enabled: bool) -> Self {
+        self.map_tls(|tls| tls.danger_accept_invalid_certs(enabled))
+    }
+
+    /// Refuse TLS versions older than `version`.
+    pub fn min_tls_version(self, version: TlsVersion) -> Self {
+        self.map_tls(|tls| tls.with_min_version(version))
+    }
+
+    /// Refuse TLS versions newer than `version`.
+    pub fn max_tls_version(self, version: TlsVersion) -> Self {
+        self.map_tls(|tls| tls.with_max_version(version))
+    }
+
+    /// Choose whether the host name is sent in the handshake; see
+    /// [`TlsConfig::with_sni`].
+    pub fn tls_sni(self, enabled: bool) -> Self {
+        self.map_tls(|tls| tls.with_sni(enabled))
+    }
+
+    /// Run background tasks on `executor`; see [`HyperBackend::with_executor`].
+    pub fn executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.backend.tasks = TaskSet::with_executor(executor).into();
+        self
+    }
+
+    /// See [`HyperBackend::with_error_for_status`].
+    pub const fn error_for_status(mut self, enabled: bool) -> Self {
+        self.backend.error_for_status = enabled;
+        self
+    }
+
+    /// See [`HyperBackend::connect_retries`].
+    pub const fn connect_retries(mut self, retries: u32) -> Self {
+        self.backend.connect_retries = retries;
+        self
+    }
+
+    /// See [`HyperBackend::negative_ttl`].
+    pub const fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.backend.dns_cache.negative_ttl = ttl;
+        self
+    }
+
+    /// See [`HyperBackend::http2_prior_knowledge`].
+    pub const fn http2_prior_knowledge(mut self) -> Self {
+        self.backend.http2_prior_knowledge = true;
+        self
+    }
+
+    /// See [`HyperBackend::resolve`].
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        self.backend = self.backend.resolve(host, addr);
+        self
+    }
+
+    /// See [`HyperBackend::with_resolver`].
+    pub fn resolver(mut self, resolver: impl Resolver) -> Self {
+        self.backend = self.backend.with_resolver(resolver);
+        self
+    }
+
+    /// See [`HyperBackend::dns_ttl`].
+    pub const fn dns_ttl(mut self, ttl: Duration) -> Self {
+        self.backend.dns_cache.ttl = ttl;
+        self
+    }
+
+    /// See [`HyperBackend::connection_attempt_delay`].
+    pub const fn connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.backend.connect.attempt_delay = bounded_connection_attempt_delay(delay);
+        self
+    }
+
+    /// See [`HyperBackend::with_address_family`].
+    pub const fn address_family(mut self, family: AddressFamily) -> Self {
+        self.backend.connect.family = family;
+        self
+    }
+
+    /// See [`HyperBackend::tcp_nodelay`].
+    pub const fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.backend.connect.socket.nodelay = enabled;
+        self
+    }
+
+    /// See [`HyperBackend::tcp_keepalive`].
+    pub const fn tcp_keepalive(mut self, time: Duration) -> Self {
+        self.backend.connect.socket.keepalive = Some(time);
+        self
+    }
+
+    /// See [`HyperBackend::tcp_keepalive_interval`].
+    pub const fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.backend.connect.socket.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// See [`HyperBackend::local_address`].
+    pub const fn local_address(mut self, addr: IpAddr) -> Self {
+        self.backend.connect.socket.local_address = Some(addr);
+        self
+    }
+
+    /// See [`HyperBackend::interface`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn interface(mut self, name: impl Into<String>) -> Self {
+        self.backend.connect.socket.interface = Some(name.into());
+        self
+    }
+
+    /// See [`HyperBackend::with_http1_title_case_headers`].
+    pub const fn http1_title_case_headers(mut self, enabled: bool) -> Self {
+        self.backend.http1.title_case_headers = enabled;
+        self
+    }
+
+    /// See [`HyperBackend::with_http1_preserve_header_case`].
+    pub const fn http1_preserve_header_case(mut self, enabled: bool) -> Self {
+        self.backend.http1.preserve_header_case = enabled;
+        self
+    }
+
+    /// See [`HyperBackend::with_http1_0`].
+    pub const fn http1_0(mut self, enabled: bool) -> Self {
+        self.backend.http1.version_1_0 = enabled;
+        self
+    }
+
+    /// See [`HyperBackend::max_connections_per_host`].
+    pub const fn max_connections_per_host(mut self, limit: usize) -> Self {
+        self.backend.pool.limit = Some(limit);
+        self
+    }
+
+    /// See [`HyperBackend::pool_acquire_timeout`].
+    pub const fn pool_acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.backend.pool.acquire_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`HyperBackend::proxy`].
+    #[cfg(feature = "proxy")]
+    pub fn proxy(mut self, proxy: crate::Proxy) -> Self {
+        self.backend.proxy = Some(proxy);
+        self
+    }
+
+    fn map_tls(mut self, f: impl FnOnce(TlsConfig) -> TlsConfig) -> Self {
+        self.tls = f(self.tls);
+        self
+    }
+
+    fn fail(&mut self, error: TlsError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Check the TLS settings and create the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first PEM document that failed to parse, or the error of
+    /// the TLS implementation rejecting the settings, such as
+    /// [`TlsError::NoVersions`]. Without a TLS implementation, any TLS
+    /// option is an error.
+    pub fn build(self) -> Result<HyperBackend, TlsError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        check_tls(&self.tls)?;
+        Ok(self.backend.with_tls(self.tls))
+    }
+}
+
+/// Set up the TLS implementation [`connect`] uses from `tls`, to report
+/// what it rejects before connecting.
+fn check_tls(tls: &TlsConfig) -> Result<(), TlsError> {
+    #[cfg(all(
+        feature = "native-tls",
+        any(not(feature = "rustls"), target_vendor = "apple")
+    ))]
+    let checked = native_tls::TlsConnector::try_from(tls).map(drop);
+    #[cfg(all(
+        feature = "rustls",
+        any(not(feature = "native-tls"), not(target_vendor = "apple"))
+    ))]
+    let checked = rustls::ClientConfig::try_from(tls).map(drop);
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    let checked = if tls.is_customized() {
+        Err(TlsError::Backend("no TLS implementation is enabled".into()))
+    } else {
+        Ok(())
+    };
+    checked
 }
 
 #[derive(Debug)]
 pub enum HyperError {
     Connection(hyper::Error),
     Io(std::io::Error),
+    Dns(DnsError),
+    Tls(TlsError),
     TlsNotAvailable,
     InvalidUri(String),
+    Cancelled,
     Remote {
         status: StatusCode,
         body: Option<String>,
@@ -78,8 +761,11 @@ impl core::fmt::Display for HyperError {
         match self {
             Self::Connection(err) => write!(f, "connection error: {err}"),
             Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Dns(err) => err.fmt(f),
+            Self::Tls(err) => err.fmt(f),
             Self::TlsNotAvailable => write!(f, "TLS requested but no TLS feature enabled"),
             Self::InvalidUri(uri) => write!(f, "invalid uri: {uri}"),
+            Self::Cancelled => Cancelled.fmt(f),
             Self::Remote { status, body, .. } => {
                 if let Some(body) = body {
                     write!(f, "remote error: {status} - {body}")
@@ -93,6 +779,16 @@ impl core::fmt::Display for HyperError {
 
 impl core::error::Error for HyperError {}
 
+impl From<TlsError> for HyperError {
+    fn from(err: TlsError) -> Self {
+        match err {
+            // Handshake failures are I/O failures of the connect phase.
+            TlsError::Handshake(err) => Self::Io(err),
+            err => Self::Tls(err),
+        }
+    }
+}
+
 impl HttpError for HyperError {
     fn status(&self) -> StatusCode {
         match self {
@@ -125,10 +821,132 @@ impl From<HyperError> for crate::Error {
             },
             HyperError::Connection(e) => Self::Transport(Box::new(e)),
             HyperError::Io(e) => Self::Io(e),
+            HyperError::Dns(e) => Self::Transport(Box::new(e)),
+            HyperError::Tls(e) => e.into(),
             HyperError::TlsNotAvailable => {
                 Self::Tls(Box::new(std::io::Error::other("TLS not available")))
             }
             HyperError::InvalidUri(uri) => Self::InvalidUri(uri),
+            HyperError::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+/// A body read that failed after `received` bytes. Every error hyper
+/// reports at this point comes from the connection, not the payload.
+fn interrupted(received: u64, error: hyper::Error) -> http_kit::BodyError {
+    let kind = if error.is_incomplete_message() {
+        io::ErrorKind::UnexpectedEof
+    } else {
+        io::ErrorKind::ConnectionAborted
+    };
+    http_kit::BodyError::Io(io::Error::new(kind, InterruptedBody::new(received, error)))
+}
+
+impl HyperBackend {
+    /// Open a connection for `request` and send it, over HTTP/2 when the
+    /// server agreed to it; the HTTP/2 connection is kept for `origin`.
+    /// `permit` is held until the connection ends, unless the connection is
+    /// a shared HTTP/2 one.
+    async fn connect_and_send(
+        &self,
+        permit: Option<ConnectionPermit>,
+        origin: Option<String>,
+        mut request: http::Request<http_kit::Body>,
+        trailers: Option<http::HeaderMap>,
+    ) -> Result<http::Response<hyper::body::Incoming>, HyperError> {
+        #[cfg(feature = "proxy")]
+        let route = self
+            .proxy
+            .as_ref()
+            .and_then(|proxy| proxy.intercept(request.uri()))
+            .map(|intercept| ProxyRoute::new(&intercept))
+            .transpose()?;
+        #[cfg(feature = "proxy")]
+        let forward = route
+            .as_ref()
+            .is_some_and(|route| route.forward(&mut request));
+        #[cfg(not(feature = "proxy"))]
+        let (route, forward) = (None::<ProxyRoute>, false);
+
+        let stream = retry_connect(self.connect_retries, || {
+            connect(&request, self, route.as_ref())
+        })
+        .await?;
+        let info = stream.connection_info().map_err(HyperError::Io)?;
+        let http2 = stream.negotiated_http2()
+            || (self.http2_prior_knowledge
+                && !forward
+                && !self.http1.version_1_0
+                && matches!(stream, MaybeTlsStream::Plain(_)));
+        if http2 {
+            let (sender, connection) =
+                hyper::client::conn::http2::Builder::new(self.tasks.lingering_spawner())
+                    .handshake(stream)
+                    .await
+                    .map_err(HyperError::Connection)?;
+            let mut permit = permit;
+            if origin.is_some()
+                && let Some(permit) = &mut permit
+            {
+                permit.release_slot();
+            }
+            self.tasks.lingering_spawner().spawn(async move {
+                let _permit = permit;
+                if let Err(err) = connection.await {
+                    warn!(error = %err, "hyper connection error");
+                }
+            });
+            if let Some(origin) = origin {
+                self.http2_connections()
+                    .insert(origin, (sender.clone(), info.clone()));
+            }
+            let mut response = send_http2(sender, request, trailers).await?;
+            response.extensions_mut().insert(info);
+            Ok(response)
+        } else {
+            // A proxy forwarding the request needs its absolute form.
+            if !forward {
+                let origin_form = request
+                    .uri()
+                    .path_and_query()
+                    .map_or("/", http::uri::PathAndQuery::as_str);
+                *request.uri_mut() = origin_form
+                    .parse()
+                    .map_err(|err| HyperError::InvalidUri(format!("{origin_form}: {err}")))?;
+            }
+            if self.http1.version_1_0 {
+                *request.version_mut() = http::Version::HTTP_10;
+            }
+            let (mut sender, connection) = hyper::client::conn::http1::Builder::new()
+                .title_case_headers(self.http1.title_case_headers)
+                .preserve_header_case(self.http1.preserve_header_case)
+                .handshake(stream)
+                .await
+                .map_err(HyperError::Connection)?;
+
+            // Drive the connection in the background while the caller consumes
+            // its body, even after the backend is dropped, and close it if the
+            // request is cancelled.
+            let cancel = request.extensions().get::<CancelToken>().cloned();
+            self.tasks.lingering_spawner().spawn(async move {
+                let _permit = permit;
+                let result = match cancel {
+                    Some(token) => token.run(connection).await.unwrap_or(Ok(())),
+                    None => connection.await,
+                };
+                if let Err(err) = result {
+                    warn!(error = %err, "hyper connection error");
+                }
+            });
+
+            let request = request.map(|body| RequestBody { body, trailers });
+            let mut response = sender
+                .send_request(request)
+                .await
+                .map_err(HyperError::Connection)?;
+            response.extensions_mut().insert(info);
+            Ok(response)
         }
     }
 }
@@ -142,6 +960,7 @@ impl Endpoint for HyperBackend {
             .body(http_kit::Body::empty())
             .unwrap();
         let mut request: http::Request<http_kit::Body> = replace(request, dummy_request);
+        let cancel = request.extensions().get::<CancelToken>().cloned();
 
         // Ensure Host header is present (required by hyper 1.0 / HTTP 1.1)
         if request.headers().get(http::header::HOST).is_none()
@@ -150,35 +969,66 @@ impl Endpoint for HyperBackend {
         {
             request.headers_mut().insert(http::header::HOST, value);
         }
-        let stream = connect(&request).await?;
-        let origin_form = request
-            .uri()
-            .path_and_query()
-            .map_or("/", http::uri::PathAndQuery::as_str);
-        *request.uri_mut() = origin_form
-            .parse()
-            .map_err(|err| HyperError::InvalidUri(format!("{origin_form}: {err}")))?;
-        let (mut sender, connection) = hyper::client::conn::http1::Builder::new()
-            .handshake(stream)
-            .await
-            .map_err(HyperError::Connection)?;
+        // Cloned rather than removed so retried requests send them again.
+        let trailers = request
+            .extensions()
+            .get::<RequestTrailers>()
+            .map(|trailers| trailers.headers().clone())
+            .filter(|trailers| !trailers.is_empty());
+        if let Some(trailers) = &trailers {
+            announce_trailers(request.headers_mut(), trailers);
+        } else {
+            announce_length(&mut request);
+        }
 
-        // Drive the connection in the background while the caller consumes its body.
-        self.spawn_background(async move {
-            if let Err(err) = connection.await {
-                warn!(error = %err, "hyper connection error");
-            }
+        let origin = request.uri().authority().map(|authority| {
+            format!(
+                "{}://{authority}",
+                request.uri().scheme_str().unwrap_or("http")
+            )
         });
+        // A fresh connection is neither taken from the shared HTTP/2
+        // connections nor added to them.
+        let fresh = request.extensions().get::<FreshConnection>().is_some();
+        let mut reused = origin
+            .as_deref()
+            .filter(|_| !fresh)
+            .and_then(|origin| self.http2_sender(origin));
+        if let Some((sender, _)) = &mut reused
+            && sender.ready().await.is_err()
+        {
+            reused = None;
+        }
+        let response = if let Some((sender, info)) = reused {
+            let mut response = send_http2(sender, request, trailers).await?;
+            response.extensions_mut().insert(info);
+            response
+        } else {
+            let permit = match &origin {
+                Some(origin) => Some(self.pool.acquire(origin).await?),
+                None => None,
+            };
+            let shared = origin.filter(|_| !fresh);
+            self.connect_and_send(permit, shared, request, trailers)
+                .await?
+        };
 
-        let response = sender
-            .send_request(request)
-            .await
-            .map_err(HyperError::Connection)?;
-
+        // HTTP/1 bodies end when their connection is closed on cancellation;
+        // an HTTP/2 body has to stop on its own.
+        let cancel = cancel.filter(|_| response.version() == http::Version::HTTP_2);
         let mut response = response.map(|body| {
-            let stream = BodyDataStream::new(body)
-                .map_err(|error| http_kit::BodyError::Other(Box::new(error)));
-            http_kit::Body::from_stream(stream)
+            let mut received = 0_u64;
+            let stream = BodyDataStream::new(body).map(move |chunk| match chunk {
+                Ok(data) => {
+                    received += data.len() as u64;
+                    Ok(data)
+                }
+                Err(error) => Err(interrupted(received, error)),
+            });
+            match cancel {
+                Some(token) => http_kit::Body::from_stream(CancellableBody::new(stream, token)),
+                None => http_kit::Body::from_stream(stream),
+            }
         });
 
         debug!(
@@ -187,15 +1037,9 @@ impl Endpoint for HyperBackend {
             "HyperBackend received response"
         );
 
-        let is_error = response.status().is_client_error() || response.status().is_server_error();
-
-        if is_error {
-            let error_msg: Option<String> = response
-                .body_mut()
-                .as_str()
-                .await
-                .ok()
-                .map(std::borrow::ToOwned::to_owned);
+        if super::is_error_status(response.status(), self.error_for_status) {
+            let error_msg =
+                super::capture_error_body(response.body_mut(), self.error_body_limit).await;
             return Err(HyperError::Remote {
                 status: response.status(),
                 body: error_msg,
@@ -210,16 +1054,226 @@ impl Endpoint for HyperBackend {
 
 impl Client for HyperBackend {}
 
+impl ClientBackend for HyperBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            proxy: cfg!(feature = "proxy"),
+            streaming_upload: true,
+            streaming_download: true,
+            http2: !self.http1.version_1_0 && (RUSTLS_IN_USE || self.http2_prior_knowledge),
+            tls_config: true,
+        }
+    }
+}
+
+/// Request body that appends trailer fields after the last data frame.
+struct RequestBody {
+    body: http_kit::Body,
+    trailers: Option<http::HeaderMap>,
+}
+
+impl hyper::body::Body for RequestBody {
+    type Data = http_kit::utils::Bytes;
+    type Error = <http_kit::Body as hyper::body::Body>::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.body).poll_frame(cx) {
+            Poll::Ready(None) => Poll::Ready(
+                this.trailers
+                    .take()
+                    .map(|trailers| Ok(hyper::body::Frame::trailers(trailers))),
+            ),
+            other => other,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers.is_none() && self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        // An unknown length makes hyper use chunked encoding, the only
+        // HTTP/1.1 framing that can carry trailers.
+        if self.trailers.is_some() {
+            hyper::body::SizeHint::default()
+        } else {
+            self.body.size_hint()
+        }
+    }
+}
+
+/// Set `Content-Length` for a body held in memory, which hyper would
+/// otherwise send chunked; some servers reject chunked requests.
+fn announce_length(request: &mut http::Request<http_kit::Body>) {
+    let headers = request.headers();
+    if headers.contains_key(http::header::CONTENT_LENGTH)
+        || headers.contains_key(http::header::TRANSFER_ENCODING)
+    {
+        return;
+    }
+    let Some(length) = request.body().len() else {
+        return;
+    };
+    // Requests without a body only announce it where one is expected.
+    if length == 0 && ![Method::POST, Method::PUT, Method::PATCH].contains(request.method()) {
+        return;
+    }
+    request
+        .headers_mut()
+        .insert(http::header::CONTENT_LENGTH, length.into());
+}
+
+/// Prepare `headers` for a chunked body followed by `trailers`.
+fn announce_trailers(headers: &mut http::HeaderMap, trailers: &http::HeaderMap) {
+    let names = trailers
+        .keys()
+        .map(http::HeaderName::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let names = http::HeaderValue::from_str(&names).expect("header names are valid header values");
+    headers.insert(http::header::TRAILER, names);
+    headers.remove(http::header::CONTENT_LENGTH);
+}
+
+/// Send `request` over an HTTP/2 connection, which takes the authority
+/// from the URI instead of the `Host` header.
+///
+/// The connection is shared, so a cancelled request drops its response
+/// future instead, which makes hyper reset the stream.
+async fn send_http2(
+    mut sender: hyper::client::conn::http2::SendRequest<RequestBody>,
+    mut request: http::Request<http_kit::Body>,
+    trailers: Option<http::HeaderMap>,
+) -> Result<http::Response<hyper::body::Incoming>, HyperError> {
+    request.headers_mut().remove(http::header::HOST);
+    let cancel = request.extensions().get::<CancelToken>().cloned();
+    let request = request.map(|body| RequestBody { body, trailers });
+    let response = sender.send_request(request);
+    match cancel {
+        Some(token) => token
+            .run(response)
+            .await
+            .map_err(|Cancelled| HyperError::Cancelled)?,
+        None => response.await,
+    }
+    .map_err(HyperError::Connection)
+}
+
+/// An HTTP/2 response body that fails once its request is cancelled.
+/// Dropping the body then resets the stream, leaving the connection to the
+/// other requests sharing it.
+struct CancellableBody<S> {
+    body: Option<S>,
+    cancelled: Pin<Box<dyn Future<Output = ()> + Send + Sync>>,
+}
+
+impl<S> CancellableBody<S> {
+    fn new(body: S, token: CancelToken) -> Self {
+        Self {
+            body: Some(body),
+            cancelled: Box::pin(async move { token.cancelled().await }),
+        }
+    }
+}
+
+impl<S> futures_util::Stream for CancellableBody<S>
+where
+    S: futures_util::Stream<Item = Result<http_kit::utils::Bytes, http_kit::BodyError>> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(body) = &mut this.body else {
+            return Poll::Ready(None);
+        };
+        if this.cancelled.as_mut().poll(cx).is_ready() {
+            this.body = None;
+            return Poll::Ready(Some(Err(http_kit::BodyError::Io(io::Error::new(
+                io::ErrorKind::Interrupted,
+                Cancelled,
+            )))));
+        }
+        Pin::new(body).poll_next(cx)
+    }
+}
+
+impl<F> hyper::rt::Executor<F> for Spawner
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    fn execute(&self, future: F) {
+        self.spawn(future);
+    }
+}
+
 // RFC 8305 defaults: Resolution Delay = 50ms, First Address Family Count = 1,
-// Connection Attempt Delay = 250ms.
+// Connection Attempt Delay = 250ms. A configured delay is kept within the
+// RFC's hard floor of 10ms and its recommended ceiling of 2s.
 const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
 const FIRST_ADDRESS_FAMILY_COUNT: usize = 1;
 const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
-const MIN_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(100);
+const MIN_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(10);
 const MAX_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_secs(2);
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Run a connection attempt, retrying I/O failures up to `retries` times.
+///
+/// Configuration errors such as an invalid URI or missing TLS support are
+/// returned immediately since another attempt cannot succeed.
+async fn retry_connect<T, F, Fut>(retries: u32, mut attempt: F) -> Result<T, HyperError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, HyperError>>,
+{
+    let mut remaining = retries;
+    loop {
+        match attempt().await {
+            Err(HyperError::Io(error)) if remaining > 0 => {
+                remaining -= 1;
+                debug!(error = %error, remaining, "connect attempt failed, retrying");
+                Timer::after(CONNECT_RETRY_DELAY).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether `https` connections use rustls, the only TLS backend that offers
+/// HTTP/2. native-tls wins on Apple platforms when both are enabled.
+const RUSTLS_IN_USE: bool = cfg!(all(
+    feature = "rustls",
+    not(all(feature = "native-tls", target_vendor = "apple"))
+));
+
+/// Protocols offered to `https` servers. async-native-tls does not report
+/// which one was picked, so connections made with native-tls stay on
+/// HTTP/1.1 and do not offer `h2`.
+#[cfg(feature = "rustls")]
+const ALPN: &[&[u8]] = &[b"h2", b"http/1.1"];
 
-async fn connect(request: &http::Request<http_kit::Body>) -> Result<MaybeTlsStream, HyperError> {
+/// Protocols offered when HTTP/2 is ruled out by
+/// [`HyperBackend::with_http1_0`].
+#[cfg(feature = "rustls")]
+const HTTP1_ALPN: &[&[u8]] = &[b"http/1.1"];
+
+async fn connect(
+    request: &http::Request<http_kit::Body>,
+    backend: &HyperBackend,
+    route: Option<&ProxyRoute>,
+) -> Result<MaybeTlsStream, HyperError> {
+    let tls = &backend.tls;
+    #[cfg(feature = "rustls")]
+    let alpn = if backend.http1.version_1_0 {
+        HTTP1_ALPN
+    } else {
+        ALPN
+    };
     let uri = request.uri();
     let host = uri
         .host()
@@ -233,10 +1287,14 @@ async fn connect(request: &http::Request<http_kit::Body>) -> Result<MaybeTlsStre
     };
     let port = uri.port_u16().unwrap_or(if use_tls { 443 } else { 80 });
 
-    let stream = connect_happy_eyeballs(host.as_str(), port)
-        .await
-        .map_err(HyperError::Io)?;
-    stream.set_nodelay(true).map_err(HyperError::Io)?;
+    let stream = match route {
+        #[cfg(feature = "proxy")]
+        Some(route) => route.connect(backend, &host, port, use_tls).await?,
+        _ => {
+            connect_happy_eyeballs(host.as_str(), port, &backend.dns_cache, &backend.connect)
+                .await?
+        }
+    };
 
     if use_tls {
         // TLS selection logic:
@@ -244,17 +1302,17 @@ async fn connect(request: &http::Request<http_kit::Body>) -> Result<MaybeTlsStre
         //    - On Apple platforms: use native-tls
         //    - On other platforms: use rustls with system certificates
         // 2. When only native-tls is enabled: use native-tls
-        // 3. When only rustls is enabled: use rustls with system certificates
+        // 3. When only rustls is enabled: use rustls
+        // Either way `tls` supplies the roots, identity and verification.
 
         // Case: Both TLS implementations available, Apple platform -> use native-tls
         #[cfg(all(feature = "native-tls", feature = "rustls", target_vendor = "apple"))]
         {
-            let connector = async_native_tls::TlsConnector::new();
-            let tls = connector
-                .connect(host.as_str(), stream)
+            let stream = tls
+                .connect_native(&host, stream)
                 .await
-                .map_err(|err| HyperError::Io(std::io::Error::other(err)))?;
-            return Ok(MaybeTlsStream::Native(tls));
+                .map_err(HyperError::from)?;
+            return Ok(MaybeTlsStream::Native(stream));
         }
 
         // Case: Both TLS implementations available, non-Apple platform -> use rustls
@@ -264,28 +1322,36 @@ async fn connect(request: &http::Request<http_kit::Body>) -> Result<MaybeTlsStre
             not(target_vendor = "apple")
         ))]
         {
-            return connect_rustls(host, stream).await;
+            let stream = tls
+                .connect_rustls(&host, stream, alpn)
+                .await
+                .map_err(HyperError::from)?;
+            return Ok(MaybeTlsStream::Rustls(Box::new(stream)));
         }
 
         // Case: Only native-tls enabled
         #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
         {
-            let connector = async_native_tls::TlsConnector::new();
-            let tls = connector
-                .connect(host.as_str(), stream)
+            let stream = tls
+                .connect_native(&host, stream)
                 .await
-                .map_err(|err| HyperError::Io(std::io::Error::other(err)))?;
-            return Ok(MaybeTlsStream::Native(tls));
+                .map_err(HyperError::from)?;
+            return Ok(MaybeTlsStream::Native(stream));
         }
 
         // Case: Only rustls enabled
         #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
         {
-            return connect_rustls(host, stream).await;
+            let stream = tls
+                .connect_rustls(&host, stream, alpn)
+                .await
+                .map_err(HyperError::from)?;
+            return Ok(MaybeTlsStream::Rustls(Box::new(stream)));
         }
 
         #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
         {
+            let _ = tls;
             return Err(HyperError::TlsNotAvailable);
         }
     }
@@ -293,30 +1359,60 @@ async fn connect(request: &http::Request<http_kit::Body>) -> Result<MaybeTlsStre
     Ok(MaybeTlsStream::Plain(stream))
 }
 
-async fn connect_happy_eyeballs(host: &str, port: u16) -> io::Result<TcpStream> {
+async fn connect_happy_eyeballs(
+    host: &str,
+    port: u16,
+    dns_cache: &DnsCache,
+    options: &ConnectOptions,
+) -> Result<TcpStream, HyperError> {
     if let Ok(ip) = host.parse::<IpAddr>() {
         let addr = SocketAddr::new(ip, port);
-        return connect_with_timeout(addr)
+        if !options.family.allows(&addr) {
+            return Err(HyperError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{addr}: {EXCLUDED_FAMILY}"),
+            )));
+        }
+        return connect_with_timeout(addr, &options.socket)
             .await
-            .map_err(|error| io::Error::new(error.kind(), format!("{addr}: {error}")));
+            .map_err(|error| {
+                HyperError::Io(io::Error::new(error.kind(), format!("{addr}: {error}")))
+            });
     }
 
-    let mut state = HappyEyeballsState::new();
+    if let Some(error) = dns_cache.cached_failure(host) {
+        return Err(HyperError::Dns(error));
+    }
+
+    let mut state = HappyEyeballsState::new(options.attempt_delay, options.family);
     let mut attempts = FuturesUnordered::new();
-    let mut resolver = start_resolution(host, port);
+    let mut resolver = dns_cache.lookup(host, port);
     let mut resolver_closed = false;
 
     loop {
         state.rebuild_pending();
 
         if let Some(addr) = state.pop_next_attempt(Instant::now()) {
-            let attempt: AttemptFuture = Box::pin(connect_attempt(addr));
+            let attempt: AttemptFuture = Box::pin(connect_attempt(addr, options.socket.clone()));
             attempts.push(attempt);
             continue;
         }
 
         if state.is_terminal(&attempts) {
-            return Err(state.into_connect_error());
+            // Nothing was ever attempted, so either the preference excluded
+            // every address, which says nothing about the host, or
+            // resolution itself failed.
+            if state.attempted.is_empty() && state.excluded {
+                return Err(HyperError::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{host}: {EXCLUDED_FAMILY}"),
+                )));
+            }
+            if state.attempted.is_empty() {
+                let message = state.into_connect_error().to_string();
+                return Err(HyperError::Dns(dns_cache.record_failure(host, message)));
+            }
+            return Err(HyperError::Io(state.into_connect_error()));
         }
 
         let resolver_event = async {
@@ -355,10 +1451,311 @@ async fn connect_happy_eyeballs(host: &str, port: u16) -> io::Result<TcpStream>
                     state.mark_resolution_stream_closed();
                 }
             }
-            () = resolution_delay.fuse() => {
-                state.open_resolution_gate();
+            () = resolution_delay.fuse() => {
+                state.open_resolution_gate();
+            }
+            () = next_attempt_due.fuse() => {}
+        }
+    }
+}
+
+type StartResolution = fn(&str, u16) -> UnboundedReceiver<ResolutionEvent>;
+
+type ResolutionStream<'a> = Pin<Box<dyn futures_util::Stream<Item = ResolutionEvent> + Send + 'a>>;
+
+/// Resolves host names for [`HyperBackend`] in place of the operating
+/// system, for example over DNS-over-HTTPS; see
+/// [`HyperBackend::with_resolver`].
+///
+/// ```rust,no_run
+/// use std::{io, net::SocketAddr};
+/// use zenwave::backend::{HyperBackend, Resolver};
+///
+/// struct Fixed(SocketAddr);
+///
+/// impl Resolver for Fixed {
+///     async fn resolve(&self, _host: &str) -> io::Result<Vec<SocketAddr>> {
+///         Ok(vec![self.0])
+///     }
+/// }
+///
+/// let backend = HyperBackend::new().with_resolver(Fixed(([10, 0, 0, 7], 0).into()));
+/// ```
+pub trait Resolver: Send + Sync + 'static {
+    /// The addresses of `host`, most preferred first. A port of zero is
+    /// replaced by the port of the URL.
+    ///
+    /// Connections race the addresses as they do for the system resolver,
+    /// so a list mixing IPv6 and IPv4 falls back quickly between families.
+    fn resolve(&self, host: &str) -> impl Future<Output = io::Result<Vec<SocketAddr>>> + Send;
+}
+
+type ResolveFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+/// Object-safe form of [`Resolver`].
+trait DynResolver: Send + Sync {
+    fn resolve_boxed<'a>(&'a self, host: &'a str) -> ResolveFuture<'a>;
+}
+
+impl<R: Resolver> DynResolver for R {
+    fn resolve_boxed<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(self.resolve(host))
+    }
+}
+
+#[derive(Clone)]
+struct CustomResolver(std::sync::Arc<dyn DynResolver>);
+
+impl core::fmt::Debug for CustomResolver {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("CustomResolver")
+    }
+}
+
+/// Where host names are resolved, with overrides and caches of successful
+/// and failed lookups in front.
+#[derive(Debug, Clone)]
+struct DnsCache {
+    negative_ttl: Duration,
+    failures: Shared<Mutex<BTreeMap<String, NegativeEntry>>>,
+    /// How long resolved addresses are kept; zero keeps none.
+    ttl: Duration,
+    /// Resolved addresses by host and port.
+    resolved: Shared<Mutex<BTreeMap<(String, u16), PositiveEntry>>>,
+    /// Addresses set with [`HyperBackend::resolve`], by lowercase host.
+    overrides: BTreeMap<String, Vec<SocketAddr>>,
+    custom: Option<CustomResolver>,
+    resolver: StartResolution,
+}
+
+#[derive(Debug)]
+struct PositiveEntry {
+    expires: Instant,
+    addrs: Vec<SocketAddr>,
+}
+
+#[derive(Debug)]
+struct NegativeEntry {
+    expires: Instant,
+    error: DnsError,
+}
+
+impl DnsCache {
+    const fn new() -> Self {
+        Self {
+            negative_ttl: Duration::ZERO,
+            failures: Shared::new(),
+            ttl: Duration::ZERO,
+            resolved: Shared::new(),
+            overrides: BTreeMap::new(),
+            custom: None,
+            resolver: start_resolution,
+        }
+    }
+
+    /// Resolve `host`, answering from the overrides and the cache when
+    /// possible.
+    fn lookup<'a>(&'a self, host: &'a str, port: u16) -> ResolutionStream<'a> {
+        let with_port = move |addrs: &[SocketAddr]| -> Vec<SocketAddr> {
+            addrs
+                .iter()
+                .map(|addr| match addr.port() {
+                    0 => SocketAddr::new(addr.ip(), port),
+                    _ => *addr,
+                })
+                .collect()
+        };
+        if let Some(addrs) = self.overrides.get(&host.to_ascii_lowercase()) {
+            return Box::pin(futures_util::stream::iter(resolved_events(with_port(
+                addrs,
+            ))));
+        }
+        let key = (host.to_string(), port);
+        if let Some(entry) = self.resolved().get(&key)
+            && Instant::now() < entry.expires
+        {
+            return Box::pin(futures_util::stream::iter(resolved_events(
+                entry.addrs.clone(),
+            )));
+        }
+
+        let events: ResolutionStream<'a> = match &self.custom {
+            Some(resolver) => Box::pin(
+                futures_util::stream::once(resolver.0.resolve_boxed(host)).flat_map(
+                    move |result| {
+                        futures_util::stream::iter(match result {
+                            Ok(addrs) => resolved_events(with_port(&addrs)),
+                            Err(error) => failed_events(&error.to_string()),
+                        })
+                    },
+                ),
+            ),
+            None => Box::pin((self.resolver)(host, port)),
+        };
+        if self.ttl.is_zero() {
+            return events;
+        }
+        // The full, sorted answer is the one worth keeping.
+        Box::pin(events.inspect(move |event| {
+            if let ResolutionEventKind::SortedSnapshot(ResolutionResult::Addresses(addrs)) =
+                &event.kind
+            {
+                let entry = PositiveEntry {
+                    expires: Instant::now() + self.ttl,
+                    addrs: addrs.clone(),
+                };
+                self.resolved().insert(key.clone(), entry);
+            }
+        }))
+    }
+
+    fn resolved(&self) -> std::sync::MutexGuard<'_, BTreeMap<(String, u16), PositiveEntry>> {
+        self.resolved
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn failures(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, NegativeEntry>> {
+        self.failures
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Return the remembered failure for `host`, dropping it once expired.
+    fn cached_failure(&self, host: &str) -> Option<DnsError> {
+        let mut failures = self.failures();
+        let entry = failures.get(host)?;
+        if Instant::now() < entry.expires {
+            return Some(entry.error.cached());
+        }
+        failures.remove(host);
+        None
+    }
+
+    fn record_failure(&self, host: &str, message: String) -> DnsError {
+        let error = DnsError::new(host.to_string(), message);
+        if !self.negative_ttl.is_zero() {
+            self.failures().insert(
+                host.to_string(),
+                NegativeEntry {
+                    expires: Instant::now() + self.negative_ttl,
+                    error: error.clone(),
+                },
+            );
+        }
+        error
+    }
+
+    fn clear(&self) {
+        self.failures().clear();
+        self.resolved().clear();
+    }
+}
+
+/// The events of a finished lookup that found `addrs`, in this order.
+fn resolved_events(addrs: Vec<SocketAddr>) -> Vec<ResolutionEvent> {
+    let result = |addrs: Vec<SocketAddr>| {
+        if addrs.is_empty() {
+            ResolutionResult::Empty
+        } else {
+            ResolutionResult::Addresses(addrs)
+        }
+    };
+    let (ipv6, ipv4) = addrs.iter().partition(|addr| addr.is_ipv6());
+    [
+        ResolutionEventKind::SortedSnapshot(result(addrs)),
+        ResolutionEventKind::Family {
+            family: AddressFamilyKind::Ipv6,
+            result: result(ipv6),
+        },
+        ResolutionEventKind::Family {
+            family: AddressFamilyKind::Ipv4,
+            result: result(ipv4),
+        },
+    ]
+    .into_iter()
+    .map(|kind| ResolutionEvent { kind })
+    .collect()
+}
+
+/// The events of a lookup that failed with `message`.
+fn failed_events(message: &str) -> Vec<ResolutionEvent> {
+    [AddressFamilyKind::Ipv6, AddressFamilyKind::Ipv4]
+        .into_iter()
+        .map(|family| ResolutionEvent {
+            kind: ResolutionEventKind::Family {
+                family,
+                result: ResolutionResult::Failed(message.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// The IP versions [`HyperBackend`] connects over; see
+/// [`HyperBackend::with_address_family`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum AddressFamily {
+    /// Race IPv6 and IPv4 addresses, preferring IPv6 (RFC 8305).
+    #[default]
+    Auto,
+    /// Only connect over IPv4.
+    Ipv4Only,
+    /// Only connect over IPv6.
+    Ipv6Only,
+}
+
+const EXCLUDED_FAMILY: &str = "excluded by the address family preference";
+
+impl AddressFamily {
+    const fn allows_kind(self, kind: AddressFamilyKind) -> bool {
+        !matches!(
+            (self, kind),
+            (Self::Ipv4Only, AddressFamilyKind::Ipv6) | (Self::Ipv6Only, AddressFamilyKind::Ipv4)
+        )
+    }
+
+    const fn allows(self, addr: &SocketAddr) -> bool {
+        self.allows_kind(if addr.is_ipv6() {
+            AddressFamilyKind::Ipv6
+        } else {
+            AddressFamilyKind::Ipv4
+        })
+    }
+
+    /// Whether `kind` carries addresses of an excluded family.
+    fn excludes_any(self, kind: &ResolutionEventKind) -> bool {
+        match kind {
+            ResolutionEventKind::Family {
+                family,
+                result: ResolutionResult::Addresses(addrs),
+            } => !addrs.is_empty() && !self.allows_kind(*family),
+            ResolutionEventKind::SortedSnapshot(ResolutionResult::Addresses(addrs)) => {
+                addrs.iter().any(|addr| !self.allows(addr))
+            }
+            _ => false,
+        }
+    }
+
+    /// `kind` without the addresses of excluded families; an excluded
+    /// family resolves to a failure naming the preference.
+    fn restrict(self, kind: ResolutionEventKind) -> ResolutionEventKind {
+        match kind {
+            ResolutionEventKind::Family { family, .. } if !self.allows_kind(family) => {
+                ResolutionEventKind::Family {
+                    family,
+                    result: ResolutionResult::Failed(EXCLUDED_FAMILY.to_string()),
+                }
+            }
+            ResolutionEventKind::SortedSnapshot(ResolutionResult::Addresses(mut addrs)) => {
+                addrs.retain(|addr| self.allows(addr));
+                ResolutionEventKind::SortedSnapshot(if addrs.is_empty() {
+                    ResolutionResult::Empty
+                } else {
+                    ResolutionResult::Addresses(addrs)
+                })
             }
-            () = next_attempt_due.fuse() => {}
+            kind => kind,
         }
     }
 }
@@ -441,11 +1838,15 @@ struct HappyEyeballsState {
     pending: VecDeque<SocketAddr>,
     attempted: HashSet<SocketAddr>,
     last_attempt_started_at: Option<Instant>,
+    attempt_delay: Duration,
+    family: AddressFamily,
+    /// Whether the preference dropped resolved addresses.
+    excluded: bool,
     attempt_failures: Vec<String>,
 }
 
 impl HappyEyeballsState {
-    fn new() -> Self {
+    fn new(attempt_delay: Duration, family: AddressFamily) -> Self {
         Self {
             ipv6: FamilyResolution::Pending,
             ipv4: FamilyResolution::Pending,
@@ -455,12 +1856,16 @@ impl HappyEyeballsState {
             pending: VecDeque::new(),
             attempted: HashSet::new(),
             last_attempt_started_at: None,
+            attempt_delay,
+            family,
+            excluded: false,
             attempt_failures: Vec::new(),
         }
     }
 
     fn apply_resolution(&mut self, event: ResolutionEvent) {
-        match event.kind {
+        self.excluded |= self.family.excludes_any(&event.kind);
+        match self.family.restrict(event.kind) {
             ResolutionEventKind::Family { family, result } => {
                 let resolution = match result {
                     ResolutionResult::Addresses(addrs) => FamilyResolution::Ready(addrs),
@@ -605,7 +2010,7 @@ impl HappyEyeballsState {
             return None;
         }
         self.last_attempt_started_at
-            .map(|started_at| started_at + bounded_connection_attempt_delay())
+            .map(|started_at| started_at + self.attempt_delay)
     }
 
     const fn open_resolution_gate(&mut self) {
@@ -654,15 +2059,105 @@ impl HappyEyeballsState {
     }
 }
 
-async fn connect_attempt(addr: SocketAddr) -> AttemptOutcome {
+async fn connect_attempt(addr: SocketAddr, socket: SocketOptions) -> AttemptOutcome {
     AttemptOutcome {
         addr,
-        result: connect_with_timeout(addr).await,
+        result: connect_with_timeout(addr, &socket).await,
+    }
+}
+
+/// How connections are made, as configured on [`HyperBackend`].
+#[derive(Clone, Debug)]
+struct ConnectOptions {
+    attempt_delay: Duration,
+    family: AddressFamily,
+    socket: SocketOptions,
+}
+
+impl ConnectOptions {
+    const fn new() -> Self {
+        Self {
+            attempt_delay: CONNECTION_ATTEMPT_DELAY,
+            family: AddressFamily::Auto,
+            socket: SocketOptions {
+                nodelay: true,
+                keepalive: None,
+                keepalive_interval: None,
+                local_address: None,
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                interface: None,
+            },
+        }
+    }
+}
+
+/// Options applied to every socket before it connects.
+#[derive(Clone, Debug)]
+struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    local_address: Option<IpAddr>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    interface: Option<String>,
+}
+
+impl SocketOptions {
+    fn apply(&self, socket: &socket2::Socket) -> io::Result<()> {
+        socket.set_tcp_nodelay(self.nodelay)?;
+        if let Some(time) = self.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(time);
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_vendor = "apple",
+                windows
+            ))]
+            let keepalive = match self.keepalive_interval {
+                Some(interval) => keepalive.with_interval(interval),
+                None => keepalive,
+            };
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(interface) = &self.interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        if let Some(local) = self.local_address {
+            socket.bind(&SocketAddr::new(local, 0).into())?;
+        }
+        Ok(())
+    }
+}
+
+/// Open a TCP connection to `addr` from a socket configured with `options`.
+async fn connect_socket(addr: SocketAddr, options: &SocketOptions) -> io::Result<TcpStream> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    options.apply(&socket)?;
+    socket.set_nonblocking(true)?;
+    match socket.connect(&addr.into()) {
+        Ok(()) => {}
+        #[cfg(unix)]
+        Err(error) if error.raw_os_error() == Some(libc::EINPROGRESS) => {}
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+        Err(error) => return Err(error),
     }
+    let stream = async_io::Async::new_nonblocking(std::net::TcpStream::from(socket))?;
+    stream.writable().await?;
+    if let Some(error) = stream.get_ref().take_error()? {
+        return Err(error);
+    }
+    Ok(TcpStream::from(stream))
 }
 
-async fn connect_with_timeout(addr: SocketAddr) -> io::Result<TcpStream> {
-    let connect = TcpStream::connect(addr);
+async fn connect_with_timeout(addr: SocketAddr, socket: &SocketOptions) -> io::Result<TcpStream> {
+    let connect = connect_socket(addr, socket);
     let timeout = async {
         Timer::after(CONNECT_TIMEOUT).await;
         Err(io::Error::new(
@@ -800,10 +2295,14 @@ fn interleave_address_families(
     ordered
 }
 
-fn bounded_connection_attempt_delay() -> Duration {
-    CONNECTION_ATTEMPT_DELAY
-        .max(MIN_CONNECTION_ATTEMPT_DELAY)
-        .min(MAX_CONNECTION_ATTEMPT_DELAY)
+const fn bounded_connection_attempt_delay(delay: Duration) -> Duration {
+    if delay.as_nanos() < MIN_CONNECTION_ATTEMPT_DELAY.as_nanos() {
+        MIN_CONNECTION_ATTEMPT_DELAY
+    } else if delay.as_nanos() > MAX_CONNECTION_ATTEMPT_DELAY.as_nanos() {
+        MAX_CONNECTION_ATTEMPT_DELAY
+    } else {
+        delay
+    }
 }
 
 async fn timer_at(deadline: Option<Instant>) {
@@ -815,47 +2314,6 @@ async fn timer_at(deadline: Option<Instant>) {
     }
 }
 
-/// Connect using rustls with system certificates.
-#[cfg(feature = "rustls")]
-#[allow(dead_code)] // Used on non-Apple platforms; unused on Apple when both TLS features enabled
-async fn connect_rustls(host: String, stream: TcpStream) -> Result<MaybeTlsStream, HyperError> {
-    use std::sync::Arc;
-
-    use futures_rustls::{
-        TlsConnector,
-        client::TlsStream as RustlsStream,
-        rustls::{self, pki_types::ServerName},
-    };
-
-    // Load system certificates
-    let mut root_store = rustls::RootCertStore::empty();
-
-    // Load system certificates (rustls-native-certs returns CertificateResult with certs and errors)
-    let cert_result = rustls_native_certs::load_native_certs();
-    for cert in cert_result.certs {
-        // Ignore invalid certificates, just skip them
-        let _ = root_store.add(cert);
-    }
-
-    // If no system certs were loaded, fall back to webpki roots
-    if root_store.is_empty() {
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-    }
-
-    let config = rustls::ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-    let connector = TlsConnector::from(Arc::new(config));
-    let server_name = ServerName::try_from(host.clone())
-        .map_err(|err| HyperError::Io(std::io::Error::other(err)))?;
-
-    let stream: RustlsStream<TcpStream> = connector
-        .connect(server_name, stream)
-        .await
-        .map_err(|err| HyperError::Io(std::io::Error::other(err)))?;
-    Ok(MaybeTlsStream::Rustls(Box::new(stream)))
-}
-
 enum MaybeTlsStream {
     Plain(TcpStream),
     #[cfg(feature = "native-tls")]
@@ -870,6 +2328,49 @@ enum MaybeTlsStream {
 
 impl Unpin for MaybeTlsStream {}
 
+impl MaybeTlsStream {
+    /// Whether the server agreed to HTTP/2 during the TLS handshake.
+    #[allow(clippy::missing_const_for_fn)] // Only const without rustls
+    fn negotiated_http2(&self) -> bool {
+        match self {
+            #[cfg(feature = "rustls")]
+            Self::Rustls(stream) => stream.get_ref().1.alpn_protocol() == Some(b"h2"),
+            _ => false,
+        }
+    }
+
+    fn connection_info(&self) -> io::Result<ConnectionInfo> {
+        let (tcp, tls_version, alpn) = match self {
+            Self::Plain(stream) => (stream, None, None),
+            // async-native-tls exposes neither the version nor ALPN.
+            #[cfg(feature = "native-tls")]
+            Self::Native(stream) => (stream.get_ref(), None, None),
+            #[cfg(feature = "rustls")]
+            Self::Rustls(stream) => {
+                use futures_rustls::rustls::ProtocolVersion;
+
+                let (tcp, session) = stream.get_ref();
+                let version = match session.protocol_version() {
+                    Some(ProtocolVersion::TLSv1_2) => Some(TlsVersion::Tls12),
+                    Some(ProtocolVersion::TLSv1_3) => Some(TlsVersion::Tls13),
+                    _ => None,
+                };
+                let alpn = session
+                    .alpn_protocol()
+                    .map(|protocol| String::from_utf8_lossy(protocol).into_owned());
+                (tcp, version, alpn)
+            }
+        };
+        Ok(ConnectionInfo {
+            local_addr: Some(tcp.local_addr()?),
+            peer_addr: Some(tcp.peer_addr()?),
+            tls_version,
+            alpn,
+            reused: false,
+        })
+    }
+}
+
 impl hyper::rt::Read for MaybeTlsStream {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -955,15 +2456,24 @@ impl hyper::rt::Write for MaybeTlsStream {
 #[cfg(test)]
 mod tests {
     use super::{
-        AddressFamilyKind, HappyEyeballsState, HyperBackend, ResolutionEvent, ResolutionEventKind,
-        ResolutionResult, connect_happy_eyeballs, interleave_address_families,
+        AddressFamily, AddressFamilyKind, CONNECT_TIMEOUT, CONNECTION_ATTEMPT_DELAY,
+        ConnectOptions, DnsCache, HappyEyeballsState, HyperBackend, HyperError, PoolStats,
+        ResolutionEvent, ResolutionEventKind, ResolutionResult, Resolver, connect_happy_eyeballs,
+        interleave_address_families, retry_connect,
     };
-    use crate::Client as _;
+    use crate::{Client as _, cancel::CancelToken};
+    use futures_channel::mpsc::{UnboundedReceiver, unbounded};
     use futures_util::{StreamExt as _, future::Either};
+    use http::StatusCode;
+    use http_kit::HttpError as _;
     use std::{
-        io::{Read as _, Write as _},
+        io::{self, Read as _, Write as _},
         net::{SocketAddr, TcpListener},
-        sync::mpsc,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
         thread,
         time::{Duration, Instant},
     };
@@ -1040,7 +2550,8 @@ mod tests {
             .expect("response tail must write");
     }
 
-    fn read_http_request(socket: &mut std::net::TcpStream) {
+    /// Read a request up to the end of its head, and return the head.
+    fn read_http_request(socket: &mut std::net::TcpStream) -> String {
         let mut request = [0_u8; 4_096];
         let mut filled = 0_usize;
         loop {
@@ -1053,7 +2564,8 @@ mod tests {
                 .windows(4)
                 .any(|window| window == b"\r\n\r\n")
             {
-                return;
+                return String::from_utf8(request[..filled].to_vec())
+                    .expect("test request header must be text");
             }
             assert!(
                 filled < request.len(),
@@ -1062,6 +2574,411 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fresh_connection_requests_open_their_own_connections() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = thread::spawn(move || {
+            // Keep every connection open so a pooling backend could reuse it.
+            let mut sockets = Vec::new();
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().expect("request must arrive");
+                read_http_request(&mut socket);
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .expect("response must write");
+                sockets.push(socket);
+            }
+            sockets.len()
+        });
+
+        let mut client = HyperBackend::new();
+        futures_executor::block_on(async {
+            for _ in 0..2 {
+                let response = client
+                    .post(format!("http://{address}/orders"))
+                    .expect("test request must build")
+                    .fresh_connection()
+                    .await
+                    .expect("request must succeed");
+                assert_eq!(response.status(), http::StatusCode::OK);
+            }
+        });
+        assert_eq!(server.join().expect("server must finish"), 2);
+    }
+
+    #[test]
+    fn shutdown_stops_connection_drivers() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let (release, released) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            // Announce more body than is sent, so the connection stays busy.
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial")
+                .expect("response must write");
+            let _ = released.recv();
+        });
+
+        let mut client = HyperBackend::new();
+        futures_executor::block_on(async {
+            let response = client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .await
+                .expect("request must succeed");
+            assert_eq!(client.tasks.running(), 1);
+
+            assert!(client.shutdown(STREAMING_TEST_TIMEOUT).await);
+            assert_eq!(client.tasks.running(), 0);
+            assert!(response.into_body().into_bytes().await.is_err());
+        });
+        release.send(()).expect("server must wait");
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn cancelled_requests_close_their_connection() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial!")
+                .expect("response must write");
+            socket
+                .set_read_timeout(Some(STREAMING_TEST_TIMEOUT))
+                .expect("read timeout must apply");
+            // Only a closed connection ends this read early.
+            socket.read(&mut [0_u8; 1]).expect("client must close")
+        });
+
+        let token = CancelToken::new();
+        let canceller = {
+            let token = token.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                token.cancel();
+            })
+        };
+        let mut client = HyperBackend::new();
+        let error = futures_executor::block_on(
+            client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .with_cancel(token)
+                .bytes(),
+        )
+        .unwrap_err();
+        assert!(error.is_cancelled(), "{error:?}");
+        assert_eq!(server.join().expect("server must finish"), 0);
+        canceller.join().expect("canceller must finish");
+    }
+
+    #[test]
+    fn connection_drivers_end_with_their_response_bodies() {
+        const REQUESTS: usize = 32;
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = thread::spawn(move || {
+            // Keep-alive responses: only the client can end the connections.
+            let mut sockets = Vec::new();
+            for _ in 0..REQUESTS {
+                let (mut socket, _) = listener.accept().expect("request must arrive");
+                read_http_request(&mut socket);
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .expect("response must write");
+                sockets.push(socket);
+            }
+            sockets
+        });
+
+        let mut client = HyperBackend::new();
+        futures_executor::block_on(async {
+            for _ in 0..REQUESTS {
+                let body = client
+                    .get(format!("http://{address}/"))
+                    .expect("test request must build")
+                    .string()
+                    .await
+                    .expect("request must succeed");
+                assert_eq!(body, "ok");
+            }
+        });
+        // Without an executor every driver has its own thread, which ends
+        // with it.
+        let start = Instant::now();
+        while client.tasks.running() > 0 {
+            assert!(
+                start.elapsed() < STREAMING_TEST_TIMEOUT,
+                "{} connection drivers still running",
+                client.tasks.running()
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+        drop(server.join().expect("server must finish"));
+    }
+
+    #[test]
+    fn drain_lets_in_flight_bodies_finish() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let (release, released) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\npartial")
+                .expect("response must write");
+            released.recv().expect("test must release the tail");
+            socket.write_all(b"ly").expect("tail must write");
+        });
+
+        let mut client = HyperBackend::new();
+        futures_executor::block_on(async {
+            let response = client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .await
+                .expect("request must succeed");
+            let (drained, body) =
+                futures_util::future::join(client.drain(STREAMING_TEST_TIMEOUT), async {
+                    release.send(()).expect("server must wait");
+                    response.into_body().into_bytes().await
+                })
+                .await;
+            assert!(drained);
+            assert_eq!(&body.expect("body must complete")[..], b"partially");
+        });
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn bodies_outlive_the_backend() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let (release, released) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello")
+                .expect("response must write");
+            released.recv().expect("test must release the tail");
+            socket.write_all(b"world").expect("tail must write");
+        });
+
+        let mut client = HyperBackend::new();
+        let response = futures_executor::block_on(async {
+            client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .await
+                .expect("request must succeed")
+        });
+        drop(client);
+        release.send(()).expect("server must wait");
+        let body = futures_executor::block_on(response.into_body().into_string())
+            .expect("body must complete");
+        assert_eq!(body, "helloworld");
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn connections_per_host_wait_for_a_free_slot() {
+        const REQUESTS: usize = 50;
+        const LIMIT: usize = 4;
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let peak = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let peak = Arc::clone(&peak);
+            thread::spawn(move || {
+                let open = Arc::new(AtomicUsize::new(0));
+                let handlers: Vec<_> = (0..REQUESTS)
+                    .map(|_| {
+                        let (mut socket, _) = listener.accept().expect("request must arrive");
+                        let (open, peak) = (Arc::clone(&open), Arc::clone(&peak));
+                        thread::spawn(move || {
+                            // Counted until the body ends, before which the
+                            // client cannot give up the connection.
+                            peak.fetch_max(
+                                open.fetch_add(1, Ordering::SeqCst) + 1,
+                                Ordering::SeqCst,
+                            );
+                            read_http_request(&mut socket);
+                            socket
+                                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n")
+                                .expect("response header must write");
+                            thread::sleep(Duration::from_millis(50));
+                            open.fetch_sub(1, Ordering::SeqCst);
+                            socket.write_all(b"ok").expect("response body must write");
+                        })
+                    })
+                    .collect();
+                for handler in handlers {
+                    handler.join().expect("handler must finish");
+                }
+            })
+        };
+
+        let client = HyperBackend::new()
+            .max_connections_per_host(LIMIT)
+            .into_shared();
+        let bodies =
+            futures_executor::block_on(futures_util::future::join_all((0..REQUESTS).map(|_| {
+                let client = client.clone();
+                async move { client.get(format!("http://{address}/"))?.string().await }
+            })));
+        assert!(
+            bodies
+                .iter()
+                .all(|body| body.as_ref().is_ok_and(|body| body == "ok")),
+            "{bodies:?}"
+        );
+        assert_eq!(peak.load(Ordering::SeqCst), LIMIT);
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn pool_acquire_timeout_fails_requests_waiting_for_a_slot() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let (release, released) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial")
+                .expect("response must write");
+            let _ = released.recv();
+        });
+
+        let mut client = HyperBackend::new()
+            .max_connections_per_host(1)
+            .pool_acquire_timeout(Duration::from_millis(50));
+        futures_executor::block_on(async {
+            let response = client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .await
+                .expect("request must succeed");
+            assert_eq!(
+                client.pool_stats(),
+                PoolStats {
+                    in_flight: 1,
+                    queued: 0
+                }
+            );
+
+            let error = client
+                .get(format!("http://{address}/"))
+                .expect("test request must build")
+                .await
+                .unwrap_err();
+            assert!(error.is_timeout(), "{error:?}");
+            assert_eq!(client.pool_stats().queued, 0);
+            drop(response);
+        });
+        release.send(()).expect("server must wait");
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn trailers_follow_the_chunked_request_body() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            let mut received = Vec::new();
+            let mut buffer = [0_u8; 1_024];
+            // A chunked body ends with the zero-size chunk, the trailers and a blank line.
+            while !(received.ends_with(b"\r\n\r\n")
+                && received.windows(5).any(|window| window == b"\r\n0\r\n"))
+            {
+                let read = socket.read(&mut buffer).expect("request must be readable");
+                assert_ne!(read, 0, "request ended before its trailers");
+                received.extend_from_slice(&buffer[..read]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .expect("response must write");
+            String::from_utf8(received).expect("request must be text")
+        });
+
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+        let mut client = HyperBackend::new();
+        let response = futures_executor::block_on(async {
+            client
+                .post(format!("http://{address}/upload"))
+                .expect("test request must build")
+                .bytes_body(b"payload".to_vec())
+                .trailers(trailers)
+                .await
+        })
+        .expect("request must succeed");
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let request = server
+            .join()
+            .expect("server must finish")
+            .to_ascii_lowercase();
+        let (head, body) = request
+            .split_once("\r\n\r\n")
+            .expect("request must have a head");
+        assert!(head.contains("\r\ntrailer: grpc-status"), "{head}");
+        assert!(head.contains("\r\ntransfer-encoding: chunked"), "{head}");
+        assert!(!head.contains("content-length"), "{head}");
+        let payload = body.find("payload").expect("body must be sent");
+        let trailer = body
+            .find("\r\n0\r\ngrpc-status: 0\r\n")
+            .expect("trailer must be sent");
+        assert!(payload < trailer, "{body}");
+    }
+
+    #[test]
+    fn in_memory_bodies_are_sent_with_content_length() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            let mut received = Vec::new();
+            let mut buffer = [0_u8; 1_024];
+            while !received.windows(7).any(|window| window == b"payload") {
+                let read = socket.read(&mut buffer).expect("request must be readable");
+                assert_ne!(read, 0, "request ended before its body");
+                received.extend_from_slice(&buffer[..read]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .expect("response must write");
+            String::from_utf8(received).expect("request must be text")
+        });
+
+        let mut client = HyperBackend::new();
+        let response = futures_executor::block_on(async {
+            client
+                .post(format!("http://{address}/upload"))
+                .expect("test request must build")
+                .bytes_body(b"payload".to_vec())
+                .await
+        })
+        .expect("request must succeed");
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let request = server
+            .join()
+            .expect("server must finish")
+            .to_ascii_lowercase();
+        assert!(request.contains("\r\ncontent-length: 7\r\n"), "{request}");
+        assert!(!request.contains("transfer-encoding"), "{request}");
+    }
+
     #[test]
     fn response_headers_arrive_before_a_streaming_body_completes() {
         let server = TestStreamingServer::start();
@@ -1142,6 +3059,74 @@ mod tests {
         server.finish();
     }
 
+    #[test]
+    fn retry_connect_stops_after_first_success() {
+        let mut attempts = 0;
+        let result = futures_executor::block_on(retry_connect(3, || {
+            attempts += 1;
+            let outcome = if attempts == 1 {
+                Err(HyperError::Io(std::io::ErrorKind::ConnectionRefused.into()))
+            } else {
+                Ok(attempts)
+            };
+            std::future::ready(outcome)
+        }));
+        assert_eq!(result.expect("second attempt must succeed"), 2);
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn retry_connect_does_not_retry_configuration_errors() {
+        let mut attempts = 0;
+        let result = futures_executor::block_on(retry_connect(3, || {
+            attempts += 1;
+            std::future::ready(Err::<(), _>(HyperError::InvalidUri("ftp".to_string())))
+        }));
+        assert!(matches!(result, Err(HyperError::InvalidUri(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn connect_retries_reach_a_late_listener_and_send_once() {
+        // Reserve a port, then release it so the first connect is refused.
+        let address = TcpListener::bind(("127.0.0.1", 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("test port must be reserved");
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let listener = TcpListener::bind(address).expect("late listener must bind");
+            ready_tx.send(()).expect("ready signal must send");
+            let (mut socket, _) = listener.accept().expect("request must arrive");
+            read_http_request(&mut socket);
+            socket
+                .write_all(
+                    b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .expect("response must write");
+            listener
+                .set_nonblocking(true)
+                .expect("listener must become non-blocking");
+            thread::sleep(Duration::from_millis(100));
+            listener.accept().is_err()
+        });
+
+        let mut client = HyperBackend::new().connect_retries(5);
+        let response = futures_executor::block_on(async {
+            client
+                .post(format!("http://{address}/submit"))
+                .expect("test request must build")
+                .await
+        })
+        .expect("request must succeed after connect retries");
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+        ready_rx.recv().expect("listener must have started");
+        assert!(
+            server.join().expect("server must finish"),
+            "request must be sent exactly once"
+        );
+    }
+
     #[test]
     fn interleaves_addresses_with_first_family_count() {
         let ipv6 = vec![
@@ -1173,7 +3158,7 @@ mod tests {
 
     #[test]
     fn promotes_ipv6_when_aaaa_arrives_during_resolution_delay() {
-        let mut state = HappyEyeballsState::new();
+        let mut state = HappyEyeballsState::new(CONNECTION_ATTEMPT_DELAY, AddressFamily::Auto);
         state.apply_resolution(ResolutionEvent {
             kind: ResolutionEventKind::Family {
                 family: AddressFamilyKind::Ipv4,
@@ -1205,7 +3190,7 @@ mod tests {
 
     #[test]
     fn holds_ipv4_until_resolution_delay_expires_when_aaaa_is_still_pending() {
-        let mut state = HappyEyeballsState::new();
+        let mut state = HappyEyeballsState::new(CONNECTION_ATTEMPT_DELAY, AddressFamily::Auto);
         state.apply_resolution(ResolutionEvent {
             kind: ResolutionEventKind::Family {
                 family: AddressFamilyKind::Ipv4,
@@ -1230,8 +3215,13 @@ mod tests {
 
     #[test]
     fn literal_ip_connect_does_not_report_opposite_family_resolution() {
-        let error = smol::block_on(connect_happy_eyeballs("127.0.0.1", 9))
-            .expect_err("discard port should not accept connections in tests");
+        let error = smol::block_on(connect_happy_eyeballs(
+            "127.0.0.1",
+            9,
+            &DnsCache::new(),
+            &ConnectOptions::new(),
+        ))
+        .expect_err("discard port should not accept connections in tests");
         let message = error.to_string();
         assert!(
             !message.contains("Ipv6 resolution"),
@@ -1242,4 +3232,389 @@ mod tests {
             "literal IP connection error should name the attempted socket address: {message}",
         );
     }
+
+    static FAILED_LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Resolver stub that counts lookups and fails every one of them.
+    fn failing_resolver(_host: &str, _port: u16) -> UnboundedReceiver<ResolutionEvent> {
+        FAILED_LOOKUPS.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = unbounded();
+        for family in [AddressFamilyKind::Ipv6, AddressFamilyKind::Ipv4] {
+            let result = ResolutionResult::Failed("no such host".to_string());
+            sender
+                .unbounded_send(ResolutionEvent {
+                    kind: ResolutionEventKind::Family { family, result },
+                })
+                .unwrap();
+        }
+        receiver
+    }
+
+    #[test]
+    fn failed_lookups_are_cached_for_the_negative_ttl() {
+        let lookup = |cache: &DnsCache| {
+            let Err(HyperError::Dns(error)) = smol::block_on(connect_happy_eyeballs(
+                "nowhere.invalid",
+                80,
+                cache,
+                &ConnectOptions::new(),
+            )) else {
+                panic!("expected a DNS error");
+            };
+            error
+        };
+        let cache = DnsCache {
+            negative_ttl: Duration::from_mins(1),
+            resolver: failing_resolver,
+            ..DnsCache::new()
+        };
+
+        let first = lookup(&cache);
+        assert!(!first.is_cached());
+        assert_eq!(first.host(), "nowhere.invalid");
+
+        let second = lookup(&cache);
+        assert!(second.is_cached());
+        assert_eq!(FAILED_LOOKUPS.load(Ordering::SeqCst), 1);
+
+        let error = crate::Error::from(HyperError::Dns(second));
+        assert!(matches!(error, crate::Error::Transport(_)));
+        assert!(error.to_string().ends_with("(cached)"), "{error}");
+
+        // Once the entry expires the resolver is consulted again.
+        cache
+            .failures()
+            .get_mut("nowhere.invalid")
+            .expect("the failure must be cached")
+            .expires = Instant::now();
+        assert!(!lookup(&cache).is_cached());
+        assert_eq!(FAILED_LOOKUPS.load(Ordering::SeqCst), 2);
+
+        cache.clear();
+        assert!(!lookup(&cache).is_cached());
+        assert_eq!(FAILED_LOOKUPS.load(Ordering::SeqCst), 3);
+    }
+
+    /// Answer `requests` requests, one per connection, with their `Host`
+    /// header.
+    fn serve_host_headers(listener: TcpListener, requests: usize) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            for _ in 0..requests {
+                let (mut socket, _) = listener.accept().expect("request must arrive");
+                let head = read_http_request(&mut socket);
+                let host = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("host: "))
+                    .expect("request must carry a Host header");
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{host}",
+                    host.len()
+                );
+                socket
+                    .write_all(response.as_bytes())
+                    .expect("response must write");
+            }
+        })
+    }
+
+    fn get(client: &mut HyperBackend, url: &str) -> Result<String, crate::Error> {
+        futures_executor::block_on(async { Ok(client.get(url)?.string().await?.to_string()) })
+    }
+
+    #[test]
+    fn resolve_overrides_connect_elsewhere_but_keep_the_host() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let address = listener.local_addr().expect("test address must exist");
+        let server = serve_host_headers(listener, 2);
+
+        let mut client = HyperBackend::new()
+            .resolve("API.test", address)
+            .resolve("any-port.test", SocketAddr::from(([127, 0, 0, 1], 0)));
+        assert_eq!(get(&mut client, "http://api.test/").unwrap(), "api.test");
+        let port = address.port();
+        assert_eq!(
+            get(&mut client, &format!("http://any-port.test:{port}/")).unwrap(),
+            format!("any-port.test:{port}")
+        );
+        server.join().expect("server must finish");
+    }
+
+    /// Resolves every host to the loopback address, counting the lookups.
+    struct Loopback(Arc<AtomicUsize>);
+
+    impl Resolver for Loopback {
+        async fn resolve(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            if host.ends_with(".invalid") {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "no such host"));
+            }
+            Ok(vec![SocketAddr::from(([127, 0, 0, 1], 0))])
+        }
+    }
+
+    #[test]
+    fn custom_resolver_answers_are_cached_for_the_ttl() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let port = listener
+            .local_addr()
+            .expect("test address must exist")
+            .port();
+        let server = serve_host_headers(listener, 4);
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let url = format!("http://service.test:{port}/");
+
+        let mut client = HyperBackend::new()
+            .with_resolver(Loopback(Arc::clone(&lookups)))
+            .dns_ttl(Duration::from_mins(1));
+        for _ in 0..2 {
+            assert_eq!(
+                get(&mut client, &url).unwrap(),
+                format!("service.test:{port}")
+            );
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+        client.clear_dns_cache();
+        get(&mut client, &url).unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // Without a TTL every connection asks the resolver.
+        let mut client = HyperBackend::new().with_resolver(Loopback(Arc::clone(&lookups)));
+        get(&mut client, &url).unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+        server.join().expect("server must finish");
+
+        let error = get(&mut client, "http://nowhere.invalid/").unwrap_err();
+        assert!(error.to_string().contains("no such host"), "{error}");
+    }
+
+    /// Answers with an unreachable IPv6 address ahead of the loopback one.
+    struct BrokenIpv6;
+
+    impl Resolver for BrokenIpv6 {
+        async fn resolve(&self, _host: &str) -> io::Result<Vec<SocketAddr>> {
+            // 100::/64 is the discard-only prefix of RFC 6666.
+            let black_hole = "[100::1]:0".parse().expect("valid IPv6");
+            Ok(vec![black_hole, SocketAddr::from(([127, 0, 0, 1], 0))])
+        }
+    }
+
+    #[test]
+    fn unreachable_addresses_only_cost_the_connection_attempt_delay() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let port = listener
+            .local_addr()
+            .expect("test address must exist")
+            .port();
+        let server = serve_host_headers(listener, 1);
+
+        let mut client = HyperBackend::builder()
+            .resolver(BrokenIpv6)
+            .connection_attempt_delay(Duration::from_millis(50))
+            .build()
+            .expect("plain settings must build");
+        let started = Instant::now();
+        let body = get(&mut client, &format!("http://dual.test:{port}/")).unwrap();
+        assert_eq!(body, format!("dual.test:{port}"));
+        assert!(
+            started.elapsed() < CONNECT_TIMEOUT,
+            "the IPv4 attempt must not wait for the IPv6 one to time out: {:?}",
+            started.elapsed(),
+        );
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn address_family_preference_drops_the_other_family() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let port = listener
+            .local_addr()
+            .expect("test address must exist")
+            .port();
+        let server = serve_host_headers(listener, 1);
+        let url = format!("http://dual.test:{port}/");
+
+        // Without the dead IPv6 address to race, IPv4 connects right away.
+        let mut client = HyperBackend::new()
+            .with_resolver(BrokenIpv6)
+            .with_address_family(AddressFamily::Ipv4Only);
+        let started = Instant::now();
+        assert_eq!(get(&mut client, &url).unwrap(), format!("dual.test:{port}"));
+        assert!(started.elapsed() < CONNECT_TIMEOUT);
+        server.join().expect("server must finish");
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let mut client = HyperBackend::new()
+            .with_resolver(Loopback(Arc::clone(&lookups)))
+            .with_address_family(AddressFamily::Ipv6Only)
+            .negative_ttl(Duration::from_mins(1));
+        for _ in 0..2 {
+            let error = get(&mut client, &url).unwrap_err();
+            assert!(error.to_string().contains("address family"), "{error}");
+            assert!(!error.to_string().contains("cached"), "{error}");
+        }
+        // The host resolved, so its lookup is not remembered as a failure.
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+        let error = get(&mut client, &format!("http://127.0.0.1:{port}/")).unwrap_err();
+        assert!(error.to_string().contains("address family"), "{error}");
+    }
+
+    #[test]
+    fn socket_options_are_applied_before_connecting() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let port = listener
+            .local_addr()
+            .expect("test address must exist")
+            .port();
+        let backend = HyperBackend::builder()
+            .tcp_nodelay(false)
+            .tcp_keepalive(Duration::from_secs(30))
+            .tcp_keepalive_interval(Duration::from_secs(5))
+            .local_address([127, 0, 0, 1].into())
+            .build()
+            .expect("plain settings must build");
+
+        let stream = smol::block_on(connect_happy_eyeballs(
+            "127.0.0.1",
+            port,
+            &backend.dns_cache,
+            &backend.connect,
+        ))
+        .expect("test server must accept");
+        let (_accepted, peer) = listener.accept().expect("connection must arrive");
+        assert_eq!(stream.local_addr().expect("socket must be bound"), peer);
+        let socket = socket2::SockRef::from(&stream);
+        assert!(!socket.tcp_nodelay().expect("nodelay must be readable"));
+        assert!(socket.keepalive().expect("keepalive must be readable"));
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(
+                socket
+                    .tcp_keepalive_time()
+                    .expect("keepalive time must be readable"),
+                Duration::from_secs(30)
+            );
+            assert_eq!(
+                socket
+                    .tcp_keepalive_interval()
+                    .expect("keepalive interval must be readable"),
+                Duration::from_secs(5)
+            );
+        }
+
+        // Connections use TCP_NODELAY unless told otherwise.
+        let stream = smol::block_on(connect_happy_eyeballs(
+            "127.0.0.1",
+            port,
+            &DnsCache::new(),
+            &ConnectOptions::new(),
+        ))
+        .expect("test server must accept");
+        assert!(stream.nodelay().expect("nodelay must be readable"));
+    }
+
+    /// Serve `requests` connections like a legacy server that only knows
+    /// the `Content-Type` header in title case; it answers with the request
+    /// line.
+    fn serve_picky(listener: TcpListener, requests: usize) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            for _ in 0..requests {
+                let (mut socket, _) = listener.accept().expect("request must arrive");
+                let mut head = Vec::new();
+                let mut buffer = [0_u8; 1_024];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = socket.read(&mut buffer).expect("request must be readable");
+                    assert_ne!(read, 0, "request ended before its header");
+                    head.extend_from_slice(&buffer[..read]);
+                }
+                let head = String::from_utf8(head).expect("request header must be text");
+                let line = head.lines().next().unwrap_or_default();
+                let status = if head.contains("\r\nContent-Type: text/plain\r\n") {
+                    "200 OK"
+                } else {
+                    "400 Bad Request"
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{line}",
+                    line.len()
+                );
+                socket
+                    .write_all(response.as_bytes())
+                    .expect("response must write");
+            }
+        })
+    }
+
+    #[test]
+    fn http1_title_case_headers_and_version_reach_picky_servers() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let url = format!(
+            "http://{}/legacy",
+            listener.local_addr().expect("test address must exist")
+        );
+        let server = serve_picky(listener, 3);
+        let send = |client: &mut HyperBackend| -> Result<String, crate::Error> {
+            futures_executor::block_on(async {
+                let line = client
+                    .get(url.as_str())?
+                    .header(http::header::CONTENT_TYPE, "text/plain")?
+                    .string()
+                    .await?;
+                Ok(line.to_string())
+            })
+        };
+
+        let error = send(&mut HyperBackend::new()).unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        let mut client = HyperBackend::new().with_http1_title_case_headers(true);
+        assert_eq!(send(&mut client).unwrap(), "GET /legacy HTTP/1.1");
+        let mut client = HyperBackend::new()
+            .with_http1_title_case_headers(true)
+            .with_http1_0(true);
+        assert_eq!(send(&mut client).unwrap(), "GET /legacy HTTP/1.0");
+        server.join().expect("server must finish");
+    }
+
+    #[test]
+    fn truncated_bodies_are_transport_errors() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("test server must bind");
+        let url = format!(
+            "http://{}/",
+            listener.local_addr().expect("test address must exist")
+        );
+        // Each response promises 100 bytes, sends 50, then hangs up.
+        let server = thread::spawn(move || {
+            for status in ["200 OK", "503 Service Unavailable"] {
+                let (mut socket, _) = listener.accept().expect("request must arrive");
+                let mut head = Vec::new();
+                let mut buffer = [0_u8; 1_024];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = socket.read(&mut buffer).expect("request must be readable");
+                    assert_ne!(read, 0, "request ended before its header");
+                    head.extend_from_slice(&buffer[..read]);
+                }
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: 100\r\nX-Request-Id: 42\r\n\r\n{}",
+                    "x".repeat(50)
+                );
+                socket
+                    .write_all(response.as_bytes())
+                    .expect("response must write");
+            }
+        });
+        let mut client = HyperBackend::new();
+
+        let error =
+            futures_executor::block_on(async { client.get(url.as_str())?.bytes().await.map(drop) })
+                .unwrap_err();
+        assert!(error.is_network_error(), "{error:?}");
+        let interrupted = error.interrupted_body().expect("body was cut short");
+        assert_eq!(interrupted.bytes_received(), 50);
+
+        let error = futures_executor::block_on(async { client.get(url.as_str())?.await.map(drop) })
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response = error.response().expect("the error keeps the response");
+        assert_eq!(response.headers()["x-request-id"], "42");
+        server.join().expect("server must finish");
+    }
 }
@@ -0,0 +1,149 @@
+//! Limiting the connections a [`HyperBackend`](super::HyperBackend) keeps
+//! open to each origin.
+//!
+//! Every new connection holds a permit of its origin until the task driving
+//! it ends, except a shared HTTP/2 connection, which gives its slot back once
+//! it is open. Once the limit is reached, further connections wait for a
+//! permit instead of failing, optionally up to an acquire timeout.
+
+use async_lock::{Semaphore, SemaphoreGuardArc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use super::Shared;
+use crate::timeout::with_timeout;
+
+/// Counters of the connections a [`HyperBackend`](super::HyperBackend)
+/// opened; see [`HyperBackend::pool_stats`](super::HyperBackend::pool_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// Connections being opened or still in use.
+    pub in_flight: usize,
+    /// Requests waiting for a connection because their origin reached
+    /// [`max_connections_per_host`](super::HyperBackend::max_connections_per_host).
+    pub queued: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct ConnectionPool {
+    /// Connections allowed per origin; `None` allows any number.
+    pub(super) limit: Option<usize>,
+    /// How long a request waits for a permit before timing out.
+    pub(super) acquire_timeout: Option<Duration>,
+    hosts: Shared<Mutex<BTreeMap<String, Arc<Host>>>>,
+}
+
+#[derive(Debug)]
+struct Host {
+    permits: Option<Arc<Semaphore>>,
+    in_flight: AtomicUsize,
+    queued: AtomicUsize,
+}
+
+/// Held by the task driving a connection; frees its slot when dropped.
+#[derive(Debug)]
+pub(super) struct ConnectionPermit {
+    host: Arc<Host>,
+    slot: Option<SemaphoreGuardArc>,
+}
+
+impl ConnectionPermit {
+    /// Free the slot of the origin early while the connection still counts
+    /// as in flight.
+    pub(super) fn release_slot(&mut self) {
+        self.slot = None;
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.host.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a waiting request for as long as it waits, even if it is dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConnectionPool {
+    pub(super) const fn new() -> Self {
+        Self {
+            limit: None,
+            acquire_timeout: None,
+            hosts: Shared::new(),
+        }
+    }
+
+    fn hosts(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Arc<Host>>> {
+        self.hosts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for a connection slot of `origin`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Timeout`] when the acquire timeout elapses
+    /// first.
+    pub(super) async fn acquire(&self, origin: &str) -> Result<ConnectionPermit, crate::Error> {
+        let host = Arc::clone(self.hosts().entry(origin.to_owned()).or_insert_with(|| {
+            Arc::new(Host {
+                permits: self
+                    .limit
+                    .map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+                in_flight: AtomicUsize::new(0),
+                queued: AtomicUsize::new(0),
+            })
+        }));
+        let permit = match &host.permits {
+            Some(permits) => Some(self.wait(permits, &host.queued).await?),
+            None => None,
+        };
+        host.in_flight.fetch_add(1, Ordering::SeqCst);
+        Ok(ConnectionPermit { host, slot: permit })
+    }
+
+    /// Take one of `permits`, counted in `queued` while waiting for it.
+    async fn wait(
+        &self,
+        permits: &Arc<Semaphore>,
+        queued: &AtomicUsize,
+    ) -> Result<SemaphoreGuardArc, crate::Error> {
+        if let Some(permit) = permits.try_acquire_arc() {
+            return Ok(permit);
+        }
+        let _queued = Queued::new(queued);
+        match self.acquire_timeout {
+            Some(timeout) => with_timeout(timeout, permits.acquire_arc())
+                .await
+                .map_err(|_| crate::Error::Timeout { partial: None }),
+            None => Ok(permits.acquire_arc().await),
+        }
+    }
+
+    /// The counters summed over every origin.
+    pub(super) fn stats(&self) -> PoolStats {
+        self.hosts()
+            .values()
+            .fold(PoolStats::default(), |stats, host| PoolStats {
+                in_flight: stats.in_flight + host.in_flight.load(Ordering::SeqCst),
+                queued: stats.queued + host.queued.load(Ordering::SeqCst),
+            })
+    }
+}
@@ -0,0 +1,347 @@
+//! Reaching origins through the proxy a [`Proxy`](crate::Proxy) matched.
+//!
+//! Plain `http` requests are forwarded to an HTTP proxy in absolute form.
+//! `https` requests go through a `CONNECT` tunnel so that TLS still runs
+//! end to end with the origin. With the `socks` feature, `socks5` and
+//! `socks5h` proxies tunnel both.
+
+use async_net::TcpStream;
+use futures_util::{AsyncReadExt as _, AsyncWriteExt as _};
+use http::{HeaderValue, header::PROXY_AUTHORIZATION};
+use hyper::http;
+use std::io;
+
+use super::{HyperBackend, HyperError, connect_happy_eyeballs};
+use crate::proxy::Intercept;
+
+/// Longest `CONNECT` response head read before giving up on the proxy.
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+/// Where a request goes instead of its origin.
+#[derive(Debug)]
+pub(super) struct ProxyRoute {
+    kind: Kind,
+    host: String,
+    port: u16,
+    authorization: Option<HeaderValue>,
+    #[cfg(feature = "socks")]
+    credentials: Option<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Http,
+    /// `remote_dns` leaves resolving the origin to the proxy (`socks5h`).
+    #[cfg(feature = "socks")]
+    Socks5 {
+        remote_dns: bool,
+    },
+}
+
+impl ProxyRoute {
+    pub(super) fn new(intercept: &Intercept) -> Result<Self, HyperError> {
+        let uri = intercept.uri();
+        let (kind, default_port) = match uri.scheme_str().unwrap_or("http") {
+            "http" => (Kind::Http, 80),
+            #[cfg(feature = "socks")]
+            "socks5" => (Kind::Socks5 { remote_dns: false }, 1080),
+            #[cfg(feature = "socks")]
+            "socks5h" => (Kind::Socks5 { remote_dns: true }, 1080),
+            other => {
+                return Err(HyperError::InvalidUri(format!(
+                    "unsupported proxy scheme `{other}`"
+                )));
+            }
+        };
+        let host = uri
+            .host()
+            .ok_or_else(|| HyperError::InvalidUri(uri.to_string()))?;
+        Ok(Self {
+            kind,
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port: uri.port_u16().unwrap_or(default_port),
+            authorization: intercept.basic_auth().cloned(),
+            #[cfg(feature = "socks")]
+            credentials: intercept
+                .raw_auth()
+                .map(|(user, pass)| (user.to_string(), pass.to_string())),
+        })
+    }
+
+    /// Whether `request` is sent to the proxy itself rather than through a
+    /// tunnel; if so, it gets the proxy's `Proxy-Authorization`.
+    pub(super) fn forward(&self, request: &mut http::Request<http_kit::Body>) -> bool {
+        if !matches!(self.kind, Kind::Http) || request.uri().scheme_str() == Some("https") {
+            return false;
+        }
+        if let Some(authorization) = &self.authorization {
+            request
+                .headers_mut()
+                .insert(PROXY_AUTHORIZATION, authorization.clone());
+        }
+        true
+    }
+
+    /// Connect to the proxy and, unless it forwards requests, open a tunnel
+    /// to `host:port` through it.
+    pub(super) async fn connect(
+        &self,
+        backend: &HyperBackend,
+        host: &str,
+        port: u16,
+        use_tls: bool,
+    ) -> Result<TcpStream, HyperError> {
+        let mut stream =
+            connect_happy_eyeballs(&self.host, self.port, &backend.dns_cache, &backend.connect)
+                .await?;
+        match self.kind {
+            Kind::Http if !use_tls => {}
+            Kind::Http => self.http_connect(&mut stream, host, port).await?,
+            #[cfg(feature = "socks")]
+            Kind::Socks5 { remote_dns } => {
+                let target = if remote_dns {
+                    socks::Target::Name(host.trim_start_matches('[').trim_end_matches(']'))
+                } else {
+                    socks::Target::Addr(socks::resolve(backend, host, port).await?)
+                };
+                let credentials = self
+                    .credentials
+                    .as_ref()
+                    .map(|(user, pass)| (user.as_str(), pass.as_str()));
+                socks::connect(&mut stream, target, port, credentials)
+                    .await
+                    .map_err(HyperError::Io)?;
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Ask an HTTP proxy for a tunnel with `CONNECT host:port`.
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), HyperError> {
+        let authority = format!("{host}:{port}");
+        let mut head =
+            format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n").into_bytes();
+        if let Some(authorization) = &self.authorization {
+            head.extend_from_slice(b"Proxy-Authorization: ");
+            head.extend_from_slice(authorization.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        stream.write_all(&head).await.map_err(HyperError::Io)?;
+
+        // Read byte by byte: whatever follows the head belongs to the origin.
+        let mut response = Vec::new();
+        let mut byte = [0_u8];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_RESPONSE_HEAD {
+                return Err(proxy_error(format!(
+                    "proxy response to CONNECT {authority} is too long"
+                )));
+            }
+            if stream.read(&mut byte).await.map_err(HyperError::Io)? == 0 {
+                return Err(proxy_error(format!(
+                    "proxy closed the connection during CONNECT {authority}"
+                )));
+            }
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let status = status_line.split(' ').nth(1).unwrap_or_default();
+        if status.len() == 3 && status.starts_with('2') {
+            Ok(())
+        } else {
+            Err(proxy_error(format!(
+                "proxy refused CONNECT {authority}: {status_line}"
+            )))
+        }
+    }
+}
+
+fn proxy_error(message: String) -> HyperError {
+    HyperError::Io(io::Error::new(io::ErrorKind::ConnectionRefused, message))
+}
+
+/// The client side of SOCKS5 (RFC 1928) with username/password
+/// authentication (RFC 1929).
+#[cfg(feature = "socks")]
+mod socks {
+    use async_net::TcpStream;
+    use futures_util::{AsyncReadExt as _, AsyncWriteExt as _, StreamExt as _};
+    use std::{
+        io,
+        net::{IpAddr, SocketAddr},
+    };
+
+    use super::super::{HyperBackend, HyperError, ResolutionEventKind, ResolutionResult};
+
+    const VERSION: u8 = 5;
+    const NO_AUTHENTICATION: u8 = 0;
+    const USERNAME_PASSWORD: u8 = 2;
+    const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+    const CONNECT: u8 = 1;
+    const IPV4: u8 = 1;
+    const DOMAIN_NAME: u8 = 3;
+    const IPV6: u8 = 4;
+
+    /// The origin as sent to the proxy.
+    pub(super) enum Target<'a> {
+        Name(&'a str),
+        Addr(SocketAddr),
+    }
+
+    /// Resolve `host` locally, as `socks5` (without `h`) asks.
+    pub(super) async fn resolve(
+        backend: &HyperBackend,
+        host: &str,
+        port: u16,
+    ) -> Result<SocketAddr, HyperError> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(SocketAddr::new(ip, port));
+        }
+        let mut events = backend.dns_cache.lookup(host, port);
+        let mut failure = None;
+        while let Some(event) = events.next().await {
+            match backend.connect.family.restrict(event.kind) {
+                ResolutionEventKind::SortedSnapshot(ResolutionResult::Addresses(addrs))
+                | ResolutionEventKind::Family {
+                    result: ResolutionResult::Addresses(addrs),
+                    ..
+                } => {
+                    if let Some(addr) = addrs.first() {
+                        return Ok(*addr);
+                    }
+                }
+                ResolutionEventKind::Family {
+                    result: ResolutionResult::Failed(message),
+                    ..
+                } => failure = Some(message),
+                _ => {}
+            }
+        }
+        let message = failure.unwrap_or_else(|| "no addresses found".to_string());
+        Err(HyperError::Dns(
+            backend.dns_cache.record_failure(host, message),
+        ))
+    }
+
+    pub(super) async fn connect(
+        stream: &mut TcpStream,
+        target: Target<'_>,
+        port: u16,
+        credentials: Option<(&str, &str)>,
+    ) -> io::Result<()> {
+        let method = if credentials.is_some() {
+            USERNAME_PASSWORD
+        } else {
+            NO_AUTHENTICATION
+        };
+        stream.write_all(&[VERSION, 1, method]).await?;
+        let mut reply = [0_u8; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != VERSION {
+            return Err(refused("proxy does not speak SOCKS5".to_string()));
+        }
+        match (reply[1], credentials) {
+            (NO_AUTHENTICATION, _) => {}
+            (USERNAME_PASSWORD, Some((user, pass))) => authenticate(stream, user, pass).await?,
+            (NO_ACCEPTABLE_METHOD, _) => {
+                return Err(refused(
+                    "SOCKS5 proxy accepts none of the offered authentication methods".to_string(),
+                ));
+            }
+            (other, _) => {
+                return Err(refused(format!(
+                    "SOCKS5 proxy chose unsupported method {other}"
+                )));
+            }
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0];
+        match target {
+            Target::Addr(SocketAddr::V4(addr)) => {
+                request.push(IPV4);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+            Target::Addr(SocketAddr::V6(addr)) => {
+                request.push(IPV6);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+            Target::Name(name) => {
+                let length = u8::try_from(name.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "host name too long for SOCKS5")
+                })?;
+                request.extend_from_slice(&[DOMAIN_NAME, length]);
+                request.extend_from_slice(name.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0_u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0 {
+            return Err(refused(format!(
+                "SOCKS5 proxy refused the connection: {}",
+                reply_message(reply[1])
+            )));
+        }
+        // Skip the address the proxy bound, then the port.
+        let bound = match reply[3] {
+            IPV4 => 4,
+            IPV6 => 16,
+            DOMAIN_NAME => {
+                let mut length = [0_u8];
+                stream.read_exact(&mut length).await?;
+                usize::from(length[0])
+            }
+            other => return Err(refused(format!("SOCKS5 proxy sent address type {other}"))),
+        };
+        let mut skipped = vec![0_u8; bound + 2];
+        stream.read_exact(&mut skipped).await
+    }
+
+    async fn authenticate(stream: &mut TcpStream, user: &str, pass: &str) -> io::Result<()> {
+        let too_long =
+            |_| io::Error::new(io::ErrorKind::InvalidInput, "SOCKS5 credentials too long");
+        let mut request = vec![1, u8::try_from(user.len()).map_err(too_long)?];
+        request.extend_from_slice(user.as_bytes());
+        request.push(u8::try_from(pass.len()).map_err(too_long)?);
+        request.extend_from_slice(pass.as_bytes());
+        stream.write_all(&request).await?;
+        let mut reply = [0_u8; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] == 0 {
+            Ok(())
+        } else {
+            Err(refused("SOCKS5 proxy rejected the credentials".to_string()))
+        }
+    }
+
+    fn refused(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::ConnectionRefused, message)
+    }
+
+    const fn reply_message(code: u8) -> &'static str {
+        match code {
+            1 => "general failure",
+            2 => "not allowed by ruleset",
+            3 => "network unreachable",
+            4 => "host unreachable",
+            5 => "connection refused",
+            6 => "TTL expired",
+            7 => "command not supported",
+            8 => "address type not supported",
+            _ => "unknown error",
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! A backend answering from canned responses, for tests.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use http_kit::{Body, Endpoint, Method, Request, Response, StatusCode};
+
+use super::{Capabilities, ClientBackend};
+use crate::{
+    Client, ResponseExt,
+    recorder::{DEFAULT_BODY_LIMIT, RecordedRequest},
+};
+
+type Responder = dyn Fn(&Request) -> Response + Send + Sync;
+
+#[derive(Clone)]
+struct Rule {
+    method: Method,
+    pattern: String,
+    respond: Arc<Responder>,
+}
+
+/// A backend that answers requests from registered rules instead of the
+/// network, and records every request it receives.
+///
+/// Rules are tried in the order they were added; the first one whose method
+/// and URI pattern match produces the response. Requests no rule matches get
+/// a `404 Not Found`. Like the real backends, 4xx and 5xx responses are
+/// reported as [`crate::Error::Http`] unless
+/// [`with_error_for_status`](Self::with_error_for_status) turns that off.
+///
+/// A pattern starting with `/` is matched against the path alone; any other
+/// pattern against the whole URI, such as `https://api.example.com/users/*`.
+/// In both, `*` stands for any run of characters except `/`.
+///
+/// Clones share the recorded requests.
+///
+/// ```rust
+/// # futures_executor::block_on(async {
+/// use zenwave::{Body, Client, Method, Response, backend::MockBackend};
+///
+/// let backend = MockBackend::new().on(Method::GET, "/users/*", |request| {
+///     Response::new(Body::from(format!("user at {}", request.uri().path())))
+/// });
+/// let mut client = backend.clone().follow_redirect();
+/// let body = client.get("https://api.example.com/users/42").unwrap().string().await.unwrap();
+/// assert_eq!(body, "user at /users/42");
+/// assert_eq!(backend.requests().len(), 1);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct MockBackend {
+    rules: Vec<Rule>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    error_for_status: bool,
+}
+
+impl core::fmt::Debug for MockBackend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MockBackend")
+            .field(
+                "rules",
+                &self
+                    .rules
+                    .iter()
+                    .map(|rule| format!("{} {}", rule.method, rule.pattern))
+                    .collect::<Vec<_>>(),
+            )
+            .field("requests", &self.lock().len())
+            .field("error_for_status", &self.error_for_status)
+            .finish()
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    /// Create a backend without rules, answering every request with `404`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            requests: Arc::default(),
+            error_for_status: true,
+        }
+    }
+
+    /// Answer `method` requests whose URI matches `pattern` with the
+    /// response `respond` builds from the request.
+    #[must_use]
+    pub fn on(
+        mut self,
+        method: Method,
+        pattern: impl Into<String>,
+        respond: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        self.rules.push(Rule {
+            method,
+            pattern: pattern.into(),
+            respond: Arc::new(respond),
+        });
+        self
+    }
+
+    /// Choose whether 4xx and 5xx responses are returned as errors, as the
+    /// real backends do by default.
+    #[must_use]
+    pub const fn with_error_for_status(mut self, enabled: bool) -> Self {
+        self.error_for_status = enabled;
+        self
+    }
+
+    /// The requests received so far, oldest first.
+    ///
+    /// Check them with [`RecordedRequest::matches`].
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().clone()
+    }
+
+    /// Forget every recorded request.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<RecordedRequest>> {
+        self.requests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn answer(&self, request: &Request) -> Response {
+        let uri = request.uri();
+        let rule = self.rules.iter().find(|rule| {
+            rule.method == request.method()
+                && if rule.pattern.starts_with('/') {
+                    matches(rule.pattern.as_bytes(), uri.path().as_bytes())
+                } else {
+                    matches(rule.pattern.as_bytes(), uri.to_string().as_bytes())
+                }
+        });
+        if let Some(rule) = rule {
+            return (rule.respond)(request);
+        }
+        let mut response = Response::new(Body::from(format!(
+            "no mock for {} {uri}",
+            request.method()
+        )));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        response
+    }
+}
+
+/// Match `text` against `pattern`, where `*` stands for any run of
+/// characters except `/`.
+fn matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => {
+            let segment = text
+                .iter()
+                .position(|&byte| byte == b'/')
+                .unwrap_or(text.len());
+            (0..=segment).any(|skip| matches(rest, &text[skip..]))
+        }
+        Some((&byte, rest)) => text
+            .split_first()
+            .is_some_and(|(&first, text)| first == byte && matches(rest, text)),
+    }
+}
+
+impl Endpoint for MockBackend {
+    type Error = crate::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let recorded = RecordedRequest::capture(request, DEFAULT_BODY_LIMIT).await?;
+        self.lock().push(recorded);
+        let response = self.answer(request);
+        if super::is_error_status(response.status(), self.error_for_status) {
+            return response.error_for_status().await;
+        }
+        Ok(response)
+    }
+}
+
+impl Client for MockBackend {}
+
+impl ClientBackend for MockBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use http_kit::{Body, HttpError, Method, Response, StatusCode};
+
+    use super::{MockBackend, matches};
+    use crate::{Client, recorder::RequestMatcher};
+
+    fn text(body: &'static str) -> Response {
+        Response::new(Body::from(body))
+    }
+
+    #[test]
+    fn patterns_match_within_segments() {
+        assert!(matches(b"/users/*", b"/users/42"));
+        assert!(matches(b"/users/*/posts", b"/users/42/posts"));
+        assert!(matches(b"/files/*.json", b"/files/a.json"));
+        assert!(!matches(b"/users/*", b"/users/42/posts"));
+        assert!(!matches(b"/users/*", b"/accounts/42"));
+    }
+
+    #[test]
+    fn first_matching_rule_answers() {
+        let mut backend = MockBackend::new()
+            .on(Method::GET, "https://other.example/users/*", |_| {
+                text("elsewhere")
+            })
+            .on(Method::GET, "/users/me", |_| text("me"))
+            .on(Method::GET, "/users/*", |_| text("someone"))
+            .on(Method::POST, "/users/*", |_| text("created"));
+
+        block_on(async {
+            for (method, uri, expected) in [
+                (Method::GET, "https://api.example/users/me", "me"),
+                (
+                    Method::GET,
+                    "https://api.example/users/42?full=1",
+                    "someone",
+                ),
+                (Method::POST, "https://api.example/users/42", "created"),
+                (Method::GET, "https://other.example/users/me", "elsewhere"),
+            ] {
+                let body = backend.method(method, uri).unwrap().string().await.unwrap();
+                assert_eq!(body, expected, "{uri}");
+            }
+        });
+    }
+
+    #[test]
+    fn unmatched_requests_fall_through_to_404() {
+        let mut backend = MockBackend::new().on(Method::GET, "/users/*", |_| text("user"));
+
+        block_on(async {
+            let error = backend
+                .delete("https://api.example/users/42")
+                .unwrap()
+                .await
+                .unwrap_err();
+            assert_eq!(error.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                error.response_body(),
+                Some("no mock for DELETE https://api.example/users/42")
+            );
+
+            let mut backend = backend.with_error_for_status(false);
+            let response = backend
+                .get("https://api.example/teams")
+                .unwrap()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn requests_are_recorded() {
+        let backend = MockBackend::new().on(Method::POST, "/items", |_| text("ok"));
+        let mut client = backend.clone().bearer_auth("secret");
+
+        block_on(async {
+            client
+                .post("https://api.example/items")
+                .unwrap()
+                .bytes_body(b"widget".to_vec())
+                .await
+                .unwrap();
+        });
+
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0].matches(
+                &RequestMatcher::new()
+                    .method(Method::POST)
+                    .path("/items")
+                    .header("authorization", "Bearer secret")
+                    .body("widget")
+            )
+        );
+        backend.clear();
+        assert!(backend.requests().is_empty());
+    }
+}
@@ -15,16 +15,20 @@
 //! On native platforms, users can choose their preferred backend:
 //!
 //! - **`hyper-backend`** (default): Uses hyper with async-io/async-net. Supports
-//!   both `rustls` (default) and `native-tls` for TLS.
+//!   both `rustls` (default) and `native-tls` for TLS, and proxies with the
+//!   `proxy` feature.
 //! - **`curl-backend`**: Uses libcurl via the `curl` crate. Includes proxy support.
 //! - **`apple-backend`**: Uses Apple's native `NSURLSession` (macOS/iOS only).
 //!
+//! With the `test-util` feature, `MockBackend` answers from canned responses
+//! instead of the network.
+//!
 //! The default configuration uses `hyper-backend` with `rustls` TLS.
 
 #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
 mod hyper;
 #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
-pub use hyper::HyperBackend;
+pub use hyper::{AddressFamily, HyperBackend, HyperBackendBuilder, PoolStats, Resolver};
 
 #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
 mod curl;
@@ -36,6 +40,173 @@ mod apple;
 #[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
 pub use apple::AppleBackend;
 
+mod any;
+pub use any::{AnyBackend, BACKEND_ENV};
+
+#[cfg(feature = "test-util")]
+mod mock;
+#[cfg(feature = "test-util")]
+pub use mock::MockBackend;
+
+use futures_util::{StreamExt, stream};
+use http_kit::{Body, utils::Bytes};
+
+/// Features a backend supports, as reported by [`ClientBackend::capabilities`].
+///
+/// Libraries built on zenwave can consult this at runtime instead of assuming
+/// every backend behaves like the default one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// A proxy can be configured on the backend.
+    pub proxy: bool,
+    /// Request bodies are sent as they are produced instead of being buffered.
+    pub streaming_upload: bool,
+    /// Response bodies are delivered as they arrive instead of being buffered.
+    pub streaming_download: bool,
+    /// HTTP/2 can be negotiated.
+    pub http2: bool,
+    /// TLS settings (roots, client certificates, versions) can be configured.
+    pub tls_config: bool,
+}
+
+/// A transport that actually sends requests, as opposed to middleware.
+pub trait ClientBackend: crate::Client {
+    /// Report which optional features this backend supports.
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// Request extension holding trailer fields to send after the request body.
+///
+/// Set through `RequestBuilder::trailers`.
+/// The hyper backend sends them with chunked transfer encoding and announces
+/// their names in a `Trailer` header; other backends ignore them.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTrailers(http_kit::header::HeaderMap);
+
+impl RequestTrailers {
+    /// Wrap the trailer fields to send.
+    #[must_use]
+    pub const fn new(trailers: http_kit::header::HeaderMap) -> Self {
+        Self(trailers)
+    }
+
+    /// The trailer fields.
+    #[must_use]
+    pub const fn headers(&self) -> &http_kit::header::HeaderMap {
+        &self.0
+    }
+}
+
+/// Request extension asking the backend to send the request on a new
+/// connection instead of a pooled one.
+///
+/// Set through `RequestBuilder::fresh_connection`. A pooled connection the
+/// server is closing at the same moment can fail mid-send, leaving it unknown
+/// whether a non-idempotent request was applied; a fresh connection avoids
+/// that race. `HyperBackend` skips the HTTP/2 connection it shares between
+/// requests to an origin and keeps the new connection to this request; its
+/// HTTP/1 connections serve one request each anyway. `CurlBackend` opens a
+/// new connection for every request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FreshConnection;
+
+/// Response extension describing the connection that carried the response.
+///
+/// Read it with [`ResponseExt::connection_info`](crate::ResponseExt::connection_info).
+/// `HyperBackend` fills in every field it knows; `CurlBackend` reports the
+/// addresses only. Through a proxy, `peer_addr` is the proxy's address.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// Local address of the connection.
+    pub local_addr: Option<std::net::SocketAddr>,
+    /// Address the connection went to.
+    pub peer_addr: Option<std::net::SocketAddr>,
+    /// Negotiated TLS version; `None` for plain connections or when the TLS
+    /// implementation does not report it.
+    pub tls_version: Option<crate::tls::TlsVersion>,
+    /// Protocol agreed on through ALPN, such as `h2`.
+    pub alpn: Option<String>,
+    /// Whether the connection had already carried another request.
+    pub reused: bool,
+}
+
+/// Whether a backend should surface `status` as an error response.
+///
+/// Backends convert 4xx/5xx statuses into [`crate::Error::Http`] unless the
+/// caller turned that off with `with_error_for_status(false)`.
+pub(crate) fn is_error_status(status: http_kit::StatusCode, error_for_status: bool) -> bool {
+    error_for_status && (status.is_client_error() || status.is_server_error())
+}
+
+/// Bytes of an error response body captured into [`crate::Error::Http`] by
+/// default; see `with_error_body_limit` on the backends.
+pub(crate) const DEFAULT_ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Appended to captured error bodies cut at the limit.
+const TRUNCATION_MARKER: &str = "…";
+
+/// The text of an error body, cut to at most `limit` bytes.
+///
+/// A cut never splits a character; text that is cut gets a trailing `…`.
+/// Bodies that are not UTF-8 have no text.
+pub(crate) fn error_body_text(bytes: &[u8], limit: usize) -> Option<String> {
+    if bytes.len() <= limit {
+        return core::str::from_utf8(bytes).ok().map(str::to_owned);
+    }
+    let head = &bytes[..limit];
+    let text = match core::str::from_utf8(head) {
+        Ok(text) => text,
+        // Only the character straddling the limit is incomplete.
+        Err(error) if error.error_len().is_none() => {
+            core::str::from_utf8(&head[..error.valid_up_to()]).expect("prefix was validated")
+        }
+        Err(_) => return None,
+    };
+    Some(format!("{text}{TRUNCATION_MARKER}"))
+}
+
+/// Capture the text of an error response body, reading at most `limit + 1`
+/// bytes so a huge body is never buffered.
+///
+/// `body` is put back whole: the bytes read here followed by the rest of
+/// the original stream.
+pub(crate) async fn capture_error_body(body: &mut Body, limit: usize) -> Option<String> {
+    let mut rest = core::mem::replace(body, Body::empty());
+    let mut head = Vec::new();
+    let mut failed = None;
+    let mut finished = false;
+    while head.len() <= limit {
+        match rest.next().await {
+            Some(Ok(chunk)) => head.extend_from_slice(&chunk),
+            Some(Err(error)) => {
+                failed = Some(error);
+                break;
+            }
+            None => {
+                finished = true;
+                break;
+            }
+        }
+    }
+    let text = if failed.is_some() {
+        None
+    } else {
+        error_body_text(&head, limit)
+    };
+    let head = Bytes::from(head);
+    *body = if finished {
+        Body::from(head)
+    } else {
+        let head = stream::once(async move { Ok(head) });
+        let failed = stream::iter(failed.map(Err));
+        Body::from_stream(head.chain(failed).chain(rest))
+    };
+    text
+}
+
 // ============================================================================
 // Default backend selection for native platforms (non-wasm32)
 // ============================================================================
@@ -135,3 +306,39 @@ compile_error!(
      The web backend using the browser's Fetch API is always used automatically. \
      Please remove the `curl-backend` feature when targeting wasm32."
 );
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{capture_error_body, error_body_text};
+    use futures_util::stream;
+    use http_kit::{Body, utils::Bytes};
+    use std::convert::Infallible;
+
+    #[test]
+    fn error_body_text_cuts_at_the_limit() {
+        assert_eq!(error_body_text(b"short", 5).as_deref(), Some("short"));
+        assert_eq!(error_body_text(b"too long", 3).as_deref(), Some("too…"));
+        assert_eq!(error_body_text(b"too long", 0).as_deref(), Some("…"));
+        assert_eq!(error_body_text(&[0xff, b'a'], 8), None);
+    }
+
+    #[test]
+    fn error_body_text_keeps_characters_whole() {
+        // "é" is two bytes; a limit inside it drops the character.
+        assert_eq!(error_body_text("aé!".as_bytes(), 2).as_deref(), Some("a…"));
+        assert_eq!(error_body_text("aé!".as_bytes(), 3).as_deref(), Some("aé…"));
+    }
+
+    #[test]
+    fn capture_reads_only_up_to_the_limit_and_restores_the_body() {
+        let chunks = ["0123", "4567", "89ab", "cdef"]
+            .map(|chunk| Ok::<_, Infallible>(Bytes::from_static(chunk.as_bytes())));
+        let mut body = Body::from_stream(stream::iter(chunks));
+
+        async_io::block_on(async {
+            let text = capture_error_body(&mut body, 6).await;
+            assert_eq!(text.as_deref(), Some("012345…"));
+            assert_eq!(body.into_string().await.unwrap(), "0123456789abcdef");
+        });
+    }
+}
@@ -18,10 +18,13 @@ use web_sys::{
     wasm_bindgen::{JsCast, JsValue},
 };
 
+use super::{Capabilities, ClientBackend};
 use crate::{Client, error::HttpErrorResponse};
 /// HTTP client backend for browser environments using `fetch`.
 pub struct WebBackend {
     window: SingleThreaded<Window>,
+    error_for_status: bool,
+    error_body_limit: usize,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -140,8 +143,32 @@ impl WebBackend {
 
         Self {
             window: SingleThreaded(window),
+            error_for_status: true,
+            error_body_limit: super::DEFAULT_ERROR_BODY_LIMIT,
         }
     }
+
+    /// Choose whether 4xx/5xx responses are returned as [`crate::Error::Http`].
+    ///
+    /// Enabled by default. When disabled, every response is returned as `Ok`
+    /// so error bodies can be inspected like any other response.
+    #[must_use]
+    pub fn with_error_for_status(mut self, enabled: bool) -> Self {
+        self.error_for_status = enabled;
+        self
+    }
+
+    /// Capture at most `limit` bytes of an error response body into
+    /// [`crate::Error::Http`]; longer bodies are cut and end with `…`.
+    ///
+    /// This keeps a server answering with a huge error payload from being
+    /// buffered in memory. The response kept in the error still holds the
+    /// whole body. Defaults to 64 KiB.
+    #[must_use]
+    pub fn with_error_body_limit(mut self, limit: usize) -> Self {
+        self.error_body_limit = limit;
+        self
+    }
 }
 
 impl Default for WebBackend {
@@ -156,13 +183,22 @@ impl Endpoint for WebBackend {
         &mut self,
         request: &mut http_kit::Request,
     ) -> Result<http_kit::Response, Self::Error> {
-        fetch(&self.window, request).await.map_err(Into::into)
+        fetch(
+            &self.window,
+            request,
+            self.error_for_status,
+            self.error_body_limit,
+        )
+        .await
+        .map_err(Into::into)
     }
 }
 
 fn fetch(
     window: &Window,
     request: &mut http_kit::Request,
+    error_for_status: bool,
+    error_body_limit: usize,
 ) -> impl Future<Output = Result<http_kit::Response, WebError>> + Send {
     SingleThreaded(async move {
         let request_init = web_sys::RequestInit::new();
@@ -282,19 +318,14 @@ fn fetch(
             })
             .unwrap_or_else(http_kit::Body::empty);
 
-        let is_error = status.is_client_error() || status.is_server_error();
+        let is_error = super::is_error_status(status, error_for_status);
         let mut response: http::Response<http_kit::Body> = http::Response::new(body);
 
         *response.headers_mut() = headers;
         *response.status_mut() = status;
 
         if is_error {
-            let body = response
-                .body_mut()
-                .as_str()
-                .await
-                .ok()
-                .map(|text| text.to_owned());
+            let body = super::capture_error_body(response.body_mut(), error_body_limit).await;
             return Err(WebError::remote(status, body, response));
         }
         Ok(response)
@@ -310,3 +341,16 @@ fn transport_error(message: impl Into<String>) -> io::Error {
 }
 
 impl Client for WebBackend {}
+
+impl ClientBackend for WebBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            proxy: false,
+            streaming_upload: true,
+            streaming_download: true,
+            // The browser negotiates HTTP/2 on its own.
+            http2: true,
+            tls_config: false,
+        }
+    }
+}
@@ -5,10 +5,13 @@ use std::{
     time::{Duration, Instant, SystemTime},
 };
 
-use http::{HeaderMap, HeaderValue, Method, Response as HttpResponse, StatusCode, header};
+use http::{HeaderMap, HeaderValue, Method, Response as HttpResponse, StatusCode, Uri, header};
 use httpdate::parse_http_date;
+use sha2::{Digest, Sha256};
 
 use http_kit::utils::Bytes;
+
+use crate::site_data::{self, ClearSiteData};
 use http_kit::{Endpoint, HttpError, Middleware, Request, Response, middleware::MiddlewareError};
 
 /// Middleware implementing an in-memory HTTP cache.
@@ -19,6 +22,7 @@ use http_kit::{Endpoint, HttpError, Middleware, Request, Response, middleware::M
 #[derive(Debug, Default)]
 pub struct Cache {
     entries: HashMap<String, CachedResponse>,
+    digest_revalidation: bool,
 }
 
 impl Cache {
@@ -27,9 +31,44 @@ impl Cache {
     pub fn new() -> Self {
         Self {
             entries: HashMap::new(),
+            digest_revalidation: false,
         }
     }
 
+    /// Enable content-addressed revalidation.
+    ///
+    /// A SHA-256 digest of every stored body is kept alongside the entry. When
+    /// a revalidation request gets a full `200` instead of `304`, but the
+    /// response proves it is unchanged, the new body is dropped unread and the
+    /// cached bytes are served instead. A `Content-Digest`/`Digest` `sha-256`
+    /// value must match the stored body whenever the response carries one; a
+    /// strong `ETag` equal to the cached one is only trusted without it.
+    ///
+    /// Dropping an unread body aborts the transfer, so the backend closes the
+    /// connection or resets the stream rather than reusing it, which is
+    /// cheaper than downloading a large body again.
+    #[must_use]
+    pub const fn digest_revalidation(mut self, enabled: bool) -> Self {
+        self.digest_revalidation = enabled;
+        self
+    }
+
+    /// Evict every entry of the origin of `uri` when `response` carries
+    /// `Clear-Site-Data: "cache"`, returning whether it did.
+    fn clear_site_data(&mut self, uri: &Uri, response: &Response) -> bool {
+        let Some(directives) = ClearSiteData::from_headers(response.headers()) else {
+            return false;
+        };
+        if !directives.cache {
+            return false;
+        }
+        if let Some(origin) = site_data::origin(&uri.to_string()) {
+            self.entries
+                .retain(|key, _| site_data::origin(key).as_ref() != Some(&origin));
+        }
+        true
+    }
+
     fn cache_key(request: &Request) -> Option<String> {
         if *request.method() != Method::GET {
             return None;
@@ -45,20 +84,27 @@ impl Middleware for Cache {
         request: &mut Request,
         mut next: E,
     ) -> Result<Response, http_kit::middleware::MiddlewareError<E::Error, Self::Error>> {
+        // Backends consume the request while sending it.
+        let uri = request.uri().clone();
         let Some(key) = Self::cache_key(request) else {
-            return next
+            let response = next
                 .respond(request)
                 .await
-                .map_err(MiddlewareError::Endpoint);
+                .map_err(MiddlewareError::Endpoint)?;
+            self.clear_site_data(&uri, &response);
+            return Ok(response);
         };
 
         let request_cc = CacheControl::from_header_map(request.headers());
+        let auth_present = request.headers().contains_key(header::AUTHORIZATION);
         if request_cc.no_store {
             self.entries.remove(&key);
-            return next
+            let response = next
                 .respond(request)
                 .await
-                .map_err(MiddlewareError::Endpoint);
+                .map_err(MiddlewareError::Endpoint)?;
+            self.clear_site_data(&uri, &response);
+            return Ok(response);
         }
 
         let now = Instant::now();
@@ -75,9 +121,10 @@ impl Middleware for Cache {
             let entry_requires_revalidation = entry.must_revalidate || !entry.is_fresh(now);
             let needs_revalidation = request_cc.no_cache || entry_requires_revalidation;
             if needs_revalidation && entry.can_revalidate() {
-                let owned_entry = self.entries.remove(&key).unwrap();
-                owned_entry.apply_conditional_headers(request.headers_mut());
-                cached_entry = Some(owned_entry);
+                // The entry stays stored until the outcome is known: a failed
+                // revalidation that gets retried must still find it.
+                entry.apply_conditional_headers(request.headers_mut());
+                cached_entry = Some(entry.clone());
             } else if entry_requires_revalidation && !entry.can_revalidate() {
                 self.entries.remove(&key);
             }
@@ -87,10 +134,14 @@ impl Middleware for Cache {
             .respond(request)
             .await
             .map_err(MiddlewareError::Endpoint)?;
+        if self.clear_site_data(&uri, &response) {
+            // Nothing from before the clear may be served again.
+            return Ok(response);
+        }
         if response.status() == StatusCode::NOT_MODIFIED {
             if let Some(mut entry) = cached_entry {
                 entry.update_from_304(&response, now);
-                let response = entry.to_response(now);
+                let response = entry.to_response(Instant::now());
                 self.entries.insert(key, entry);
                 return Ok(response);
             }
@@ -99,17 +150,36 @@ impl Middleware for Cache {
             return Ok(response);
         }
 
+        if let Some(mut entry) = cached_entry
+            && response.status() == StatusCode::OK
+            && entry.matches_unchanged(&response)
+        {
+            // Same representation as the cached one: drop the body unread and
+            // refresh the entry as if the server had replied 304.
+            entry.update_from_304(&response, now);
+            drop(response);
+            let response = entry.to_response(Instant::now());
+            self.entries.insert(key, entry);
+            return Ok(response);
+        }
+
         let response_cc = CacheControl::from_header_map(response.headers());
-        let auth_present = request.headers().contains_key(header::AUTHORIZATION);
         let allow_shared = !auth_present || response_cc.public;
-
-        if allow_shared && !response_cc.no_store {
-            let (response, entry) =
-                CachedResponse::from_response(response, response_cc, now, request_cc.no_cache)
-                    .await
-                    .map_err(MiddlewareError::Middleware)?;
+        // A stored 503 would be served to the retries meant to get past it.
+        let storable = !response.status().is_server_error();
+
+        if allow_shared && storable && !response_cc.no_store {
+            let (response, entry) = CachedResponse::from_response(
+                response,
+                response_cc,
+                now,
+                request_cc.no_cache,
+                self.digest_revalidation,
+            )
+            .await
+            .map_err(MiddlewareError::Middleware)?;
             if let Some(entry) = entry {
-                let result = entry.to_response(now);
+                let result = entry.to_response(Instant::now());
                 self.entries.insert(key, entry);
                 return Ok(result);
             }
@@ -125,11 +195,18 @@ struct CachedResponse {
     status: StatusCode,
     headers: HeaderMap,
     body: Bytes,
+    /// When the request that produced the entry was sent, so time spent
+    /// waiting for the response, retries included, counts towards its age
+    /// (the `response_delay` of RFC 9111 §4.2.3).
     stored_at: Instant,
+    /// The `Age` the response already had when it arrived.
+    initial_age: Duration,
     freshness: Option<Duration>,
     must_revalidate: bool,
     etag: Option<HeaderValue>,
     last_modified: Option<HeaderValue>,
+    /// SHA-256 of `body`, kept only when digest revalidation is enabled.
+    digest: Option<[u8; 32]>,
 }
 
 /// Errors that can occur while caching HTTP responses.
@@ -163,6 +240,7 @@ impl CachedResponse {
         directives: CacheControl,
         now: Instant,
         request_no_cache: bool,
+        keep_digest: bool,
     ) -> Result<(Response, Option<Self>), CacheError> {
         let (mut parts, body) = response.into_parts();
         let etag = parts.headers.get(header::ETAG).cloned();
@@ -186,6 +264,8 @@ impl CachedResponse {
         }
 
         let bytes = body.into_bytes().await?;
+        let digest = keep_digest.then(|| Sha256::digest(&bytes).into());
+        let initial_age = age_header(&parts.headers);
         parts.headers.remove(header::AGE);
         let response = HttpResponse::from_parts(parts, http_kit::Body::from(bytes.clone()));
 
@@ -196,17 +276,40 @@ impl CachedResponse {
                 headers: headers_snapshot,
                 body: bytes,
                 stored_at: now,
+                initial_age,
                 freshness,
                 must_revalidate,
                 etag,
                 last_modified,
+                digest,
             }),
         ))
     }
 
+    /// Whether a full response received while revalidating carries a strong
+    /// validator proving it is identical to the cached body.
+    fn matches_unchanged(&self, response: &Response) -> bool {
+        let Some(digest) = self.digest else {
+            return false;
+        };
+
+        // A digest the server sent always wins over the ETag.
+        if let Some(sent) = sha256_from_headers(response.headers()) {
+            return sent == digest;
+        }
+
+        match (&self.etag, response.headers().get(header::ETAG)) {
+            (Some(cached), Some(fresh)) => !fresh.as_bytes().starts_with(b"W/") && cached == fresh,
+            _ => false,
+        }
+    }
+
+    fn age(&self, now: Instant) -> Duration {
+        self.initial_age + now.saturating_duration_since(self.stored_at)
+    }
+
     fn is_fresh(&self, now: Instant) -> bool {
-        self.freshness
-            .is_some_and(|fresh| now.duration_since(self.stored_at) < fresh)
+        self.freshness.is_some_and(|fresh| self.age(now) < fresh)
     }
 
     const fn can_revalidate(&self) -> bool {
@@ -224,6 +327,7 @@ impl CachedResponse {
 
     fn update_from_304(&mut self, response: &Response, now: Instant) {
         self.stored_at = now;
+        self.initial_age = age_header(response.headers());
         for name in &[
             header::CACHE_CONTROL,
             header::ETAG,
@@ -253,7 +357,7 @@ impl CachedResponse {
         let mut headers = self.headers.clone();
         headers.insert(
             header::AGE,
-            HeaderValue::from_str(&now.duration_since(self.stored_at).as_secs().to_string())
+            HeaderValue::from_str(&self.age(now).as_secs().to_string())
                 .unwrap_or_else(|_| HeaderValue::from_static("0")),
         );
 
@@ -267,6 +371,46 @@ impl CachedResponse {
     }
 }
 
+/// The `Age` header in `headers`, zero when missing or malformed.
+fn age_header(headers: &HeaderMap) -> Duration {
+    headers
+        .get(header::AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map_or(Duration::ZERO, Duration::from_secs)
+}
+
+/// Extract a `sha-256` digest from `Content-Digest` (RFC 9530) or the older
+/// `Digest` (RFC 3230) header.
+fn sha256_from_headers(headers: &HeaderMap) -> Option<[u8; 32]> {
+    use base64::Engine;
+
+    let values = headers
+        .get_all("content-digest")
+        .iter()
+        .chain(headers.get_all("digest").iter());
+    for value in values {
+        let Ok(text) = value.to_str() else {
+            continue;
+        };
+        for item in text.split(',') {
+            let Some((algorithm, encoded)) = item.trim().split_once('=') else {
+                continue;
+            };
+            if !algorithm.trim().eq_ignore_ascii_case("sha-256") {
+                continue;
+            }
+            let encoded = encoded.trim().trim_matches(':');
+            if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(encoded)
+                && let Ok(digest) = <[u8; 32]>::try_from(decoded.as_slice())
+            {
+                return Some(digest);
+            }
+        }
+    }
+    None
+}
+
 #[derive(Debug, Default, Clone)]
 #[allow(clippy::struct_excessive_bools)]
 struct CacheControl {
@@ -311,7 +455,7 @@ impl CacheControl {
 mod tests {
     use super::*;
     use http::Request as HttpRequest;
-    use http_kit::{Body, Method};
+    use http_kit::{Body, Method, endpoint::WithMiddleware};
     use std::{
         convert::Infallible,
         sync::{
@@ -377,6 +521,317 @@ mod tests {
         });
     }
 
+    #[test]
+    fn digest_revalidation_serves_cached_bytes_for_unchanged_200() {
+        async_io::block_on(async {
+            let backend = UnconditionalEndpoint::new(UNCHANGED_DIGEST);
+            let mut cache = Cache::new().digest_revalidation(true);
+
+            let mut request = new_request();
+            let mut endpoint = backend.clone();
+            let response = cache.handle(&mut request, &mut endpoint).await.unwrap();
+            assert_eq!(body_text(response).await, "payload");
+            assert_eq!(backend.bodies_read(), 1);
+
+            let mut request = new_request();
+            let mut endpoint = backend.clone();
+            let response = cache.handle(&mut request, &mut endpoint).await.unwrap();
+            assert_eq!(body_text(response).await, "payload");
+            assert_eq!(backend.calls(), 2);
+            // The second body was dropped without being polled.
+            assert_eq!(backend.bodies_read(), 1);
+        });
+    }
+
+    #[test]
+    fn digest_revalidation_replaces_entry_when_digest_differs() {
+        async_io::block_on(async {
+            let backend = UnconditionalEndpoint::new(
+                "sha-256=:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=:",
+            );
+            let mut cache = Cache::new().digest_revalidation(true);
+
+            for _ in 0..2 {
+                let mut request = new_request();
+                let mut endpoint = backend.clone();
+                let response = cache.handle(&mut request, &mut endpoint).await.unwrap();
+                assert_eq!(body_text(response).await, "payload");
+            }
+            assert_eq!(backend.bodies_read(), 2);
+        });
+    }
+
+    #[test]
+    fn digest_revalidation_checks_the_digest_despite_a_matching_etag() {
+        async_io::block_on(async {
+            let backend = UnconditionalEndpoint::new(
+                "sha-256=:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=:",
+            )
+            .with_etag("\"v1\"");
+            let mut cache = Cache::new().digest_revalidation(true);
+
+            for _ in 0..2 {
+                let mut request = new_request();
+                let mut endpoint = backend.clone();
+                let response = cache.handle(&mut request, &mut endpoint).await.unwrap();
+                assert_eq!(body_text(response).await, "payload");
+            }
+            assert_eq!(backend.bodies_read(), 2);
+        });
+    }
+
+    #[test]
+    fn digest_revalidation_is_off_by_default() {
+        async_io::block_on(async {
+            let backend = UnconditionalEndpoint::new(UNCHANGED_DIGEST);
+            let mut cache = Cache::new();
+
+            for _ in 0..2 {
+                let mut request = new_request();
+                let mut endpoint = backend.clone();
+                let response = cache.handle(&mut request, &mut endpoint).await.unwrap();
+                assert_eq!(body_text(response).await, "payload");
+            }
+            assert_eq!(backend.bodies_read(), 2);
+        });
+    }
+
+    #[test]
+    fn clear_site_data_evicts_the_origin() {
+        async_io::block_on(async {
+            let backend = CountingEndpoint::new("hello", &[("cache-control", "max-age=60")]);
+            let mut cache = Cache::new();
+            let other = || {
+                HttpRequest::get("http://other.example/data")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+            cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            cache
+                .handle(&mut other(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(backend.calls(), 2);
+
+            let logout = CountingEndpoint::new("", &[("clear-site-data", "\"cache\"")]);
+            let mut request = HttpRequest::post("http://example.com/logout")
+                .body(Body::empty())
+                .unwrap();
+            cache
+                .handle(&mut request, &mut logout.clone())
+                .await
+                .unwrap();
+
+            cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            cache
+                .handle(&mut other(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(backend.calls(), 3, "only example.com was refetched");
+        });
+    }
+
+    /// One scripted network outcome.
+    enum Scripted {
+        Fail,
+        Respond(StatusCode, &'static [(&'static str, &'static str)]),
+    }
+
+    /// Plays back a script of outcomes in order, counting network hits.
+    #[derive(Clone)]
+    struct ScriptedEndpoint {
+        script: Arc<std::sync::Mutex<std::collections::VecDeque<Scripted>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ScriptedEndpoint {
+        fn new(script: impl IntoIterator<Item = Scripted>) -> Self {
+            Self {
+                script: Arc::new(std::sync::Mutex::new(script.into_iter().collect())),
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Endpoint for ScriptedEndpoint {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let next = self.script.lock().unwrap().pop_front();
+            match next.expect("script exhausted") {
+                Scripted::Fail => Err(crate::Error::Transport("connection reset".into())),
+                Scripted::Respond(status, headers) => {
+                    let mut builder = HttpResponse::builder().status(status);
+                    for (name, value) in headers {
+                        builder = builder.header(*name, *value);
+                    }
+                    let body = if status == StatusCode::NOT_MODIFIED {
+                        Body::empty()
+                    } else {
+                        Body::from("v1")
+                    };
+                    Ok(builder.body(body).unwrap())
+                }
+            }
+        }
+    }
+
+    impl crate::Client for ScriptedEndpoint {}
+
+    fn age(response: &Response) -> u64 {
+        response.headers()[header::AGE]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn retries_below_the_cache_store_once_and_count_towards_age() {
+        use crate::Client;
+
+        async_io::block_on(async {
+            let backend = ScriptedEndpoint::new([
+                Scripted::Fail,
+                Scripted::Respond(StatusCode::OK, &[("cache-control", "max-age=60")]),
+            ]);
+            let mut network = backend
+                .clone()
+                .retry(3)
+                .min_delay(Duration::from_secs(1))
+                .jitter(false);
+            let mut cache = Cache::new();
+
+            let response = cache
+                .handle(&mut new_request(), &mut network)
+                .await
+                .unwrap();
+            assert!(
+                (1..=2).contains(&age(&response)),
+                "{:?}",
+                response.headers()
+            );
+            assert_eq!((backend.calls(), cache.entries.len()), (2, 1));
+
+            let response = cache
+                .handle(&mut new_request(), &mut network)
+                .await
+                .unwrap();
+            assert!(
+                (1..=2).contains(&age(&response)),
+                "{:?}",
+                response.headers()
+            );
+            assert_eq!(body_text(response).await, "v1");
+            assert_eq!((backend.calls(), cache.entries.len()), (2, 1));
+        });
+    }
+
+    #[test]
+    fn failed_revalidation_keeps_the_entry_for_the_retry() {
+        use crate::Client;
+
+        async_io::block_on(async {
+            let validated: &[(&str, &str)] = &[("cache-control", "max-age=0"), ("etag", "\"v1\"")];
+            let backend = ScriptedEndpoint::new([
+                Scripted::Respond(StatusCode::OK, validated),
+                Scripted::Fail,
+                Scripted::Respond(StatusCode::NOT_MODIFIED, &[("cache-control", "max-age=60")]),
+            ]);
+            let mut cache = Cache::new();
+            cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+
+            let mut client = WithMiddleware::new(backend.clone(), &mut cache)
+                .retry(3)
+                .min_delay(Duration::from_millis(1));
+            let response = client.respond(&mut new_request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(body_text(response).await, "v1");
+            assert_eq!((backend.calls(), cache.entries.len()), (3, 1));
+
+            // The 304 refreshed the entry, so this is served locally.
+            let response = cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(body_text(response).await, "v1");
+            assert_eq!(backend.calls(), 3);
+        });
+    }
+
+    #[test]
+    fn retried_server_errors_are_not_cached() {
+        use crate::Client;
+
+        async_io::block_on(async {
+            let fresh: &[(&str, &str)] = &[("cache-control", "max-age=60")];
+            let backend = ScriptedEndpoint::new([
+                Scripted::Respond(StatusCode::SERVICE_UNAVAILABLE, fresh),
+                Scripted::Respond(StatusCode::OK, fresh),
+            ]);
+            let mut cache = Cache::new();
+            let mut client = WithMiddleware::new(backend.clone(), &mut cache)
+                .retry(3)
+                .min_delay(Duration::from_millis(1));
+
+            let response = client.respond(&mut new_request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = client.respond(&mut new_request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!((backend.calls(), cache.entries.len()), (2, 1));
+        });
+    }
+
+    #[test]
+    fn upstream_age_counts_towards_freshness() {
+        async_io::block_on(async {
+            let backend = ScriptedEndpoint::new([
+                Scripted::Respond(
+                    StatusCode::OK,
+                    &[("cache-control", "max-age=60"), ("age", "30")],
+                ),
+                Scripted::Respond(
+                    StatusCode::OK,
+                    &[("cache-control", "max-age=60"), ("age", "60")],
+                ),
+                Scripted::Respond(StatusCode::OK, &[("cache-control", "max-age=60")]),
+            ]);
+            let mut cache = Cache::new();
+
+            let response = cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(age(&response), 30);
+            cache.entries.clear();
+
+            // Already as old as its lifetime: stored, but stale at once.
+            let response = cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(age(&response), 60);
+            cache
+                .handle(&mut new_request(), &mut backend.clone())
+                .await
+                .unwrap();
+            assert_eq!(backend.calls(), 3);
+        });
+    }
+
     fn new_request() -> Request {
         HttpRequest::builder()
             .method(Method::GET)
@@ -430,6 +885,65 @@ mod tests {
         }
     }
 
+    /// `sha-256` of `payload` in `Content-Digest` form.
+    const UNCHANGED_DIGEST: &str = "sha-256=:I59Z7VXnN8dxR89VrQwbAwttfudIp0JpUvm4UtWpNeU=:";
+
+    /// Always replies `200` with the same body, ignoring conditional headers,
+    /// and counts how many response bodies were actually read.
+    #[derive(Clone)]
+    struct UnconditionalEndpoint {
+        calls: Arc<AtomicUsize>,
+        bodies_read: Arc<AtomicUsize>,
+        digest: &'static str,
+        etag: &'static str,
+    }
+
+    impl UnconditionalEndpoint {
+        fn new(digest: &'static str) -> Self {
+            Self {
+                calls: Arc::new(AtomicUsize::new(0)),
+                bodies_read: Arc::new(AtomicUsize::new(0)),
+                digest,
+                etag: "W/\"v1\"",
+            }
+        }
+
+        const fn with_etag(mut self, etag: &'static str) -> Self {
+            self.etag = etag;
+            self
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        fn bodies_read(&self) -> usize {
+            self.bodies_read.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Endpoint for UnconditionalEndpoint {
+        type Error = Infallible;
+        fn respond(
+            &mut self,
+            _request: &mut Request,
+        ) -> impl std::future::Future<Output = Result<Response, Self::Error>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let bodies_read = Arc::clone(&self.bodies_read);
+            let body = futures_util::stream::once(async move {
+                bodies_read.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, Infallible>(Bytes::from_static(b"payload"))
+            });
+            std::future::ready(Ok(HttpResponse::builder()
+                .status(StatusCode::OK)
+                .header(header::ETAG, self.etag)
+                .header(header::CACHE_CONTROL, "no-cache")
+                .header("content-digest", self.digest)
+                .body(Body::from_stream(body))
+                .unwrap()))
+        }
+    }
+
     #[derive(Clone)]
     struct ConditionalEndpoint {
         calls: Arc<AtomicUsize>,
@@ -0,0 +1,140 @@
+//! Cancelling in-flight requests from elsewhere.
+//!
+//! Dropping a request future abandons it, but a backend may keep the
+//! connection it opened running in the background. A [`CancelToken`] passed
+//! to `RequestBuilder::with_cancel` ends the request with
+//! [`Error::Cancelled`](crate::Error::Cancelled) instead, and tells the
+//! backend to close the connection.
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), zenwave::Error> {
+//! use zenwave::{Client, cancel::CancelToken};
+//!
+//! let token = CancelToken::new();
+//! let handle = token.clone();
+//! // Elsewhere, e.g. when the user navigates away:
+//! handle.cancel();
+//!
+//! let mut client = zenwave::client();
+//! let error = client
+//!     .get("https://example.com/large")?
+//!     .with_cancel(token)
+//!     .bytes()
+//!     .await
+//!     .unwrap_err();
+//! assert!(error.is_cancelled());
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{
+    future::Future,
+    pin::pin,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::sync::Arc;
+
+use event_listener::Event;
+use futures_util::future::{Either, select};
+use http_kit::{HttpError, StatusCode};
+use thiserror::Error;
+
+/// A handle that cancels the requests it was passed to.
+///
+/// Clones share their state: cancelling any of them cancels all requests
+/// that were given one. Cancelling is permanent.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    /// Wakes the requests waiting for cancellation. A request that ends
+    /// first stops listening, so a long-lived token does not collect them.
+    event: Event,
+}
+
+impl CancelToken {
+    /// Create a token that has not been cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every request holding this token or one of its clones.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.shared.event.notify(usize::MAX);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the token is cancelled.
+    pub async fn cancelled(&self) {
+        while !self.is_cancelled() {
+            let listener = self.shared.event.listen();
+            // Checked again once listening so a concurrent `cancel` cannot
+            // slip in between and go unnoticed.
+            if self.is_cancelled() {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    /// Run `future` unless the token is cancelled first.
+    ///
+    /// The token is polled first, so that a cancellation wins over the
+    /// failure it causes in the backend.
+    pub(crate) async fn run<F: Future>(&self, future: F) -> Result<F::Output, Cancelled> {
+        match select(pin!(self.cancelled()), pin!(future)).await {
+            Either::Left(((), _)) => Err(Cancelled),
+            Either::Right((output, _)) => Ok(output),
+        }
+    }
+}
+
+/// Error returned when a request's [`CancelToken`] was cancelled.
+#[derive(Debug, Error)]
+#[error("request cancelled")]
+pub struct Cancelled;
+
+impl HttpError for Cancelled {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+impl From<Cancelled> for crate::Error {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CancelToken;
+
+    #[test]
+    fn finished_requests_stop_listening() {
+        let token = CancelToken::new();
+        async_io::block_on(async {
+            for _ in 0..100 {
+                assert_eq!(token.run(async { 1 }).await.ok(), Some(1));
+            }
+        });
+        assert_eq!(token.shared.event.total_listeners(), 0);
+
+        let waiting = token.clone();
+        let waiter = std::thread::spawn(move || async_io::block_on(waiting.cancelled()));
+        token.cancel();
+        waiter.join().expect("the waiter must wake");
+        assert!(async_io::block_on(token.run(async { 1 })).is_err());
+    }
+}
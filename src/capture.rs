@@ -0,0 +1,179 @@
+//! Capture of the request as it was actually sent.
+//!
+//! Middleware such as authentication, cookies and compression rewrite the
+//! request on its way to the backend. [`CaptureSentRequest`] records the final
+//! method, URI and headers in a [`SentRequest`] response extension, so the
+//! exact request can be inspected without a recording proxy.
+
+use std::any::Any;
+
+use http_kit::{
+    Endpoint, Method, Middleware, Request, Response, Uri, header::HeaderMap,
+    middleware::MiddlewareError,
+};
+
+/// Response extension holding the method, URI and headers that were sent.
+///
+/// The body is never captured.
+#[derive(Debug, Clone)]
+pub struct SentRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+}
+
+impl SentRequest {
+    fn new(request: &Request) -> Self {
+        Self {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            headers: request.headers().clone(),
+        }
+    }
+
+    /// The request method.
+    #[must_use]
+    pub const fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The request URI.
+    #[must_use]
+    pub const fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The request headers after every middleware ran.
+    #[must_use]
+    pub const fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+/// Middleware that attaches a [`SentRequest`] to every response.
+///
+/// The snapshot is taken as the request is handed to the wrapped client,
+/// since backends consume the request while sending it. It reflects the
+/// middleware applied after this one, so apply it first, directly on the
+/// backend, to see the request as it goes out. HTTP errors carry
+/// the snapshot on their response when the wrapped client reports
+/// [`crate::Error`], as the built-in backends do.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptureSentRequest;
+
+impl Middleware for CaptureSentRequest {
+    type Error = std::convert::Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let sent = SentRequest::new(request);
+        match next.respond(request).await {
+            Ok(mut response) => {
+                response.extensions_mut().insert(sent);
+                Ok(response)
+            }
+            Err(mut error) => {
+                if let Some(response) = (&mut error as &mut dyn Any)
+                    .downcast_mut::<crate::Error>()
+                    .and_then(crate::Error::http_response_mut)
+                {
+                    response.response.extensions_mut().insert(sent);
+                }
+                Err(MiddlewareError::Endpoint(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptureSentRequest, SentRequest};
+    use crate::ResponseExt;
+    use futures_executor::block_on;
+    use http_kit::{
+        Body, Endpoint, Method, Request, Response, StatusCode, endpoint::WithMiddleware, header,
+        middleware::MiddlewareError,
+    };
+
+    /// Fails every request with a 500 produced the way backends report it.
+    struct FailingEndpoint;
+
+    impl Endpoint for FailingEndpoint {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            Err(response.error_for_status_ref().unwrap_err())
+        }
+    }
+
+    #[test]
+    fn attaches_snapshot_to_http_errors() {
+        block_on(async {
+            let mut client = WithMiddleware::new(FailingEndpoint, CaptureSentRequest);
+            let mut request = http::Request::builder()
+                .method(Method::PUT)
+                .uri("http://example.com/items/1")
+                .header(header::IF_MATCH, "\"v1\"")
+                .body(Body::empty())
+                .unwrap();
+
+            let Err(MiddlewareError::Endpoint(crate::Error::Http { response, .. })) =
+                client.respond(&mut request).await
+            else {
+                panic!("expected an HTTP error");
+            };
+            let sent = response.response.sent_request().unwrap();
+            assert_eq!(sent.method(), Method::PUT);
+            assert_eq!(sent.uri(), "http://example.com/items/1");
+            assert_eq!(sent.headers()[header::IF_MATCH], "\"v1\"");
+        });
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+    #[test]
+    fn captures_requests_sent_by_a_real_backend() {
+        use crate::{Client, backend::HyperBackend};
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0_u8];
+            while !head.ends_with(b"\r\n\r\n") {
+                socket.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+
+        let mut client = HyperBackend::new().capture_sent_request();
+        let response = block_on(
+            client
+                .post(format!("http://{address}/items"))
+                .unwrap()
+                .header("x-a", "1")
+                .unwrap()
+                .into_future(),
+        )
+        .unwrap();
+        let sent = response.sent_request().unwrap();
+        assert_eq!(sent.method(), Method::POST);
+        assert_eq!(sent.uri().path(), "/items");
+        assert_eq!(sent.headers()["x-a"], "1");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn absent_without_the_middleware() {
+        let response = Response::new(Body::empty());
+        assert!(response.extensions().get::<SentRequest>().is_none());
+        assert!(response.sent_request().is_none());
+    }
+}
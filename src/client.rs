@@ -1,12 +1,19 @@
 #![allow(clippy::cast_sign_loss)]
 
-use core::{fmt::Display, pin::Pin, time::Duration};
-use std::marker::PhantomData;
+use core::{
+    fmt::Display,
+    pin::{Pin, pin},
+    time::Duration,
+};
 use std::{fmt::Debug, future::Future};
+use std::{marker::PhantomData, time::SystemTime};
 
 #[cfg(not(target_arch = "wasm32"))]
 use futures_io::AsyncRead;
-use futures_util::{Stream, StreamExt};
+use futures_util::{
+    Stream, StreamExt,
+    future::{self, Either},
+};
 use http::{HeaderName, HeaderValue, header};
 use http_kit::{
     Endpoint, Method, Middleware, Request, Response, Uri,
@@ -16,25 +23,55 @@ use http_kit::{
 };
 use serde::de::DeserializeOwned;
 
+mod curl_command;
+pub use curl_command::CurlCommandOptions;
+#[cfg(not(target_arch = "wasm32"))]
+mod body_writer;
+#[cfg(not(target_arch = "wasm32"))]
+pub use body_writer::BodyWriter;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod download;
+mod shared;
 #[cfg(not(target_arch = "wasm32"))]
 pub use download::{DownloadError, DownloadOptions, DownloadReport};
+pub use shared::SharedClient;
 
+#[cfg(feature = "compression")]
+use crate::compress::{CompressRequest, Encoding};
 use crate::{
-    auth::{BasicAuth, BearerAuth},
+    ResponseExt,
+    auth::{ApiKeyAuth, BasicAuth, BearerAuth},
     cache::Cache,
+    cancel::{CancelToken, Cancelled},
+    capture::CaptureSentRequest,
     cookie::CookieStore,
+    encoding::FlagUndecoded,
+    error::PartialResponse,
+    locale::AcceptLanguage,
+    logging::{LogConfig, Logging},
+    ratelimit::{ConcurrencyLimit, RateLimit},
     redirect::FollowRedirect,
+    request_id::RequestId,
     retry::Retry,
-    timeout::Timeout,
+    sanitize::StrictResponseHeaders,
+    timeout::{IdleTimeout, ReadTimeout, Timeout, TimeoutError, timeout_future, with_timeout},
+    trace::{PropagateTrace, TraceContext},
 };
 
+/// Per-request deadline and how to report it in the client's error type.
+type RequestTimeout<E> = (Duration, fn() -> E);
+
+/// Per-request cancellation and how to report it in the client's error type.
+type RequestCancel<E> = (CancelToken, fn() -> E);
+
 /// Builder for HTTP requests using a Client.
 #[derive(Debug)]
 pub struct RequestBuilder<'a, T: Client> {
     client: T,
     request: Request,
+    timeout: Option<RequestTimeout<T::Error>>,
+    cancel: Option<RequestCancel<T::Error>>,
     _marker: PhantomData<&'a mut T>,
 }
 
@@ -43,10 +80,46 @@ impl<'a, T: Client> IntoFuture for RequestBuilder<'a, T> {
 
     type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'a>>;
 
-    fn into_future(mut self) -> Self::IntoFuture {
+    fn into_future(self) -> Self::IntoFuture {
+        let Self {
+            mut client,
+            mut request,
+            timeout,
+            cancel,
+            ..
+        } = self;
         Box::pin(async move {
-            let mut request = self.request;
-            self.client.respond(&mut request).await
+            // The deadline or the cancellation, whichever comes first, as the
+            // error it is reported with.
+            let interrupt = async move {
+                let deadline = async {
+                    match timeout {
+                        Some((duration, timed_out)) => {
+                            timeout_future(duration).await;
+                            timed_out()
+                        }
+                        None => future::pending().await,
+                    }
+                };
+                let cancelled = async {
+                    match cancel {
+                        Some((token, cancelled)) => {
+                            token.cancelled().await;
+                            cancelled()
+                        }
+                        None => future::pending().await,
+                    }
+                };
+                match future::select(pin!(deadline), pin!(cancelled)).await {
+                    Either::Left((error, _)) | Either::Right((error, _)) => error,
+                }
+            };
+            // Polled first, so that a cancellation wins over the failure it
+            // causes in the backend.
+            match future::select(pin!(interrupt), pin!(client.respond(&mut request))).await {
+                Either::Left((error, _)) => Err(error),
+                Either::Right((response, _)) => response,
+            }
         })
     }
 }
@@ -69,20 +142,112 @@ fn invalid_request_with_prefix(prefix: &str, error: impl Display) -> crate::Erro
     crate::Error::InvalidRequest(message)
 }
 
+/// Insert a single value, or append every value when there are several.
+fn merge_header(headers: &mut http::HeaderMap, name: HeaderName, values: Vec<HeaderValue>) {
+    if let [value] = values.as_slice() {
+        headers.insert(name, value.clone());
+    } else {
+        for value in values {
+            headers.append(&name, value);
+        }
+    }
+}
+
 impl<T: Client> RequestBuilder<'_, T> {
-    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+    /// A request with an empty body, sent through `client`.
+    fn new<U>(client: T, method: Method, uri: U) -> Result<Self, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        // `Uri` drops the fragment, so keep it for redirects to carry forward.
+        let fragment = uri
+            .to_string()
+            .split_once('#')
+            .map(|(_, fragment)| fragment.to_string());
+        let uri = uri.try_into().map_err(invalid_uri)?;
+        // Credentials in the URI are sent as Basic auth, never in the
+        // request line.
+        let (uri, authorization) = crate::auth::take_userinfo(uri).map_err(invalid_uri)?;
+        let mut request = http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(http_kit::Body::empty())
+            .map_err(invalid_request)?;
+        if let Some(authorization) = authorization {
+            request
+                .headers_mut()
+                .insert(http_kit::header::AUTHORIZATION, authorization);
+        }
+        if let Some(fragment) = fragment {
+            request
+                .extensions_mut()
+                .insert(crate::redirect::UrlFragment(fragment));
+        }
+
+        Ok(Self {
+            client,
+            request,
+            timeout: None,
+            cancel: None,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Set an `Authorization: Bearer` header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token` is not a valid header value; use
+    /// [`Self::try_bearer_auth`] for tokens from untrusted input.
+    #[must_use]
+    pub fn bearer_auth(self, token: impl Into<String>) -> Self {
+        self.try_bearer_auth(token)
+            .expect("bearer token is not a valid header value")
+    }
+
+    /// Set an `Authorization: Bearer` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when `token` is not a valid header value,
+    /// for example because it contains a line break.
+    pub fn try_bearer_auth(mut self, token: impl Into<String>) -> Result<Self, crate::Error> {
         let auth_value = format!("Bearer {}", token.into());
+        let auth_value = HeaderValue::try_from(auth_value).map_err(invalid_request)?;
         self.request
             .headers_mut()
-            .insert(http_kit::header::AUTHORIZATION, auth_value.parse().unwrap());
-        self
+            .insert(http_kit::header::AUTHORIZATION, auth_value);
+        Ok(self)
     }
 
+    /// Set an `Authorization: Basic` header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the credentials do not form a valid header value; use
+    /// [`Self::try_basic_auth`] for credentials from untrusted input.
+    #[must_use]
     pub fn basic_auth(
-        mut self,
+        self,
         username: impl Into<String>,
         password: Option<impl Into<String>>,
     ) -> Self {
+        self.try_basic_auth(username, password)
+            .expect("basic credentials are not a valid header value")
+    }
+
+    /// Set an `Authorization: Basic` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when the credentials do not form a valid
+    /// header value.
+    pub fn try_basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: Option<impl Into<String>>,
+    ) -> Result<Self, crate::Error> {
         use base64::Engine;
 
         let credentials = match password {
@@ -92,11 +257,12 @@ impl<T: Client> RequestBuilder<'_, T> {
 
         let encoded = base64::engine::general_purpose::STANDARD.encode(credentials.as_bytes());
         let auth_value = format!("Basic {encoded}");
+        let auth_value = HeaderValue::try_from(auth_value).map_err(invalid_request)?;
 
         self.request
             .headers_mut()
-            .insert(http_kit::header::AUTHORIZATION, auth_value.parse().unwrap());
-        self
+            .insert(http_kit::header::AUTHORIZATION, auth_value);
+        Ok(self)
     }
 
     /// Insert or replace a request header.
@@ -116,6 +282,184 @@ impl<T: Client> RequestBuilder<'_, T> {
         Ok(self)
     }
 
+    /// Add a request header, keeping any values already set for `name`.
+    ///
+    /// Use this for headers that may repeat, such as `Accept`; each call
+    /// sends one more value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when the header name or value cannot be parsed.
+    pub fn append_header(
+        mut self,
+        name: impl TryInto<HeaderName, Error: Display>,
+        value: impl TryInto<HeaderValue, Error: Display>,
+    ) -> Result<Self, crate::Error> {
+        let header_name: http_kit::header::HeaderName = name.try_into().map_err(invalid_request)?;
+        let header_value: http_kit::header::HeaderValue =
+            value.try_into().map_err(invalid_request)?;
+        self.request.headers_mut().append(header_name, header_value);
+        Ok(self)
+    }
+
+    /// Mark this request as safe to retry even if its method is not idempotent.
+    ///
+    /// [`Retry`](crate::retry::Retry) only replays idempotent methods by
+    /// default; use this for a `POST` the server deduplicates, for example
+    /// through an `Idempotency-Key` header.
+    #[must_use]
+    pub fn idempotent(mut self) -> Self {
+        self.request
+            .extensions_mut()
+            .insert(crate::retry::Idempotent);
+        self
+    }
+
+    /// Fail this request if the response headers do not arrive within
+    /// `duration`.
+    ///
+    /// A [`Timeout`] middleware on the client still applies, so the shorter
+    /// of the two wins. Awaiting the builder does not bound reading the
+    /// response body, but the helpers that buffer it ([`Self::json`],
+    /// [`Self::bytes`], downloads, ...) apply `duration` to the whole
+    /// exchange. A timeout that fires mid-body reports what had arrived in
+    /// [`crate::Error::partial_response`].
+    #[must_use]
+    pub fn timeout(mut self, duration: Duration) -> Self
+    where
+        T::Error: From<TimeoutError>,
+    {
+        self.timeout = Some((duration, || TimeoutError.into()));
+        self
+    }
+
+    /// End this request with [`crate::Error::Cancelled`] once `token` is
+    /// cancelled.
+    ///
+    /// The buffering helpers ([`Self::json`], [`Self::bytes`], downloads,
+    /// ...) stay cancellable while they read the body. Backends that drive
+    /// connections in the background, like
+    /// [`HyperBackend`](crate::backend::HyperBackend), close the request's
+    /// connection too, or reset its stream on a shared HTTP/2 connection.
+    #[must_use]
+    pub fn with_cancel(mut self, token: CancelToken) -> Self
+    where
+        T::Error: From<Cancelled>,
+    {
+        self.request.extensions_mut().insert(token.clone());
+        self.cancel = Some((token, || Cancelled.into()));
+        self
+    }
+
+    /// Send this request on a new connection, never a pooled one.
+    ///
+    /// Use it for critical non-idempotent writes, where a pooled connection
+    /// closing mid-send would leave the outcome unknown. See
+    /// [`FreshConnection`](crate::backend::FreshConnection).
+    #[must_use]
+    pub fn fresh_connection(mut self) -> Self {
+        self.request
+            .extensions_mut()
+            .insert(crate::backend::FreshConnection);
+        self
+    }
+
+    /// Send `trailers` after the request body.
+    ///
+    /// The body is sent with chunked transfer encoding and the trailer names
+    /// are announced in a `Trailer` header, which is how streaming protocols
+    /// deliver a status once the upload has finished. Only the hyper backend
+    /// sends trailers; other backends drop them.
+    #[must_use]
+    pub fn trailers(mut self, trailers: http::HeaderMap) -> Self {
+        self.request
+            .extensions_mut()
+            .insert(crate::backend::RequestTrailers::new(trailers));
+        self
+    }
+
+    /// Merge every entry of `map` into the request headers.
+    ///
+    /// Names with a single value in `map` replace any existing value, like
+    /// [`RequestBuilder::header`]; names with several values are appended so
+    /// none of them is lost.
+    #[must_use]
+    pub fn headers(mut self, map: http::HeaderMap) -> Self {
+        let request_headers = self.request.headers_mut();
+        let mut current: Option<(HeaderName, Vec<HeaderValue>)> = None;
+        // `HeaderMap::into_iter` yields the name only for the first value of each entry.
+        for (name, value) in map {
+            if let Some(name) = name
+                && let Some((previous, values)) = current.replace((name, Vec::new()))
+            {
+                merge_header(request_headers, previous, values);
+            }
+            if let Some((_, values)) = current.as_mut() {
+                values.push(value);
+            }
+        }
+        if let Some((name, values)) = current {
+            merge_header(request_headers, name, values);
+        }
+        self
+    }
+
+    /// Set a weighted `Accept-Language` header from language ranges in preference order.
+    ///
+    /// Entries may carry an explicit weight (`en;q=0.8`); unweighted entries after
+    /// the first receive a descending weight based on their position, never above
+    /// the weight of the entry before them and never `q=0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a language tag or weight is malformed.
+    pub fn accept_language(mut self, languages: &[&str]) -> Result<Self, crate::Error> {
+        let value = crate::locale::accept_language_value(languages)?;
+        self.request
+            .headers_mut()
+            .insert(header::ACCEPT_LANGUAGE, value);
+        Ok(self)
+    }
+
+    /// Make the request conditional on the resource no longer matching `etag`.
+    ///
+    /// `etag` is usually the value of [`ResponseExt::etag`] from an earlier
+    /// response; a bare tag without quotes is quoted. A server that still has
+    /// that version answers `304 Not Modified` without a body.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when `etag` is not a valid header value.
+    pub fn if_none_match(mut self, etag: &str) -> Result<Self, crate::Error> {
+        let etag = etag.trim();
+        let quoted = etag == "*" || etag.starts_with("W/\"") || etag.starts_with('"');
+        let value = if quoted {
+            HeaderValue::try_from(etag)
+        } else {
+            HeaderValue::try_from(format!("\"{etag}\""))
+        }
+        .map_err(invalid_request)?;
+        self.request
+            .headers_mut()
+            .insert(header::IF_NONE_MATCH, value);
+        Ok(self)
+    }
+
+    /// Make the request conditional on the resource having changed after
+    /// `date`, such as the [`ResponseExt::last_modified`] of an earlier
+    /// response.
+    ///
+    /// The date is sent in the IMF-fixdate format, at one-second precision.
+    #[must_use]
+    pub fn if_modified_since(mut self, date: SystemTime) -> Self {
+        let value = HeaderValue::try_from(httpdate::fmt_http_date(date))
+            .expect("HTTP dates are valid header values");
+        self.request
+            .headers_mut()
+            .insert(header::IF_MODIFIED_SINCE, value);
+        self
+    }
+
     /// Set a JSON-encoded body for the request.
     ///
     /// # Errors
@@ -137,6 +481,69 @@ impl<T: Client> RequestBuilder<'_, T> {
         Ok(self)
     }
 
+    /// Stream `body` as JSON, serializing it while the request is sent
+    /// instead of into one string up front.
+    ///
+    /// Serialization runs on a dedicated thread and only a few 16 KiB chunks
+    /// are buffered, so memory stays flat however large the payload. The
+    /// length is unknown in advance, so the body is sent without
+    /// `Content-Length`, with chunked transfer encoding on HTTP/1.1, and some
+    /// servers refuse that. A serialization error fails the request rather
+    /// than being returned here. For small payloads [`json_body`](Self::json_body)
+    /// is simpler and cheaper.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn json_stream<B: serde::Serialize + Send + 'static>(mut self, body: B) -> Self {
+        let stream = body_writer::SyncStream::new(body_writer::json(body));
+        *self.request.body_mut() = http_kit::Body::from_stream(stream);
+        self.request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        self
+    }
+
+    /// Set a `MessagePack`-encoded body for the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when the payload cannot be serialized to `MessagePack`.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack_body<B: serde::Serialize>(mut self, body: &B) -> Result<Self, crate::Error> {
+        let bytes = rmp_serde::to_vec_named(body).map_err(|error| {
+            invalid_request_with_prefix("failed to serialize MessagePack body: ", error)
+        })?;
+
+        *self.request.body_mut() = http_kit::Body::from(bytes);
+        self.request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/msgpack"),
+        );
+
+        Ok(self)
+    }
+
+    /// Set a CBOR-encoded body for the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when the payload cannot be serialized to CBOR.
+    #[cfg(feature = "cbor")]
+    pub fn cbor_body<B: serde::Serialize>(mut self, body: &B) -> Result<Self, crate::Error> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(body, &mut bytes).map_err(|error| {
+            invalid_request_with_prefix("failed to serialize CBOR body: ", error)
+        })?;
+
+        *self.request.body_mut() = http_kit::Body::from(bytes);
+        self.request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/cbor"),
+        );
+
+        Ok(self)
+    }
+
     pub fn bytes_body(mut self, bytes: Vec<u8>) -> Self {
         *self.request.body_mut() = http_kit::Body::from(bytes);
         self
@@ -192,6 +599,21 @@ impl<T: Client> RequestBuilder<'_, T> {
         Ok(self.reader_body(file, Some(metadata.len())))
     }
 
+    /// Send `events` as a `text/event-stream` body, one chunk per event.
+    ///
+    /// See [`crate::sse`] for the framing.
+    #[must_use]
+    pub fn sse_body<S>(mut self, events: S) -> Self
+    where
+        S: Stream<Item = crate::sse::SseEvent> + Send + Sync + 'static,
+    {
+        self.request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        self.stream_body(crate::sse::EventSink::new(events))
+    }
+
     /// Attach a streaming body composed from arbitrary async chunks.
     pub fn stream_body<Chunk, ErrType, S>(mut self, stream: S) -> Self
     where
@@ -204,6 +626,35 @@ impl<T: Client> RequestBuilder<'_, T> {
         self
     }
 
+    /// Send the chunks of `chunks` as the body, pulled one at a time as the
+    /// backend writes the request.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn iter_body<I>(mut self, chunks: I) -> Self
+    where
+        I: IntoIterator<Item: Into<Bytes>> + Send + 'static,
+        I::IntoIter: Send + 'static,
+    {
+        let chunks = futures_util::stream::iter(chunks)
+            .map(|chunk| Ok::<Bytes, core::convert::Infallible>(chunk.into()));
+        *self.request.body_mut() =
+            http_kit::Body::from_stream(body_writer::SyncStream::new(chunks));
+        self
+    }
+
+    /// Stream the body from chunks written to the returned [`BodyWriter`],
+    /// typically from another task while the request is in flight.
+    ///
+    /// Up to `capacity` chunks are buffered before
+    /// [`BodyWriter::write`] waits for the backend to catch up.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn channel_body(mut self, capacity: usize) -> (BodyWriter, Self) {
+        let (writer, body) = body_writer::channel(capacity);
+        *self.request.body_mut() = http_kit::Body::from_stream(body_writer::SyncStream::new(body));
+        (writer, self)
+    }
+
     /// Download the response body into the provided path, resuming partial files automatically.
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn download_to_path(
@@ -222,6 +673,36 @@ impl<T: Client> RequestBuilder<'_, T> {
     ) -> Result<DownloadReport, DownloadError<T::Error>> {
         download::download_to_path(self, path, options).await
     }
+
+    /// Render the request as a copy-pasteable `curl` command.
+    ///
+    /// Credentials in `Authorization`, `Proxy-Authorization` and `Cookie`
+    /// headers are redacted; use [`Self::to_curl_command_with`] to keep them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a header value or the
+    /// in-memory body is not valid UTF-8 text.
+    pub fn to_curl_command(&self) -> Result<String, crate::Error> {
+        self.to_curl_command_with(&CurlCommandOptions::default())
+    }
+
+    /// Render the request as a `curl` command using custom [`CurlCommandOptions`].
+    ///
+    /// Arguments are single-quoted for POSIX shells. Streaming bodies cannot
+    /// be rendered inline, so the command reads them from stdin with
+    /// `--data-binary @-`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a header value or the
+    /// in-memory body is not valid UTF-8 text.
+    pub fn to_curl_command_with(
+        &self,
+        options: &CurlCommandOptions,
+    ) -> Result<String, crate::Error> {
+        curl_command::render(&self.request, options)
+    }
 }
 
 // Consuming helpers for any client whose error can be normalized into zenwave::Error.
@@ -235,9 +716,7 @@ where
     ///
     /// Returns an error if the request fails or the response body is not valid JSON for `Res`.
     pub async fn json<Res: DeserializeOwned>(self) -> Result<Res, crate::Error> {
-        let response = self.await.map_err(Into::into)?;
-        let mut body = response.into_body();
-        Ok(body.into_json().await?)
+        Ok(self.receive().await?.into_json().await?)
     }
 
     /// Read the response body as text.
@@ -246,8 +725,7 @@ where
     ///
     /// Returns an error if the request fails or the response body cannot be decoded as text.
     pub async fn string(self) -> Result<ByteStr, crate::Error> {
-        let response = self.await.map_err(Into::into)?;
-        let body = response.into_body();
+        let body = self.receive().await?.into_body();
         Ok(body.into_string().await?)
     }
 
@@ -257,19 +735,41 @@ where
     ///
     /// Returns an error if the request fails or the response body stream errors.
     pub async fn bytes(self) -> Result<Bytes, crate::Error> {
-        let response = self.await.map_err(Into::into)?;
-        let body = response.into_body();
+        let body = self.receive().await?.into_body();
         Ok(body.into_bytes().await?)
     }
 
+    /// Deserialize the response body as `MessagePack`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the response body is not valid `MessagePack` for `Res`.
+    #[cfg(feature = "msgpack")]
+    pub async fn msgpack<Res: DeserializeOwned>(self) -> Result<Res, crate::Error> {
+        let bytes = self.bytes().await?;
+        rmp_serde::from_slice(&bytes)
+            .map_err(|error| http_kit::BodyError::Other(Box::new(error)).into())
+    }
+
+    /// Deserialize the response body as CBOR.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the response body is not valid CBOR for `Res`.
+    #[cfg(feature = "cbor")]
+    pub async fn cbor<Res: DeserializeOwned>(self) -> Result<Res, crate::Error> {
+        let bytes = self.bytes().await?;
+        ciborium::from_reader(bytes.as_ref())
+            .map_err(|error| http_kit::BodyError::Other(Box::new(error)).into())
+    }
+
     /// Deserialize the response body as form data.
     ///
     /// # Errors
     ///
     /// Returns an error if the request fails or the response body cannot be decoded into `Res`.
     pub async fn form<Res: DeserializeOwned>(self) -> Result<Res, crate::Error> {
-        let response = self.await.map_err(Into::into)?;
-        let mut body = response.into_body();
+        let mut body = self.receive().await?.into_body();
         Ok(body.into_form().await?)
     }
 
@@ -283,6 +783,76 @@ where
         let body = response.into_body();
         Ok(body.into_sse())
     }
+
+    /// Send the request and stream the response body chunk by chunk.
+    ///
+    /// Unlike [`Self::bytes`], the body is not buffered, so a [`Self::timeout`]
+    /// only bounds the wait for the response headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails; the stream yields an error if
+    /// reading the body fails.
+    pub async fn bytes_stream(
+        self,
+    ) -> Result<impl Stream<Item = Result<Bytes, crate::Error>> + Send + Unpin, crate::Error> {
+        let response = self.await.map_err(Into::into)?;
+        Ok(response.into_byte_stream())
+    }
+
+    /// Send the request and return the response for a buffering helper.
+    ///
+    /// With a [`Self::timeout`], the body is read into memory under the same
+    /// deadline, and a timeout keeps the status, headers and byte count of
+    /// the response it interrupted. A [`Self::with_cancel`] token likewise
+    /// covers the body.
+    async fn receive(mut self) -> Result<Response, crate::Error> {
+        let timeout = self.timeout.take().map(|(duration, _)| duration);
+        let cancel = self.cancel.clone().map(|(token, _)| token);
+        if timeout.is_none() && cancel.is_none() {
+            return self.await.map_err(Into::into);
+        }
+        let progress = std::sync::Mutex::new(None::<PartialResponse>);
+        let receive = async {
+            let response = self.await.map_err(Into::into)?;
+            let (parts, mut body) = response.into_parts();
+            *progress.lock().unwrap() = Some(PartialResponse {
+                status: parts.status,
+                headers: parts.headers.clone(),
+                bytes_received: 0,
+            });
+            let mut buffer = Vec::new();
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk?;
+                buffer.extend_from_slice(&chunk);
+                if let Some(partial) = progress.lock().unwrap().as_mut() {
+                    partial.bytes_received += chunk.len() as u64;
+                }
+            }
+            Ok(Response::from_parts(parts, http_kit::Body::from(buffer)))
+        };
+        let receive = async {
+            match timeout {
+                Some(duration) => {
+                    with_timeout(duration, receive)
+                        .await
+                        .unwrap_or_else(|TimeoutError| {
+                            Err(crate::Error::Timeout {
+                                partial: progress.lock().unwrap().take().map(Box::new),
+                            })
+                        })
+                }
+                None => receive.await,
+            }
+        };
+        match cancel {
+            Some(token) => token
+                .run(receive)
+                .await
+                .unwrap_or_else(|Cancelled| Err(crate::Error::Cancelled)),
+            None => receive.await,
+        }
+    }
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
@@ -339,47 +909,392 @@ mod tests {
     }
 
     #[test]
-    fn file_body_streams_files_without_buffering() {
+    fn download_to_path_restarts_when_content_range_does_not_match() {
+        let payload: Vec<u8> = (0..4096).map(|i| (i % 241) as u8).collect();
         let dir = tempdir().unwrap();
-        let path = dir.path().join("upload.bin");
-        let payload: Vec<u8> = (0..2048)
-            .map(|i| u8::try_from(i % 256).expect("value fits in u8"))
-            .collect();
+        let path = dir.path().join("download.bin");
         async_io::block_on(async {
-            fs::write(&path, &payload).await.unwrap();
-
-            let backend = RecordingBackend::default();
-            let recorded = backend.recorded.clone();
-            let mut client = backend;
+            fs::write(&path, &payload[..1024]).await.unwrap();
 
-            client
-                .post("http://example.com/upload")
-                .unwrap()
-                .file_body(&path)
-                .await
+            let mut client = FakeBackend::with_range_skew(payload.clone(), 24);
+            let report = client
+                .get("http://example.com/file.bin")
                 .unwrap()
+                .download_to_path(&path)
                 .await
                 .unwrap();
 
-            let data = recorded.lock().await.clone();
-            assert_eq!(data, payload);
+            assert_eq!(report.resumed_from, 0);
+            assert_eq!(report.bytes_written, payload.len() as u64);
+            let final_bytes = fs::read(&path).await.unwrap();
+            assert_eq!(final_bytes, payload);
         });
     }
 
-    #[test]
-    fn stream_body_uploads_chunks() {
-        let backend = RecordingBackend::default();
-        let recorded = backend.recorded.clone();
-        let mut client = backend;
-
-        async_io::block_on(async {
-            let stream = stream::iter(vec![
-                Ok::<_, std::io::Error>(Bytes::from_static(b"chunk-a")),
-                Ok(Bytes::from_static(b"chunk-b")),
-            ]);
+    /// Sends `chunks` chunks of eight bytes, pausing `interval` before each.
+    struct DrippingBackend {
+        chunks: usize,
+        interval: Duration,
+    }
 
-            client
-                .post("http://example.com/upload")
+    impl Endpoint for DrippingBackend {
+        type Error = Infallible;
+        async fn respond(
+            &mut self,
+            _request: &mut Request,
+        ) -> Result<Response<http_kit::Body>, Self::Error> {
+            let interval = self.interval;
+            let body = stream::unfold(self.chunks, move |remaining| async move {
+                if remaining == 0 {
+                    return None;
+                }
+                async_io::Timer::after(interval).await;
+                Some((
+                    Ok::<_, std::io::Error>(Bytes::from_static(b"dripdrip")),
+                    remaining - 1,
+                ))
+            });
+            Ok(Response::new(http_kit::Body::from_stream(body)))
+        }
+    }
+
+    impl Client for DrippingBackend {}
+
+    #[test]
+    fn download_aborts_when_below_min_speed() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("slow.bin");
+        let options = DownloadOptions::default().min_speed(10_000, Duration::from_millis(100));
+        let mut client = DrippingBackend {
+            chunks: 50,
+            interval: Duration::from_millis(20),
+        };
+
+        let started = std::time::Instant::now();
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/slow.bin")
+                .unwrap()
+                .download_to_path_with(&path, options),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            DownloadError::TooSlow {
+                required: 10_000,
+                ..
+            }
+        ));
+        // 50 chunks would take a full second without the speed check.
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn download_meeting_min_speed_completes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("steady.bin");
+        let options = DownloadOptions::default().min_speed(100, Duration::from_millis(50));
+        let mut client = DrippingBackend {
+            chunks: 8,
+            interval: Duration::from_millis(10),
+        };
+
+        let report = async_io::block_on(
+            client
+                .get("http://example.com/steady.bin")
+                .unwrap()
+                .download_to_path_with(&path, options),
+        )
+        .unwrap();
+        assert_eq!(report.bytes_written, 64);
+    }
+
+    /// Responds with an empty body after `delay`.
+    struct SlowBackend {
+        delay: Duration,
+    }
+
+    impl Endpoint for SlowBackend {
+        type Error = crate::Error;
+        async fn respond(
+            &mut self,
+            _request: &mut Request,
+        ) -> Result<Response<http_kit::Body>, Self::Error> {
+            async_io::Timer::after(self.delay).await;
+            Ok(Response::new(http_kit::Body::empty()))
+        }
+    }
+
+    impl Client for SlowBackend {}
+
+    /// Sends its headers and one chunk, then never finishes the body.
+    struct StallingBackend;
+
+    impl Endpoint for StallingBackend {
+        type Error = crate::Error;
+        async fn respond(
+            &mut self,
+            _request: &mut Request,
+        ) -> Result<Response<http_kit::Body>, Self::Error> {
+            let body = stream::once(async { Ok::<_, Infallible>(Bytes::from_static(b"partial!")) })
+                .chain(stream::pending());
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .header("x-request-id", "42")
+                .body(http_kit::Body::from_stream(body))
+                .unwrap())
+        }
+    }
+
+    impl Client for StallingBackend {}
+
+    /// Answers with the request path after a delay that shrinks with each
+    /// request, so later requests finish first.
+    #[derive(Clone, Default)]
+    struct StaggeredBackend {
+        in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Endpoint for StaggeredBackend {
+        type Error = crate::Error;
+        async fn respond(
+            &mut self,
+            request: &mut Request,
+        ) -> Result<Response<http_kit::Body>, Self::Error> {
+            use std::sync::atomic::Ordering;
+
+            let path = request.uri().path().to_string();
+            let index: u64 = path.trim_start_matches("/item/").parse().unwrap();
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            async_io::Timer::after(Duration::from_millis(60 - 20 * index)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Response::new(http_kit::Body::from(path)))
+        }
+    }
+
+    impl Client for StaggeredBackend {}
+
+    fn item(index: usize) -> Request {
+        http::Request::get(format!("http://example.com/item/{index}"))
+            .body(http_kit::Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn batch_returns_results_in_request_order() {
+        let client = StaggeredBackend::default();
+
+        let results = async_io::block_on(client.batch((0..3).map(item).collect()));
+
+        let mut bodies = Vec::new();
+        for result in results {
+            let body = async_io::block_on(result.unwrap().into_body().into_string()).unwrap();
+            bodies.push(body.to_string());
+        }
+        assert_eq!(bodies, ["/item/0", "/item/1", "/item/2"]);
+        assert_eq!(client.peak.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn batch_respects_a_shared_concurrency_limit() {
+        let backend = StaggeredBackend::default();
+        let client = WithMiddleware::new(backend.clone(), ConcurrencyLimit::new(1));
+
+        let results = async_io::block_on(client.batch((0..3).map(item).collect()));
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(backend.peak.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn request_timeout_reports_partial_response() {
+        let mut client = StallingBackend;
+
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/stall")
+                .unwrap()
+                .timeout(Duration::from_millis(50))
+                .bytes(),
+        )
+        .unwrap_err();
+
+        let partial = error.partial_response().expect("headers had arrived");
+        assert_eq!(partial.status, StatusCode::ACCEPTED);
+        assert_eq!(partial.headers["x-request-id"], "42");
+        assert_eq!(partial.bytes_received, 8);
+    }
+
+    #[test]
+    fn request_timeout_before_headers_has_no_partial_response() {
+        let mut client = SlowBackend {
+            delay: Duration::from_secs(5),
+        };
+
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/slow")
+                .unwrap()
+                .timeout(Duration::from_millis(50))
+                .json::<serde_json::Value>(),
+        )
+        .unwrap_err();
+
+        assert!(error.is_timeout());
+        assert!(error.partial_response().is_none());
+    }
+
+    #[test]
+    fn download_timeout_reports_partial_response() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stall.bin");
+        let mut client = StallingBackend;
+
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/stall.bin")
+                .unwrap()
+                .timeout(Duration::from_millis(50))
+                .download_to_path(&path),
+        )
+        .unwrap_err();
+
+        let DownloadError::TimedOut(partial) = error else {
+            panic!("expected a timeout, got {error:?}");
+        };
+        assert_eq!(partial.status, StatusCode::ACCEPTED);
+        assert_eq!(partial.bytes_received, 8);
+    }
+
+    #[test]
+    fn cancelled_requests_return_promptly() {
+        let token = CancelToken::new();
+        let canceller = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                token.cancel();
+            })
+        };
+        let mut client = SlowBackend {
+            delay: Duration::from_secs(5),
+        };
+
+        let started = std::time::Instant::now();
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/slow")
+                .unwrap()
+                .with_cancel(token.clone())
+                .into_future(),
+        )
+        .unwrap_err();
+        assert!(error.is_cancelled(), "{error:?}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        canceller.join().unwrap();
+
+        // A cancelled token stops later requests, mid-body included.
+        let error = async_io::block_on(
+            StallingBackend
+                .get("http://example.com/stall")
+                .unwrap()
+                .with_cancel(token)
+                .bytes(),
+        )
+        .unwrap_err();
+        assert!(error.is_cancelled(), "{error:?}");
+    }
+
+    #[test]
+    fn request_timeout_fails_slow_responses() {
+        let mut client = SlowBackend {
+            delay: Duration::from_secs(5),
+        };
+
+        let started = std::time::Instant::now();
+        let error = async_io::block_on(
+            client
+                .get("http://example.com/slow")
+                .unwrap()
+                .timeout(Duration::from_millis(50))
+                .into_future(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, crate::Error::Timeout { .. }), "{error:?}");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn request_timeout_applies_per_request() {
+        let mut client = SlowBackend {
+            delay: Duration::from_millis(20),
+        };
+
+        async_io::block_on(async {
+            let response = client
+                .get("http://example.com/report")
+                .unwrap()
+                .timeout(Duration::from_secs(5))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let error = client
+                .get("http://example.com/health")
+                .unwrap()
+                .timeout(Duration::from_millis(1))
+                .await
+                .unwrap_err();
+            assert!(matches!(error, crate::Error::Timeout { .. }), "{error:?}");
+        });
+    }
+
+    #[test]
+    fn file_body_streams_files_without_buffering() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("upload.bin");
+        let payload: Vec<u8> = (0..2048)
+            .map(|i| u8::try_from(i % 256).expect("value fits in u8"))
+            .collect();
+        async_io::block_on(async {
+            fs::write(&path, &payload).await.unwrap();
+
+            let backend = RecordingBackend::default();
+            let recorded = backend.recorded.clone();
+            let mut client = backend;
+
+            client
+                .post("http://example.com/upload")
+                .unwrap()
+                .file_body(&path)
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+
+            let data = recorded.lock().await.clone();
+            assert_eq!(data, payload);
+        });
+    }
+
+    #[test]
+    fn stream_body_uploads_chunks() {
+        let backend = RecordingBackend::default();
+        let recorded = backend.recorded.clone();
+        let mut client = backend;
+
+        async_io::block_on(async {
+            let stream = stream::iter(vec![
+                Ok::<_, std::io::Error>(Bytes::from_static(b"chunk-a")),
+                Ok(Bytes::from_static(b"chunk-b")),
+            ]);
+
+            client
+                .post("http://example.com/upload")
                 .unwrap()
                 .stream_body(stream)
                 .await
@@ -390,10 +1305,276 @@ mod tests {
         });
     }
 
+    #[test]
+    fn iter_body_sends_every_chunk() {
+        let backend = RecordingBackend::default();
+        let recorded = backend.recorded.clone();
+        let mut client = backend;
+
+        async_io::block_on(async {
+            client
+                .post("http://example.com/upload")
+                .unwrap()
+                .iter_body([&b"chunk-a"[..], b"chunk-b"])
+                .await
+                .unwrap();
+
+            let data = recorded.lock().await.clone();
+            assert_eq!(data, b"chunk-achunk-b");
+        });
+    }
+
+    #[test]
+    fn json_stream_sends_large_payloads() {
+        let backend = RecordingBackend::default();
+        let recorded = backend.recorded.clone();
+        let mut client = backend;
+        // About 1 MB of JSON, many chunks.
+        let payload: Vec<u64> = (0..100_000).map(|n| n * 7919).collect();
+
+        async_io::block_on(async {
+            client
+                .post("http://example.com/upload")
+                .unwrap()
+                .json_stream(payload.clone())
+                .await
+                .unwrap();
+
+            let data = recorded.lock().await.clone();
+            let received: Vec<u64> = serde_json::from_slice(&data).unwrap();
+            assert_eq!(received, payload);
+        });
+    }
+
+    #[test]
+    fn channel_body_forwards_chunks_in_order() {
+        let backend = RecordingBackend::default();
+        let recorded = backend.recorded.clone();
+        let mut client = backend;
+
+        smol::block_on(async {
+            let (mut writer, request) = client
+                .post("http://example.com/upload")
+                .unwrap()
+                .channel_body(1);
+            let producer = smol::spawn(async move {
+                for index in 0..5 {
+                    writer.write(format!("{index},")).await.unwrap();
+                }
+                writer.finish();
+            });
+
+            request.await.unwrap();
+            producer.await;
+            let data = recorded.lock().await.clone();
+            assert_eq!(data, b"0,1,2,3,4,");
+        });
+    }
+
+    #[test]
+    fn aborted_channel_body_fails_the_request() {
+        let mut client = RecordingBackend::default();
+
+        smol::block_on(async {
+            let (mut writer, request) = client
+                .post("http://example.com/upload")
+                .unwrap()
+                .channel_body(4);
+            let producer = smol::spawn(async move {
+                writer.write("partial").await.unwrap();
+                writer.abort("source went away");
+            });
+
+            let error = request.await.unwrap_err();
+            producer.await;
+            assert!(error.to_string().contains("source went away"), "{error}");
+
+            // A dropped writer ends the body cleanly unless told otherwise.
+            let (writer, request) = client
+                .post("http://example.com/upload")
+                .unwrap()
+                .channel_body(4);
+            drop(writer);
+            request.await.unwrap();
+
+            let (writer, request) = client
+                .post("http://example.com/upload")
+                .unwrap()
+                .channel_body(4);
+            drop(writer.error_on_drop(true));
+            request.await.unwrap_err();
+        });
+    }
+
+    #[test]
+    fn sse_body_frames_events() {
+        use crate::sse::SseEvent;
+
+        let backend = RecordingBackend::default();
+        let recorded = backend.recorded.clone();
+        let mut client = backend.capture_sent_request();
+
+        async_io::block_on(async {
+            let events = stream::iter([
+                SseEvent::comment("connected\nas test"),
+                SseEvent::new("line one\nline two").event("log").id("1"),
+                SseEvent::new("{}").retry(std::time::Duration::from_millis(500)),
+            ]);
+            let response = client
+                .post("http://example.com/ingest")
+                .unwrap()
+                .sse_body(events)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.sent_request().unwrap().headers()[header::CONTENT_TYPE],
+                "text/event-stream"
+            );
+            let data = recorded.lock().await.clone();
+            assert_eq!(
+                String::from_utf8(data).unwrap(),
+                ": connected\n: as test\n\
+                 event: log\nid: 1\ndata: line one\ndata: line two\n\n\
+                 retry: 500\ndata: {}\n\n"
+            );
+        });
+    }
+
+    #[test]
+    fn request_ids_are_unique_per_request() {
+        let mut client = RecordingBackend::default()
+            .capture_sent_request()
+            .with_request_id(crate::request_id::X_REQUEST_ID);
+
+        async_io::block_on(async {
+            let mut ids = Vec::new();
+            for _ in 0..2 {
+                let response = client.get("http://example.com/").unwrap().await.unwrap();
+                let sent = response.sent_request().unwrap().headers()["x-request-id"].clone();
+                let id = response
+                    .extensions()
+                    .get::<crate::request_id::SentRequestId>()
+                    .unwrap();
+                assert_eq!(sent, id.as_str());
+                ids.push(sent);
+            }
+            assert_ne!(ids[0], ids[1]);
+
+            let response = client
+                .get("http://example.com/")
+                .unwrap()
+                .header("x-request-id", "from-upstream")
+                .unwrap()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.sent_request().unwrap().headers()["x-request-id"],
+                "from-upstream"
+            );
+        });
+    }
+
+    #[test]
+    fn capture_sent_request_sees_headers_added_by_middleware() {
+        let mut client = RecordingBackend::default()
+            .capture_sent_request()
+            .bearer_auth("token-123");
+
+        async_io::block_on(async {
+            let builder = client.get("http://example.com/").unwrap();
+            assert!(
+                !builder
+                    .request
+                    .headers()
+                    .contains_key(header::AUTHORIZATION)
+            );
+
+            let response = builder.await.unwrap();
+            let sent = response.sent_request().unwrap();
+            assert_eq!(sent.method(), Method::GET);
+            assert_eq!(sent.headers()[header::AUTHORIZATION], "Bearer token-123");
+        });
+    }
+
+    #[test]
+    fn headers_merges_a_prebuilt_map() {
+        let mut client = RecordingBackend::default();
+        let mut map = http::HeaderMap::new();
+        map.insert("x-request-source", HeaderValue::from_static("template"));
+        map.append(header::ACCEPT, HeaderValue::from_static("text/html"));
+        map.append(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+        let builder = client
+            .get("http://example.com/")
+            .unwrap()
+            .header("x-request-source", "builder")
+            .unwrap()
+            .headers(map);
+
+        let headers = builder.request.headers();
+        assert_eq!(headers["x-request-source"], "template");
+        let accept: Vec<_> = headers.get_all(header::ACCEPT).iter().collect();
+        assert_eq!(accept, ["text/html", "application/json"]);
+    }
+
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Payload {
+        id: u32,
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    fn sample_payload() -> Payload {
+        Payload {
+            id: 7,
+            name: "zenwave".to_string(),
+            tags: vec!["binary".to_string(), "serde".to_string()],
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trips_through_echo_backend() {
+        async_io::block_on(async {
+            let mut client = EchoBackend;
+            let echoed: Payload = client
+                .post("http://example.com/echo")
+                .unwrap()
+                .msgpack_body(&sample_payload())
+                .unwrap()
+                .msgpack()
+                .await
+                .unwrap();
+            assert_eq!(echoed, sample_payload());
+        });
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trips_through_echo_backend() {
+        async_io::block_on(async {
+            let mut client = EchoBackend;
+            let echoed: Payload = client
+                .post("http://example.com/echo")
+                .unwrap()
+                .cbor_body(&sample_payload())
+                .unwrap()
+                .cbor()
+                .await
+                .unwrap();
+            assert_eq!(echoed, sample_payload());
+        });
+    }
+
     #[derive(Clone)]
     struct FakeBackend {
         payload: Arc<Vec<u8>>,
         honor_range: bool,
+        /// Serve ranges starting this many bytes before the requested one.
+        range_skew: usize,
     }
 
     impl FakeBackend {
@@ -401,6 +1582,15 @@ mod tests {
             Self {
                 payload: Arc::new(payload),
                 honor_range: true,
+                range_skew: 0,
+            }
+        }
+
+        fn with_range_skew(payload: Vec<u8>, range_skew: usize) -> Self {
+            Self {
+                payload: Arc::new(payload),
+                honor_range: true,
+                range_skew,
             }
         }
 
@@ -408,6 +1598,7 @@ mod tests {
             Self {
                 payload: Arc::new(payload),
                 honor_range: false,
+                range_skew: 0,
             }
         }
     }
@@ -417,6 +1608,7 @@ mod tests {
             Self {
                 payload: Arc::new(Vec::new()),
                 honor_range: true,
+                range_skew: 0,
             }
         }
     }
@@ -429,7 +1621,7 @@ mod tests {
         ) -> impl std::future::Future<Output = Result<Response<http_kit::Body>, Self::Error>>
         {
             let start = if self.honor_range {
-                parse_range(request)
+                parse_range(request).saturating_sub(self.range_skew)
             } else {
                 0
             };
@@ -478,7 +1670,7 @@ mod tests {
     }
 
     impl Endpoint for RecordingBackend {
-        type Error = Infallible;
+        type Error = crate::Error;
         async fn respond(
             &mut self,
             request: &mut Request,
@@ -487,7 +1679,10 @@ mod tests {
                 .body_mut()
                 .take()
                 .unwrap_or_else(|_| http_kit::Body::empty());
-            let bytes = body.into_bytes().await.expect("failed to read body");
+            let bytes = body
+                .into_bytes()
+                .await
+                .map_err(|error| crate::Error::Other(Box::new(error)))?;
             *self.recorded.lock().await = bytes.to_vec();
 
             Ok(Response::builder()
@@ -499,6 +1694,33 @@ mod tests {
 
     impl Client for RecordingBackend {}
 
+    /// Echoes the request body and content type back as the response.
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    #[derive(Clone, Default)]
+    struct EchoBackend;
+
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    impl Endpoint for EchoBackend {
+        type Error = crate::Error;
+        async fn respond(
+            &mut self,
+            request: &mut Request,
+        ) -> Result<Response<http_kit::Body>, Self::Error> {
+            let body = request
+                .body_mut()
+                .take()
+                .unwrap_or_else(|_| http_kit::Body::empty());
+            let mut response = Response::builder().status(StatusCode::OK);
+            if let Some(content_type) = request.headers().get(header::CONTENT_TYPE) {
+                response = response.header(header::CONTENT_TYPE, content_type);
+            }
+            Ok(response.body(body).unwrap())
+        }
+    }
+
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    impl Client for EchoBackend {}
+
     fn parse_range(request: &Request) -> usize {
         request
             .headers()
@@ -528,27 +1750,127 @@ pub trait Client: Endpoint + Sized {
         Retry::new(self, max_retries)
     }
 
+    /// Make the client cheap to clone and usable through `&self`, sending
+    /// each request through a clone of it; see [`SharedClient`].
+    fn into_shared(self) -> SharedClient<Self>
+    where
+        Self: Clone,
+    {
+        SharedClient::new(self)
+    }
+
     /// Enable HTTP caching middleware.
     fn enable_cache(self) -> impl Client {
         WithMiddleware::new(self, Cache::new())
     }
 
+    /// Record the method, URI and headers each request was sent with.
+    ///
+    /// Responses carry a [`crate::capture::SentRequest`], available through
+    /// [`crate::ResponseExt::sent_request`]; only headers are copied, never the body.
+    fn capture_sent_request(self) -> impl Client {
+        WithMiddleware::new(self, CaptureSentRequest)
+    }
+
+    /// Send the W3C `traceparent` (and `tracestate`) of `context` with each
+    /// request.
+    ///
+    /// See [`PropagateTrace`].
+    fn propagate_trace(self, context: TraceContext) -> impl Client {
+        WithMiddleware::new(self, PropagateTrace::new(context))
+    }
+
+    /// Give each request a unique ID in `header_name` unless it has one.
+    ///
+    /// Responses carry the ID sent as a [`crate::request_id::SentRequestId`];
+    /// see [`RequestId`].
+    fn with_request_id(self, header_name: HeaderName) -> impl Client {
+        WithMiddleware::new(self, RequestId::with_header(header_name))
+    }
+
     /// Enable cookie management.
     fn enable_cookie(self) -> impl Client {
         WithMiddleware::new(self, CookieStore::default())
     }
 
     /// Enable cookie management with persistent backing storage (native targets only).
+    ///
+    /// The file is named after the current executable; see
+    /// [`CookieStore::persistent_default`] for how it is shared.
     #[cfg(not(target_arch = "wasm32"))]
     fn enable_persistent_cookie(self) -> impl Client {
         WithMiddleware::new(self, CookieStore::persistent_default())
     }
 
+    /// Compress request bodies with `encoding` (requires the `compression` feature).
+    ///
+    /// Empty bodies and bodies that already set `Content-Encoding` are sent unchanged.
+    #[cfg(feature = "compression")]
+    fn compress_request(self, encoding: Encoding) -> impl Client {
+        WithMiddleware::new(self, CompressRequest::new(encoding))
+    }
+
     /// Enforce a timeout for individual requests issued by this client.
     fn timeout(self, duration: Duration) -> impl Client {
         WithMiddleware::new(self, Timeout::new(duration))
     }
 
+    /// Fail requests when no response data arrives for `duration`.
+    ///
+    /// The clock restarts on every body chunk, so long but steady downloads
+    /// are never cut off.
+    fn idle_timeout(self, duration: Duration) -> impl Client {
+        WithMiddleware::new(self, IdleTimeout::new(duration))
+    }
+
+    /// Fail response bodies when no chunk arrives for `duration`.
+    ///
+    /// Unlike [`Client::idle_timeout`], waiting for the headers is left to
+    /// [`Client::timeout`], so the two combine into "headers within N
+    /// seconds, then no gap longer than M".
+    fn read_timeout(self, duration: Duration) -> impl Client {
+        WithMiddleware::new(self, ReadTimeout::new(duration))
+    }
+
+    /// Mark responses whose body still carries a content coding.
+    ///
+    /// See [`FlagUndecoded`] for the policy.
+    fn flag_undecoded_encodings(self) -> impl Client {
+        WithMiddleware::new(self, FlagUndecoded)
+    }
+
+    /// Drop response header values that are not plain text.
+    ///
+    /// See [`StrictResponseHeaders`] for what is rejected.
+    fn strict_response_headers(self) -> impl Client {
+        WithMiddleware::new(self, StrictResponseHeaders)
+    }
+
+    /// Throttle requests to `per_second` on average, allowing bursts of `burst`.
+    ///
+    /// See [`RateLimit`] for the token-bucket semantics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `per_second` is zero.
+    fn rate_limit(self, per_second: u32, burst: u32) -> impl Client {
+        WithMiddleware::new(self, RateLimit::new(per_second, burst))
+    }
+
+    /// Log every request and response as described by `config`.
+    ///
+    /// Credential headers are masked by default; see [`LogConfig`].
+    fn log_requests(self, config: LogConfig) -> impl Client {
+        WithMiddleware::new(self, Logging::new(config))
+    }
+
+    /// Allow at most `limit` requests in flight at once.
+    ///
+    /// See [`ConcurrencyLimit`] for when permits are released.
+    fn max_concurrent(self, limit: usize) -> impl Client {
+        WithMiddleware::new(self, ConcurrencyLimit::new(limit))
+    }
+
     /// Add Bearer Token Authentication middleware.
     fn bearer_auth(self, token: impl Into<String>) -> impl Client {
         WithMiddleware::new(self, BearerAuth::new(token))
@@ -563,8 +1885,66 @@ pub trait Client: Endpoint + Sized {
         WithMiddleware::new(self, BasicAuth::new(username, password))
     }
 
+    /// Add a static API key to every request.
+    fn api_key_auth(self, key: ApiKeyAuth) -> impl Client {
+        WithMiddleware::new(self, key)
+    }
+
+    /// Send a default `Accept-Language` header built from `languages`.
+    ///
+    /// Requests that set their own `Accept-Language` keep it unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a language tag or weight is malformed.
+    fn default_locale(self, languages: &[&str]) -> Result<impl Client, crate::Error> {
+        Ok(WithMiddleware::new(self, AcceptLanguage::new(languages)?))
+    }
+
+    /// Send every request in `requests` concurrently and return their
+    /// results in the same order.
+    ///
+    /// Each request is sent through its own clone of this client, such as a
+    /// [`HyperBackend`](crate::backend::HyperBackend) or a [`SharedClient`],
+    /// so limits that clones share still apply: wrapping the client in a
+    /// [`ConcurrencyLimit`] caps how many of the requests are in flight.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(backend: impl zenwave::Client + Clone) {
+    /// use zenwave::{Client, endpoint::WithMiddleware, ratelimit::ConcurrencyLimit};
+    ///
+    /// let client = WithMiddleware::new(backend, ConcurrencyLimit::new(4));
+    /// let requests = (1..=10)
+    ///     .map(|page| {
+    ///         http::Request::get(format!("https://example.com/items?page={page}"))
+    ///             .body(zenwave::Body::empty())
+    ///             .unwrap()
+    ///     })
+    ///     .collect();
+    /// for result in client.batch(requests).await {
+    ///     println!("{:?}", result.map(|response| response.status()));
+    /// }
+    /// # }
+    /// ```
+    fn batch(
+        &self,
+        requests: Vec<Request>,
+    ) -> impl Future<Output = Vec<Result<Response, Self::Error>>> + Send
+    where
+        Self: Clone,
+    {
+        let sends = requests.into_iter().map(|mut request| {
+            let mut client = self.clone();
+            async move { client.respond(&mut request).await }
+        });
+        futures_util::future::join_all(sends)
+    }
+
     /// Create a request with the specified method and URI.
     ///
+    /// A fragment in `uri` is never sent; it is kept in a
+    /// [`UrlFragment`](crate::redirect::UrlFragment) request extension.
+    ///
     /// # Errors
     ///
     /// Returns [`crate::Error::InvalidUri`] when `uri` cannot be parsed, or
@@ -575,21 +1955,10 @@ pub trait Client: Endpoint + Sized {
         uri: U,
     ) -> Result<RequestBuilder<'_, &mut Self>, crate::Error>
     where
-        U: TryInto<Uri>,
+        U: TryInto<Uri> + Display,
         U::Error: Display,
     {
-        let uri = uri.try_into().map_err(invalid_uri)?;
-        let request = http::Request::builder()
-            .method(method)
-            .uri(uri)
-            .body(http_kit::Body::empty())
-            .map_err(invalid_request)?;
-
-        Ok(RequestBuilder {
-            client: self,
-            request,
-            _marker: PhantomData,
-        })
+        RequestBuilder::new(self, method, uri)
     }
 
     /// Create a GET request.
@@ -599,12 +1968,54 @@ pub trait Client: Endpoint + Sized {
     /// Returns any error produced by [`Client::method`].
     fn get<U>(&mut self, uri: U) -> Result<RequestBuilder<'_, &mut Self>, crate::Error>
     where
-        U: TryInto<Uri>,
+        U: TryInto<Uri> + Display,
         U::Error: Display,
     {
         self.method(Method::GET, uri)
     }
 
+    /// Create a request to `template` with its `{name}` placeholders
+    /// replaced by the percent-encoded values in `params`.
+    ///
+    /// See [`expand_uri`](crate::uri_template::expand_uri) for the syntax.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`expand_uri`](crate::uri_template::expand_uri)
+    /// or [`Client::method`].
+    fn method_template(
+        &mut self,
+        method: Method,
+        template: &str,
+        params: &[(&str, &str)],
+    ) -> Result<RequestBuilder<'_, &mut Self>, crate::Error> {
+        let uri = crate::uri_template::expand_uri(template, params)?;
+        self.method(method, uri)
+    }
+
+    /// Create a GET request to a URI template; see [`Client::method_template`].
+    ///
+    /// ```rust,no_run
+    /// # async fn example(mut client: impl zenwave::Client) -> Result<(), zenwave::Error> {
+    /// // Requests https://api.example.com/users/a%2Fb
+    /// let response = client
+    ///     .get_template("https://api.example.com/users/{id}", &[("id", "a/b")])?
+    ///     .await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`Client::method_template`].
+    fn get_template(
+        &mut self,
+        template: &str,
+        params: &[(&str, &str)],
+    ) -> Result<RequestBuilder<'_, &mut Self>, crate::Error> {
+        self.method_template(Method::GET, template, params)
+    }
+
     /// Create a POST request.
     ///
     /// # Errors
@@ -612,7 +2023,7 @@ pub trait Client: Endpoint + Sized {
     /// Returns any error produced by [`Client::method`].
     fn post<U>(&mut self, uri: U) -> Result<RequestBuilder<'_, &mut Self>, crate::Error>
     where
-        U: TryInto<Uri>,
+        U: TryInto<Uri> + Display,
         U::Error: Display,
     {
         self.method(Method::POST, uri)
@@ -625,7 +2036,7 @@ pub trait Client: Endpoint + Sized {
     /// Returns any error produced by [`Client::method`].
     fn put<'a, U>(&mut self, uri: U) -> Result<RequestBuilder<'_, &mut Self>, crate::Error>
     where
-        U: TryInto<Uri>,
+        U: TryInto<Uri> + Display,
         U::Error: Display,
         Self: 'a,
     {
@@ -639,7 +2050,7 @@ pub trait Client: Endpoint + Sized {
     /// Returns any error produced by [`Client::method`].
     fn delete<U>(&mut self, uri: U) -> Result<RequestBuilder<'_, &mut Self>, crate::Error>
     where
-        U: TryInto<Uri>,
+        U: TryInto<Uri> + Display,
         U::Error: Display,
     {
         self.method(Method::DELETE, uri)
@@ -0,0 +1,218 @@
+//! Request bodies produced by iterators, by serializers and by pushing chunks
+//! from a task.
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    io,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use futures_channel::mpsc;
+use futures_util::{Stream, StreamExt};
+use http_kit::utils::Bytes;
+
+/// Makes a `Send` stream `Sync`, as request bodies must be. The body only
+/// ever polls it through `&mut`, so the lock is never contended.
+pub(super) struct SyncStream<S>(Mutex<S>);
+
+impl<S> SyncStream<S> {
+    pub(super) const fn new(stream: S) -> Self {
+        Self(Mutex::new(stream))
+    }
+}
+
+impl<S: Stream + Unpin> Stream for SyncStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .poll_next_unpin(cx)
+    }
+}
+
+/// How the body ends once every chunk was read, shared between the writer
+/// and the body.
+type Failure = Arc<Mutex<Option<io::Error>>>;
+
+/// Sending half of a request body created by
+/// [`RequestBuilder::channel_body`](super::RequestBuilder::channel_body).
+///
+/// Chunks are handed to the backend as it reads the body; `write` waits
+/// while the channel is full. End the body with [`finish`](Self::finish), or
+/// fail it with [`abort`](Self::abort). A writer dropped without either ends
+/// the body as if finished, unless [`error_on_drop`](Self::error_on_drop)
+/// was set.
+#[derive(Debug)]
+pub struct BodyWriter {
+    chunks: mpsc::Sender<Bytes>,
+    failure: Failure,
+    error_on_drop: bool,
+    ended: bool,
+}
+
+impl BodyWriter {
+    /// Fail the body instead of ending it when the writer is dropped without
+    /// [`finish`](Self::finish), so an interrupted producer cannot send a
+    /// truncated body that looks complete. Disabled by default.
+    #[must_use]
+    pub const fn error_on_drop(mut self, enabled: bool) -> Self {
+        self.error_on_drop = enabled;
+        self
+    }
+
+    /// Send `chunk`, waiting for room in the channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] with [`io::ErrorKind::BrokenPipe`] when
+    /// the request stopped reading the body, for example because it failed.
+    pub async fn write(&mut self, chunk: impl Into<Bytes>) -> Result<(), crate::Error> {
+        core::future::poll_fn(|cx| self.chunks.poll_ready(cx))
+            .await
+            .and_then(|()| self.chunks.start_send(chunk.into()))
+            .map_err(|_| {
+                crate::Error::Io(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "request body is no longer read",
+                ))
+            })
+    }
+
+    /// End the body after the chunks written so far.
+    pub fn finish(mut self) {
+        self.ended = true;
+    }
+
+    /// Fail the body with `error` after the chunks written so far, so the
+    /// request fails instead of sending a partial body as complete.
+    pub fn abort(mut self, error: impl Into<Box<dyn core::error::Error + Send + Sync>>) {
+        self.fail(io::Error::other(error));
+    }
+
+    fn fail(&mut self, error: io::Error) {
+        *self.failure.lock().unwrap_or_else(PoisonError::into_inner) = Some(error);
+        self.ended = true;
+    }
+}
+
+impl Drop for BodyWriter {
+    fn drop(&mut self) {
+        if !self.ended && self.error_on_drop {
+            self.fail(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "body writer dropped before finishing",
+            ));
+        }
+    }
+}
+
+/// Receiving half, read by the backend as the request body.
+pub(super) struct ChannelBody {
+    chunks: mpsc::Receiver<Bytes>,
+    failure: Failure,
+}
+
+/// Create a writer and the body it feeds, buffering up to `capacity` chunks.
+pub(super) fn channel(capacity: usize) -> (BodyWriter, ChannelBody) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let failure = Failure::default();
+    let writer = BodyWriter {
+        chunks: sender,
+        failure: Arc::clone(&failure),
+        error_on_drop: false,
+        ended: false,
+    };
+    (
+        writer,
+        ChannelBody {
+            chunks: receiver,
+            failure,
+        },
+    )
+}
+
+impl Stream for ChannelBody {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.chunks.poll_next_unpin(cx) {
+            Poll::Ready(Some(chunk)) => Poll::Ready(Some(Ok(chunk))),
+            // The writer is gone; report its failure once, if any.
+            Poll::Ready(None) => Poll::Ready(
+                self.failure
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take()
+                    .map(Err),
+            ),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Size of the chunks [`json`] hands to the body.
+const JSON_CHUNK: usize = 16 * 1024;
+
+/// Serialize `value` as JSON on a dedicated thread, feeding the body as the
+/// backend reads it. At most a few chunks are buffered, so the thread waits
+/// while the backend is behind. A serialization failure fails the body.
+pub(super) fn json<T: serde::Serialize + Send + 'static>(value: T) -> ChannelBody {
+    use std::io::Write;
+
+    /// Cuts the serializer's output into chunks sent through the writer.
+    struct Chunker {
+        writer: BodyWriter,
+        buffer: Vec<u8>,
+    }
+
+    impl Chunker {
+        fn send(&mut self) -> io::Result<()> {
+            let chunk = core::mem::replace(&mut self.buffer, Vec::with_capacity(JSON_CHUNK));
+            async_io::block_on(self.writer.write(chunk)).map_err(|_| {
+                io::Error::new(io::ErrorKind::BrokenPipe, "request body is no longer read")
+            })
+        }
+    }
+
+    impl Write for Chunker {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.buffer.extend_from_slice(bytes);
+            if self.buffer.len() >= JSON_CHUNK {
+                self.send()?;
+            }
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.buffer.is_empty() {
+                Ok(())
+            } else {
+                self.send()
+            }
+        }
+    }
+
+    let (writer, body) = channel(2);
+    std::thread::spawn(move || {
+        let mut chunker = Chunker {
+            writer,
+            buffer: Vec::with_capacity(JSON_CHUNK),
+        };
+        let written = serde_json::to_writer(&mut chunker, &value)
+            .map_err(io::Error::from)
+            .and_then(|()| chunker.flush());
+        match written {
+            Ok(()) => chunker.writer.finish(),
+            // Nobody reads the body anymore.
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(error) => chunker.writer.abort(error),
+        }
+    });
+    body
+}
@@ -0,0 +1,194 @@
+use futures_util::FutureExt;
+use http::{HeaderName, Method, header};
+use http_kit::Request;
+
+/// Options for [`RequestBuilder::to_curl_command_with`](super::RequestBuilder::to_curl_command_with).
+#[derive(Debug, Clone)]
+pub struct CurlCommandOptions {
+    /// Replace credentials in `Authorization`, `Proxy-Authorization` and
+    /// `Cookie` headers with a placeholder.
+    pub redact_credentials: bool,
+}
+
+impl Default for CurlCommandOptions {
+    fn default() -> Self {
+        Self {
+            redact_credentials: true,
+        }
+    }
+}
+
+const REDACTED: &str = "REDACTED";
+
+pub fn render(request: &Request, options: &CurlCommandOptions) -> Result<String, crate::Error> {
+    let (data, streamed) = data_arg(request)?;
+
+    // One line per option so long commands stay readable.
+    let mut args = vec!["curl".to_string()];
+
+    // curl sends POST whenever there is data and GET otherwise.
+    let implied = if data.is_some() {
+        Method::POST
+    } else {
+        Method::GET
+    };
+    match *request.method() {
+        ref method if *method == implied => {}
+        Method::HEAD if data.is_none() => args.push("--head".to_string()),
+        ref method => {
+            args.push(format!("-X {}", shell_quote(method.as_str())));
+        }
+    }
+    args.push(shell_quote(&request.uri().to_string()));
+
+    for (name, value) in request.headers() {
+        let value = value.to_str().map_err(|_| {
+            crate::Error::InvalidRequest(format!("header `{name}` is not printable text"))
+        })?;
+        let value = if options.redact_credentials {
+            redact(name, value)
+        } else {
+            value.to_string()
+        };
+        args.push(format!("-H {}", shell_quote(&format!("{name}: {value}"))));
+    }
+
+    args.extend(data);
+
+    let command = args.join(" \\\n  ");
+    if streamed {
+        Ok(format!(
+            "# The request body is a stream; pipe it to this command on stdin.\n{command}"
+        ))
+    } else {
+        Ok(command)
+    }
+}
+
+/// The `--data-binary` option for the request body, if any, and whether it
+/// reads a streamed body from stdin.
+fn data_arg(request: &Request) -> Result<(Option<String>, bool), crate::Error> {
+    match request
+        .body()
+        .try_clone()
+        .and_then(|body| body.into_bytes().now_or_never())
+    {
+        Some(Ok(bytes)) if bytes.is_empty() => Ok((None, false)),
+        Some(Ok(bytes)) => {
+            let text = std::str::from_utf8(&bytes).map_err(|_| {
+                crate::Error::InvalidRequest(
+                    "request body is binary; save it to a file and pass `--data-binary @file`"
+                        .to_string(),
+                )
+            })?;
+            Ok((Some(format!("--data-binary {}", shell_quote(text))), false))
+        }
+        Some(Err(error)) => Err(error.into()),
+        None => Ok((Some("--data-binary @-".to_string()), true)),
+    }
+}
+
+fn redact(name: &HeaderName, value: &str) -> String {
+    if *name == header::AUTHORIZATION || *name == header::PROXY_AUTHORIZATION {
+        // Keep the scheme so the command still shows which auth method is used.
+        value.split_once(' ').map_or_else(
+            || REDACTED.to_string(),
+            |(scheme, _)| format!("{scheme} {REDACTED}"),
+        )
+    } else if *name == header::COOKIE {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quote `value` for POSIX shells: wrap it in single quotes, which keep every
+/// character literal, and close/escape/reopen around embedded single quotes.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{CurlCommandOptions, render, shell_quote};
+    use futures_util::stream;
+    use http_kit::{Body, Method, Request, utils::Bytes};
+
+    fn request(method: Method, body: Body) -> Request {
+        http::Request::builder()
+            .method(method)
+            .uri("https://api.example.com/items?q=1")
+            .header("authorization", "Bearer secret-token")
+            .header("cookie", "session=abc")
+            .header("x-note", "it's fine")
+            .body(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn quotes_embedded_single_quotes() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$HOME `id`"), "'$HOME `id`'");
+    }
+
+    #[test]
+    fn renders_multiline_body_and_redacts_credentials() {
+        let request = request(Method::POST, Body::from("line one\nline 'two'"));
+        let command = render(&request, &CurlCommandOptions::default()).unwrap();
+        assert_eq!(
+            command,
+            "curl \\\n  'https://api.example.com/items?q=1' \\\n  \
+             -H 'authorization: Bearer REDACTED' \\\n  -H 'cookie: REDACTED' \\\n  \
+             -H 'x-note: it'\\''s fine' \\\n  --data-binary 'line one\nline '\\''two'\\'''"
+        );
+    }
+
+    #[test]
+    fn keeps_credentials_when_redaction_is_disabled() {
+        let request = request(Method::GET, Body::empty());
+        let options = CurlCommandOptions {
+            redact_credentials: false,
+        };
+        let command = render(&request, &options).unwrap();
+        assert!(command.contains("-H 'authorization: Bearer secret-token'"));
+        assert!(command.contains("-H 'cookie: session=abc'"));
+        assert!(!command.contains("-X"));
+        assert!(!command.contains("--data-binary"));
+    }
+
+    #[test]
+    fn streaming_body_reads_from_stdin() {
+        let chunks = stream::iter([Ok::<_, std::io::Error>(Bytes::from_static(b"chunk"))]);
+        let request = request(Method::PUT, Body::from_stream(chunks));
+        let command = render(&request, &CurlCommandOptions::default()).unwrap();
+        assert!(command.starts_with("# The request body is a stream"));
+        assert!(command.ends_with("--data-binary @-"));
+    }
+
+    #[test]
+    fn names_the_method_only_when_curl_would_pick_another() {
+        let options = CurlCommandOptions::default();
+
+        let command = render(&request(Method::GET, Body::from("{}")), &options).unwrap();
+        assert!(command.contains("-X 'GET'"));
+
+        let command = render(&request(Method::POST, Body::empty()), &options).unwrap();
+        assert!(command.contains("-X 'POST'"));
+
+        let command = render(&request(Method::HEAD, Body::empty()), &options).unwrap();
+        assert!(command.contains("--head"));
+        assert!(!command.contains("-X"));
+
+        let command = render(&request(Method::DELETE, Body::from("{}")), &options).unwrap();
+        assert!(command.contains("-X 'DELETE'"));
+    }
+
+    #[test]
+    fn binary_body_is_rejected() {
+        let request = request(Method::POST, Body::from(vec![0xff, 0xfe]));
+        assert!(matches!(
+            render(&request, &CurlCommandOptions::default()),
+            Err(crate::Error::InvalidRequest(_))
+        ));
+    }
+}
@@ -1,16 +1,20 @@
 use std::{
+    collections::VecDeque,
     io::{ErrorKind, SeekFrom},
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use async_fs::OpenOptions;
-use futures_util::StreamExt;
+use async_io::Timer;
+use futures_util::{StreamExt, future::Either};
 use http_kit::{
     BodyError, HttpError, StatusCode, header,
     utils::{AsyncSeekExt, AsyncWriteExt},
 };
 
-use super::RequestBuilder;
+use super::{RequestBuilder, RequestTimeout};
+use crate::{cancel::Cancelled, error::PartialResponse, retry::RequestHead, timeout::with_timeout};
 
 #[derive(Debug, thiserror::Error)]
 pub enum DownloadError<E: HttpError> {
@@ -28,6 +32,22 @@ pub enum DownloadError<E: HttpError> {
 
     #[error("upstream returned unsuccessful status: {0}")]
     Upstream(StatusCode),
+
+    /// Throughput fell below [`DownloadOptions::min_speed`].
+    #[error("transfer too slow: {observed} B/s over the last {window:?}, need {required} B/s")]
+    TooSlow {
+        /// Average rate observed over the window, in bytes per second.
+        observed: u64,
+        /// Minimum rate that was required, in bytes per second.
+        required: u64,
+        /// Window the rate was averaged over.
+        window: Duration,
+    },
+
+    /// The request's [`RequestBuilder::timeout`] passed while the body was
+    /// being written.
+    #[error("download timed out after {} bytes", .0.bytes_received)]
+    TimedOut(Box<PartialResponse>),
 }
 
 impl<E: HttpError> HttpError for DownloadError<E> {
@@ -38,6 +58,7 @@ impl<E: HttpError> HttpError for DownloadError<E> {
             Self::Body(_) => StatusCode::BAD_GATEWAY,
             Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Upstream(status) => *status,
+            Self::TooSlow { .. } | Self::TimedOut(_) => StatusCode::GATEWAY_TIMEOUT,
         }
     }
 }
@@ -58,6 +79,18 @@ where
             DownloadError::Upstream(status) => {
                 Self::Download(DownloadErrorKind::UpstreamError(status))
             }
+            DownloadError::TooSlow {
+                observed,
+                required,
+                window,
+            } => Self::Download(DownloadErrorKind::TooSlow {
+                observed,
+                required,
+                window,
+            }),
+            DownloadError::TimedOut(partial) => Self::Timeout {
+                partial: Some(partial),
+            },
         }
     }
 }
@@ -84,18 +117,106 @@ impl DownloadReport {
 #[derive(Debug, Clone, Copy)]
 pub struct DownloadOptions {
     /// Attempt to resume when the destination file already contains data.
+    ///
+    /// A `206` whose `Content-Range` does not start where the file ends is
+    /// discarded and the download restarted from scratch. Requests whose
+    /// body cannot be sent twice are never resumed.
     pub resume_existing: bool,
+    min_speed: Option<MinSpeed>,
 }
 
 impl Default for DownloadOptions {
     fn default() -> Self {
         Self {
             resume_existing: true,
+            min_speed: None,
         }
     }
 }
 
+impl DownloadOptions {
+    /// Abort the download when fewer than `bytes_per_sec` arrive on average
+    /// over the last `window`, like curl's `--speed-limit`/`--speed-time`.
+    ///
+    /// The first check happens once `window` has elapsed. Unlike an idle
+    /// timeout, this also catches connections that keep delivering data,
+    /// just too slowly.
+    #[must_use]
+    pub const fn min_speed(mut self, bytes_per_sec: u64, window: Duration) -> Self {
+        self.min_speed = Some(MinSpeed {
+            bytes_per_sec,
+            window,
+        });
+        self
+    }
+}
+
+/// Minimum throughput required by [`DownloadOptions::min_speed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MinSpeed {
+    /// Required average rate in bytes per second.
+    bytes_per_sec: u64,
+    /// Sliding window the rate is averaged over.
+    window: Duration,
+}
+
+/// Sliding-window throughput tracker for [`MinSpeed`].
+struct SpeedMonitor {
+    limit: MinSpeed,
+    started: Instant,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedMonitor {
+    fn new(limit: MinSpeed) -> Self {
+        Self {
+            limit,
+            started: Instant::now(),
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, now: Instant, bytes: u64) {
+        self.samples.push_back((now, bytes));
+    }
+
+    /// Returns the observed rate when it is below the limit.
+    fn check(&mut self, now: Instant) -> Option<u64> {
+        let window = self.limit.window;
+        if now.duration_since(self.started) < window {
+            return None;
+        }
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window)
+        {
+            self.samples.pop_front();
+        }
+
+        let bytes: u64 = self.samples.iter().map(|(_, len)| len).sum();
+        let window_millis = window.as_millis().max(1);
+        let observed = u128::from(bytes) * 1000 / window_millis;
+        (observed < u128::from(self.limit.bytes_per_sec))
+            .then(|| u64::try_from(observed).unwrap_or(u64::MAX))
+    }
+}
+
 pub async fn download_to_path<T: crate::Client>(
+    builder: RequestBuilder<'_, T>,
+    path: impl AsRef<Path>,
+    options: DownloadOptions,
+) -> Result<DownloadReport, DownloadError<T::Error>> {
+    match builder.cancel.clone() {
+        Some((token, cancelled)) => token
+            .run(download(builder, path, options))
+            .await
+            .unwrap_or_else(|Cancelled| Err(DownloadError::Remote(cancelled()))),
+        None => download(builder, path, options).await,
+    }
+}
+
+async fn download<T: crate::Client>(
     mut builder: RequestBuilder<'_, T>,
     path: impl AsRef<Path>,
     options: DownloadOptions,
@@ -113,6 +234,14 @@ pub async fn download_to_path<T: crate::Client>(
         0
     };
 
+    // Resuming is only safe when the request can be sent again from scratch
+    // should the server resume from another offset.
+    let replay = if existing_len > 0 {
+        builder.request.body().try_clone()
+    } else {
+        None
+    };
+    let mut existing_len = if replay.is_some() { existing_len } else { 0 };
     if existing_len > 0 {
         let value = format!("bytes={existing_len}-");
         builder = builder
@@ -120,43 +249,70 @@ pub async fn download_to_path<T: crate::Client>(
             .map_err(|error| DownloadError::Build(Box::new(error)))?;
     }
 
-    let response = builder.await.map_err(DownloadError::Remote)?;
+    let started = Instant::now();
+    let RequestBuilder {
+        mut client,
+        mut request,
+        timeout,
+        ..
+    } = builder;
+    // Backends take the request apart while sending it, so keep its head in
+    // case the download has to start over.
+    let head = (existing_len > 0).then(|| RequestHead::of(&request));
+    let mut response = send(&mut client, &mut request, timeout, started).await?;
+    if let Some(head) = head
+        && response.status() == StatusCode::PARTIAL_CONTENT
+        && content_range_start(&response) != Some(existing_len)
+    {
+        // Appending a range that starts elsewhere would corrupt the file.
+        drop(response);
+        head.restore(&mut request);
+        request.headers_mut().remove(header::RANGE);
+        if let Some(body) = replay {
+            *request.body_mut() = body;
+        }
+        existing_len = 0;
+        response = send(&mut client, &mut request, timeout, started).await?;
+    }
+    let timeout = timeout.map(|(duration, _)| duration);
     let status = response.status();
-    let mut body = response.into_body();
+    let (parts, mut body) = response.into_parts();
 
     if !(status.is_success() || status == StatusCode::PARTIAL_CONTENT) {
         return Err(DownloadError::Upstream(status));
     }
 
-    let mut resumed_from = 0_u64;
-    let mut file = if existing_len > 0 && status == StatusCode::PARTIAL_CONTENT {
-        resumed_from = existing_len;
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(false)
-            .open(&path_buf)
-            .await
-            .map_err(DownloadError::Io)?;
-        file.seek(SeekFrom::Start(existing_len))
-            .await
-            .map_err(DownloadError::Io)?;
-        file
+    let resumed_from = if status == StatusCode::PARTIAL_CONTENT {
+        existing_len
     } else {
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&path_buf)
-            .await
-            .map_err(DownloadError::Io)?
+        0
     };
+    let mut file = open_destination(&path_buf, resumed_from)
+        .await
+        .map_err(DownloadError::Io)?;
 
     let mut bytes_written = 0_u64;
-    while let Some(chunk) = body.next().await {
-        let chunk = chunk.map_err(DownloadError::Body)?;
-        file.write_all(&chunk).await.map_err(DownloadError::Io)?;
-        bytes_written += chunk.len() as u64;
+    let transfer = write_body(
+        &mut body,
+        &mut file,
+        options.min_speed.map(SpeedMonitor::new),
+        &mut bytes_written,
+    );
+    match timeout {
+        Some(timeout) => {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            match with_timeout(remaining, transfer).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    return Err(DownloadError::TimedOut(Box::new(PartialResponse {
+                        status,
+                        headers: parts.headers,
+                        bytes_received: bytes_written,
+                    })));
+                }
+            }
+        }
+        None => transfer.await?,
     }
     file.flush().await.map_err(DownloadError::Io)?;
 
@@ -166,3 +322,92 @@ pub async fn download_to_path<T: crate::Client>(
         bytes_written,
     })
 }
+
+/// Open `path` for writing at `offset`, truncating it unless resuming.
+async fn open_destination(path: &Path, offset: u64) -> std::io::Result<async_fs::File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(offset == 0)
+        .open(path)
+        .await?;
+    if offset > 0 {
+        file.seek(SeekFrom::Start(offset)).await?;
+    }
+    Ok(file)
+}
+
+/// Send `request`, bounded by what is left of `timeout` since `started`.
+async fn send<T: crate::Client>(
+    client: &mut T,
+    request: &mut http_kit::Request,
+    timeout: Option<RequestTimeout<T::Error>>,
+    started: Instant,
+) -> Result<http_kit::Response, DownloadError<T::Error>> {
+    let response = client.respond(request);
+    let result = match timeout {
+        Some((duration, timed_out)) => {
+            with_timeout(duration.saturating_sub(started.elapsed()), response)
+                .await
+                .unwrap_or_else(|_| Err(timed_out()))
+        }
+        None => response.await,
+    };
+    result.map_err(DownloadError::Remote)
+}
+
+/// The first byte of a `206` response, from its `Content-Range`.
+fn content_range_start(response: &http_kit::Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    crate::range::parse_content_range(value).map(|(first, _, _)| first)
+}
+
+/// Copy `body` into `file`, counting the bytes in `written` as they land.
+async fn write_body<E: HttpError>(
+    body: &mut http_kit::Body,
+    file: &mut async_fs::File,
+    mut monitor: Option<SpeedMonitor>,
+    written: &mut u64,
+) -> Result<(), DownloadError<E>> {
+    loop {
+        let chunk = match &mut monitor {
+            None => body.next().await,
+            Some(monitor) => {
+                // Wake up at least once per window so a stalled body is caught too.
+                let next = body.next();
+                let tick = Timer::after(monitor.limit.window);
+                let chunk = match futures_util::future::select(next, tick).await {
+                    Either::Left((chunk, _)) => chunk.map(Some),
+                    Either::Right(_) => Some(None),
+                };
+
+                let now = Instant::now();
+                if let Some(Some(Ok(chunk))) = &chunk {
+                    monitor.record(now, chunk.len() as u64);
+                }
+                if let Some(observed) = monitor.check(now) {
+                    return Err(DownloadError::TooSlow {
+                        observed,
+                        required: monitor.limit.bytes_per_sec,
+                        window: monitor.limit.window,
+                    });
+                }
+                match chunk {
+                    Some(None) => continue,
+                    Some(Some(chunk)) => Some(chunk),
+                    None => None,
+                }
+            }
+        };
+        let Some(chunk) = chunk else { break };
+
+        let chunk = chunk.map_err(DownloadError::Body)?;
+        file.write_all(&chunk).await.map_err(DownloadError::Io)?;
+        *written += chunk.len() as u64;
+    }
+    Ok(())
+}
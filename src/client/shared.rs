@@ -0,0 +1,189 @@
+//! A client shared by many tasks.
+
+use core::fmt::Display;
+
+use http_kit::{Endpoint, Method, Request, Response, Uri};
+
+use super::{Client, RequestBuilder};
+
+/// A client used through `&self` from many tasks.
+///
+/// Each request is sent through its own clone of the client, so requests
+/// from different tasks never wait for one another. State is shared as far
+/// as the clones of the client share it: the connections and DNS caches of
+/// a [`HyperBackend`](crate::backend::HyperBackend) or the budget of a
+/// [`ConcurrencyLimit`](crate::ratelimit::ConcurrencyLimit) are.
+///
+/// Middleware that keeps state of its own, such as a
+/// [`CookieStore`](crate::cookie::CookieStore) or a [`Cache`](crate::Cache),
+/// cannot be cloned, so a stack holding it cannot be shared at all rather
+/// than losing cookies or cached responses between clones. Give each task a
+/// client of its own where cookies must be kept:
+///
+/// ```rust,compile_fail
+/// use zenwave::{Client, backend::HyperBackend};
+///
+/// let shared = HyperBackend::new().follow_redirect().into_shared();
+/// // The cookie jar cannot be cloned, so neither can this stack.
+/// let not_shared = HyperBackend::new().enable_cookie().into_shared();
+/// ```
+///
+/// ```rust,no_run
+/// # async fn example() -> Result<(), zenwave::Error> {
+/// use zenwave::{Client, backend::HyperBackend};
+///
+/// let client = HyperBackend::new().follow_redirect().into_shared();
+/// let worker = client.clone();
+/// let (a, b) = futures_util::future::join(
+///     client.get("https://example.com/a")?.string(),
+///     worker.get("https://example.com/b")?.string(),
+/// )
+/// .await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedClient<C> {
+    client: C,
+}
+
+impl<C: Client + Clone> SharedClient<C> {
+    /// Share `client`.
+    pub const fn new(client: C) -> Self {
+        Self { client }
+    }
+
+    /// Create a request with the specified method and URI, sent through a
+    /// clone of this client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidUri`] when `uri` cannot be parsed, or
+    /// [`crate::Error::InvalidRequest`] when the request cannot be constructed.
+    pub fn method<U>(
+        &self,
+        method: Method,
+        uri: U,
+    ) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        RequestBuilder::new(self.clone(), method, uri)
+    }
+
+    /// Create a GET request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn get<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::GET, uri)
+    }
+
+    /// Create a HEAD request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn head<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::HEAD, uri)
+    }
+
+    /// Create a POST request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn post<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::POST, uri)
+    }
+
+    /// Create a PUT request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn put<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::PUT, uri)
+    }
+
+    /// Create a PATCH request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn patch<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::PATCH, uri)
+    }
+
+    /// Create a DELETE request.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`SharedClient::method`].
+    pub fn delete<U>(&self, uri: U) -> Result<RequestBuilder<'_, Self>, crate::Error>
+    where
+        U: TryInto<Uri> + Display,
+        U::Error: Display,
+    {
+        self.method(Method::DELETE, uri)
+    }
+
+    /// Create a request to a URI template; see [`Client::method_template`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`Client::method_template`].
+    pub fn method_template(
+        &self,
+        method: Method,
+        template: &str,
+        params: &[(&str, &str)],
+    ) -> Result<RequestBuilder<'_, Self>, crate::Error> {
+        let uri = crate::uri_template::expand_uri(template, params)?;
+        self.method(method, uri)
+    }
+
+    /// Create a GET request to a URI template; see [`Client::method_template`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by [`Client::method_template`].
+    pub fn get_template(
+        &self,
+        template: &str,
+        params: &[(&str, &str)],
+    ) -> Result<RequestBuilder<'_, Self>, crate::Error> {
+        self.method_template(Method::GET, template, params)
+    }
+}
+
+impl<C: Client + Clone> Endpoint for SharedClient<C> {
+    type Error = C::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        self.client.clone().respond(request).await
+    }
+}
+
+impl<C: Client + Clone> Client for SharedClient<C> {}
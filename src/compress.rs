@@ -0,0 +1,199 @@
+//! Request body compression middleware.
+//!
+//! [`CompressRequest`] encodes outgoing request bodies on the fly and labels
+//! them with `Content-Encoding`, for servers that accept compressed uploads.
+//! The body is compressed as it streams, so large uploads are never buffered.
+
+use std::{convert::Infallible, io};
+
+use async_compression::futures::bufread::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+use futures_util::{
+    TryStreamExt,
+    io::{AsyncRead, BufReader},
+};
+use http_kit::{
+    Body, Endpoint, Middleware, Request, Response,
+    header::{self, HeaderValue},
+    middleware::MiddlewareError,
+};
+
+/// Content coding applied to request bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `gzip` (RFC 1952).
+    Gzip,
+    /// `deflate`, i.e. zlib-wrapped DEFLATE (RFC 1950).
+    Deflate,
+    /// `br` (RFC 7932).
+    Brotli,
+}
+
+impl Encoding {
+    /// The `Content-Encoding` token for this coding.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "br",
+        }
+    }
+}
+
+/// Middleware that compresses request bodies with a fixed [`Encoding`].
+///
+/// Empty bodies and bodies that already carry a `Content-Encoding` header
+/// are sent unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressRequest {
+    encoding: Encoding,
+}
+
+impl CompressRequest {
+    /// Construct the middleware for `encoding`.
+    #[must_use]
+    pub const fn new(encoding: Encoding) -> Self {
+        Self { encoding }
+    }
+}
+
+impl Middleware for CompressRequest {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let already_encoded = request.headers().contains_key(header::CONTENT_ENCODING);
+        let empty = request.body().is_empty() == Some(true);
+
+        if !already_encoded && !empty {
+            let body = std::mem::replace(request.body_mut(), Body::empty());
+            *request.body_mut() = compress(body, self.encoding);
+
+            let headers = request.headers_mut();
+            headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(self.encoding.as_str()),
+            );
+            // The compressed length is unknown until the stream is drained.
+            headers.remove(header::CONTENT_LENGTH);
+        }
+
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+fn compress(body: Body, encoding: Encoding) -> Body {
+    let reader = body.map_err(io::Error::other).into_async_read();
+    match encoding {
+        Encoding::Gzip => encoded(GzipEncoder::new(reader)),
+        Encoding::Deflate => encoded(ZlibEncoder::new(reader)),
+        Encoding::Brotli => encoded(BrotliEncoder::new(reader)),
+    }
+}
+
+fn encoded(encoder: impl AsyncRead + Send + Sync + 'static) -> Body {
+    Body::from_reader(BufReader::new(encoder), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+    use crate::Client;
+    use async_compression::futures::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+    use async_lock::Mutex;
+    use futures_executor::block_on;
+    use futures_util::io::{AsyncRead, AsyncReadExt};
+    use http_kit::{
+        Body, Endpoint, Request, Response,
+        header::{self, HeaderMap},
+    };
+    use std::sync::Arc;
+
+    const PAYLOAD: &str = "zenwave zenwave zenwave zenwave zenwave zenwave zenwave zenwave";
+
+    type Recorded = (HeaderMap, Vec<u8>);
+
+    /// Records the headers and raw bytes of the last request it received.
+    #[derive(Clone, Default)]
+    struct RecordingEndpoint {
+        seen: Arc<Mutex<Option<Recorded>>>,
+    }
+
+    impl Endpoint for RecordingEndpoint {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let body = std::mem::replace(request.body_mut(), Body::empty());
+            let bytes = body.into_bytes().await.unwrap().to_vec();
+            *self.seen.lock().await = Some((request.headers().clone(), bytes));
+            Ok(Response::new(Body::empty()))
+        }
+    }
+
+    impl Client for RecordingEndpoint {}
+
+    async fn record(
+        encoding: Encoding,
+        body: &'static str,
+        content_encoding: Option<&'static str>,
+    ) -> Recorded {
+        let endpoint = RecordingEndpoint::default();
+        let seen = endpoint.seen.clone();
+        let mut client = endpoint.compress_request(encoding);
+
+        let mut builder = client
+            .post("http://example.com/upload")
+            .unwrap()
+            .header(header::CONTENT_LENGTH, body.len().to_string())
+            .unwrap()
+            .bytes_body(body.as_bytes().to_vec());
+        if let Some(value) = content_encoding {
+            builder = builder.header(header::CONTENT_ENCODING, value).unwrap();
+        }
+        builder.await.unwrap();
+
+        seen.lock().await.take().unwrap()
+    }
+
+    async fn decode(reader: impl AsyncRead + Unpin) -> String {
+        let mut reader = reader;
+        let mut out = String::new();
+        reader.read_to_string(&mut out).await.unwrap();
+        out
+    }
+
+    #[test]
+    fn compresses_body_and_sets_content_encoding() {
+        block_on(async {
+            for encoding in [Encoding::Gzip, Encoding::Deflate, Encoding::Brotli] {
+                let (headers, bytes) = record(encoding, PAYLOAD, None).await;
+                assert_eq!(headers[header::CONTENT_ENCODING], encoding.as_str());
+                assert!(!headers.contains_key(header::CONTENT_LENGTH));
+                assert_ne!(bytes, PAYLOAD.as_bytes());
+
+                let decoded = match encoding {
+                    Encoding::Gzip => decode(GzipDecoder::new(&bytes[..])).await,
+                    Encoding::Deflate => decode(ZlibDecoder::new(&bytes[..])).await,
+                    Encoding::Brotli => decode(BrotliDecoder::new(&bytes[..])).await,
+                };
+                assert_eq!(decoded, PAYLOAD);
+            }
+        });
+    }
+
+    #[test]
+    fn skips_empty_and_already_encoded_bodies() {
+        block_on(async {
+            let (headers, bytes) = record(Encoding::Gzip, "", None).await;
+            assert!(!headers.contains_key(header::CONTENT_ENCODING));
+            assert!(bytes.is_empty());
+
+            let (headers, bytes) = record(Encoding::Gzip, PAYLOAD, Some("identity")).await;
+            assert_eq!(headers[header::CONTENT_ENCODING], "identity");
+            assert_eq!(bytes, PAYLOAD.as_bytes());
+        });
+    }
+}
@@ -1,13 +1,16 @@
 //! Middleware for managing cookies in HTTP requests and responses.
 
 use crate::header;
-use crate::{Endpoint, Middleware, Request, Response};
+use crate::sanitize::{MAX_SET_COOKIE_LEN, is_clean};
+use crate::site_data::ClearSiteData;
+use crate::{Endpoint, Middleware, Request, Response, error::captured_response};
 use http_kit::HttpError;
 use http_kit::cookie::{Cookie, CookieJar};
-use http_kit::header::HeaderValue;
+use http_kit::header::{HeaderMap, HeaderValue};
 use http_kit::middleware::MiddlewareError;
 #[cfg(not(target_arch = "wasm32"))]
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 #[cfg(not(target_arch = "wasm32"))]
 use {
@@ -15,7 +18,7 @@ use {
     async_lock::Mutex as AsyncMutex,
     serde_json,
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         convert::TryFrom,
         io::ErrorKind,
         path::{Path, PathBuf},
@@ -26,10 +29,20 @@ use {
 #[cfg(not(target_arch = "wasm32"))]
 use time::OffsetDateTime;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "browser-cookies"))]
+mod browser;
+#[cfg(all(not(target_arch = "wasm32"), feature = "browser-cookies"))]
+mod sqlite;
+#[cfg(all(not(target_arch = "wasm32"), feature = "browser-cookies"))]
+pub use browser::{Browser, BrowserImport};
+
 /// Middleware for managing cookies in HTTP requests and responses.
 #[derive(Debug)]
 pub struct CookieStore {
     store: CookieJar,
+    /// The host that set each host-only cookie (one without `Domain`), by
+    /// name, so `Clear-Site-Data` can tell whose it is.
+    hosts: std::collections::HashMap<String, String>,
     #[cfg(not(target_arch = "wasm32"))]
     persistence: Option<Persistence>,
 }
@@ -52,6 +65,10 @@ pub enum CookieError {
     /// Encountered an invalid cookie header value.
     #[error("Invalid cookie header")]
     InvalidCookieHeader,
+
+    /// Failed to read a browser's cookie database.
+    #[error("Failed to import browser cookies: {0}")]
+    FailToImportBrowserCookies(std::io::Error),
 }
 impl HttpError for CookieError {}
 
@@ -65,6 +82,7 @@ impl From<CookieError> for crate::Error {
             CookieError::FailToParseCookiesFromDisk(e) => CookieErrorKind::ParseFailed(e),
             CookieError::FailToPersistCookiesToDisk(e) => CookieErrorKind::PersistFailed(e),
             CookieError::InvalidCookieHeader => CookieErrorKind::InvalidHeader,
+            CookieError::FailToImportBrowserCookies(e) => CookieErrorKind::ImportFailed(e),
         };
 
         Self::Cookie(kind)
@@ -75,6 +93,7 @@ impl Default for CookieStore {
     fn default() -> Self {
         Self {
             store: CookieJar::new(),
+            hosts: std::collections::HashMap::new(),
             #[cfg(not(target_arch = "wasm32"))]
             persistence: None,
         }
@@ -82,32 +101,120 @@ impl Default for CookieStore {
 }
 
 impl CookieStore {
-    /// Enable persistent storage using the default path for the current crate.
+    /// Enable persistent storage using a default path derived from the
+    /// current executable name.
+    ///
+    /// Every process started from an executable with the same name shares this
+    /// file, and therefore its sessions. Use [`CookieStore::persistent_for_app`]
+    /// or [`CookieStore::persistent_with_path`] when that is not intended.
+    ///
+    /// Earlier releases stored every application's cookies in one shared
+    /// `zenwave_cookie_store_zenwave.json` file; it is read once as a fallback
+    /// when the new file does not exist yet, and never written again.
     #[cfg(not(target_arch = "wasm32"))]
     #[must_use]
     pub fn persistent_default() -> Self {
-        default_cookie_path().map_or_else(Self::default, Self::persistent_with_path)
+        executable_name()
+            .and_then(|name| app_cookie_path(&name))
+            .map_or_else(Self::default, Self::persistent_with_legacy_fallback)
+    }
+
+    /// Enable persistent storage in the default data directory, keyed by an
+    /// explicit application identifier.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn persistent_for_app(app: &str) -> Self {
+        app_cookie_path(app).map_or_else(Self::default, Self::persistent_with_legacy_fallback)
     }
 
     /// Enable persistent storage using the provided path.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn persistent_with_path(path: impl Into<PathBuf>) -> Self {
         Self {
-            store: CookieJar::new(),
-            persistence: Some(Persistence::new(path.into())),
+            persistence: Some(Persistence::new(path.into(), None)),
+            ..Self::default()
+        }
+    }
+
+    /// Add the cookies saved by a browser profile to the jar, returning how
+    /// many were imported and how many were left out encrypted.
+    ///
+    /// `profile` is the browser's profile directory, or its cookie database
+    /// file. Expired cookies, Chromium cookies stored encrypted and Firefox
+    /// cookies belonging to container tabs or private windows are skipped;
+    /// host-only cookies stay host-only. The database is read on a blocking
+    /// thread. The browser keeps recent changes in a write-ahead log until
+    /// it exits, so close it first for an up-to-date import.
+    ///
+    /// ```rust,no_run
+    /// # async fn example() -> Result<(), zenwave::cookie::CookieError> {
+    /// use zenwave::cookie::{Browser, CookieStore};
+    ///
+    /// let mut store = CookieStore::default();
+    /// store
+    ///     .import_from_browser(Browser::Firefox, "/home/me/.mozilla/firefox/abcd1234.default")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CookieError::FailToImportBrowserCookies`] when the database
+    /// cannot be read or does not have the browser's layout.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "browser-cookies"))]
+    pub async fn import_from_browser(
+        &mut self,
+        browser: Browser,
+        profile: impl AsRef<Path>,
+    ) -> Result<BrowserImport, CookieError> {
+        let path = browser.database_path(profile.as_ref());
+        let now = OffsetDateTime::now_utc();
+        let (cookies, encrypted) =
+            blocking::unblock(move || browser.read_cookies(std::fs::read(path)?, now))
+                .await
+                .map_err(CookieError::FailToImportBrowserCookies)?;
+        let imported = cookies.len();
+        for browser::BrowserCookie { cookie, host } in cookies {
+            match host {
+                Some(host) => {
+                    self.hosts.insert(cookie.name().to_string(), host);
+                }
+                None => {
+                    self.hosts.remove(cookie.name());
+                }
+            }
+            self.touch(cookie.name());
+            self.store.add(cookie);
+        }
+        Ok(BrowserImport {
+            imported,
+            encrypted,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn persistent_with_legacy_fallback(path: PathBuf) -> Self {
+        let legacy = legacy_cookie_path().filter(|legacy| *legacy != path);
+        Self {
+            persistence: Some(Persistence::new(path, legacy)),
+            ..Self::default()
         }
     }
 
     async fn prepare(&mut self) -> Result<(), CookieError> {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            if let Some(path) = self
+            if let Some((path, legacy)) = self
                 .persistence
                 .as_ref()
                 .filter(|p| !p.initialized)
-                .map(|p| p.path.clone())
+                .map(|p| (p.path.clone(), p.legacy_path.clone()))
             {
-                self.load_from_disk(&path).await?;
+                let found = self.load_from_disk(&path).await?;
+                if !found && let Some(legacy) = legacy {
+                    self.load_from_disk(&legacy).await?;
+                }
                 if let Some(persistence) = self
                     .persistence
                     .as_mut()
@@ -121,45 +228,79 @@ impl CookieStore {
     }
 
     #[allow(unused_variables)]
-    async fn finalize(&self, updated: bool) -> Result<(), CookieError> {
+    async fn finalize(&mut self, updated: bool) -> Result<(), CookieError> {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            if updated && let Some(persistence) = &self.persistence {
-                self.persist_to_path(&persistence.path).await?;
+            if updated && let Some(path) = self.persistence.as_ref().map(|p| p.path.clone()) {
+                self.persist_to_path(&path).await?;
             }
         }
         Ok(())
     }
 
+    /// Record that this store set or removed the cookie `name`, so the next
+    /// write keeps its version over the one on disk.
+    #[allow(unused_variables)]
+    fn touch(&mut self, name: &str) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(persistence) = &mut self.persistence {
+            persistence.changed.insert(name.to_owned());
+        }
+    }
+
+    /// Load cookies from `path`, returning whether the file existed.
     #[cfg(not(target_arch = "wasm32"))]
-    async fn load_from_disk(&mut self, path: &Path) -> Result<(), CookieError> {
+    async fn load_from_disk(&mut self, path: &Path) -> Result<bool, CookieError> {
         let lock = file_mutex(path).await;
         let _guard = lock.lock().await;
 
-        let data = match async_fs::read(path).await {
-            Ok(data) => data,
-            Err(err) if err.kind() == ErrorKind::NotFound => {
-                return Ok(());
-            }
-            Err(err) => return Err(CookieError::FailToLoadCookiesFromDisk(err)),
+        let Some(cookies) = read_persisted(path).await? else {
+            return Ok(false);
         };
-
-        if !data.is_empty() {
-            let cookies: Vec<PersistedCookie> =
-                serde_json::from_slice(&data).map_err(CookieError::FailToParseCookiesFromDisk)?;
-            for stored in cookies {
-                self.store.add(stored.into_cookie());
-            }
+        for cookie in cookies {
+            self.store.add(cookie);
         }
-
-        Ok(())
+        Ok(true)
     }
 
+    /// Write the jar to `path`, merged with what other processes persisted
+    /// since this store last read it.
+    ///
+    /// The file is re-read under its lock; cookies this store changed since
+    /// then win, and every other cookie is taken from disk.
     #[cfg(not(target_arch = "wasm32"))]
-    async fn persist_to_path(&self, path: &Path) -> Result<(), CookieError> {
+    async fn persist_to_path(&mut self, path: &Path) -> Result<(), CookieError> {
         let lock = file_mutex(path).await;
         let _guard = lock.lock().await;
 
+        if let Some(parent) = path.parent() {
+            async_fs::create_dir_all(parent)
+                .await
+                .map_err(CookieError::FailToPersistCookiesToDisk)?;
+        }
+        let _file_lock = lock_file(path)
+            .await
+            .map_err(CookieError::FailToPersistCookiesToDisk)?;
+
+        if let Some(persistence) = &mut self.persistence {
+            let changed = std::mem::take(&mut persistence.changed);
+            if let Some(cookies) = read_persisted(path).await? {
+                let mut merged = CookieJar::new();
+                for cookie in cookies {
+                    if !changed.contains(cookie.name()) {
+                        merged.add(cookie);
+                    }
+                }
+                for cookie in self.store.iter() {
+                    if changed.contains(cookie.name()) {
+                        merged.add(cookie.clone());
+                    }
+                }
+                self.hosts.retain(|name, _| merged.get(name).is_some());
+                self.store = merged;
+            }
+        }
+
         let snapshot: Vec<PersistedCookie> = self
             .store
             .iter()
@@ -167,12 +308,6 @@ impl CookieStore {
             .collect();
         let data = serde_json::to_vec(&snapshot).expect("failed to serialize cookies to JSON"); // Safety: Serialization should not fail.
 
-        if let Some(parent) = path.parent() {
-            async_fs::create_dir_all(parent)
-                .await
-                .map_err(CookieError::FailToPersistCookiesToDisk)?;
-        }
-
         let tmp = path.with_extension("tmp");
         async_fs::write(&tmp, &data)
             .await
@@ -207,52 +342,215 @@ impl Middleware for CookieStore {
                 .map_err(|_| MiddlewareError::Middleware(CookieError::InvalidCookieHeader))?,
         );
 
-        let res = next
-            .respond(request)
-            .await
-            .map_err(MiddlewareError::Endpoint)?;
-
-        let mut updated = false;
-        for set_cookie in res.headers().get_all(header::SET_COOKIE) {
-            let set_cookie = set_cookie
-                .to_str()
-                .map_err(|_| MiddlewareError::Middleware(CookieError::InvalidCookieHeader))?;
-            let cookie = set_cookie
-                .parse::<Cookie>()
-                .map_err(|_| MiddlewareError::Middleware(CookieError::InvalidCookieHeader))?;
+        // Backends consume the request while sending it.
+        let host = request.uri().host().map(str::to_ascii_lowercase);
+        let result = next.respond(request).await;
+        // Backends report 4xx and 5xx responses as errors that keep the
+        // response, and those set cookies too, such as a CSRF token on a 401.
+        let headers = match &result {
+            Ok(res) => Some(res.headers()),
+            Err(error) => captured_response(error).map(Response::headers),
+        };
+        let updated = headers.is_some_and(|headers| self.store_cookies(host.as_deref(), headers));
+
+        match result {
+            Ok(res) => {
+                self.finalize(updated)
+                    .await
+                    .map_err(MiddlewareError::Middleware)?;
+                Ok(res)
+            }
+            Err(error) => {
+                // The HTTP error matters more to the caller than the jar.
+                if let Err(persist_error) = self.finalize(updated).await {
+                    warn!(error = %persist_error, "failed to persist cookies");
+                }
+                Err(MiddlewareError::Endpoint(error))
+            }
+        }
+    }
+}
+
+impl CookieStore {
+    /// Apply `Clear-Site-Data` and store the `Set-Cookie` headers of a
+    /// response from `host`, returning whether the jar changed.
+    fn store_cookies(&mut self, host: Option<&str>, headers: &HeaderMap) -> bool {
+        // Clear first, so cookies set by the same response are kept.
+        let mut updated = match (ClearSiteData::from_headers(headers), host) {
+            (Some(directives), Some(host)) if directives.cookies => self.clear_host(host),
+            _ => false,
+        };
+        for set_cookie in headers.get_all(header::SET_COOKIE) {
+            // A bad cookie from the server must not fail the request or
+            // poison the jar, so it is dropped.
+            let Some(cookie) = parse_set_cookie(set_cookie) else {
+                warn!(
+                    len = set_cookie.len(),
+                    "ignoring malformed Set-Cookie header"
+                );
+                continue;
+            };
+            match (cookie.domain(), host) {
+                (None, Some(host)) => {
+                    self.hosts
+                        .insert(cookie.name().to_string(), host.to_owned());
+                }
+                _ => {
+                    self.hosts.remove(cookie.name());
+                }
+            }
+            self.touch(cookie.name());
             self.store.add(cookie);
             updated = true;
         }
-        self.finalize(updated)
-            .await
-            .map_err(MiddlewareError::Middleware)?;
-        Ok(res)
+        updated
+    }
+
+    /// Drop the cookies `host` would be sent, for `Clear-Site-Data`,
+    /// returning whether any were removed.
+    ///
+    /// Host-only cookies loaded from disk have no recorded host and, since
+    /// the jar sends them everywhere, count as belonging to every host.
+    fn clear_host(&mut self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let cleared: Vec<String> = self
+            .store
+            .iter()
+            .filter(|cookie| match cookie.domain() {
+                Some(domain) => {
+                    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                    host == domain || host.ends_with(&format!(".{domain}"))
+                }
+                None => self
+                    .hosts
+                    .get(cookie.name())
+                    .is_none_or(|set_by| *set_by == host),
+            })
+            .map(|cookie| cookie.name().to_string())
+            .collect();
+        for name in &cleared {
+            self.touch(name);
+            self.store.force_remove(name);
+            self.hosts.remove(name);
+        }
+        !cleared.is_empty()
+    }
+}
+
+/// Parse a `Set-Cookie` value, or `None` when it is malformed or unsafe to keep.
+fn parse_set_cookie(value: &HeaderValue) -> Option<Cookie<'static>> {
+    if value.len() > MAX_SET_COOKIE_LEN || !is_clean(value.as_bytes()) {
+        return None;
     }
+    value.to_str().ok()?.parse().ok()
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Debug)]
 struct Persistence {
     path: PathBuf,
+    /// Pre-migration shared file, read only when `path` does not exist yet.
+    legacy_path: Option<PathBuf>,
     initialized: bool,
+    /// Cookies set or removed since the file was last read or written.
+    changed: HashSet<String>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 impl Persistence {
     #[allow(clippy::missing_const_for_fn)]
-    fn new(path: PathBuf) -> Self {
+    fn new(path: PathBuf, legacy_path: Option<PathBuf>) -> Self {
         Self {
             path,
+            legacy_path,
             initialized: false,
+            changed: HashSet::new(),
         }
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn default_cookie_path() -> Option<PathBuf> {
+fn app_cookie_path(app: &str) -> Option<PathBuf> {
     let dir = dirs::data_local_dir()?;
-    let crate_name = env!("CARGO_PKG_NAME");
-    Some(dir.join(format!("zenwave_cookie_store_{crate_name}.json")))
+    Some(dir.join(cookie_file_name(app)))
+}
+
+/// The file every application shared before paths were derived per app.
+#[cfg(not(target_arch = "wasm32"))]
+fn legacy_cookie_path() -> Option<PathBuf> {
+    app_cookie_path("zenwave")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn cookie_file_name(app: &str) -> String {
+    let app: String = app
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("zenwave_cookie_store_{app}.json")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn executable_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.file_stem()?.to_string_lossy().into_owned())
+}
+
+/// Take an advisory lock on a sidecar `.lock` file so other processes
+/// persisting the same store wait until this one has re-read, merged and
+/// written the file. The lock is released when the returned file is dropped.
+///
+/// Opening and waiting for the lock block, so both happen on a blocking
+/// thread.
+#[cfg(not(target_arch = "wasm32"))]
+async fn lock_file(path: &Path) -> std::io::Result<std::fs::File> {
+    let path = path.with_extension("lock");
+    blocking::unblock(move || {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        file.lock()?;
+        Ok(file)
+    })
+    .await
+}
+
+/// Read the cookies persisted at `path`, or `None` when there is no file.
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_persisted(path: &Path) -> Result<Option<Vec<Cookie<'static>>>, CookieError> {
+    let data = match async_fs::read(path).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(CookieError::FailToLoadCookiesFromDisk(err)),
+    };
+    if data.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    let cookies: Vec<PersistedCookie> =
+        serde_json::from_slice(&data).map_err(CookieError::FailToParseCookiesFromDisk)?;
+    Ok(Some(
+        cookies
+            .into_iter()
+            // The file may have been edited or written by an older version;
+            // skip entries that would corrupt the Cookie header.
+            .filter(|stored| {
+                let valid = stored.is_valid();
+                if !valid {
+                    warn!(path = %path.display(), "ignoring invalid persisted cookie");
+                }
+                valid
+            })
+            .map(PersistedCookie::into_cookie)
+            .collect(),
+    ))
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -284,6 +582,21 @@ impl PersistedCookie {
         }
     }
 
+    fn is_valid(&self) -> bool {
+        let fields = [
+            Some(&self.name),
+            Some(&self.value),
+            self.domain.as_ref(),
+            self.path.as_ref(),
+        ];
+        !self.name.is_empty()
+            && self.name.len() + self.value.len() <= MAX_SET_COOKIE_LEN
+            && fields
+                .into_iter()
+                .flatten()
+                .all(|field| is_clean(field.as_bytes()))
+    }
+
     fn into_cookie(self) -> Cookie<'static> {
         let mut builder = Cookie::build((self.name, self.value));
         if let Some(domain) = self.domain {
@@ -355,6 +668,237 @@ mod tests {
         });
     }
 
+    #[test]
+    fn app_paths_are_derived_per_application() {
+        assert_eq!(
+            cookie_file_name("my app/1"),
+            "zenwave_cookie_store_my_app_1.json"
+        );
+
+        let exe = executable_name().expect("test binary must have a name");
+        assert_ne!(cookie_file_name(&exe), cookie_file_name("zenwave"));
+
+        // Stores of different applications do not see each other's cookies.
+        let dir = tempdir().unwrap();
+        async_io::block_on(async {
+            let mut app =
+                CookieStore::persistent_with_path(dir.path().join(cookie_file_name(&exe)));
+            app.handle(&mut new_request(), &mut SetCookieEndpoint)
+                .await
+                .unwrap();
+
+            let mut other =
+                CookieStore::persistent_with_path(dir.path().join(cookie_file_name("zenwave")));
+            let mut echo = RecordingEndpoint::default();
+            other.handle(&mut new_request(), &mut echo).await.unwrap();
+            assert!(!echo.last_cookie().unwrap_or_default().contains("session"));
+        });
+    }
+
+    #[test]
+    fn legacy_file_is_read_when_new_path_is_missing() {
+        let dir = tempdir().unwrap();
+        let legacy = dir.path().join("zenwave_cookie_store_zenwave.json");
+        let path = dir.path().join("zenwave_cookie_store_myapp.json");
+
+        async_io::block_on(async {
+            let mut old = CookieStore::persistent_with_path(legacy.clone());
+            let mut request = new_request();
+            old.handle(&mut request, &mut SetCookieEndpoint)
+                .await
+                .unwrap();
+
+            let mut migrated = CookieStore {
+                persistence: Some(Persistence::new(path.clone(), Some(legacy.clone()))),
+                ..CookieStore::default()
+            };
+            let mut echo = RecordingEndpoint::default();
+            let mut request = new_request();
+            migrated.handle(&mut request, &mut echo).await.unwrap();
+
+            let header = echo.last_cookie().expect("cookie header missing");
+            assert!(header.contains("session=abc"));
+            assert!(!path.exists(), "nothing new was set, so nothing is written");
+        });
+    }
+
+    /// Sends crafted `Set-Cookie` values next to a valid one.
+    struct CraftedCookieEndpoint;
+
+    impl Endpoint for CraftedCookieEndpoint {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            Ok(HttpResponse::builder()
+                .header(header::SET_COOKIE, &b"latin=caf\xe9"[..])
+                .header(header::SET_COOKIE, format!("big={}", "a".repeat(5000)))
+                .header(header::SET_COOKIE, "=nameless")
+                .header(header::SET_COOKIE, "good=1")
+                .body(Body::empty())
+                .unwrap())
+        }
+    }
+
+    #[test]
+    fn crafted_set_cookie_values_are_ignored() {
+        async_io::block_on(async {
+            let mut store = CookieStore::default();
+            let response = store
+                .handle(&mut new_request(), &mut CraftedCookieEndpoint)
+                .await
+                .expect("bad cookies must not fail the request");
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let mut echo = RecordingEndpoint::default();
+            store.handle(&mut new_request(), &mut echo).await.unwrap();
+            assert_eq!(echo.last_cookie().as_deref(), Some("good=1"));
+        });
+    }
+
+    #[test]
+    fn invalid_persisted_cookies_are_skipped_on_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cookies.json");
+        std::fs::write(
+            &path,
+            r#"[
+                {"name":"evil","value":"a\r\nX-Injected: 1","domain":null,"path":null,
+                 "secure":false,"http_only":false,"expires":null},
+                {"name":"nul","value":"a\u0000b","domain":null,"path":null,
+                 "secure":false,"http_only":false,"expires":null},
+                {"name":"session","value":"abc","domain":null,"path":null,
+                 "secure":false,"http_only":false,"expires":null}
+            ]"#,
+        )
+        .unwrap();
+
+        async_io::block_on(async {
+            let mut store = CookieStore::persistent_with_path(path);
+            let mut echo = RecordingEndpoint::default();
+            store.handle(&mut new_request(), &mut echo).await.unwrap();
+            assert_eq!(echo.last_cookie().as_deref(), Some("session=abc"));
+        });
+    }
+
+    #[test]
+    fn stores_sharing_a_file_keep_each_others_cookies() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cookies.json");
+
+        async_io::block_on(async {
+            let mut first = CookieStore::persistent_with_path(path.clone());
+            let mut second = CookieStore::persistent_with_path(path.clone());
+            let mut quiet = SiteEndpoint {
+                set_cookie: None,
+                clear_site_data: None,
+            };
+            // Both read the file before either writes to it.
+            for store in [&mut first, &mut second] {
+                store.handle(&mut new_request(), &mut quiet).await.unwrap();
+            }
+            for (store, cookie) in [(&mut first, "a=1"), (&mut second, "b=2")] {
+                let mut endpoint = SiteEndpoint {
+                    set_cookie: Some(cookie),
+                    clear_site_data: None,
+                };
+                store
+                    .handle(&mut new_request(), &mut endpoint)
+                    .await
+                    .unwrap();
+            }
+
+            let mut restored = CookieStore::persistent_with_path(path);
+            let mut echo = RecordingEndpoint::default();
+            restored
+                .handle(&mut new_request(), &mut echo)
+                .await
+                .unwrap();
+            let header = echo.last_cookie().expect("cookie header missing");
+            assert!(header.contains("a=1"), "{header}");
+            assert!(header.contains("b=2"), "{header}");
+        });
+    }
+
+    /// Answers with the given `Set-Cookie` and `Clear-Site-Data` values.
+    struct SiteEndpoint {
+        set_cookie: Option<&'static str>,
+        clear_site_data: Option<&'static str>,
+    }
+
+    impl Endpoint for SiteEndpoint {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            let mut response = HttpResponse::builder();
+            if let Some(cookie) = self.set_cookie {
+                response = response.header(header::SET_COOKIE, cookie);
+            }
+            if let Some(directives) = self.clear_site_data {
+                response = response.header("clear-site-data", directives);
+            }
+            Ok(response.body(Body::empty()).unwrap())
+        }
+    }
+
+    fn request_to(uri: &str) -> Request {
+        HttpRequest::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn clear_site_data_drops_only_that_origins_cookies() {
+        async_io::block_on(async {
+            let mut store = CookieStore::default();
+            for (uri, cookie) in [
+                ("https://a.example/login", "session=a"),
+                ("https://a.example/login", "shared=a; Domain=a.example"),
+                ("https://b.example/login", "other=b"),
+            ] {
+                let mut endpoint = SiteEndpoint {
+                    set_cookie: Some(cookie),
+                    clear_site_data: None,
+                };
+                store
+                    .handle(&mut request_to(uri), &mut endpoint)
+                    .await
+                    .unwrap();
+            }
+
+            let mut logout = SiteEndpoint {
+                set_cookie: None,
+                clear_site_data: Some(r#""cookies""#),
+            };
+            store
+                .handle(&mut request_to("https://a.example/logout"), &mut logout)
+                .await
+                .unwrap();
+
+            let names: Vec<&str> = store.store.iter().map(Cookie::name).collect();
+            assert_eq!(names, ["other"]);
+        });
+    }
+
+    #[test]
+    fn clear_site_data_without_cookies_directive_keeps_cookies() {
+        async_io::block_on(async {
+            let mut store = CookieStore::default();
+            let mut endpoint = SiteEndpoint {
+                set_cookie: Some("session=a"),
+                clear_site_data: Some(r#""cache""#),
+            };
+            store
+                .handle(&mut request_to("https://a.example/"), &mut endpoint)
+                .await
+                .unwrap();
+            assert!(store.store.get("session").is_some());
+        });
+    }
+
+    fn new_request() -> Request {
+        HttpRequest::builder()
+            .method(http_kit::Method::GET)
+            .uri("https://example.com")
+            .body(Body::empty())
+            .unwrap()
+    }
+
     struct SetCookieEndpoint;
 
     impl Endpoint for SetCookieEndpoint {
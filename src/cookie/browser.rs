@@ -0,0 +1,328 @@
+//! Reading the cookie databases of installed browsers.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use http_kit::cookie::Cookie;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use super::PersistedCookie;
+use super::sqlite::{Database, Row, Value};
+
+/// Seconds between the Windows epoch (1601-01-01) used by Chromium and the
+/// Unix epoch.
+const WINDOWS_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// A browser whose cookie database can be imported with
+/// [`CookieStore::import_from_browser`](super::CookieStore::import_from_browser).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Browser {
+    /// Firefox, which keeps cookies in `cookies.sqlite` in the profile
+    /// directory.
+    Firefox,
+    /// Chrome and other Chromium-based browsers, which keep cookies in
+    /// `Network/Cookies` (`Cookies` before Chrome 96) in the profile
+    /// directory.
+    ///
+    /// Chromium encrypts cookie values with a key held by the operating
+    /// system's keychain. Encrypted values cannot be read here, so those
+    /// cookies are left out and counted in [`BrowserImport::encrypted`].
+    Chrome,
+}
+
+/// What [`CookieStore::import_from_browser`](super::CookieStore::import_from_browser)
+/// added to the jar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BrowserImport {
+    /// Cookies added to the jar.
+    pub imported: usize,
+    /// Cookies left out because the browser stored their value encrypted.
+    pub encrypted: usize,
+}
+
+/// A cookie read from a browser's database.
+#[derive(Debug)]
+pub(super) struct BrowserCookie {
+    pub(super) cookie: Cookie<'static>,
+    /// The host that set the cookie, when it is host-only.
+    pub(super) host: Option<String>,
+}
+
+impl Browser {
+    /// The cookie database in `profile`, which may also name the database
+    /// file directly.
+    pub(super) fn database_path(self, profile: &Path) -> PathBuf {
+        if profile.is_file() {
+            return profile.to_path_buf();
+        }
+        match self {
+            Self::Firefox => profile.join("cookies.sqlite"),
+            Self::Chrome => {
+                let network = profile.join("Network").join("Cookies");
+                if network.exists() {
+                    network
+                } else {
+                    profile.join("Cookies")
+                }
+            }
+        }
+    }
+
+    /// Decode the unexpired cookies in the database `data`, along with the
+    /// number of encrypted cookies left out.
+    pub(super) fn read_cookies(
+        self,
+        data: Vec<u8>,
+        now: OffsetDateTime,
+    ) -> io::Result<(Vec<BrowserCookie>, usize)> {
+        let database = Database::parse(data)?;
+        let (table, decode): (_, Decode) = match self {
+            Self::Firefox => ("moz_cookies", firefox_cookie),
+            Self::Chrome => ("cookies", chrome_cookie),
+        };
+        let table = database.table(table)?;
+
+        let mut skipped = 0usize;
+        let mut encrypted = 0usize;
+        let mut cookies = Vec::new();
+        for row in table.rows() {
+            let (cookie, host) = match decode(row) {
+                Entry::Cookie(cookie, host) => (cookie, host),
+                Entry::Encrypted => {
+                    encrypted += 1;
+                    continue;
+                }
+                Entry::Invalid => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let unexpired = cookie
+                .expires
+                .is_none_or(|expires| expires > i128::from(now.unix_timestamp()));
+            if cookie.is_valid() && unexpired {
+                cookies.push(BrowserCookie {
+                    cookie: cookie.into_cookie(),
+                    host,
+                });
+            }
+        }
+        if skipped > 0 {
+            warn!(browser = ?self, skipped, "skipped browser cookies that cannot be imported");
+        }
+        if encrypted > 0 {
+            warn!(browser = ?self, encrypted, "skipped encrypted browser cookies");
+        }
+        Ok((cookies, encrypted))
+    }
+}
+
+fn text(row: Row<'_>, column: &str) -> Option<String> {
+    row.get(column).as_text().map(ToString::to_string)
+}
+
+fn flag(row: Row<'_>, column: &str) -> bool {
+    row.get(column).as_integer().is_some_and(|value| value != 0)
+}
+
+/// A row of a browser's cookie table.
+enum Entry {
+    /// A cookie, with the host that set it when it is host-only.
+    Cookie(PersistedCookie, Option<String>),
+    /// A cookie whose value the browser encrypted.
+    Encrypted,
+    /// A row that does not hold an importable cookie.
+    Invalid,
+}
+
+type Decode = fn(Row<'_>) -> Entry;
+
+/// Split a browser's host column into the cookie's domain and, for a
+/// host-only cookie, its host. Domain cookies are stored with a leading dot.
+fn domain_and_host(host: Option<String>) -> (Option<String>, Option<String>) {
+    match host {
+        Some(host) if host.starts_with('.') => (Some(host[1..].to_string()), None),
+        Some(host) => (None, Some(host.to_ascii_lowercase())),
+        None => (None, None),
+    }
+}
+
+fn firefox_cookie(row: Row<'_>) -> Entry {
+    // Cookies of container tabs, private windows and isolated sites are kept
+    // apart from the default context; they are not imported.
+    if row
+        .get("originAttributes")
+        .as_text()
+        .is_some_and(|attrs| !attrs.is_empty())
+    {
+        return Entry::Invalid;
+    }
+    let Some(expiry) = row.get("expiry").as_integer() else {
+        return Entry::Invalid;
+    };
+    // Newer Firefox releases store `expiry` in milliseconds, not seconds.
+    let expires = if expiry > 100_000_000_000 {
+        expiry / 1000
+    } else {
+        expiry
+    };
+    let (domain, host) = domain_and_host(text(row, "host"));
+    let (Some(name), Some(value)) = (text(row, "name"), text(row, "value")) else {
+        return Entry::Invalid;
+    };
+    let cookie = PersistedCookie {
+        name,
+        value,
+        domain,
+        path: text(row, "path"),
+        secure: flag(row, "isSecure"),
+        http_only: flag(row, "isHttpOnly"),
+        expires: Some(i128::from(expires)),
+    };
+    Entry::Cookie(cookie, host)
+}
+
+fn chrome_cookie(row: Row<'_>) -> Entry {
+    let Some(name) = text(row, "name") else {
+        return Entry::Invalid;
+    };
+    let value = text(row, "value").unwrap_or_default();
+    let encrypted = row
+        .get("encrypted_value")
+        .as_blob()
+        .is_some_and(|blob| !blob.is_empty());
+    if value.is_empty() && encrypted {
+        return Entry::Encrypted;
+    }
+    // Microseconds since 1601; zero marks a session cookie.
+    let expires = match row.get("expires_utc") {
+        Value::Integer(0) | Value::Null => None,
+        Value::Integer(micros) => Some(i128::from(micros / 1_000_000 - WINDOWS_EPOCH_OFFSET)),
+        _ => return Entry::Invalid,
+    };
+    let (domain, host) = domain_and_host(text(row, "host_key"));
+    let cookie = PersistedCookie {
+        name,
+        value,
+        domain,
+        path: text(row, "path"),
+        secure: flag(row, "is_secure"),
+        http_only: flag(row, "is_httponly"),
+        expires,
+    };
+    Entry::Cookie(cookie, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Browser;
+    use std::path::Path;
+    use time::OffsetDateTime;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn reads_firefox_cookies() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let (read, encrypted) = Browser::Firefox
+            .read_cookies(fixture("firefox_cookies.sqlite"), now)
+            .unwrap();
+        assert_eq!(encrypted, 0);
+        let hosts: Vec<_> = read.iter().map(|cookie| cookie.host.clone()).collect();
+        let cookies: Vec<_> = read.into_iter().map(|cookie| cookie.cookie).collect();
+
+        // 65 rows: one expired and one from a container tab are left out.
+        assert_eq!(cookies.len(), 63);
+
+        let session = cookies.iter().find(|c| c.name() == "session").unwrap();
+        assert_eq!(session.value(), "abc123");
+        assert_eq!(session.domain(), Some("example.com"));
+        assert_eq!(session.path(), Some("/"));
+        assert_eq!(session.secure(), Some(true));
+        assert_eq!(session.http_only(), Some(true));
+        assert_eq!(
+            session
+                .expires_datetime()
+                .map(OffsetDateTime::unix_timestamp),
+            Some(4_102_444_800)
+        );
+
+        let theme = cookies.iter().position(|c| c.name() == "theme").unwrap();
+        assert_eq!(
+            (cookies[theme].value(), cookies[theme].path()),
+            ("dark", Some("/app"))
+        );
+        assert_eq!(cookies[theme].secure(), Some(false));
+        // Host-only: no domain, and the host that set it is kept.
+        assert_eq!(cookies[theme].domain(), None);
+        assert_eq!(hosts[theme].as_deref(), Some("example.com"));
+
+        // Stored across overflow pages.
+        let large = cookies.iter().find(|c| c.name() == "large").unwrap();
+        assert_eq!(large.value(), "x".repeat(1500));
+
+        assert!(cookies.iter().any(|c| c.name() == "filler59"));
+        assert!(!cookies.iter().any(|c| c.name() == "expired"));
+        assert!(!cookies.iter().any(|c| c.name() == "container"));
+    }
+
+    #[test]
+    fn reads_chrome_cookies() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let (read, encrypted) = Browser::Chrome
+            .read_cookies(fixture("chrome_cookies.sqlite"), now)
+            .unwrap();
+        assert_eq!(encrypted, 0);
+        assert_eq!(read.len(), 2, "the expired cookie is left out");
+
+        let session = read.iter().find(|c| c.cookie.name() == "session").unwrap();
+        assert_eq!(session.cookie.value(), "abc123");
+        assert_eq!(session.cookie.domain(), Some("example.com"));
+        assert_eq!(session.host, None);
+        assert_eq!(session.cookie.secure(), Some(true));
+        assert_eq!(session.cookie.http_only(), Some(true));
+        assert_eq!(
+            session
+                .cookie
+                .expires_datetime()
+                .map(OffsetDateTime::unix_timestamp),
+            Some(4_102_444_800)
+        );
+
+        let theme = read.iter().find(|c| c.cookie.name() == "theme").unwrap();
+        assert_eq!(theme.cookie.domain(), None);
+        assert_eq!(theme.host.as_deref(), Some("app.example.com"));
+        assert_eq!(theme.cookie.path(), Some("/app"));
+        assert_eq!(theme.cookie.expires_datetime(), None, "a session cookie");
+    }
+
+    #[test]
+    fn encrypted_chrome_cookies_are_counted_and_left_out() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let (read, encrypted) = Browser::Chrome
+            .read_cookies(fixture("chrome_cookies_encrypted.sqlite"), now)
+            .unwrap();
+        assert_eq!(encrypted, 1);
+        let names: Vec<_> = read.iter().map(|c| c.cookie.name()).collect();
+        assert_eq!(names, ["plain"]);
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_databases() {
+        let now = OffsetDateTime::now_utc();
+        let error = Browser::Firefox
+            .read_cookies(b"{\"cookies\": []}".to_vec(), now)
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
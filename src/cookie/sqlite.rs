@@ -0,0 +1,431 @@
+//! Just enough of the `SQLite` file format to read a table of a browser's
+//! cookie database.
+//!
+//! Only rowid tables in UTF-8 databases are supported, which covers the
+//! cookie stores of Firefox and Chromium-based browsers. Writes that are
+//! still in a `-wal` file next to the database are not seen, so the browser
+//! should be closed (or the database copied) before importing.
+//!
+//! See <https://www.sqlite.org/fileformat.html>.
+
+use std::io::{self, ErrorKind};
+
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+const HEADER_LEN: usize = 100;
+
+const INTERIOR_TABLE_PAGE: u8 = 0x05;
+const LEAF_TABLE_PAGE: u8 = 0x0d;
+
+/// A column value.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub(super) const fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_blob(&self) -> Option<&[u8]> {
+        match self {
+            Self::Blob(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Every row of one table, with the column names from its schema.
+#[derive(Debug)]
+pub(super) struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    /// Iterate over the rows; columns are looked up by name with [`Row::get`].
+    pub(super) fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        self.rows.iter().map(|values| Row {
+            columns: &self.columns,
+            values,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Row<'a> {
+    columns: &'a [String],
+    values: &'a [Value],
+}
+
+impl Row<'_> {
+    /// The value of `column`, `Null` when the row predates the column or the
+    /// table has no such column.
+    pub(super) fn get(&self, column: &str) -> &Value {
+        self.columns
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(column))
+            .and_then(|index| self.values.get(index))
+            .unwrap_or(&Value::Null)
+    }
+}
+
+/// A database file loaded into memory.
+#[derive(Debug)]
+pub(super) struct Database {
+    data: Vec<u8>,
+    page_size: usize,
+    usable_size: usize,
+}
+
+impl Database {
+    pub(super) fn parse(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < HEADER_LEN || !data.starts_with(MAGIC) {
+            return Err(invalid("not an SQLite database"));
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(invalid("invalid page size"));
+        }
+        let reserved = usize::from(data[20]);
+        let encoding = u32::from_be_bytes([data[56], data[57], data[58], data[59]]);
+        if encoding > 1 {
+            return Err(invalid("only UTF-8 databases are supported"));
+        }
+        Ok(Self {
+            data,
+            page_size,
+            usable_size: page_size - reserved,
+        })
+    }
+
+    /// Read every row of the table `name`.
+    pub(super) fn table(&self, name: &str) -> io::Result<Table> {
+        // The schema table is rooted at page 1: (type, name, tbl_name, rootpage, sql).
+        let mut found = None;
+        self.scan(1, &mut |record| {
+            if record.first().and_then(Value::as_text) == Some("table")
+                && record.get(1).and_then(Value::as_text) == Some(name)
+            {
+                found = Some(record);
+            }
+            Ok(())
+        })?;
+        let schema = found.ok_or_else(|| invalid(format!("no `{name}` table")))?;
+        let root = schema
+            .get(3)
+            .and_then(Value::as_integer)
+            .and_then(|page| u32::try_from(page).ok())
+            .ok_or_else(|| invalid("invalid root page"))?;
+        let columns = schema
+            .get(4)
+            .and_then(Value::as_text)
+            .ok_or_else(|| invalid("missing table definition"))
+            .and_then(column_names)?;
+
+        let mut rows = Vec::new();
+        self.scan(root, &mut |record| {
+            rows.push(record);
+            Ok(())
+        })?;
+        Ok(Table { columns, rows })
+    }
+
+    /// Call `visit` with the record of every row in the table b-tree rooted
+    /// at `root`, in rowid order.
+    fn scan(
+        &self,
+        root: u32,
+        visit: &mut dyn FnMut(Vec<Value>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut pending = vec![root];
+        // A well-formed tree visits each page once; more means a cycle.
+        let mut budget = self.page_count();
+        while let Some(number) = pending.pop() {
+            budget = budget
+                .checked_sub(1)
+                .ok_or_else(|| invalid("b-tree cycle"))?;
+            let page = self.page(number)?;
+            // Page 1 starts with the file header.
+            let header = if number == 1 { HEADER_LEN } else { 0 };
+            let kind = *page.get(header).ok_or_else(truncated)?;
+            let cells = usize::from(read_u16(page, header + 3)?);
+            match kind {
+                LEAF_TABLE_PAGE => {
+                    for index in 0..cells {
+                        let offset = usize::from(read_u16(page, header + 8 + index * 2)?);
+                        visit(self.leaf_cell(page, offset)?)?;
+                    }
+                }
+                INTERIOR_TABLE_PAGE => {
+                    // Pushed in reverse so children pop in rowid order.
+                    pending.push(read_u32(page, header + 8)?);
+                    for index in (0..cells).rev() {
+                        let offset = usize::from(read_u16(page, header + 12 + index * 2)?);
+                        pending.push(read_u32(page, offset)?);
+                    }
+                }
+                _ => return Err(invalid("not a table b-tree page")),
+            }
+        }
+        Ok(())
+    }
+
+    const fn page_count(&self) -> usize {
+        self.data.len() / self.page_size
+    }
+
+    fn page(&self, number: u32) -> io::Result<&[u8]> {
+        let index = usize::try_from(number)
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .ok_or_else(|| invalid("invalid page number"))?;
+        let start = index * self.page_size;
+        self.data
+            .get(start..start + self.page_size)
+            .ok_or_else(truncated)
+    }
+
+    /// Decode the record stored in the table leaf cell at `offset`,
+    /// following overflow pages for large rows.
+    fn leaf_cell(&self, page: &[u8], offset: usize) -> io::Result<Vec<Value>> {
+        let (payload_len, used) = read_varint(page, offset)?;
+        let (_rowid, rowid_len) = read_varint(page, offset + used)?;
+        let start = offset + used + rowid_len;
+        let payload_len = usize::try_from(payload_len)
+            .ok()
+            .filter(|&len| len <= self.data.len())
+            .ok_or_else(|| invalid("oversized payload"))?;
+
+        let usable = self.usable_size;
+        let max_local = usable - 35;
+        let local = if payload_len <= max_local {
+            payload_len
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (payload_len - min_local) % (usable - 4);
+            if local <= max_local { local } else { min_local }
+        };
+
+        let mut payload = page
+            .get(start..start + local)
+            .ok_or_else(truncated)?
+            .to_vec();
+        if local < payload_len {
+            let mut next = read_u32(page, start + local)?;
+            // Like the b-tree, a chain longer than the file has a cycle.
+            let mut budget = self.page_count();
+            while payload.len() < payload_len {
+                budget = budget
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid("overflow page cycle"))?;
+                let overflow = self.page(next)?;
+                let take = (payload_len - payload.len()).min(usable - 4);
+                payload.extend_from_slice(overflow.get(4..4 + take).ok_or_else(truncated)?);
+                next = read_u32(overflow, 0)?;
+            }
+        }
+        parse_record(&payload)
+    }
+}
+
+fn parse_record(payload: &[u8]) -> io::Result<Vec<Value>> {
+    let (header_len, mut cursor) = read_varint(payload, 0)?;
+    let header_len = usize::try_from(header_len).map_err(|_| invalid("oversized header"))?;
+    let mut body = header_len;
+    let mut values = Vec::new();
+    while cursor < header_len {
+        let (serial, used) = read_varint(payload, cursor)?;
+        cursor += used;
+        let (value, len) = decode(serial, payload.get(body..).ok_or_else(truncated)?)?;
+        body += len;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Decode one value of the given serial type, returning it and its length.
+fn decode(serial: u64, bytes: &[u8]) -> io::Result<(Value, usize)> {
+    let int = |len: usize| -> io::Result<(Value, usize)> {
+        let raw = bytes.get(..len).ok_or_else(truncated)?;
+        // Sign-extend from the first byte.
+        let mut value = i64::from(raw[0].cast_signed());
+        for &byte in &raw[1..] {
+            value = (value << 8) | i64::from(byte);
+        }
+        Ok((Value::Integer(value), len))
+    };
+    match serial {
+        0 => Ok((Value::Null, 0)),
+        1 => int(1),
+        2 => int(2),
+        3 => int(3),
+        4 => int(4),
+        5 => int(6),
+        6 => int(8),
+        7 => {
+            let raw = bytes.get(..8).ok_or_else(truncated)?;
+            let bits = u64::from_be_bytes(raw.try_into().expect("slice of eight bytes"));
+            Ok((Value::Real(f64::from_bits(bits)), 8))
+        }
+        8 => Ok((Value::Integer(0), 0)),
+        9 => Ok((Value::Integer(1), 0)),
+        10 | 11 => Err(invalid("reserved serial type")),
+        _ => {
+            let len = usize::try_from((serial - 12) / 2).map_err(|_| truncated())?;
+            let raw = bytes.get(..len).ok_or_else(truncated)?;
+            let value = if serial.is_multiple_of(2) {
+                Value::Blob(raw.to_vec())
+            } else {
+                Value::Text(String::from_utf8_lossy(raw).into_owned())
+            };
+            Ok((value, len))
+        }
+    }
+}
+
+/// Column names in declaration order from a `CREATE TABLE` statement.
+fn column_names(sql: &str) -> io::Result<Vec<String>> {
+    let missing = || invalid("table definition has no column list");
+    let open = sql.find('(').ok_or_else(missing)?;
+    let close = sql[open..].rfind(')').ok_or_else(missing)? + open;
+    let mut definitions = Vec::new();
+    let mut depth = 0;
+    let mut start = open + 1;
+    for (index, ch) in sql[..close].char_indices().skip_while(|&(i, _)| i <= open) {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                definitions.push(&sql[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    definitions.push(&sql[start..close]);
+
+    let names = definitions
+        .into_iter()
+        .filter_map(|definition| definition.split_whitespace().next())
+        .filter(|name| {
+            !["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+                .iter()
+                .any(|keyword| name.eq_ignore_ascii_case(keyword))
+        })
+        .map(|name| name.trim_matches(['"', '`', '[', ']']).to_string())
+        .collect();
+    Ok(names)
+}
+
+/// Read a big-endian variable-length integer, returning it and its length.
+fn read_varint(bytes: &[u8], offset: usize) -> io::Result<(u64, usize)> {
+    let mut value = 0u64;
+    for index in 0..9 {
+        let byte = *bytes.get(offset + index).ok_or_else(truncated)?;
+        if index == 8 {
+            return Ok(((value << 8) | u64::from(byte), 9));
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    unreachable!("the ninth byte always ends a varint")
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    let raw = bytes.get(offset..offset + 2).ok_or_else(truncated)?;
+    Ok(u16::from_be_bytes([raw[0], raw[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let raw = bytes.get(offset..offset + 4).ok_or_else(truncated)?;
+    Ok(u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]))
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn truncated() -> io::Error {
+    invalid("database file is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, HEADER_LEN, LEAF_TABLE_PAGE, MAGIC, column_names};
+    use std::io::ErrorKind;
+
+    /// A two-page database whose schema page holds one cell announcing a
+    /// payload of `payload_len` bytes.
+    fn database_with_payload(payload_len: u64) -> Database {
+        let mut data = vec![0_u8; 1024];
+        data[..16].copy_from_slice(MAGIC);
+        data[16..18].copy_from_slice(&512_u16.to_be_bytes());
+        data[56..60].copy_from_slice(&1_u32.to_be_bytes());
+        data[HEADER_LEN] = LEAF_TABLE_PAGE;
+        data[HEADER_LEN + 3..HEADER_LEN + 5].copy_from_slice(&1_u16.to_be_bytes());
+        data[HEADER_LEN + 8..HEADER_LEN + 10].copy_from_slice(&200_u16.to_be_bytes());
+        // The payload length as a varint, then rowid 1.
+        let mut varint = Vec::new();
+        let mut rest = payload_len;
+        loop {
+            varint.push(u8::try_from(rest & 0x7f).unwrap());
+            rest >>= 7;
+            if rest == 0 {
+                break;
+            }
+        }
+        varint.reverse();
+        let last = varint.len() - 1;
+        for byte in &mut varint[..last] {
+            *byte |= 0x80;
+        }
+        varint.push(1);
+        data[200..200 + varint.len()].copy_from_slice(&varint);
+        Database::parse(data).unwrap()
+    }
+
+    #[test]
+    fn payloads_larger_than_the_file_are_errors() {
+        let error = database_with_payload(1 << 40).table("cookies").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("oversized payload"), "{error}");
+    }
+
+    #[test]
+    fn column_names_skip_constraints() {
+        let sql = "CREATE TABLE t (id INTEGER, \"name\" TEXT DEFAULT (''), \
+                   UNIQUE (id, name))";
+        assert_eq!(column_names(sql).unwrap(), ["id", "name"]);
+    }
+
+    #[test]
+    fn malformed_table_definitions_are_errors() {
+        for sql in [
+            "CREATE TABLE t",
+            "CREATE TABLE t ) x (",
+            "CREATE TABLE t (id",
+        ] {
+            assert!(column_names(sql).is_err(), "{sql}");
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Policy for response `Content-Encoding`s that are not decoded.
+//!
+//! Zenwave hands response bodies over exactly as the server sent them. A
+//! body with a `Content-Encoding` other than `identity` is therefore still
+//! encoded, and parsing it as JSON or text would fail with a misleading
+//! error or produce garbage. [`FlagUndecoded`] makes this explicit: it drops
+//! no-op `identity` codings and marks the rest with an [`UndecodedEncoding`]
+//! extension. [`crate::ResponseExt::is_decoded`] reports whether a body is
+//! plain, and [`crate::ResponseExt::into_json`] refuses the bodies it marked.
+
+use std::convert::Infallible;
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{self, HeaderMap},
+    middleware::MiddlewareError,
+};
+use tracing::warn;
+
+/// Response extension naming the content codings left on the body, in the
+/// order they were applied (for example `gzip` or `x-custom`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndecodedEncoding(String);
+
+impl UndecodedEncoding {
+    /// The codings, comma-separated as in the `Content-Encoding` header.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The codings in `headers` other than `identity`, or `None` when the body
+/// is plain.
+pub(crate) fn undecoded(headers: &HeaderMap) -> Option<String> {
+    let codings: Vec<&str> = headers
+        .get_all(header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+        .collect();
+    (!codings.is_empty()).then(|| codings.join(", "))
+}
+
+/// Middleware applying the `Content-Encoding` policy described in the
+/// [module documentation](self).
+///
+/// `Content-Encoding: identity` is removed, since the body is already plain.
+/// Any other coding is left untouched, recorded in an [`UndecodedEncoding`]
+/// extension and logged as a warning.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlagUndecoded;
+
+impl Middleware for FlagUndecoded {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        // Backends consume the request while sending it.
+        let uri = request.uri().clone();
+        let mut response = next
+            .respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)?;
+        if !response.headers().contains_key(header::CONTENT_ENCODING) {
+            return Ok(response);
+        }
+        match undecoded(response.headers()) {
+            None => {
+                response.headers_mut().remove(header::CONTENT_ENCODING);
+            }
+            Some(encoding) => {
+                warn!(%uri, %encoding, "response body left encoded");
+                response
+                    .extensions_mut()
+                    .insert(UndecodedEncoding(encoding));
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{FlagUndecoded, UndecodedEncoding};
+    use crate::ResponseExt;
+    use http_kit::{Body, Endpoint, Request, Response, endpoint::WithMiddleware, header};
+
+    /// Answers with a JSON body and the configured `Content-Encoding`.
+    struct Encoded(Option<&'static str>);
+
+    impl Endpoint for Encoded {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            let mut response =
+                http::Response::builder().header(header::CONTENT_TYPE, "application/json");
+            if let Some(encoding) = self.0 {
+                response = response.header(header::CONTENT_ENCODING, encoding);
+            }
+            Ok(response.body(Body::from(r#"{"ok":true}"#)).unwrap())
+        }
+    }
+
+    fn respond(encoding: Option<&'static str>) -> Response {
+        let mut client = WithMiddleware::new(Encoded(encoding), FlagUndecoded);
+        let mut request = http::Request::get("https://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        async_io::block_on(client.respond(&mut request)).unwrap()
+    }
+
+    #[test]
+    fn identity_is_removed() {
+        let response = respond(Some("identity"));
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        assert!(response.is_decoded());
+        let value: serde_json::Value = async_io::block_on(response.into_json()).unwrap();
+        assert_eq!(value, serde_json::json!({"ok": true}));
+    }
+
+    #[test]
+    fn unknown_encoding_is_flagged() {
+        let response = respond(Some("identity, x-custom"));
+        assert_eq!(
+            response
+                .extensions()
+                .get::<UndecodedEncoding>()
+                .map(UndecodedEncoding::name),
+            Some("x-custom")
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_ENCODING],
+            "identity, x-custom"
+        );
+        assert!(!response.is_decoded());
+        let error = async_io::block_on(response.into_json::<serde_json::Value>()).unwrap_err();
+        assert!(error.to_string().contains("x-custom"), "{error}");
+    }
+
+    #[test]
+    fn encoded_header_alone_does_not_refuse_the_body() {
+        // The Apple and web backends decode bodies but keep the header.
+        let mut request = http::Request::get("https://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        let response = async_io::block_on(Encoded(Some("gzip")).respond(&mut request)).unwrap();
+        let value: serde_json::Value = async_io::block_on(response.into_json()).unwrap();
+        assert_eq!(value, serde_json::json!({"ok": true}));
+    }
+
+    #[test]
+    fn plain_response_is_untouched() {
+        let response = respond(None);
+        assert!(response.extensions().get::<UndecodedEncoding>().is_none());
+        assert!(response.is_decoded());
+    }
+}
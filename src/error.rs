@@ -10,7 +10,8 @@
 //! The [`Error`] type implements [`http_kit::HttpError`] trait and provides
 //! rich helper methods for error classification and handling.
 
-use http_kit::{BodyError, Response, StatusCode};
+use crate::{auth::AuthChallenge, retry::Attempts, timeout::TimeoutError};
+use http_kit::{BodyError, Response, StatusCode, header::HeaderMap};
 use std::error::Error as StdError;
 use thiserror::Error;
 
@@ -44,7 +45,16 @@ pub enum Error {
 
     /// Request timed out.
     #[error("request timed out")]
-    Timeout,
+    Timeout {
+        /// What had arrived when the deadline passed while the response body
+        /// was being read; `None` when no response had been received.
+        partial: Option<Box<PartialResponse>>,
+    },
+
+    /// The request's [`CancelToken`](crate::cancel::CancelToken) was
+    /// cancelled.
+    #[error("request cancelled")]
+    Cancelled,
 
     /// Too many redirects were followed.
     #[error("too many redirects (max {max})")]
@@ -53,10 +63,24 @@ pub enum Error {
         max: u32,
     },
 
+    /// A redirect chain revisited a URL it had already requested.
+    #[error("redirect loop detected at {url}")]
+    RedirectLoop {
+        /// URL that was visited a second time
+        url: String,
+    },
+
     /// Invalid redirect Location header.
     #[error("invalid redirect location")]
     InvalidRedirectLocation,
 
+    /// A redirect required resending a streaming request body that cannot be replayed.
+    #[error("{status} redirect requires resending a request body that cannot be replayed")]
+    RedirectBodyNotReplayable {
+        /// Status of the redirect response (307 or 308)
+        status: StatusCode,
+    },
+
     /// URI parsing error.
     #[error("invalid URI: {0}")]
     InvalidUri(String),
@@ -67,7 +91,7 @@ pub enum Error {
 
     /// Response body parsing error (JSON, form, string, etc.).
     #[error("failed to parse response body: {0}")]
-    BodyParse(#[from] BodyError),
+    BodyParse(#[source] BodyError),
 
     /// Response body exceeded the caller-provided in-memory limit.
     #[error("response body exceeds the {limit}-byte limit")]
@@ -99,6 +123,20 @@ pub enum Error {
     /// Other uncategorized error.
     #[error("other error: {0}")]
     Other(#[source] Box<dyn StdError + Send + Sync>),
+
+    /// The last error of a request that [`crate::retry::Retry`] sent more
+    /// than once.
+    ///
+    /// The classification helpers, such as [`Error::kind`] and
+    /// [`Error::response`], look through to the last error.
+    #[error("{last} (after {} attempts)", attempts.total())]
+    Retried {
+        /// Error of the final attempt
+        #[source]
+        last: Box<Self>,
+        /// Summary of every attempt
+        attempts: Attempts,
+    },
 }
 
 /// HTTP error response details.
@@ -109,7 +147,8 @@ pub enum Error {
 pub struct HttpErrorResponse {
     /// Complete HTTP response (including headers, body, etc.)
     pub response: Response,
-    /// Response body as text (if available and UTF-8)
+    /// Response body as text (if available and UTF-8), cut at the backend's
+    /// error body limit
     pub body_text: Option<String>,
 }
 
@@ -121,6 +160,135 @@ impl std::fmt::Display for HttpErrorResponse {
     }
 }
 
+/// Find the response captured by an [`Error::Http`], even when middleware
+/// errors wrap it.
+pub(crate) fn captured_response<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a Response> {
+    core::iter::successors(Some(error), |&error| error.source())
+        .find_map(|error| error.downcast_ref::<Box<HttpErrorResponse>>())
+        .map(|captured| &captured.response)
+}
+
+/// Status, headers and body size of a response whose request timed out
+/// while its body was being read.
+///
+/// Carried by [`Error::Timeout`] for diagnostics; the body itself is not kept.
+#[derive(Debug, Clone)]
+pub struct PartialResponse {
+    /// Status of the response.
+    pub status: StatusCode,
+    /// Response headers.
+    pub headers: HeaderMap,
+    /// Number of body bytes read before the deadline.
+    pub bytes_received: u64,
+}
+
+/// Host name resolution failure, reported inside [`Error::Transport`].
+///
+/// Backends that cache failed lookups return the cached failure until its
+/// TTL expires; [`DnsError::is_cached`] tells the two cases apart, which
+/// explains why a DNS fix can take a while to be noticed.
+#[derive(Debug, Clone)]
+pub struct DnsError {
+    host: String,
+    message: String,
+    cached: bool,
+}
+
+impl DnsError {
+    #[cfg(all(feature = "hyper-backend", not(target_arch = "wasm32")))]
+    pub(crate) const fn new(host: String, message: String) -> Self {
+        Self {
+            host,
+            message,
+            cached: false,
+        }
+    }
+
+    #[cfg(all(feature = "hyper-backend", not(target_arch = "wasm32")))]
+    pub(crate) fn cached(&self) -> Self {
+        Self {
+            cached: true,
+            ..self.clone()
+        }
+    }
+
+    /// The host name that failed to resolve.
+    #[must_use]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Whether this failure was served from the negative cache instead of a
+    /// fresh lookup.
+    #[must_use]
+    pub const fn is_cached(&self) -> bool {
+        self.cached
+    }
+}
+
+impl StdError for DnsError {}
+
+impl std::fmt::Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to resolve `{}`: {}", self.host, self.message)?;
+        if self.cached {
+            f.write_str(" (cached)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Connection failure while a response body was being read, reported
+/// inside [`Error::Transport`].
+///
+/// The status and headers had arrived by then; [`bytes_received`] tells how
+/// much of the body did too.
+///
+/// [`bytes_received`]: InterruptedBody::bytes_received
+#[derive(Debug)]
+pub struct InterruptedBody {
+    bytes_received: u64,
+    source: Box<dyn StdError + Send + Sync>,
+}
+
+impl InterruptedBody {
+    #[cfg_attr(
+        not(all(feature = "hyper-backend", not(target_arch = "wasm32"))),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(
+        bytes_received: u64,
+        source: impl Into<Box<dyn StdError + Send + Sync>>,
+    ) -> Self {
+        Self {
+            bytes_received,
+            source: source.into(),
+        }
+    }
+
+    /// Number of body bytes read before the connection failed.
+    #[must_use]
+    pub const fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+}
+
+impl StdError for InterruptedBody {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+impl std::fmt::Display for InterruptedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "connection failed after {} body bytes: {}",
+            self.bytes_received, self.source
+        )
+    }
+}
+
 /// Cookie-related errors.
 #[derive(Debug, Error)]
 pub enum CookieErrorKind {
@@ -139,6 +307,10 @@ pub enum CookieErrorKind {
     /// Invalid cookie header.
     #[error("invalid cookie header")]
     InvalidHeader,
+
+    /// Failed to import cookies from a browser.
+    #[error("failed to import browser cookies: {0}")]
+    ImportFailed(#[source] std::io::Error),
 }
 
 /// OAuth2-related errors.
@@ -160,6 +332,19 @@ pub enum OAuth2ErrorKind {
     /// Invalid token response format.
     #[error("invalid token response: {0}")]
     InvalidTokenResponse(String),
+
+    /// Token endpoint refused the request with an RFC 6749 error code.
+    #[error("token endpoint returned error: {status} - {error}")]
+    Provider {
+        /// HTTP status code from token endpoint
+        status: StatusCode,
+        /// Error code, such as `invalid_grant` or `invalid_client`
+        error: String,
+        /// Human-readable explanation, if provided
+        description: Option<String>,
+        /// Page documenting the error, if provided
+        uri: Option<String>,
+    },
 }
 
 /// Download-related errors.
@@ -176,6 +361,17 @@ pub enum DownloadErrorKind {
     /// Failed to read response body.
     #[error("failed to read response body: {0}")]
     BodyRead(String),
+
+    /// Throughput stayed below the configured minimum speed.
+    #[error("transfer too slow: {observed} B/s over the last {window:?}, need {required} B/s")]
+    TooSlow {
+        /// Average rate observed over the window, in bytes per second.
+        observed: u64,
+        /// Minimum rate that was required, in bytes per second.
+        required: u64,
+        /// Window the rate was averaged over.
+        window: std::time::Duration,
+    },
 }
 
 /// WebSocket-related errors.
@@ -198,40 +394,71 @@ impl Error {
     /// Check if this is a network transport error.
     #[must_use]
     pub const fn is_network_error(&self) -> bool {
-        matches!(self, Self::Transport(_) | Self::Tls(_))
+        match self {
+            Self::Retried { last, .. } => last.is_network_error(),
+            _ => matches!(self, Self::Transport(_) | Self::Tls(_)),
+        }
     }
 
     /// Check if this is a timeout error.
     #[must_use]
     pub const fn is_timeout(&self) -> bool {
-        matches!(self, Self::Timeout)
+        match self {
+            Self::Retried { last, .. } => last.is_timeout(),
+            _ => matches!(self, Self::Timeout { .. }),
+        }
+    }
+
+    /// Check if the request was cancelled through its
+    /// [`CancelToken`](crate::cancel::CancelToken).
+    #[must_use]
+    pub const fn is_cancelled(&self) -> bool {
+        match self {
+            Self::Retried { last, .. } => last.is_cancelled(),
+            _ => matches!(self, Self::Cancelled),
+        }
     }
 
     /// Check if this is a client error (4xx HTTP status).
     #[must_use]
     pub fn is_client_error(&self) -> bool {
-        matches!(self, Self::Http { status, .. } if status.is_client_error())
+        match self {
+            Self::Retried { last, .. } => last.is_client_error(),
+            _ => matches!(self, Self::Http { status, .. } if status.is_client_error()),
+        }
     }
 
     /// Check if this is a server error (5xx HTTP status).
     #[must_use]
     pub fn is_server_error(&self) -> bool {
-        matches!(self, Self::Http { status, .. } if status.is_server_error())
+        match self {
+            Self::Retried { last, .. } => last.is_server_error(),
+            _ => matches!(self, Self::Http { status, .. } if status.is_server_error()),
+        }
     }
 
     /// Check if this is a redirect-related error.
     #[must_use]
     pub const fn is_redirect_error(&self) -> bool {
-        matches!(
-            self,
-            Self::TooManyRedirects { .. } | Self::InvalidRedirectLocation
-        )
+        match self {
+            Self::Retried { last, .. } => last.is_redirect_error(),
+            _ => matches!(
+                self,
+                Self::TooManyRedirects { .. }
+                    | Self::RedirectLoop { .. }
+                    | Self::InvalidRedirectLocation
+                    | Self::RedirectBodyNotReplayable { .. }
+            ),
+        }
     }
 
     /// Check if this is a request construction error.
     #[must_use]
     pub const fn is_request_error(&self) -> bool {
-        matches!(self, Self::InvalidRequest(_) | Self::InvalidUri(_))
+        match self {
+            Self::Retried { last, .. } => last.is_request_error(),
+            _ => matches!(self, Self::InvalidRequest(_) | Self::InvalidUri(_)),
+        }
     }
 
     /// Get the response body text (if this is an HTTP error).
@@ -239,6 +466,7 @@ impl Error {
     pub fn response_body(&self) -> Option<&str> {
         match self {
             Self::Http { response, .. } => response.body_text.as_deref(),
+            Self::Retried { last, .. } => last.response_body(),
             _ => None,
         }
     }
@@ -248,10 +476,63 @@ impl Error {
     pub const fn response(&self) -> Option<&Response> {
         match self {
             Self::Http { response, .. } => Some(&response.response),
+            Self::Retried { last, .. } => last.response(),
+            _ => None,
+        }
+    }
+
+    /// The HTTP error response, for middleware that annotates it.
+    pub(crate) fn http_response_mut(&mut self) -> Option<&mut HttpErrorResponse> {
+        match self {
+            Self::Http { response, .. } => Some(response),
+            Self::Retried { last, .. } => last.http_response_mut(),
+            _ => None,
+        }
+    }
+
+    /// The part of the response that had arrived when a timeout fired
+    /// mid-body (if this is such a timeout).
+    #[must_use]
+    pub fn partial_response(&self) -> Option<&PartialResponse> {
+        match self {
+            Self::Timeout { partial } => partial.as_deref(),
+            Self::Retried { last, .. } => last.partial_response(),
+            _ => None,
+        }
+    }
+
+    /// The failure that cut a response body short, when the connection
+    /// broke while it was being read.
+    #[must_use]
+    pub fn interrupted_body(&self) -> Option<&InterruptedBody> {
+        match self {
+            Self::Transport(error) => error.downcast_ref(),
+            Self::Retried { last, .. } => last.interrupted_body(),
+            _ => None,
+        }
+    }
+
+    /// The attempts [`crate::retry::Retry`] made before returning this
+    /// error, when it sent the request more than once.
+    #[must_use]
+    pub const fn attempts(&self) -> Option<&Attempts> {
+        match self {
+            Self::Retried { attempts, .. } => Some(attempts),
             _ => None,
         }
     }
 
+    /// The `WWW-Authenticate` challenge of a 401 response.
+    ///
+    /// Bearer servers explain a rejected token here; see
+    /// [`AuthChallenge::error`] and [`AuthChallenge::error_description`].
+    #[must_use]
+    pub fn auth_challenge(&self) -> Option<AuthChallenge> {
+        self.response()
+            .filter(|response| response.status() == StatusCode::UNAUTHORIZED)
+            .and_then(|response| AuthChallenge::from_headers(response.headers()))
+    }
+
     /// Attempt to deserialize the HTTP error response body as a specific type.
     ///
     /// This is useful for APIs that return structured error responses.
@@ -289,6 +570,7 @@ impl Error {
                 .body_text
                 .as_ref()
                 .and_then(|text| serde_json::from_str(text).ok()),
+            Self::Retried { last, .. } => last.deserialize_http_error(),
             _ => None,
         }
     }
@@ -302,8 +584,12 @@ impl Error {
             Self::Http { .. } => ErrorKind::Http,
             Self::Transport(_) => ErrorKind::Transport,
             Self::Tls(_) => ErrorKind::Tls,
-            Self::Timeout => ErrorKind::Timeout,
-            Self::TooManyRedirects { .. } | Self::InvalidRedirectLocation => ErrorKind::Redirect,
+            Self::Timeout { .. } => ErrorKind::Timeout,
+            Self::Cancelled => ErrorKind::Cancelled,
+            Self::TooManyRedirects { .. }
+            | Self::RedirectLoop { .. }
+            | Self::InvalidRedirectLocation
+            | Self::RedirectBodyNotReplayable { .. } => ErrorKind::Redirect,
             Self::InvalidUri(_) | Self::InvalidRequest(_) => ErrorKind::Request,
             Self::BodyParse(_) => ErrorKind::BodyParse,
             Self::ResponseBodyTooLarge { .. } => ErrorKind::ResponseBodyLimit,
@@ -313,6 +599,7 @@ impl Error {
             Self::WebSocket(_) => ErrorKind::WebSocket,
             Self::Io(_) => ErrorKind::Io,
             Self::Other(_) => ErrorKind::Other,
+            Self::Retried { last, .. } => last.kind(),
         }
     }
 }
@@ -330,6 +617,8 @@ pub enum ErrorKind {
     Tls,
     /// Timeout error
     Timeout,
+    /// The request was cancelled
+    Cancelled,
     /// Redirect error
     Redirect,
     /// Request construction error
@@ -359,6 +648,7 @@ impl std::fmt::Display for ErrorKind {
             Self::Transport => write!(f, "transport"),
             Self::Tls => write!(f, "tls"),
             Self::Timeout => write!(f, "timeout"),
+            Self::Cancelled => write!(f, "cancelled"),
             Self::Redirect => write!(f, "redirect"),
             Self::Request => write!(f, "request"),
             Self::BodyParse => write!(f, "body_parse"),
@@ -373,13 +663,45 @@ impl std::fmt::Display for ErrorKind {
     }
 }
 
+impl From<BodyError> for Error {
+    fn from(error: BodyError) -> Self {
+        match error {
+            // A body that stalled past an idle or read timeout timed out; it
+            // did not fail to parse.
+            BodyError::Io(io) if wraps::<TimeoutError>(&io) => Self::Timeout { partial: None },
+            // Neither did one whose connection broke.
+            BodyError::Io(io) if wraps::<InterruptedBody>(&io) => {
+                Self::Transport(io.into_inner().expect("checked above"))
+            }
+            error => Self::BodyParse(error),
+        }
+    }
+}
+
+/// Whether `io` carries a `T`.
+fn wraps<T: StdError + 'static>(io: &std::io::Error) -> bool {
+    matches!(io.get_ref(), Some(inner) if inner.is::<T>())
+}
+
+// Lets layers that cannot fail, such as header-setting middleware, compose
+// with the others.
+impl From<core::convert::Infallible> for Error {
+    fn from(error: core::convert::Infallible) -> Self {
+        match error {}
+    }
+}
+
 // Implement http_kit::HttpError trait for Error
 impl http_kit::HttpError for Error {
     fn status(&self) -> StatusCode {
         match self {
-            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            Self::Retried { last, .. } => last.status(),
             Self::Http { status, .. }
-            | Self::OAuth2(OAuth2ErrorKind::TokenEndpointError { status, .. })
+            | Self::OAuth2(
+                OAuth2ErrorKind::TokenEndpointError { status, .. }
+                | OAuth2ErrorKind::Provider { status, .. },
+            )
             | Self::Download(DownloadErrorKind::UpstreamError(status)) => *status,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -1,17 +1,27 @@
-use futures_util::StreamExt;
+use std::time::SystemTime;
+
+use futures_util::{Stream, StreamExt};
 use http_kit::{
     BodyError,
     sse::SseStream,
     utils::{ByteStr, Bytes},
 };
 
+use mime::Mime;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::backend::ConnectionInfo;
+use crate::{capture::SentRequest, encoding::UndecodedEncoding, retry::Attempts};
+
 /// Extension trait for `Response` to add additional functionality.
 pub trait ResponseExt {
     /// Consumes the response body and parses it as JSON into the specified type.
     ///
     /// # Errors
     ///
-    /// Returns an error if the body cannot be parsed as JSON.
+    /// Returns an error if the body cannot be parsed as JSON, or without
+    /// reading it when [`FlagUndecoded`](crate::encoding::FlagUndecoded)
+    /// marked the body as still encoded.
     fn into_json<T: serde::de::DeserializeOwned>(
         self,
     ) -> impl Future<Output = Result<T, BodyError>> + Send;
@@ -32,6 +42,32 @@ pub trait ResponseExt {
     /// Returns an error if the body cannot be converted to bytes.
     fn into_bytes(self) -> impl Future<Output = Result<Bytes, BodyError>> + Send;
 
+    /// Consumes the response and returns its body as a stream of chunks, in
+    /// the order they arrive.
+    ///
+    /// Use it to process a large body incrementally, for example to report
+    /// progress, instead of buffering it with [`Self::into_bytes`].
+    fn into_byte_stream(
+        self,
+    ) -> impl Stream<Item = Result<Bytes, crate::Error>> + Send + Unpin + 'static;
+
+    /// Consumes the response and streams its body into the file at `path`,
+    /// returning the number of bytes written.
+    ///
+    /// The file is created, or truncated when it exists. Use it to persist a
+    /// response whose headers were already inspected; to resume interrupted
+    /// transfers, use `RequestBuilder::download_to_path` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] when the file cannot be written, or a
+    /// body error when the response stream fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_to_file(
+        self,
+        path: impl AsRef<std::path::Path> + Send,
+    ) -> impl Future<Output = Result<u64, crate::Error>> + Send;
+
     /// Consumes the response body and returns at most `limit` bytes.
     ///
     /// Streaming stops as soon as the configured limit is exceeded, so an
@@ -46,6 +82,116 @@ pub trait ResponseExt {
         limit: usize,
     ) -> impl Future<Output = Result<Bytes, crate::Error>> + Send;
 
+    /// Consumes the response body and parses it as JSON, reading at most
+    /// `max_bytes` bytes.
+    ///
+    /// The size check happens while streaming, before any parsing starts, so
+    /// an oversized payload from an untrusted endpoint is rejected without
+    /// being buffered in full.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::ResponseBodyTooLarge`] when the body exceeds
+    /// `max_bytes`, or [`crate::Error::BodyParse`] when the body is not valid
+    /// JSON for `T`.
+    fn json_with_limit<T: serde::de::DeserializeOwned>(
+        self,
+        max_bytes: usize,
+    ) -> impl Future<Output = Result<T, crate::Error>> + Send;
+
+    /// Consumes the response body and decodes it as base64 text.
+    ///
+    /// Both the standard and the URL-safe alphabet are accepted, padding is
+    /// optional, and whitespace such as the line breaks of MIME-style
+    /// wrapping is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::BodyParse`] when the body is not valid base64,
+    /// or a body error when the response stream fails.
+    fn base64_decoded(self) -> impl Future<Output = Result<Bytes, crate::Error>> + Send;
+
+    /// Consumes the response body and decodes it with the encoding named by
+    /// `label`, ignoring any charset the server declared.
+    ///
+    /// `label` is any WHATWG encoding label, such as `shift_jis` or
+    /// `windows-1252`. This is an escape hatch for servers known to mislabel
+    /// their responses; malformed sequences decode to U+FFFD.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when `label` names no known
+    /// encoding, or a body error when the response stream fails.
+    fn text_with_encoding(
+        self,
+        label: &str,
+    ) -> impl Future<Output = Result<String, crate::Error>> + Send;
+
+    /// Consumes the response body and decodes it with the charset declared
+    /// in `Content-Type`, or UTF-8 when there is none.
+    ///
+    /// See [`text_with_charset_or`](Self::text_with_charset_or). Unlike
+    /// [`into_string`](Self::into_string), which requires UTF-8, this decodes
+    /// legacy encodings such as `ISO-8859-1` or `Shift_JIS`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a body error when the response stream fails.
+    fn text_with_charset(self) -> impl Future<Output = Result<String, crate::Error>> + Send;
+
+    /// Consumes the response body and decodes it with the charset declared
+    /// in `Content-Type`, or the encoding named by `fallback` when none is
+    /// declared or the declared one is unknown.
+    ///
+    /// A byte order mark takes precedence over both, as in browsers, and
+    /// malformed sequences decode to U+FFFD.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when `fallback` names no known
+    /// encoding, or a body error when the response stream fails.
+    fn text_with_charset_or(
+        self,
+        fallback: &str,
+    ) -> impl Future<Output = Result<String, crate::Error>> + Send;
+
+    /// Returns the parsed `Content-Type` header, or `None` when it is missing
+    /// or malformed.
+    fn content_type(&self) -> Option<Mime>;
+
+    /// Returns the request snapshot recorded by
+    /// [`Client::capture_sent_request`](crate::Client::capture_sent_request).
+    fn sent_request(&self) -> Option<&SentRequest>;
+
+    /// Returns how the attempts of a request sent through
+    /// [`Retry`](crate::retry::Retry) went, including the failures before
+    /// this response.
+    fn attempts(&self) -> Option<&Attempts>;
+
+    /// Returns the addresses, TLS version and ALPN protocol of the
+    /// connection the response arrived on, when the backend reports them.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn connection_info(&self) -> Option<&ConnectionInfo>;
+
+    /// Whether the body is plain, carrying no content coding other than
+    /// `identity`.
+    ///
+    /// Zenwave does not decompress bodies; see [`crate::encoding`].
+    fn is_decoded(&self) -> bool;
+
+    /// Returns the `ETag` header, quotes and weak prefix included, ready to
+    /// pass to [`RequestBuilder::if_none_match`](crate::client::RequestBuilder::if_none_match).
+    fn etag(&self) -> Option<String>;
+
+    /// Returns the `Last-Modified` header as a time, or `None` when it is
+    /// missing or not a valid HTTP date.
+    fn last_modified(&self) -> Option<SystemTime>;
+
+    /// Returns the language tags listed in the `Content-Language` headers.
+    ///
+    /// Comma-separated values and repeated headers are flattened in order.
+    fn content_language(&self) -> Vec<String>;
+
     /// Consumes the response, returning it unchanged when the status is a
     /// success (2xx) and a rich [`crate::Error::Http`] otherwise.
     ///
@@ -54,16 +200,34 @@ pub trait ResponseExt {
     /// error messages surface in the returned error instead of being
     /// silently dropped.
     ///
+    /// The built-in backends already turn 4xx/5xx responses into
+    /// [`crate::Error::Http`] before they reach the caller; this helper is for
+    /// responses produced elsewhere, such as custom endpoints or mocks, and for
+    /// treating any other non-2xx status (like an unfollowed 3xx) as an error.
+    ///
     /// # Errors
     ///
     /// Returns [`crate::Error::Http`] when the status is not 2xx.
     fn error_for_status(self) -> impl Future<Output = Result<Self, crate::Error>> + Send
     where
         Self: Sized;
+
+    /// Borrowing variant of [`ResponseExt::error_for_status`].
+    ///
+    /// The body is left untouched, so the returned error carries the status
+    /// and headers but no `body_text`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Http`] when the status is not 2xx.
+    fn error_for_status_ref(&self) -> Result<(), crate::Error>;
 }
 
 impl ResponseExt for crate::Response {
     async fn into_json<T: serde::de::DeserializeOwned>(self) -> Result<T, BodyError> {
+        if let Some(error) = still_encoded(&self) {
+            return Err(error);
+        }
         self.into_body().into_json().await
     }
 
@@ -78,6 +242,31 @@ impl ResponseExt for crate::Response {
         self.into_body().into_bytes()
     }
 
+    fn into_byte_stream(
+        self,
+    ) -> impl Stream<Item = Result<Bytes, crate::Error>> + Send + Unpin + 'static {
+        self.into_body().map(|chunk| chunk.map_err(Into::into))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn save_to_file(
+        self,
+        path: impl AsRef<std::path::Path> + Send,
+    ) -> Result<u64, crate::Error> {
+        use http_kit::utils::AsyncWriteExt;
+
+        let mut file = async_fs::File::create(path).await?;
+        let mut body = self.into_body();
+        let mut written = 0_u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+
     async fn into_bytes_with_limit(self, limit: usize) -> Result<Bytes, crate::Error> {
         let mut body = self.into_body();
         let mut bytes = Vec::new();
@@ -95,6 +284,114 @@ impl ResponseExt for crate::Response {
         Ok(bytes.into())
     }
 
+    async fn json_with_limit<T: serde::de::DeserializeOwned>(
+        self,
+        max_bytes: usize,
+    ) -> Result<T, crate::Error> {
+        if let Some(error) = still_encoded(&self) {
+            return Err(error.into());
+        }
+        let bytes = self.into_bytes_with_limit(max_bytes).await?;
+        serde_json::from_slice(&bytes).map_err(|error| BodyError::from(error).into())
+    }
+
+    async fn base64_decoded(self) -> Result<Bytes, crate::Error> {
+        use base64::{
+            Engine, alphabet,
+            engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+        };
+
+        let text = self.into_body().into_bytes().await?;
+        let encoded: Vec<u8> = text
+            .iter()
+            .copied()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        // The two alphabets differ only in `+/` versus `-_`.
+        let alphabet = if encoded.iter().any(|byte| matches!(byte, b'-' | b'_')) {
+            &alphabet::URL_SAFE
+        } else {
+            &alphabet::STANDARD
+        };
+        let config =
+            GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        GeneralPurpose::new(alphabet, config)
+            .decode(&encoded)
+            .map(Bytes::from)
+            .map_err(|error| BodyError::Other(Box::new(error)).into())
+    }
+
+    async fn text_with_encoding(self, label: &str) -> Result<String, crate::Error> {
+        let encoding = encoding_for_label(label)?;
+        let bytes = self.into_body().into_bytes().await?;
+        // Skip BOM sniffing too: the caller's label is authoritative.
+        let (text, _) = encoding.decode_without_bom_handling(&bytes);
+        Ok(text.into_owned())
+    }
+
+    fn text_with_charset(self) -> impl Future<Output = Result<String, crate::Error>> + Send {
+        self.text_with_charset_or("utf-8")
+    }
+
+    async fn text_with_charset_or(self, fallback: &str) -> Result<String, crate::Error> {
+        let fallback = encoding_for_label(fallback)?;
+        let encoding = self
+            .content_type()
+            .and_then(|mime| {
+                let charset = mime.get_param(mime::CHARSET)?;
+                encoding_rs::Encoding::for_label(charset.as_str().as_bytes())
+            })
+            .unwrap_or(fallback);
+        let bytes = self.into_body().into_bytes().await?;
+        let (text, _, _) = encoding.decode(&bytes);
+        Ok(text.into_owned())
+    }
+
+    fn content_type(&self) -> Option<Mime> {
+        self.headers()
+            .get(http_kit::header::CONTENT_TYPE)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn sent_request(&self) -> Option<&SentRequest> {
+        self.extensions().get::<SentRequest>()
+    }
+
+    fn attempts(&self) -> Option<&Attempts> {
+        self.extensions().get::<Attempts>()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.extensions().get::<ConnectionInfo>()
+    }
+
+    fn is_decoded(&self) -> bool {
+        self.extensions().get::<UndecodedEncoding>().is_none()
+            && crate::encoding::undecoded(self.headers()).is_none()
+    }
+
+    fn etag(&self) -> Option<String> {
+        let etag = self.headers().get(http_kit::header::ETAG)?.to_str().ok()?;
+        Some(etag.trim().to_string())
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        let date = self
+            .headers()
+            .get(http_kit::header::LAST_MODIFIED)?
+            .to_str()
+            .ok()?;
+        httpdate::parse_http_date(date).ok()
+    }
+
+    fn content_language(&self) -> Vec<String> {
+        crate::locale::content_language(self.headers())
+    }
+
     async fn error_for_status(self) -> Result<Self, crate::Error> {
         let status = self.status();
         if status.is_success() {
@@ -102,20 +399,58 @@ impl ResponseExt for crate::Response {
         }
         let (parts, body) = self.into_parts();
         let body_text = body.into_string().await.ok().map(|text| text.to_string());
-        let message = body_text.clone().unwrap_or_else(|| {
-            status
-                .canonical_reason()
-                .unwrap_or("Unknown error")
-                .to_string()
-        });
-        Err(crate::Error::Http {
-            status,
-            message,
-            response: Box::new(crate::error::HttpErrorResponse {
-                response: Self::from_parts(parts, http_kit::Body::empty()),
-                body_text,
-            }),
-        })
+        Err(http_error(
+            Self::from_parts(parts, http_kit::Body::empty()),
+            body_text,
+        ))
+    }
+
+    fn error_for_status_ref(&self) -> Result<(), crate::Error> {
+        if self.status().is_success() {
+            return Ok(());
+        }
+        let mut response = Self::new(http_kit::Body::empty());
+        *response.status_mut() = self.status();
+        *response.version_mut() = self.version();
+        response.headers_mut().clone_from(self.headers());
+        Err(http_error(response, None))
+    }
+}
+
+/// The encoding named by the WHATWG `label`.
+fn encoding_for_label(label: &str) -> Result<&'static encoding_rs::Encoding, crate::Error> {
+    encoding_rs::Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+        crate::Error::InvalidRequest(format!("unknown text encoding label `{label}`"))
+    })
+}
+
+/// The error for parsing a body that still carries a content coding.
+fn still_encoded(response: &crate::Response) -> Option<BodyError> {
+    // Only `FlagUndecoded` knows the body was left encoded: the Apple and
+    // web backends decode it but keep the `Content-Encoding` header.
+    let encoding = response.extensions().get::<UndecodedEncoding>()?.name();
+    let error = std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("response body is still `{encoding}`-encoded"),
+    );
+    Some(BodyError::Other(Box::new(error)))
+}
+
+fn http_error(response: crate::Response, body_text: Option<String>) -> crate::Error {
+    let status = response.status();
+    let message = body_text.clone().unwrap_or_else(|| {
+        status
+            .canonical_reason()
+            .unwrap_or("Unknown error")
+            .to_string()
+    });
+    crate::Error::Http {
+        status,
+        message,
+        response: Box::new(crate::error::HttpErrorResponse {
+            response,
+            body_text,
+        }),
     }
 }
 
@@ -123,8 +458,8 @@ impl ResponseExt for crate::Response {
 mod tests {
     use super::ResponseExt;
     use futures_executor::block_on;
-    use futures_util::stream;
-    use http_kit::{Body, Response, utils::Bytes};
+    use futures_util::{StreamExt, stream};
+    use http_kit::{Body, HttpError, Response, StatusCode, utils::Bytes};
 
     #[test]
     fn bounded_response_accepts_body_at_limit() {
@@ -146,4 +481,243 @@ mod tests {
             crate::Error::ResponseBodyTooLarge { limit: 8 }
         ));
     }
+
+    #[test]
+    fn byte_stream_yields_every_chunk() {
+        let chunks = stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from_static(b"zen")),
+            Ok(Bytes::from_static(b"wave")),
+            Ok(Bytes::from_static(b"!")),
+        ]);
+        let response = Response::new(Body::from_stream(chunks));
+        let received: Vec<Bytes> = block_on(
+            response
+                .into_byte_stream()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(received.len(), 3);
+        assert_eq!(received.concat(), b"zenwave!");
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_to_file_streams_the_body_to_disk() {
+        let chunks = stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from_static(b"zen")),
+            Ok(Bytes::from_static(b"wave")),
+        ]);
+        let response = Response::new(Body::from_stream(chunks));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saved.txt");
+        std::fs::write(&path, b"previous, longer contents").unwrap();
+
+        let written = block_on(response.save_to_file(&path)).unwrap();
+        assert_eq!(written, 7);
+        assert_eq!(std::fs::read(&path).unwrap(), b"zenwave");
+    }
+
+    #[test]
+    fn base64_decoded_handles_wrapped_and_url_safe_input() {
+        let original: Vec<u8> = (0..=255).collect();
+        let encoded = {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode(&original)
+        };
+        // Wrap at 76 columns like MIME, with a trailing newline.
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        let standard = lines.join("\r\n") + "\n";
+        let response = Response::new(Body::from(standard.clone()));
+        assert_eq!(block_on(response.base64_decoded()).unwrap(), original);
+
+        let url_safe = standard
+            .replace('+', "-")
+            .replace('/', "_")
+            .replace('=', "");
+        let response = Response::new(Body::from(url_safe));
+        assert_eq!(block_on(response.base64_decoded()).unwrap(), original);
+    }
+
+    #[test]
+    fn base64_decoded_rejects_invalid_input() {
+        let response = Response::new(Body::from("not base64!"));
+        let error = block_on(response.base64_decoded()).unwrap_err();
+        assert!(matches!(error, crate::Error::BodyParse(_)), "{error:?}");
+    }
+
+    #[test]
+    fn etag_and_last_modified_are_read_from_headers() {
+        let response = Response::new(Body::empty());
+        assert_eq!((response.etag(), response.last_modified()), (None, None));
+
+        let response = http::Response::builder()
+            .header(http_kit::header::ETAG, "W/\"v2\"")
+            .header(
+                http_kit::header::LAST_MODIFIED,
+                "Wed, 21 Oct 2015 07:28:00 GMT",
+            )
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(response.etag().as_deref(), Some("W/\"v2\""));
+        let since_epoch = response
+            .last_modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(since_epoch.as_secs(), 1_445_412_480);
+    }
+
+    fn response_with_status(status: StatusCode, body: &'static str) -> Response {
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = status;
+        response
+    }
+
+    #[test]
+    fn error_for_status_passes_through_success() {
+        let response = response_with_status(StatusCode::OK, "fine");
+        assert!(response.error_for_status_ref().is_ok());
+        let response = block_on(response.error_for_status()).unwrap();
+        assert_eq!(block_on(response.into_string()).unwrap().as_str(), "fine");
+    }
+
+    #[test]
+    fn error_for_status_captures_not_found_body() {
+        let response = response_with_status(StatusCode::NOT_FOUND, "no such item");
+        let error = response.error_for_status_ref().unwrap_err();
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+        assert_eq!(error.response_body(), None);
+
+        let error = block_on(response.error_for_status()).unwrap_err();
+        assert!(error.is_client_error());
+        assert_eq!(error.response_body(), Some("no such item"));
+    }
+
+    #[test]
+    fn error_for_status_reports_server_error() {
+        let response = response_with_status(StatusCode::INTERNAL_SERVER_ERROR, "");
+        let error = response.error_for_status_ref().unwrap_err();
+        assert!(error.is_server_error());
+
+        let error = block_on(response.error_for_status()).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Http {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                ..
+            }
+        ));
+    }
+
+    // "日本語" encoded as Shift_JIS.
+    const SHIFT_JIS_NIHONGO: &[u8] = &[0x93, 0xfa, 0x96, 0x7b, 0x8c, 0xea];
+
+    #[test]
+    fn text_with_encoding_overrides_declared_charset() {
+        let utf8 = Response::new(Body::from(SHIFT_JIS_NIHONGO.to_vec()));
+        assert!(block_on(utf8.into_string()).is_err());
+
+        let mut response = Response::new(Body::from(SHIFT_JIS_NIHONGO.to_vec()));
+        response.headers_mut().insert(
+            http_kit::header::CONTENT_TYPE,
+            http_kit::header::HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        let text = block_on(response.text_with_encoding("shift_jis")).unwrap();
+        assert_eq!(text, "日本語");
+    }
+
+    #[test]
+    fn text_with_encoding_rejects_unknown_label() {
+        let response = Response::new(Body::from("ignored"));
+        let error = block_on(response.text_with_encoding("klingon")).unwrap_err();
+        assert!(matches!(error, crate::Error::InvalidRequest(_)));
+    }
+
+    fn with_content_type(body: &'static [u8], content_type: &'static str) -> Response {
+        let mut response = Response::new(Body::from(body));
+        response.headers_mut().insert(
+            http_kit::header::CONTENT_TYPE,
+            http_kit::header::HeaderValue::from_static(content_type),
+        );
+        response
+    }
+
+    #[test]
+    fn text_with_charset_decodes_declared_latin1() {
+        // "café déjà vu" in ISO-8859-1.
+        let latin1 = b"caf\xe9 d\xe9j\xe0 vu";
+        let strict = with_content_type(latin1, "text/html; charset=ISO-8859-1");
+        assert!(block_on(strict.into_string()).is_err());
+
+        let response = with_content_type(latin1, "text/html; charset=ISO-8859-1");
+        assert_eq!(
+            block_on(response.text_with_charset()).unwrap(),
+            "café déjà vu"
+        );
+
+        let response = with_content_type(SHIFT_JIS_NIHONGO, "text/plain; charset=\"Shift_JIS\"");
+        assert_eq!(block_on(response.text_with_charset()).unwrap(), "日本語");
+    }
+
+    #[test]
+    fn text_with_charset_falls_back_without_a_known_charset() {
+        let response = with_content_type(b"caf\xe9", "text/plain");
+        assert_eq!(
+            block_on(response.text_with_charset_or("latin1")).unwrap(),
+            "café"
+        );
+
+        let response = with_content_type(b"caf\xe9", "text/plain; charset=x-unknown");
+        assert_eq!(
+            block_on(response.text_with_charset_or("windows-1252")).unwrap(),
+            "café"
+        );
+
+        let response = with_content_type("café".as_bytes(), "text/plain");
+        assert_eq!(block_on(response.text_with_charset()).unwrap(), "café");
+
+        let response = Response::new(Body::from("ignored"));
+        let error = block_on(response.text_with_charset_or("klingon")).unwrap_err();
+        assert!(matches!(error, crate::Error::InvalidRequest(_)));
+    }
+
+    #[test]
+    fn content_type_is_parsed() {
+        let response = with_content_type(b"", "Text/HTML; Charset=ISO-8859-1");
+        let mime = response.content_type().unwrap();
+        assert_eq!(mime.essence_str(), "text/html");
+        assert_eq!(mime.get_param(mime::CHARSET).unwrap(), "iso-8859-1");
+
+        assert!(
+            with_content_type(b"", "not a type")
+                .content_type()
+                .is_none()
+        );
+        assert!(Response::new(Body::empty()).content_type().is_none());
+    }
+
+    #[test]
+    fn json_with_limit_parses_body_just_under_limit() {
+        let payload = r#"{"name":"zenwave"}"#;
+        let response = Response::new(Body::from(payload));
+        let value: serde_json::Value =
+            block_on(response.json_with_limit(payload.len() + 1)).unwrap();
+        assert_eq!(value["name"], "zenwave");
+    }
+
+    #[test]
+    fn json_with_limit_rejects_body_just_over_limit() {
+        let payload = r#"{"name":"zenwave"}"#;
+        let response = Response::new(Body::from(payload));
+        let error =
+            block_on(response.json_with_limit::<serde_json::Value>(payload.len() - 1)).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::ResponseBodyTooLarge { limit } if limit == payload.len() - 1
+        ));
+    }
 }
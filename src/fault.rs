@@ -0,0 +1,298 @@
+//! Artificial latency and failures for resilience testing.
+//!
+//! [`FaultInjection`] makes a healthy backend misbehave on purpose, so retry,
+//! timeout and circuit-breaker logic can be exercised without a flaky server.
+//! Every decision comes from a seeded generator: the same seed and the same
+//! sequence of requests produce the same faults.
+//!
+//! ```rust,no_run
+//! # fn example(backend: impl zenwave::Client) {
+//! use std::time::Duration;
+//! use zenwave::{Client, fault::FaultInjection};
+//!
+//! let client = backend.with(
+//!     FaultInjection::new(42)
+//!         .latency(0.5, Duration::from_millis(200))
+//!         .connection_resets(0.1),
+//! );
+//! # }
+//! ```
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::{io, sync::Arc, time::Duration};
+
+use http_kit::{Endpoint, Middleware, Request, Response, middleware::MiddlewareError};
+
+/// Middleware that injects latency, synthetic errors and corrupted bodies
+/// with configured probabilities.
+///
+/// Probabilities are between `0.0` (never) and `1.0` (always). For each
+/// request the faults are rolled in order: latency, then a timeout, then a
+/// connection reset, and finally, once the response arrived, corruption of
+/// its body. Injected errors are the ones the built-in backends report:
+/// [`crate::Error::Timeout`] and an [`io::ErrorKind::ConnectionReset`] inside
+/// [`crate::Error::Transport`].
+///
+/// Clones share the generator, so requests sent through different clones of
+/// a client, as [`SharedClient`](crate::SharedClient) and
+/// [`Client::batch`](crate::Client::batch) do, draw different faults.
+#[derive(Debug, Clone)]
+pub struct FaultInjection {
+    latency: Option<(f64, Duration)>,
+    timeout: f64,
+    reset: f64,
+    corrupt: f64,
+    // xorshift64 state, never zero.
+    rng: Arc<AtomicU64>,
+}
+
+impl FaultInjection {
+    /// Create a middleware injecting no faults, with `seed` for the
+    /// generator that decides them.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        // Spread small seeds over the state; xorshift requires it non-zero.
+        let rng = seed ^ 0x9e37_79b9_7f4a_7c15;
+        Self {
+            latency: None,
+            timeout: 0.0,
+            reset: 0.0,
+            corrupt: 0.0,
+            rng: Arc::new(AtomicU64::new(if rng == 0 { 1 } else { rng })),
+        }
+    }
+
+    /// Delay requests by `delay` with the given probability.
+    #[must_use]
+    pub const fn latency(mut self, probability: f64, delay: Duration) -> Self {
+        self.latency = Some((probability, delay));
+        self
+    }
+
+    /// Fail requests with [`crate::Error::Timeout`] with the given
+    /// probability, without sending them.
+    #[must_use]
+    pub const fn timeouts(mut self, probability: f64) -> Self {
+        self.timeout = probability;
+        self
+    }
+
+    /// Fail requests with a connection reset with the given probability,
+    /// without sending them.
+    #[must_use]
+    pub const fn connection_resets(mut self, probability: f64) -> Self {
+        self.reset = probability;
+        self
+    }
+
+    /// Flip the bits of one byte of non-empty response bodies with the given
+    /// probability.
+    ///
+    /// The body is read into memory first, so a corrupted response is no
+    /// longer streamed.
+    #[must_use]
+    pub const fn corrupt_responses(mut self, probability: f64) -> Self {
+        self.corrupt = probability;
+        self
+    }
+
+    fn next_random(&self) -> u64 {
+        let advance = |mut state| {
+            crate::retry::xorshift64(&mut state);
+            state
+        };
+        let previous = self
+            .rng
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+                Some(advance(state))
+            })
+            .unwrap_or_else(|state| state);
+        advance(previous)
+    }
+
+    /// Whether an event with `probability` happens this time.
+    fn roll(&self, probability: f64) -> bool {
+        // A certain event needs no sample.
+        if probability >= 1.0 {
+            return true;
+        }
+        // The top 32 bits over 2^32 give a uniform sample in [0, 1).
+        let sample = u32::try_from(self.next_random() >> 32).unwrap_or(u32::MAX);
+        f64::from(sample) / 4_294_967_296.0 < probability
+    }
+}
+
+impl Middleware for FaultInjection {
+    type Error = crate::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        if let Some((probability, delay)) = self.latency
+            && self.roll(probability)
+        {
+            crate::retry::sleep(delay).await;
+        }
+        if self.roll(self.timeout) {
+            return Err(MiddlewareError::Middleware(crate::Error::Timeout {
+                partial: None,
+            }));
+        }
+        if self.roll(self.reset) {
+            let reset = io::Error::new(io::ErrorKind::ConnectionReset, "injected connection reset");
+            return Err(MiddlewareError::Middleware(crate::Error::Transport(
+                Box::new(reset),
+            )));
+        }
+
+        let mut response = next
+            .respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)?;
+
+        if self.roll(self.corrupt) {
+            let body = response.body_mut().take().map_err(|error| {
+                MiddlewareError::Middleware(crate::Error::Other(Box::new(error)))
+            })?;
+            let mut bytes = body
+                .into_bytes()
+                .await
+                .map_err(|error| MiddlewareError::Middleware(error.into()))?
+                .to_vec();
+            if !bytes.is_empty() {
+                let index = usize::try_from(self.next_random() % bytes.len() as u64)
+                    .expect("index is below a usize length");
+                bytes[index] ^= 0xff;
+            }
+            *response.body_mut() = http_kit::Body::from(bytes);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::FaultInjection;
+    use core::sync::atomic::Ordering;
+    use http_kit::{
+        Body, Endpoint, Request, Response, endpoint::WithMiddleware, middleware::MiddlewareError,
+    };
+    use std::{io, time::Duration};
+
+    /// Answers every request with the same body.
+    #[derive(Clone, Copy)]
+    struct Healthy;
+
+    impl Endpoint for Healthy {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            Ok(Response::new(Body::from("all good")))
+        }
+    }
+
+    fn request() -> Request {
+        http::Request::get("https://example.com/")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn injected(
+        result: Result<Response, MiddlewareError<crate::Error, crate::Error>>,
+    ) -> crate::Error {
+        match result {
+            Err(MiddlewareError::Middleware(error)) => error,
+            Err(MiddlewareError::Endpoint(error)) => panic!("backend failed: {error}"),
+            Ok(_) => panic!("no fault was injected"),
+        }
+    }
+
+    #[test]
+    fn injects_timeouts_always() {
+        let mut client = WithMiddleware::new(Healthy, FaultInjection::new(1).timeouts(1.0));
+        for _ in 0..10 {
+            let error = injected(async_io::block_on(client.respond(&mut request())));
+            assert!(error.is_timeout());
+        }
+    }
+
+    #[test]
+    fn injects_connection_resets() {
+        let mut client =
+            WithMiddleware::new(Healthy, FaultInjection::new(1).connection_resets(1.0));
+        let error = injected(async_io::block_on(client.respond(&mut request())));
+        let crate::Error::Transport(source) = error else {
+            panic!("expected a transport error, got {error:?}");
+        };
+        let io = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn passes_through_without_faults() {
+        let mut client = WithMiddleware::new(
+            Healthy,
+            FaultInjection::new(7)
+                .latency(0.0, Duration::from_secs(10))
+                .timeouts(0.0)
+                .connection_resets(0.0)
+                .corrupt_responses(0.0),
+        );
+        for _ in 0..10 {
+            let body = async_io::block_on(async {
+                let response = client.respond(&mut request()).await.unwrap();
+                response.into_body().into_string().await.unwrap()
+            });
+            assert_eq!(body, "all good");
+        }
+    }
+
+    #[test]
+    fn corrupts_one_byte_of_the_body() {
+        let mut client =
+            WithMiddleware::new(Healthy, FaultInjection::new(3).corrupt_responses(1.0));
+        let body = async_io::block_on(async {
+            let response = client.respond(&mut request()).await.unwrap();
+            response.into_body().into_bytes().await.unwrap()
+        });
+        let differing = body.iter().zip(b"all good").filter(|(a, b)| a != b).count();
+        assert_eq!((body.len(), differing), (8, 1));
+    }
+
+    #[test]
+    fn certain_faults_fire_on_the_largest_sample() {
+        let faults = FaultInjection::new(0);
+        // The next output of this state has all of its top 32 bits set.
+        faults.rng.store(0x14c8_a60a_5148_be3d, Ordering::Relaxed);
+        assert!(faults.roll(1.0));
+
+        faults.rng.store(0x14c8_a60a_5148_be3d, Ordering::Relaxed);
+        assert!(!faults.roll(0.999_999_999));
+    }
+
+    #[test]
+    fn same_seed_injects_the_same_faults() {
+        let outcomes = |seed| {
+            let mut client = WithMiddleware::new(Healthy, FaultInjection::new(seed).timeouts(0.5));
+            (0..32)
+                .map(|_| async_io::block_on(client.respond(&mut request())).is_ok())
+                .collect::<Vec<_>>()
+        };
+        let first = outcomes(99);
+        assert_eq!(first, outcomes(99));
+        assert!(first.contains(&true) && first.contains(&false));
+    }
+
+    #[test]
+    fn clones_share_the_generator() {
+        let faults = FaultInjection::new(5).timeouts(0.5);
+        let outcomes = |faults: &FaultInjection| {
+            let mut client = WithMiddleware::new(Healthy, faults.clone());
+            (0..32)
+                .map(|_| async_io::block_on(client.respond(&mut request())).is_ok())
+                .collect::<Vec<_>>()
+        };
+        // Each clone continues where the previous one left off.
+        assert_ne!(outcomes(&faults), outcomes(&faults));
+    }
+}
@@ -0,0 +1,106 @@
+//! The clients behind [`crate::get`] and the other free request functions.
+
+use std::sync::{
+    Mutex, PoisonError,
+    atomic::{AtomicU64, Ordering},
+};
+
+use http_kit::{Method, Response, Uri};
+
+use crate::{Client, DefaultClient, Error};
+
+static IDLE: Mutex<Vec<DefaultClient>> = Mutex::new(Vec::new());
+
+/// Bumped by [`shutdown_global`], so clients borrowed before it are not put
+/// back afterwards.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn idle() -> std::sync::MutexGuard<'static, Vec<DefaultClient>> {
+    IDLE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Send a body-less request with a shared default client.
+pub async fn send<U>(method: Method, uri: U) -> Result<Response, Error>
+where
+    U: TryInto<Uri> + core::fmt::Display,
+    U::Error: core::fmt::Display,
+{
+    let generation = GENERATION.load(Ordering::Acquire);
+    let mut client = idle().pop().unwrap_or_default();
+    let result = match client.method(method, uri) {
+        Ok(builder) => builder.await,
+        Err(error) => Err(error),
+    };
+    // A client whose request was cancelled is dropped with the future
+    // instead, so no half-used connection is handed to the next caller.
+    let mut idle = idle();
+    if GENERATION.load(Ordering::Acquire) == generation {
+        idle.push(client);
+    }
+    drop(idle);
+    result
+}
+
+/// Drop the clients shared by the free request functions.
+///
+/// Building a backend for every call would throw away whatever it keeps
+/// between requests, such as its DNS cache. Instead [`get`](crate::get),
+/// [`post`](crate::post), [`put`](crate::put) and [`delete`](crate::delete)
+/// borrow a [`DefaultClient`] from a process-wide set of idle clients and
+/// put it back once the response headers arrived. A call made while every
+/// client is busy creates a new one, so calls from several threads never wait
+/// for each other, and the set grows to the most calls ever in flight at
+/// once. Idle clients live until the process exits or this function drops
+/// them, together with any background tasks their backends still run.
+///
+/// The next call starts with a fresh client. Calls in flight finish
+/// normally, but their clients are not kept afterwards. Response bodies of
+/// finished calls that are still being read may be cut off, as the hyper
+/// backend reads them from its background tasks. Mostly useful between
+/// tests that must not share state.
+pub fn shutdown_global() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    let clients = std::mem::take(&mut *idle());
+    drop(clients);
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{idle, send, shutdown_global};
+    use http_kit::Method;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    #[test]
+    fn sequential_calls_share_one_client() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().unwrap();
+                let mut request = [0_u8; 1024];
+                let _ = socket.read(&mut request).unwrap();
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+            }
+        });
+
+        futures_executor::block_on(async {
+            for _ in 0..2 {
+                let response = send(Method::GET, format!("http://{address}/"))
+                    .await
+                    .unwrap();
+                assert!(response.status().is_success());
+            }
+        });
+        server.join().unwrap();
+        assert_eq!(idle().len(), 1);
+
+        shutdown_global();
+        assert!(idle().is_empty());
+    }
+}
@@ -90,37 +90,74 @@ compile_error!(
 pub mod backend;
 use backend::DefaultBackend;
 pub use cache::Cache;
-pub use client::Client;
+#[cfg(not(target_arch = "wasm32"))]
+pub use client::{BodyWriter, DownloadOptions};
+pub use client::{Client, CurlCommandOptions, SharedClient};
 pub use http_kit::*;
-pub use oauth2::OAuth2ClientCredentials;
+pub use oauth2::{OAuth2AuthorizationCode, OAuth2ClientCredentials};
 
 pub mod auth;
 pub mod cache;
+pub mod cancel;
+pub mod capture;
+/// Request body compression (requires the `compression` feature).
+#[cfg(feature = "compression")]
+pub mod compress;
 pub mod cookie;
+pub mod encoding;
 pub mod error;
+/// Fault injection for resilience testing (requires the `fault-injection` feature).
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+mod global;
+pub mod locale;
+pub mod logging;
 pub mod oauth2;
+pub mod poll;
+pub mod range;
+pub mod ratelimit;
+/// Request recording for contract tests (requires the `test-util` feature).
+#[cfg(feature = "test-util")]
+pub mod recorder;
+pub mod sanitize;
+/// AWS Signature Version 4 request signing (requires the `aws-sigv4` feature).
+#[cfg(feature = "aws-sigv4")]
+pub mod sigv4;
+pub mod sse;
+pub mod stack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod task;
 pub mod timeout;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls;
+pub mod trace;
+pub mod uri_template;
 
 mod client;
 pub mod redirect;
+pub mod request_id;
 pub mod retry;
 
 // Re-export the unified error type
 pub use error::Error;
+pub use global::shutdown_global;
 
 mod ext;
 /// Multipart/form-data utilities.
 pub mod multipart;
 #[cfg(all(not(target_arch = "wasm32"), feature = "proxy"))]
 pub mod proxy;
+mod site_data;
 /// Websocket utilities (requires the `ws` feature).
 #[cfg(feature = "ws")]
 pub mod websocket;
 
 pub use ext::ResponseExt;
+pub use mime::{self, Mime};
 #[cfg(all(not(target_arch = "wasm32"), feature = "proxy"))]
 pub use proxy::{Proxy, ProxyBuilder};
-pub use timeout::Timeout;
+pub use stack::ClientBuilder;
+pub use timeout::{IdleTimeout, ReadTimeout, Timeout};
 
 /// The default Zenwave client.
 ///
@@ -175,6 +212,24 @@ pub fn client() -> DefaultClient {
     DefaultClient::new()
 }
 
+/// Create a client with the recommended middleware stack around the
+/// default backend: an overall timeout, retries, redirects, cookies and a
+/// cache, in the order described in [`stack`].
+///
+/// Use [`stack::Recommended`] to add authentication or change the defaults.
+#[must_use]
+pub fn client_recommended() -> impl Client {
+    stack::Recommended::new().build(DefaultBackend::default())
+}
+
+/// Start configuring a client: timeout, retries, redirects, cookies, cache,
+/// default headers and authentication, stacked in the order described in
+/// [`stack`].
+#[must_use]
+pub fn builder() -> ClientBuilder {
+    ClientBuilder::new()
+}
+
 /// Create a raw default backend without redirect middleware.
 #[must_use]
 pub fn raw_client() -> DefaultBackend {
@@ -183,13 +238,19 @@ pub fn raw_client() -> DefaultBackend {
 
 /// Construct the default backend configured with a proxy matcher.
 ///
-/// This helper only exists when the default backend is curl-backend, which
-/// supports proxy configuration. Other backends do not support this API.
+/// This helper only exists with the `proxy` feature when the default backend
+/// is hyper-backend or curl-backend, which support proxy configuration. Other
+/// backends do not support this API.
 #[cfg(all(
     not(target_arch = "wasm32"),
-    feature = "curl-backend",
-    not(all(target_vendor = "apple", feature = "apple-backend")),
-    not(feature = "hyper-backend")
+    feature = "proxy",
+    any(
+        feature = "hyper-backend",
+        all(
+            feature = "curl-backend",
+            not(all(target_vendor = "apple", feature = "apple-backend"))
+        )
+    )
 ))]
 #[must_use]
 #[allow(clippy::missing_const_for_fn)]
@@ -201,67 +262,75 @@ pub fn client_with_proxy(proxy: Proxy) -> DefaultClient {
 
 #[cfg(all(
     not(target_arch = "wasm32"),
-    feature = "curl-backend",
-    not(all(target_vendor = "apple", feature = "apple-backend")),
-    not(feature = "hyper-backend")
+    feature = "proxy",
+    any(
+        feature = "hyper-backend",
+        all(
+            feature = "curl-backend",
+            not(all(target_vendor = "apple", feature = "apple-backend"))
+        )
+    )
 ))]
 impl DefaultClient {
-    /// Replace the proxy matcher on the default curl-backed client.
+    /// Replace the proxy matcher on the default client.
     #[must_use]
     pub fn proxy(self, proxy: Proxy) -> Self {
         client_with_proxy(proxy)
     }
 }
 
-/// Create a default HTTP client backend.
-/// Send a GET request to the specified URI using the default client backend.
+/// Send a GET request to the specified URI using a shared default client.
+///
+/// See [`shutdown_global`] for how the client is shared.
 ///
 /// # Errors
 /// If the request fails, an error is returned.
 pub async fn get<U>(uri: U) -> Result<Response, Error>
 where
-    U: TryInto<Uri>,
+    U: TryInto<Uri> + core::fmt::Display,
     U::Error: core::fmt::Display,
 {
-    let mut client = client();
-    client.method(Method::GET, uri)?.await
+    global::send(Method::GET, uri).await
 }
 
-/// Send a POST request to the specified URI using the default client backend.
+/// Send a POST request to the specified URI using a shared default client.
+///
+/// See [`shutdown_global`] for how the client is shared.
 ///
 /// # Errors
 /// If the request fails, an error is returned.
 pub async fn post<U>(uri: U) -> Result<Response, Error>
 where
-    U: TryInto<Uri>,
+    U: TryInto<Uri> + core::fmt::Display,
     U::Error: core::fmt::Display,
 {
-    let mut client = client();
-    client.method(Method::POST, uri)?.await
+    global::send(Method::POST, uri).await
 }
 
-/// Send a PUT request to the specified URI using the default client backend.
+/// Send a PUT request to the specified URI using a shared default client.
+///
+/// See [`shutdown_global`] for how the client is shared.
 ///
 /// # Errors
 /// If the request fails, an error is returned.
 pub async fn put<U>(uri: U) -> Result<Response, Error>
 where
-    U: TryInto<Uri>,
+    U: TryInto<Uri> + core::fmt::Display,
     U::Error: core::fmt::Display,
 {
-    let mut client = client();
-    client.method(Method::PUT, uri)?.await
+    global::send(Method::PUT, uri).await
 }
 
-/// Send a DELETE request to the specified URI using the default client backend.
+/// Send a DELETE request to the specified URI using a shared default client.
+///
+/// See [`shutdown_global`] for how the client is shared.
 ///
 /// # Errors
 /// If the request fails, an error is returned.
 pub async fn delete<U>(uri: U) -> Result<Response, Error>
 where
-    U: TryInto<Uri>,
+    U: TryInto<Uri> + core::fmt::Display,
     U::Error: core::fmt::Display,
 {
-    let mut client = client();
-    client.method(Method::DELETE, uri)?.await
+    global::send(Method::DELETE, uri).await
 }
@@ -0,0 +1,294 @@
+//! `Accept-Language` negotiation helpers.
+//!
+//! [`AcceptLanguage`] applies a validated `Accept-Language` header to every
+//! request that does not already carry one, so a client can advertise its
+//! preferred locales once instead of on every call.
+
+use std::convert::Infallible;
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{self, HeaderMap, HeaderValue},
+    middleware::MiddlewareError,
+};
+
+/// Middleware that sets a default `Accept-Language` header.
+#[derive(Debug, Clone)]
+pub struct AcceptLanguage {
+    value: HeaderValue,
+}
+
+impl AcceptLanguage {
+    /// Build the middleware from language ranges in preference order.
+    ///
+    /// Each entry is a language tag such as `de-DE` or `*`, optionally
+    /// followed by an explicit weight (`en;q=0.8`). Entries without a weight
+    /// after the first one receive a descending weight based on position,
+    /// capped at the weight of the entry before them but never below `0.001`,
+    /// since `q=0` marks a language as not acceptable. The header lists the
+    /// entries by weight; position only breaks ties.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a tag or weight is malformed.
+    pub fn new(languages: &[&str]) -> Result<Self, crate::Error> {
+        Ok(Self {
+            value: accept_language_value(languages)?,
+        })
+    }
+
+    /// The header value applied to outgoing requests.
+    #[must_use]
+    pub const fn value(&self) -> &HeaderValue {
+        &self.value
+    }
+}
+
+impl Middleware for AcceptLanguage {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        // Request-level values take precedence over the client default.
+        if !request.headers().contains_key(header::ACCEPT_LANGUAGE) {
+            request
+                .headers_mut()
+                .insert(header::ACCEPT_LANGUAGE, self.value.clone());
+        }
+
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Build a weighted `Accept-Language` header value from language ranges.
+pub(crate) fn accept_language_value(languages: &[&str]) -> Result<HeaderValue, crate::Error> {
+    if languages.is_empty() {
+        return Err(invalid("at least one language is required"));
+    }
+
+    let mut entries = Vec::with_capacity(languages.len());
+    let mut previous = 1000;
+    for (index, raw) in languages.iter().enumerate() {
+        let (tag, weight) = match raw.split_once(';') {
+            Some((tag, params)) => (tag.trim(), Some(parse_weight(params.trim())?)),
+            None => (raw.trim(), None),
+        };
+        if !is_valid_language_range(tag) {
+            return Err(invalid(format!("malformed language tag `{tag}`")));
+        }
+
+        let (millis, entry) = match weight {
+            Some(weight) => (weight_millis(weight), format!("{tag};q={weight}")),
+            None if index == 0 => (1000, tag.to_string()),
+            None => {
+                // An unweighted entry never outranks the one listed before it,
+                // but is never refused because of it either.
+                let millis = positional_weight(index).min(previous).max(1);
+                (millis, format!("{tag};q={}", format_weight(millis)))
+            }
+        };
+        previous = millis;
+        entries.push((millis, entry));
+    }
+
+    // Stable, so entries with equal weights keep the order they were given in.
+    entries.sort_by_key(|(millis, _)| core::cmp::Reverse(*millis));
+    let entries: Vec<_> = entries.into_iter().map(|(_, entry)| entry).collect();
+    HeaderValue::from_str(&entries.join(", ")).map_err(|error| invalid(error.to_string()))
+}
+
+/// Parse every `Content-Language` header into its individual language tags.
+pub(crate) fn content_language(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::CONTENT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Weight in thousandths for an unweighted entry at `index`: 0.9, 0.8, ...
+/// down to 0.1.
+fn positional_weight(index: usize) -> u16 {
+    let tenths = 10u16.saturating_sub(u16::try_from(index).unwrap_or(u16::MAX));
+    tenths.max(1) * 100
+}
+
+/// Convert a weight already accepted by [`parse_weight`] to thousandths.
+fn weight_millis(weight: &str) -> u16 {
+    let (int, frac) = weight.split_once('.').unwrap_or((weight, ""));
+    let frac = frac
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |acc, digit| acc * 10 + u16::from(digit - b'0'));
+    if int == "1" { 1000 } else { frac }
+}
+
+/// Render a weight in thousandths without trailing zeros.
+fn format_weight(millis: u16) -> String {
+    match millis {
+        0 => "0".to_string(),
+        1000.. => "1".to_string(),
+        _ => format!("0.{}", format!("{millis:03}").trim_end_matches('0')),
+    }
+}
+
+/// Validate a `q=` parameter as defined by RFC 9110 §12.4.2.
+fn parse_weight(params: &str) -> Result<&str, crate::Error> {
+    let weight = params
+        .strip_prefix("q=")
+        .or_else(|| params.strip_prefix("Q="))
+        .ok_or_else(|| invalid(format!("unsupported language parameter `{params}`")))?;
+
+    let valid = match weight.split_once('.') {
+        None => weight == "0" || weight == "1",
+        Some((int, frac)) => {
+            frac.len() <= 3
+                && frac.bytes().all(|b| b.is_ascii_digit())
+                && (int == "0" || (int == "1" && frac.bytes().all(|b| b == b'0')))
+        }
+    };
+
+    if valid {
+        Ok(weight)
+    } else {
+        Err(invalid(format!("malformed language weight `{weight}`")))
+    }
+}
+
+/// Check a language range: `*` or 1-8 letter primary subtag followed by
+/// 1-8 alphanumeric subtags separated by `-`.
+fn is_valid_language_range(tag: &str) -> bool {
+    if tag == "*" {
+        return true;
+    }
+
+    let mut subtags = tag.split('-');
+    let primary_ok = subtags.next().is_some_and(|primary| {
+        (1..=8).contains(&primary.len()) && primary.bytes().all(|b| b.is_ascii_alphabetic())
+    });
+
+    primary_ok
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
+
+fn invalid(message: impl Into<String>) -> crate::Error {
+    let message = message.into();
+    crate::Error::InvalidRequest(format!("invalid Accept-Language: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept_language_value, content_language};
+    use crate::Client;
+    use futures_executor::block_on;
+    use http_kit::{
+        Endpoint, Request, Response,
+        header::{self, HeaderMap, HeaderValue},
+    };
+
+    /// Echoes the received `Accept-Language` header back as the body.
+    struct LanguageEcho;
+
+    impl Endpoint for LanguageEcho {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let value = request
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .map(|value| value.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            Ok(Response::new(http_kit::Body::from(value)))
+        }
+    }
+
+    impl Client for LanguageEcho {}
+
+    #[test]
+    fn default_locale_applies_unless_request_overrides() {
+        block_on(async {
+            let mut client = LanguageEcho.default_locale(&["de-DE", "en"]).unwrap();
+
+            let response = client.get("http://example.com/").unwrap().await.unwrap();
+            let sent = response.into_body().into_string().await.unwrap();
+            assert_eq!(sent.as_str(), "de-DE, en;q=0.9");
+
+            let response = client
+                .get("http://example.com/")
+                .unwrap()
+                .accept_language(&["fr"])
+                .unwrap()
+                .await
+                .unwrap();
+            let sent = response.into_body().into_string().await.unwrap();
+            assert_eq!(sent.as_str(), "fr");
+        });
+    }
+
+    #[test]
+    fn keeps_explicit_weights_and_defaults_first_entry() {
+        let value = accept_language_value(&["de-DE", "en;q=0.8"]).unwrap();
+        assert_eq!(value, "de-DE, en;q=0.8");
+    }
+
+    #[test]
+    fn assigns_descending_weights_by_position() {
+        let value = accept_language_value(&["fr-CH", "fr", "en", "*;q=0.1"]).unwrap();
+        assert_eq!(value, "fr-CH, fr;q=0.9, en;q=0.8, *;q=0.1");
+    }
+
+    #[test]
+    fn unweighted_entries_never_outrank_earlier_weights() {
+        let value = accept_language_value(&["de", "en;q=0.5", "fr", "it"]).unwrap();
+        assert_eq!(value, "de, en;q=0.5, fr;q=0.5, it;q=0.5");
+
+        let value = accept_language_value(&["en;q=0.25", "de;q=0.9", "fr"]).unwrap();
+        assert_eq!(value, "de;q=0.9, fr;q=0.8, en;q=0.25");
+
+        let value = accept_language_value(&["de", "en;q=0", "fr"]).unwrap();
+        assert_eq!(value, "de, fr;q=0.001, en;q=0");
+    }
+
+    #[test]
+    fn rejects_malformed_tags_and_weights() {
+        for input in [
+            &[][..],
+            &["en_US"][..],
+            &["toolongprimary"][..],
+            &["en-"][..],
+            &["en;q=1.5"][..],
+            &["en;q=0.1234"][..],
+            &["en;level=1"][..],
+        ] {
+            assert!(
+                matches!(
+                    accept_language_value(input),
+                    Err(crate::Error::InvalidRequest(_))
+                ),
+                "{input:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_multi_value_content_language() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static("de-DE, en-CA"),
+        );
+        headers.append(header::CONTENT_LANGUAGE, HeaderValue::from_static("fr"));
+
+        assert_eq!(content_language(&headers), vec!["de-DE", "en-CA", "fr"]);
+    }
+}
@@ -0,0 +1,486 @@
+//! Logging of requests and responses.
+//!
+//! [`Logging`] records each request's method, URI, status and elapsed time,
+//! and optionally its headers and bodies. Events go through `tracing`, like
+//! the rest of this crate's diagnostics; enable `tracing`'s `log` feature to
+//! forward them to a `log` logger instead.
+//!
+//! Credentials must not end up in logs, so the values of `Authorization`,
+//! `Proxy-Authorization`, `Cookie` and `Set-Cookie` are masked unless
+//! [`LogConfig::reveal`] says otherwise.
+
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{convert::Infallible, io};
+
+use futures_util::{Stream, TryStreamExt};
+use http_kit::{
+    Body, BodyError, Endpoint, Method, Middleware, Request, Response, Uri,
+    header::{self, HeaderMap, HeaderName},
+    middleware::MiddlewareError,
+    utils::Bytes,
+};
+use tracing::{info, warn};
+
+use crate::retry::Stopwatch;
+
+const REDACTED: &str = "[redacted]";
+
+/// What [`Logging`] records besides the request line and status.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    headers: bool,
+    bodies: bool,
+    body_limit: usize,
+    redacted: Vec<HeaderName>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            headers: false,
+            bodies: false,
+            body_limit: 1024,
+            redacted: vec![
+                header::AUTHORIZATION,
+                header::PROXY_AUTHORIZATION,
+                header::COOKIE,
+                header::SET_COOKIE,
+            ],
+        }
+    }
+}
+
+impl LogConfig {
+    /// Log method, URI, status and elapsed time only.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also log request and response headers, with redacted values masked.
+    #[must_use]
+    pub const fn headers(mut self, enabled: bool) -> Self {
+        self.headers = enabled;
+        self
+    }
+
+    /// Also log request and response bodies, up to [`LogConfig::body_limit`]
+    /// bytes each.
+    ///
+    /// Buffered bodies are logged as they are. Streaming response bodies are
+    /// logged once the caller has read them, and streaming request bodies
+    /// are not logged, since reading them would consume them.
+    #[must_use]
+    pub const fn bodies(mut self, enabled: bool) -> Self {
+        self.bodies = enabled;
+        self
+    }
+
+    /// Log at most `limit` bytes of each body. Defaults to 1024.
+    #[must_use]
+    pub const fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// Mask the values of header `name` in addition to the defaults.
+    #[must_use]
+    pub fn redact(mut self, name: HeaderName) -> Self {
+        if !self.redacted.contains(&name) {
+            self.redacted.push(name);
+        }
+        self
+    }
+
+    /// Log the values of header `name` even if it is redacted by default.
+    #[must_use]
+    pub fn reveal(mut self, name: &HeaderName) -> Self {
+        self.redacted.retain(|redacted| redacted != name);
+        self
+    }
+
+    fn format_headers<'a>(&'a self, headers: &'a HeaderMap) -> Headers<'a> {
+        Headers {
+            headers,
+            redacted: &self.redacted,
+        }
+    }
+}
+
+/// Headers formatted as `name: value` pairs with redacted values masked.
+struct Headers<'a> {
+    headers: &'a HeaderMap,
+    redacted: &'a [HeaderName],
+}
+
+impl fmt::Display for Headers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.headers.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            let value = if self.redacted.contains(name) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            write!(f, "{name}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Middleware that logs every request and its response.
+///
+/// A `sending request` event is emitted before the request is sent, then
+/// `received response` with the status and elapsed time, or
+/// `request failed` with the error.
+#[derive(Debug, Clone, Default)]
+pub struct Logging {
+    config: LogConfig,
+}
+
+impl Logging {
+    /// Log requests as described by `config`.
+    #[must_use]
+    pub const fn new(config: LogConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Middleware for Logging {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let config = &self.config;
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+
+        if config.headers {
+            let headers = config.format_headers(request.headers());
+            info!(%method, %uri, %headers, "sending request");
+        } else {
+            info!(%method, %uri, "sending request");
+        }
+        if config.bodies {
+            if let Some(body) = request.body().try_clone() {
+                if let Ok(bytes) = body.into_bytes().await {
+                    let shown = &bytes[..bytes.len().min(config.body_limit)];
+                    log_body("request body", &method, &uri, shown, bytes.len());
+                }
+            } else {
+                info!(%method, %uri, "request body is streamed and not logged");
+            }
+        }
+
+        let started = Stopwatch::start();
+        let mut response = match next.respond(request).await {
+            Ok(response) => response,
+            Err(error) => {
+                let elapsed = started.elapsed();
+                warn!(%method, %uri, ?elapsed, %error, "request failed");
+                return Err(MiddlewareError::Endpoint(error));
+            }
+        };
+        let elapsed = started.elapsed();
+        let status = response.status().as_u16();
+        if config.headers {
+            let headers = config.format_headers(response.headers());
+            info!(%method, %uri, status, ?elapsed, %headers, "received response");
+        } else {
+            info!(%method, %uri, status, ?elapsed, "received response");
+        }
+
+        if config.bodies {
+            if let Some(body) = response.body().try_clone() {
+                if let Ok(bytes) = body.into_bytes().await {
+                    let shown = &bytes[..bytes.len().min(config.body_limit)];
+                    log_body("response body", &method, &uri, shown, bytes.len());
+                }
+            } else {
+                let body = std::mem::replace(response.body_mut(), Body::empty());
+                *response.body_mut() = LoggedBody::wrap(body, config.body_limit, method, uri);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Log the first bytes of a body of `total` bytes.
+fn log_body(message: &'static str, method: &Method, uri: &Uri, shown: &[u8], total: usize) {
+    let body = String::from_utf8_lossy(shown);
+    let truncated = total > shown.len();
+    info!(%method, %uri, %body, len = total, truncated, "{message}");
+}
+
+/// Streaming response body that keeps a copy of its first bytes and logs
+/// them once the body is finished or dropped.
+struct LoggedBody {
+    body: Body,
+    captured: Vec<u8>,
+    total: usize,
+    limit: usize,
+    method: Method,
+    uri: Uri,
+    logged: bool,
+}
+
+impl LoggedBody {
+    /// Wrap `body`, keeping its length known.
+    fn wrap(body: Body, limit: usize, method: Method, uri: Uri) -> Body {
+        let len = body.len();
+        let logged = Self {
+            body,
+            captured: Vec::new(),
+            total: 0,
+            limit,
+            method,
+            uri,
+            logged: false,
+        };
+        match len {
+            Some(len) => {
+                let reader = logged
+                    .map_err(|error| match error {
+                        BodyError::Io(error) => error,
+                        error => io::Error::other(error),
+                    })
+                    .into_async_read();
+                Body::from_reader(reader, len)
+            }
+            None => Body::from_stream(logged),
+        }
+    }
+
+    fn log(&mut self) {
+        if !self.logged {
+            self.logged = true;
+            log_body(
+                "response body",
+                &self.method,
+                &self.uri,
+                &self.captured,
+                self.total,
+            );
+        }
+    }
+}
+
+impl Stream for LoggedBody {
+    type Item = Result<Bytes, BodyError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = Pin::new(&mut this.body).poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.total += chunk.len();
+                let room = this.limit.saturating_sub(this.captured.len());
+                this.captured
+                    .extend_from_slice(&chunk[..chunk.len().min(room)]);
+            }
+            Poll::Ready(None) => this.log(),
+            _ => {}
+        }
+        item
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        // A body that was not read to the end is logged as far as it got.
+        self.log();
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::LogConfig;
+    use crate::Client;
+    use futures_util::stream;
+    use http_kit::{Body, Endpoint, Request, Response, header, utils::Bytes};
+    use std::{
+        convert::Infallible,
+        fmt::Write,
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        Event, Metadata,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        subscriber::Interest,
+    };
+
+    /// Subscriber that renders every event as `field=value` pairs.
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    struct Line(String);
+
+    impl Visit for Line {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            write!(self.0, "{}={value:?} ", field.name()).unwrap();
+        }
+    }
+
+    impl tracing::Subscriber for Collect {
+        fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+            Interest::always()
+        }
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut line = Line(String::new());
+            event.record(&mut line);
+            self.0.lock().unwrap().push(line.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    struct Echo {
+        streaming: bool,
+    }
+
+    impl Endpoint for Echo {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let body = if self.streaming {
+                let chunks = ["hello ", "streaming ", "world"]
+                    .map(|chunk| Ok::<_, Infallible>(Bytes::from_static(chunk.as_bytes())));
+                Body::from_stream(stream::iter(chunks))
+            } else {
+                request.body_mut().take().unwrap()
+            };
+            Ok(http::Response::builder()
+                .header(header::SET_COOKIE, "session=secret")
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(body)
+                .unwrap())
+        }
+    }
+
+    impl Client for Echo {}
+
+    fn run(config: LogConfig, streaming: bool) -> (String, Vec<String>) {
+        let events = Collect::default();
+        let body = tracing::subscriber::with_default(events.clone(), || {
+            async_io::block_on(async {
+                let mut client = Echo { streaming }.log_requests(config);
+                let mut request = http::Request::post("https://example.com/login")
+                    .header(header::AUTHORIZATION, "Bearer hunter2")
+                    .header("x-api-key", "k-123")
+                    .body(Body::from("user=alice"))
+                    .unwrap();
+                let response = client.respond(&mut request).await.unwrap();
+                response
+                    .into_body()
+                    .into_string()
+                    .await
+                    .unwrap()
+                    .to_string()
+            })
+        });
+        let events = events.0.lock().unwrap().clone();
+        (body, events)
+    }
+
+    #[test]
+    fn masks_credentials_by_default() {
+        let (_, events) = run(LogConfig::new().headers(true), false);
+        let log = events.concat();
+        assert!(log.contains("authorization: [redacted]"), "{log}");
+        assert!(log.contains("set-cookie: [redacted]"), "{log}");
+        assert!(log.contains("x-api-key: k-123"), "{log}");
+        assert!(!log.contains("hunter2") && !log.contains("secret"), "{log}");
+        assert!(log.contains("status=200"), "{log}");
+    }
+
+    #[test]
+    fn redaction_list_is_configurable() {
+        let config = LogConfig::new()
+            .headers(true)
+            .redact(header::HeaderName::from_static("x-api-key"))
+            .reveal(&header::SET_COOKIE);
+        let (_, events) = run(config, false);
+        let log = events.concat();
+        assert!(log.contains("x-api-key: [redacted]"), "{log}");
+        assert!(log.contains("set-cookie: session=secret"), "{log}");
+        assert!(log.contains("authorization: [redacted]"), "{log}");
+    }
+
+    #[test]
+    fn logs_truncated_bodies_without_consuming_them() {
+        let config = LogConfig::new().bodies(true).body_limit(5);
+        let (body, events) = run(config, false);
+        assert_eq!(body, "user=alice");
+        let bodies: Vec<_> = events.iter().filter(|e| e.contains("body=")).collect();
+        assert_eq!(bodies.len(), 2, "{events:?}");
+        assert!(
+            bodies
+                .iter()
+                .all(|e| e.contains("body=user=") && e.contains("truncated=true"))
+        );
+    }
+
+    #[test]
+    fn streaming_response_bodies_are_teed() {
+        let config = LogConfig::new().bodies(true);
+        let (body, events) = run(config, true);
+        assert_eq!(body, "hello streaming world");
+        let logged = events
+            .iter()
+            .find(|e| e.contains("response body"))
+            .expect("response body logged");
+        assert!(logged.contains("body=hello streaming world"), "{logged}");
+        assert!(logged.contains("len=21"), "{logged}");
+    }
+
+    /// Answers with a streamed body of known length.
+    struct Sized;
+
+    impl Endpoint for Sized {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            let reader = futures_util::io::Cursor::new(b"sized body".to_vec());
+            Ok(Response::new(Body::from_reader(reader, 10)))
+        }
+    }
+
+    impl Client for Sized {}
+
+    #[test]
+    fn teed_bodies_keep_their_length() {
+        let events = Collect::default();
+        let body = tracing::subscriber::with_default(events.clone(), || {
+            async_io::block_on(async {
+                let mut client = Sized.log_requests(LogConfig::new().bodies(true));
+                let mut request = http::Request::get("https://example.com/")
+                    .body(Body::empty())
+                    .unwrap();
+                let body = client.respond(&mut request).await.unwrap().into_body();
+                assert_eq!(body.len(), Some(10));
+                body.into_bytes().await.unwrap()
+            })
+        });
+        assert_eq!(body.as_ref(), b"sized body");
+        let events = events.0.lock().unwrap().clone();
+        assert!(
+            events.iter().any(|e| e.contains("body=sized body")),
+            "{events:?}"
+        );
+    }
+}
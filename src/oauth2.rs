@@ -3,14 +3,16 @@
 use core::time::Duration;
 use std::{sync::Arc, time::Instant};
 
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use futures_util::lock::Mutex;
 use http::StatusCode;
 use http_kit::{
-    BodyError, Endpoint, HttpError, Middleware, Request, Response, header,
+    BodyError, Endpoint, HttpError, Middleware, Request, Response, endpoint::AnyEndpoint, header,
     middleware::MiddlewareError,
 };
 use serde::Deserialize;
-use url::form_urlencoded::Serializer;
+use sha2::{Digest, Sha256};
+use url::form_urlencoded::{self, Serializer};
 
 use crate::{Client, DefaultBackend, client};
 
@@ -32,17 +34,43 @@ pub enum OAuth2Error<H: HttpError> {
         message: String,
     },
 
+    /// The token endpoint refused the request with an RFC 6749 §5.2 error,
+    /// such as `invalid_grant` or `invalid_client`.
+    #[error("OAuth2 token endpoint returned {status}: {error}")]
+    Provider {
+        /// HTTP status returned by the token endpoint.
+        status: StatusCode,
+        /// The `error` code.
+        error: String,
+        /// The `error_description`, if any.
+        description: Option<String>,
+        /// The `error_uri`, if any.
+        uri: Option<String>,
+    },
+
     /// The token response body could not be parsed.
     #[error("invalid token response: {0}")]
     InvalidResponse(BodyError),
+
+    /// The `state` returned to the redirect URI is not the one sent, so the
+    /// authorization response may have been forged.
+    #[error("OAuth2 state mismatch")]
+    StateMismatch,
+
+    /// No code was exchanged yet, or the token expired without a refresh
+    /// token.
+    #[error("not authorized: exchange an authorization code first")]
+    NotAuthorized,
 }
 
 impl<H: HttpError> HttpError for OAuth2Error<H> {
     fn status(&self) -> StatusCode {
         match self {
             Self::Transport(err) => err.status(),
-            Self::Upstream { status, .. } => *status,
+            Self::Upstream { status, .. } | Self::Provider { status, .. } => *status,
             Self::InvalidResponse(_) => StatusCode::BAD_GATEWAY,
+            Self::StateMismatch => StatusCode::BAD_REQUEST,
+            Self::NotAuthorized => StatusCode::UNAUTHORIZED,
         }
     }
 }
@@ -61,14 +89,29 @@ where
     fn from(err: OAuth2Error<H>) -> Self {
         use crate::error::OAuth2ErrorKind;
 
+        let message = err.to_string();
         match err {
             OAuth2Error::Transport(e) => e.into(),
             OAuth2Error::Upstream { status, message } => {
                 Self::OAuth2(OAuth2ErrorKind::TokenEndpointError { status, message })
             }
+            OAuth2Error::Provider {
+                status,
+                error,
+                description,
+                uri,
+            } => Self::OAuth2(OAuth2ErrorKind::Provider {
+                status,
+                error,
+                description,
+                uri,
+            }),
             OAuth2Error::InvalidResponse(e) => {
                 Self::OAuth2(OAuth2ErrorKind::InvalidTokenResponse(e.to_string()))
             }
+            OAuth2Error::StateMismatch | OAuth2Error::NotAuthorized => {
+                Self::OAuth2(OAuth2ErrorKind::TokenFetchFailed(message))
+            }
         }
     }
 }
@@ -77,11 +120,24 @@ where
 ///
 /// It lazily acquires an access token from the configured token endpoint and automatically adds the
 /// `Authorization: Bearer <token>` header to outgoing requests. Tokens are cached until they expire
-/// (with a small safety window) and are refreshed on-demand before dispatching the next request.
+/// (with a small safety window) and are refreshed on-demand before dispatching the next request,
+/// or in the background ahead of expiry with [`refresh_ahead`](Self::refresh_ahead). When the
+/// token endpoint returns a `refresh_token`, refreshes use it, and keep the rotated one.
 #[derive(Debug, Clone)]
 pub struct OAuth2ClientCredentials {
     config: Arc<Config>,
     token: Arc<Mutex<Option<TokenInfo>>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    refresher: Arc<Refresher>,
+}
+
+/// Background refreshes started by [`OAuth2ClientCredentials::refresh_ahead`],
+/// shared by clones.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+struct Refresher {
+    tasks: crate::task::TaskSet,
+    running: std::sync::atomic::AtomicBool,
 }
 
 #[derive(Debug, Clone)]
@@ -92,11 +148,71 @@ struct Config {
     scope: Option<String>,
     audience: Option<String>,
     safety_window: Duration,
+    token_client: Option<TokenClient>,
+    auth_method: TokenAuthMethod,
+    extra_params: Vec<(String, String)>,
+    refresh_ahead: Option<Duration>,
+}
+
+/// How [`OAuth2ClientCredentials`] authenticates to the token endpoint
+/// (RFC 6749 §2.3.1).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenAuthMethod {
+    /// Send `client_id` and `client_secret` as form fields.
+    #[default]
+    Body,
+    /// Send the credentials in an `Authorization: Basic` header
+    /// (`client_secret_basic`), leaving `client_secret` out of the form.
+    BasicHeader,
+}
+
+/// Client sending token requests in place of [`client()`], shared by clones.
+#[derive(Clone)]
+struct TokenClient(Arc<Mutex<AnyEndpoint>>);
+
+impl std::fmt::Debug for TokenClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenClient(..)")
+    }
+}
+
+impl Endpoint for TokenClient {
+    type Error = crate::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let mut client = self.0.lock().await;
+        let result = client.respond(request).await;
+        drop(client);
+        result.map_err(|error| {
+            let error: Box<dyn std::error::Error + Send + Sync> = error;
+            match error.downcast::<crate::Error>() {
+                Ok(error) => *error,
+                Err(error) => crate::Error::Other(error),
+            }
+        })
+    }
+}
+
+impl Client for TokenClient {}
+
+/// Reports the errors of `C` as [`crate::Error`], so that [`TokenClient`]
+/// recovers them after type erasure.
+struct IntoError<C>(C);
+
+impl<C> Endpoint for IntoError<C>
+where
+    C: Endpoint,
+    C::Error: Into<crate::Error>,
+{
+    type Error = crate::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        self.0.respond(request).await.map_err(Into::into)
+    }
 }
 
 #[derive(Debug, Clone)]
 struct TokenInfo {
     access_token: String,
+    refresh_token: Option<String>,
     expires_at: Instant,
 }
 
@@ -121,8 +237,14 @@ impl OAuth2ClientCredentials {
                 scope: None,
                 audience: None,
                 safety_window: Duration::from_secs(30),
+                token_client: None,
+                auth_method: TokenAuthMethod::Body,
+                extra_params: Vec::new(),
+                refresh_ahead: None,
             }),
             token: Arc::new(Mutex::new(None)),
+            #[cfg(not(target_arch = "wasm32"))]
+            refresher: Arc::default(),
         }
     }
 
@@ -144,6 +266,60 @@ impl OAuth2ClientCredentials {
         self
     }
 
+    /// Choose how the client credentials reach the token endpoint.
+    /// Defaults to [`TokenAuthMethod::Body`].
+    #[must_use]
+    pub fn auth_method(mut self, method: TokenAuthMethod) -> Self {
+        let mut cfg = (*self.config).clone();
+        cfg.auth_method = method;
+        self.config = Arc::new(cfg);
+        self
+    }
+
+    /// Add a form field to token requests, such as `resource`.
+    #[must_use]
+    pub fn with_extra_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut cfg = (*self.config).clone();
+        cfg.extra_params.push((key.into(), value.into()));
+        self.config = Arc::new(cfg);
+        self
+    }
+
+    /// Send token requests through `client` instead of a fresh [`client()`].
+    ///
+    /// Use it to apply the proxy, TLS, retry and timeout settings of the
+    /// rest of the application to the token endpoint, or to answer token
+    /// requests from a mock in tests. Clones share the client.
+    #[must_use]
+    pub fn with_token_client<C>(mut self, client: C) -> Self
+    where
+        C: Client + 'static,
+        C::Error: Into<crate::Error>,
+    {
+        let mut cfg = (*self.config).clone();
+        cfg.token_client = Some(TokenClient(Arc::new(Mutex::new(AnyEndpoint::new(
+            IntoError(client),
+        )))));
+        self.config = Arc::new(cfg);
+        self
+    }
+
+    /// Refresh the token in the background once it is due to be replaced
+    /// within `window`, so no request waits for the token endpoint.
+    ///
+    /// Requests in the window keep using the current token while one
+    /// refresh runs for all clones. A failed background refresh is logged
+    /// and retried by the next request in the window; once the token
+    /// expires, requests refresh it themselves as without this option.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn refresh_ahead(mut self, window: Duration) -> Self {
+        let mut cfg = (*self.config).clone();
+        cfg.refresh_ahead = Some(window);
+        self.config = Arc::new(cfg);
+        self
+    }
+
     async fn ensure_token(&self) -> Result<String, TokenError> {
         let now = Instant::now();
         {
@@ -151,7 +327,17 @@ impl OAuth2ClientCredentials {
             if let Some(info) = token_guard.as_ref()
                 && info.is_valid(now)
             {
-                return Ok(info.access_token.clone());
+                let access_token = info.access_token.clone();
+                #[cfg(not(target_arch = "wasm32"))]
+                if self
+                    .config
+                    .refresh_ahead
+                    .is_some_and(|window| info.expires_at.saturating_duration_since(now) <= window)
+                {
+                    drop(token_guard);
+                    self.refresh_in_background();
+                }
+                return Ok(access_token);
             }
         }
 
@@ -163,71 +349,110 @@ impl OAuth2ClientCredentials {
             return Ok(info.access_token.clone());
         }
 
-        let fetched = self.fetch_token().await?;
+        let refresh_token = token_guard
+            .as_ref()
+            .and_then(|info| info.refresh_token.clone());
+        let fetched = self.fetch_token(refresh_token).await?;
         let token_value = fetched.access_token.clone();
         *token_guard = Some(fetched);
         drop(token_guard);
         Ok(token_value)
     }
 
-    async fn fetch_token(&self) -> Result<TokenInfo, TokenError> {
-        let body = self.build_body();
-        let mut client = client();
-        let response = client
-            .post(&self.config.token_url)
-            .map_err(OAuth2Error::Transport)?
-            .header(
-                header::CONTENT_TYPE.as_str(),
-                "application/x-www-form-urlencoded",
-            )
-            .map_err(OAuth2Error::Transport)?
-            .bytes_body(body.into_bytes())
-            .await?;
-
-        let status = response.status();
-        let mut body = response.into_body();
-        if !status.is_success() {
-            let text = body
-                .into_string()
-                .await
-                .unwrap_or_else(|_| http_kit::utils::ByteStr::new());
-            return Err(OAuth2Error::Upstream {
-                status,
-                message: format!("OAuth2 token endpoint returned {status}: {text}"),
-            });
-        }
-        let token: TokenEndpointResponse = body
-            .into_json()
-            .await
-            .map_err(OAuth2Error::InvalidResponse)?;
+    /// Start a refresh unless one is running already.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn refresh_in_background(&self) {
+        use std::sync::atomic::Ordering;
 
-        let expires_in = token.expires_in.unwrap_or(3600);
-        let lifetime = Duration::from_secs(expires_in);
-        let safety = self
-            .config
-            .safety_window
-            .min(Duration::from_secs(expires_in / 2));
-        let expires_at = Instant::now() + lifetime.saturating_sub(safety);
+        if self.refresher.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let this = self.clone();
+        self.refresher.tasks.spawn(async move {
+            let refresh_token = this
+                .token
+                .lock()
+                .await
+                .as_ref()
+                .and_then(|info| info.refresh_token.clone());
+            match this.fetch_token(refresh_token).await {
+                Ok(fetched) => *this.token.lock().await = Some(fetched),
+                Err(error) => tracing::warn!(%error, "background OAuth2 token refresh failed"),
+            }
+            this.refresher.running.store(false, Ordering::Release);
+        });
+    }
 
+    /// Get a new token with `refresh_token` if there is one, falling back to
+    /// the client credentials when the provider refuses it.
+    async fn fetch_token(&self, refresh_token: Option<String>) -> Result<TokenInfo, TokenError> {
+        let (token, previous_refresh) = match refresh_token {
+            Some(refresh_token) => match self.request_token(Some(&refresh_token)).await {
+                Ok(token) => (token, Some(refresh_token)),
+                // Refresh tokens expire and get revoked; the credentials do not.
+                Err(OAuth2Error::Provider { .. }) => (self.request_token(None).await?, None),
+                Err(error) => return Err(error),
+            },
+            None => (self.request_token(None).await?, None),
+        };
         Ok(TokenInfo {
+            expires_at: expires_at(token.expires_in, self.config.safety_window),
             access_token: token.access_token,
-            expires_at,
+            // Providers that rotate refresh tokens invalidate the previous one.
+            refresh_token: token.refresh_token.or(previous_refresh),
         })
     }
 
-    fn build_body(&self) -> String {
+    async fn request_token(
+        &self,
+        refresh_token: Option<&str>,
+    ) -> Result<TokenEndpointResponse, TokenError> {
+        request_token(
+            &self.config.token_url,
+            self.build_body(refresh_token),
+            self.basic_credentials(),
+            self.config.token_client.clone(),
+        )
+        .await
+    }
+
+    /// The token request form: the refresh token grant when `refresh_token`
+    /// is given, the client credentials grant otherwise.
+    fn build_body(&self, refresh_token: Option<&str>) -> String {
         let mut serializer = Serializer::new(String::new());
-        serializer.append_pair("grant_type", "client_credentials");
+        if let Some(refresh_token) = refresh_token {
+            serializer.append_pair("grant_type", "refresh_token");
+            serializer.append_pair("refresh_token", refresh_token);
+        } else {
+            serializer.append_pair("grant_type", "client_credentials");
+        }
         serializer.append_pair("client_id", &self.config.client_id);
-        serializer.append_pair("client_secret", &self.config.client_secret);
+        if self.config.auth_method == TokenAuthMethod::Body {
+            serializer.append_pair("client_secret", &self.config.client_secret);
+        }
         if let Some(scope) = &self.config.scope {
             serializer.append_pair("scope", scope);
         }
         if let Some(audience) = &self.config.audience {
             serializer.append_pair("audience", audience);
         }
+        for (key, value) in &self.config.extra_params {
+            serializer.append_pair(key, value);
+        }
         serializer.finish()
     }
+
+    /// The Basic credentials for [`TokenAuthMethod::BasicHeader`], each
+    /// form-encoded first as RFC 6749 §2.3.1 requires.
+    fn basic_credentials(&self) -> Option<(String, String)> {
+        (self.config.auth_method == TokenAuthMethod::BasicHeader).then(|| {
+            let encode = |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect();
+            (
+                encode(&self.config.client_id),
+                encode(&self.config.client_secret),
+            )
+        })
+    }
 }
 
 impl Middleware for OAuth2ClientCredentials {
@@ -257,6 +482,379 @@ impl Middleware for OAuth2ClientCredentials {
     }
 }
 
+/// Middleware implementing the `OAuth2` authorization code flow with PKCE
+/// (RFC 7636, `S256` method).
+///
+/// The flow has three steps:
+///
+/// 1. [`authorization_request`](Self::authorization_request) builds the URL
+///    to send the user to, with a fresh `state` and code challenge.
+/// 2. Once the provider redirects back, [`exchange_code`](Self::exchange_code)
+///    checks the returned `state` and trades the code for tokens.
+/// 3. As middleware, it then adds `Authorization: Bearer <token>` to
+///    outgoing requests, using the refresh token to renew the access token
+///    when it expires. Clones share the tokens.
+///
+/// ```rust,no_run
+/// # async fn example(code: &str, state: &str) -> Result<(), zenwave::Error> {
+/// use zenwave::{Client, client, oauth2::OAuth2AuthorizationCode};
+///
+/// let auth = OAuth2AuthorizationCode::new(
+///     "https://auth.example.com/authorize",
+///     "https://auth.example.com/token",
+///     "my-app",
+///     "http://localhost:8080/callback",
+/// )
+/// .with_scope("profile");
+/// let pending = auth.authorization_request()?;
+/// println!("open {}", pending.url());
+/// // ... receive `code` and `state` on the redirect URI ...
+/// auth.exchange_code(&pending, code, state).await?;
+/// let mut api = client().with(auth);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OAuth2AuthorizationCode {
+    config: Arc<CodeConfig>,
+    token: Arc<Mutex<Option<CodeToken>>>,
+}
+
+#[derive(Debug, Clone)]
+struct CodeConfig {
+    authorize_url: String,
+    token_url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scope: Option<String>,
+    safety_window: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct CodeToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Instant,
+}
+
+/// An authorization request waiting for the user, from
+/// [`OAuth2AuthorizationCode::authorization_request`].
+///
+/// Keep it until the provider redirects back; the code verifier it holds
+/// never leaves the client until the code is exchanged.
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest {
+    url: String,
+    state: String,
+    code_verifier: String,
+}
+
+impl AuthorizationRequest {
+    /// The URL to open in the user's browser.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The `state` sent with the request, expected back on the redirect URI.
+    #[must_use]
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// The PKCE code verifier matching the challenge in the URL.
+    #[must_use]
+    pub fn code_verifier(&self) -> &str {
+        &self.code_verifier
+    }
+}
+
+/// `len` random bytes, base64url-encoded without padding.
+fn random_token(len: usize) -> Result<String, crate::Error> {
+    let mut bytes = vec![0; len];
+    getrandom::fill(&mut bytes).map_err(|error| crate::Error::Other(Box::new(error)))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// The `S256` code challenge for `verifier`.
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+impl OAuth2AuthorizationCode {
+    /// Create a middleware for the provider's authorization and token
+    /// endpoints, as the public client `client_id` redirecting to
+    /// `redirect_uri`.
+    pub fn new(
+        authorize_url: impl Into<String>,
+        token_url: impl Into<String>,
+        client_id: impl Into<String>,
+        redirect_uri: impl Into<String>,
+    ) -> Self {
+        Self {
+            config: Arc::new(CodeConfig {
+                authorize_url: authorize_url.into(),
+                token_url: token_url.into(),
+                client_id: client_id.into(),
+                client_secret: None,
+                redirect_uri: redirect_uri.into(),
+                scope: None,
+                safety_window: Duration::from_secs(30),
+            }),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Authenticate token requests with a client secret, for confidential
+    /// clients.
+    #[must_use]
+    pub fn with_client_secret(mut self, secret: impl Into<String>) -> Self {
+        let mut cfg = (*self.config).clone();
+        cfg.client_secret = Some(secret.into());
+        self.config = Arc::new(cfg);
+        self
+    }
+
+    /// Request specific scopes.
+    #[must_use]
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        let mut cfg = (*self.config).clone();
+        cfg.scope = Some(scope.into());
+        self.config = Arc::new(cfg);
+        self
+    }
+
+    /// Start an authorization with a fresh `state` and PKCE code verifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidUri`] when the authorization URL cannot
+    /// be parsed, or [`crate::Error::Other`] when no random bytes are
+    /// available.
+    pub fn authorization_request(&self) -> Result<AuthorizationRequest, crate::Error> {
+        let mut url = url::Url::parse(&self.config.authorize_url)
+            .map_err(|error| crate::Error::InvalidUri(error.to_string()))?;
+        let state = random_token(16)?;
+        let code_verifier = random_token(32)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &self.config.client_id)
+                .append_pair("redirect_uri", &self.config.redirect_uri)
+                .append_pair("state", &state)
+                .append_pair("code_challenge", &code_challenge(&code_verifier))
+                .append_pair("code_challenge_method", "S256");
+            if let Some(scope) = &self.config.scope {
+                query.append_pair("scope", scope);
+            }
+        }
+        Ok(AuthorizationRequest {
+            url: url.into(),
+            state,
+            code_verifier,
+        })
+    }
+
+    /// Exchange the `code` returned for `request` for tokens, after checking
+    /// the returned `state`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OAuth2Error::StateMismatch`] when `state` is not the one of
+    /// `request`, and the token endpoint's failures otherwise.
+    pub async fn exchange_code(
+        &self,
+        request: &AuthorizationRequest,
+        code: &str,
+        state: &str,
+    ) -> Result<(), TokenError> {
+        if state != request.state {
+            return Err(OAuth2Error::StateMismatch);
+        }
+        let body = self
+            .form("authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("code_verifier", &request.code_verifier)
+            .finish();
+        let token = request_token(&self.config.token_url, body, None, None).await?;
+        *self.token.lock().await = Some(self.store(token, None));
+        Ok(())
+    }
+
+    /// A token request form for `grant_type`, with the client credentials.
+    fn form(&self, grant_type: &str) -> Serializer<'static, String> {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", grant_type);
+        serializer.append_pair("client_id", &self.config.client_id);
+        if let Some(secret) = &self.config.client_secret {
+            serializer.append_pair("client_secret", secret);
+        }
+        serializer
+    }
+
+    /// Keep `token`, falling back to `previous_refresh` when the provider
+    /// did not rotate the refresh token.
+    fn store(&self, token: TokenEndpointResponse, previous_refresh: Option<String>) -> CodeToken {
+        CodeToken {
+            expires_at: expires_at(token.expires_in, self.config.safety_window),
+            access_token: token.access_token,
+            refresh_token: token.refresh_token.or(previous_refresh),
+        }
+    }
+
+    async fn ensure_token(&self) -> Result<String, TokenError> {
+        let mut token_guard = self.token.lock().await;
+        let Some(token) = token_guard.as_ref() else {
+            return Err(OAuth2Error::NotAuthorized);
+        };
+        if Instant::now() < token.expires_at {
+            return Ok(token.access_token.clone());
+        }
+        let Some(refresh_token) = token.refresh_token.clone() else {
+            return Err(OAuth2Error::NotAuthorized);
+        };
+
+        let body = {
+            let mut serializer = self.form("refresh_token");
+            serializer.append_pair("refresh_token", &refresh_token);
+            if let Some(scope) = &self.config.scope {
+                serializer.append_pair("scope", scope);
+            }
+            serializer.finish()
+        };
+        let fetched = request_token(&self.config.token_url, body, None, None).await?;
+        let fetched = self.store(fetched, Some(refresh_token));
+        let access_token = fetched.access_token.clone();
+        *token_guard = Some(fetched);
+        drop(token_guard);
+        Ok(access_token)
+    }
+}
+
+impl Middleware for OAuth2AuthorizationCode {
+    type Error = TokenError;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        if !request.headers().contains_key(header::AUTHORIZATION) {
+            let token = self
+                .ensure_token()
+                .await
+                .map_err(MiddlewareError::Middleware)?;
+            let mut value =
+                header::HeaderValue::try_from(format!("Bearer {token}")).map_err(|error| {
+                    MiddlewareError::Middleware(OAuth2Error::Transport(
+                        crate::Error::InvalidRequest(error.to_string()),
+                    ))
+                })?;
+            value.set_sensitive(true);
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Post the form `body` to the token endpoint through `token_client`, or a
+/// fresh default client, with `basic` credentials if any, and parse its
+/// answer.
+async fn request_token(
+    token_url: &str,
+    body: String,
+    basic: Option<(String, String)>,
+    token_client: Option<TokenClient>,
+) -> Result<TokenEndpointResponse, TokenError> {
+    match token_client {
+        Some(token_client) => send_token_request(token_client, token_url, body, basic).await,
+        None => send_token_request(client(), token_url, body, basic).await,
+    }
+}
+
+async fn send_token_request<C: Client<Error = crate::Error>>(
+    mut client: C,
+    token_url: &str,
+    body: String,
+    basic: Option<(String, String)>,
+) -> Result<TokenEndpointResponse, TokenError> {
+    let mut builder = client
+        .post(token_url)
+        .map_err(OAuth2Error::Transport)?
+        .header(
+            header::CONTENT_TYPE.as_str(),
+            "application/x-www-form-urlencoded",
+        )
+        .map_err(OAuth2Error::Transport)?;
+    if let Some((client_id, client_secret)) = basic {
+        builder = builder
+            .try_basic_auth(client_id, Some(client_secret))
+            .map_err(OAuth2Error::Transport)?;
+    }
+    let response = match builder.bytes_body(body.into_bytes()).await {
+        Ok(response) => response,
+        // Backends report error statuses as errors, with the body captured,
+        // possibly wrapped by a retrying token client.
+        Err(error) => {
+            return Err(match error.response() {
+                Some(response) => rejection(response.status(), error.response_body().unwrap_or("")),
+                None => OAuth2Error::Transport(error),
+            });
+        }
+    };
+
+    let status = response.status();
+    let mut body = response.into_body();
+    if !status.is_success() {
+        let text = body
+            .into_string()
+            .await
+            .unwrap_or_else(|_| http_kit::utils::ByteStr::new());
+        return Err(rejection(status, &text));
+    }
+    body.into_json().await.map_err(OAuth2Error::InvalidResponse)
+}
+
+/// RFC 6749 §5.2 error response of the token endpoint.
+#[derive(Debug, Deserialize)]
+struct ProviderError {
+    error: String,
+    error_description: Option<String>,
+    error_uri: Option<String>,
+}
+
+/// The error for a token endpoint answering `status` with `body`: the
+/// provider's error when the body is an RFC 6749 error response, otherwise
+/// the raw text.
+fn rejection(status: StatusCode, body: &str) -> TokenError {
+    match serde_json::from_str::<ProviderError>(body) {
+        Ok(error) => OAuth2Error::Provider {
+            status,
+            error: error.error,
+            description: error.error_description,
+            uri: error.error_uri,
+        },
+        Err(_) => OAuth2Error::Upstream {
+            status,
+            message: format!("OAuth2 token endpoint returned {status}: {body}"),
+        },
+    }
+}
+
+/// When a token lasting `expires_in` seconds (an hour if unknown) should be
+/// replaced, `safety_window` early but no earlier than halfway.
+fn expires_at(expires_in: Option<u64>, safety_window: Duration) -> Instant {
+    let expires_in = expires_in.unwrap_or(3600);
+    let lifetime = Duration::from_secs(expires_in);
+    let safety = safety_window.min(Duration::from_secs(expires_in / 2));
+    Instant::now() + lifetime.saturating_sub(safety)
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenEndpointResponse {
     access_token: String,
@@ -264,6 +862,8 @@ struct TokenEndpointResponse {
     token_type: Option<String>,
     #[serde(default)]
     expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
@@ -324,6 +924,326 @@ mod tests {
         });
     }
 
+    /// Token endpoint answering in process, recording the `Authorization`
+    /// header and form of each request. It answers with the scripted
+    /// responses in order, then with a token lasting an hour.
+    #[derive(Clone, Default)]
+    struct MockTokenEndpoint {
+        requests: Arc<std::sync::Mutex<Vec<TokenRequest>>>,
+        responses: Arc<std::sync::Mutex<std::collections::VecDeque<&'static str>>>,
+    }
+
+    /// The `Authorization` header and the form of a token request.
+    type TokenRequest = (Option<String>, String);
+
+    impl MockTokenEndpoint {
+        fn scripted(responses: impl IntoIterator<Item = &'static str>) -> Self {
+            let endpoint = Self::default();
+            endpoint.responses.lock().unwrap().extend(responses);
+            endpoint
+        }
+
+        fn requests(&self) -> Vec<TokenRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Endpoint for MockTokenEndpoint {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let authorization = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .map(|value| value.to_str().unwrap().to_string());
+            let form = request.body_mut().take().unwrap().into_string().await?;
+            self.requests
+                .lock()
+                .unwrap()
+                .push((authorization, form.to_string()));
+            let body =
+                self.responses.lock().unwrap().pop_front().unwrap_or(
+                    r#"{"access_token":"mocked","token_type":"bearer","expires_in":3600}"#,
+                );
+            Ok(HttpResponse::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap())
+        }
+    }
+
+    impl Client for MockTokenEndpoint {}
+
+    #[test]
+    fn token_requests_go_through_the_token_client() {
+        let token_endpoint = MockTokenEndpoint::default();
+        // The `.invalid` domain never resolves, so a default client would fail.
+        let mut middleware =
+            OAuth2ClientCredentials::new("https://auth.invalid/token", "abc", "xyz")
+                .with_token_client(token_endpoint.clone());
+        let mut endpoint = RecordingEndpoint::default();
+
+        smol::block_on(async {
+            for path in ["/1", "/2"] {
+                let mut request = HttpRequest::builder()
+                    .uri(format!("https://example.com{path}"))
+                    .body(Body::empty())
+                    .unwrap();
+                middleware
+                    .handle(&mut request, &mut endpoint)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        assert_eq!(endpoint.calls(), 2);
+        assert_eq!(endpoint.last_auth(), Some("Bearer mocked".to_string()));
+        let requests = token_endpoint.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0].1.contains("grant_type=client_credentials"),
+            "{requests:?}"
+        );
+    }
+
+    /// Send a request to `https://example.com/` through `middleware`,
+    /// returning the `Authorization` header it got.
+    async fn authorize(middleware: &mut OAuth2ClientCredentials) -> Option<String> {
+        let mut endpoint = RecordingEndpoint::default();
+        let mut request = HttpRequest::builder()
+            .uri("https://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        middleware
+            .handle(&mut request, &mut endpoint)
+            .await
+            .unwrap();
+        endpoint.last_auth()
+    }
+
+    #[test]
+    fn refresh_tokens_are_used_and_rotated() {
+        // Tokens lasting zero seconds expire before the next request.
+        let token_endpoint = MockTokenEndpoint::scripted([
+            r#"{"access_token":"a1","refresh_token":"r1","expires_in":0}"#,
+            r#"{"access_token":"a2","refresh_token":"r2","expires_in":0}"#,
+            r#"{"access_token":"a3","expires_in":0}"#,
+        ]);
+        let mut middleware = OAuth2ClientCredentials::new("https://auth.invalid/token", "app", "s")
+            .with_token_client(token_endpoint.clone());
+
+        smol::block_on(async {
+            for expected in ["a1", "a2", "a3", "mocked"] {
+                let authorization = authorize(&mut middleware).await;
+                assert_eq!(authorization, Some(format!("Bearer {expected}")));
+            }
+        });
+
+        let grants: Vec<_> = token_endpoint
+            .requests()
+            .iter()
+            .map(|(_, form)| {
+                (
+                    form_value(form, "grant_type").unwrap(),
+                    form_value(form, "refresh_token"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            grants,
+            [
+                ("client_credentials".to_string(), None),
+                ("refresh_token".to_string(), Some("r1".to_string())),
+                ("refresh_token".to_string(), Some("r2".to_string())),
+                // `a3` came without a new refresh token, so `r2` is kept.
+                ("refresh_token".to_string(), Some("r2".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn refresh_ahead_refreshes_once_in_the_background() {
+        // The first token is due within the window at once; the second is not.
+        let token_endpoint = MockTokenEndpoint::scripted([
+            r#"{"access_token":"old","refresh_token":"r1","expires_in":60}"#,
+            r#"{"access_token":"new","refresh_token":"r2","expires_in":3600}"#,
+        ]);
+        let middleware = OAuth2ClientCredentials::new("https://auth.invalid/token", "app", "s")
+            .with_token_client(token_endpoint.clone())
+            .refresh_ahead(Duration::from_mins(1));
+
+        smol::block_on(async {
+            assert_eq!(
+                authorize(&mut middleware.clone()).await.as_deref(),
+                Some("Bearer old")
+            );
+            let requests = (0..16).map(|_| {
+                let mut middleware = middleware.clone();
+                smol::spawn(async move { authorize(&mut middleware).await })
+            });
+            for authorization in futures_util::future::join_all(requests).await {
+                // Requests in the window never wait for the refresh.
+                assert!(
+                    matches!(authorization.as_deref(), Some("Bearer old" | "Bearer new")),
+                    "{authorization:?}"
+                );
+            }
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while authorize(&mut middleware.clone()).await.as_deref() != Some("Bearer new") {
+                assert!(Instant::now() < deadline, "the token was never refreshed");
+                smol::Timer::after(Duration::from_millis(5)).await;
+            }
+        });
+
+        let requests = token_endpoint.requests();
+        assert_eq!(requests.len(), 2, "{requests:?}");
+        assert_eq!(
+            form_value(&requests[1].1, "refresh_token").as_deref(),
+            Some("r1")
+        );
+    }
+
+    /// Token endpoint refusing every request with `400` and `body`, reported
+    /// as an HTTP error like the built-in backends do.
+    struct RejectingTokenEndpoint(&'static str);
+
+    impl Endpoint for RejectingTokenEndpoint {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            use crate::ResponseExt;
+
+            let response = HttpResponse::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(self.0))
+                .unwrap();
+            response.error_for_status().await
+        }
+    }
+
+    impl Client for RejectingTokenEndpoint {}
+
+    fn rejected(body: &'static str) -> crate::Error {
+        rejected_through(RejectingTokenEndpoint(body))
+    }
+
+    fn rejected_through<C>(token_client: C) -> crate::Error
+    where
+        C: Client + 'static,
+        C::Error: Into<crate::Error>,
+    {
+        let mut middleware = OAuth2ClientCredentials::new("https://auth.invalid/token", "a", "b")
+            .with_token_client(token_client);
+        let mut request = HttpRequest::builder()
+            .uri("https://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        match smol::block_on(middleware.handle(&mut request, &mut RecordingEndpoint::default())) {
+            Err(MiddlewareError::Middleware(error)) => error.into(),
+            other => panic!("expected a token error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn provider_errors_are_structured() {
+        let error = rejected(
+            r#"{"error":"invalid_client","error_description":"unknown client","error_uri":"https://auth.example.com/errors"}"#,
+        );
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        let crate::Error::OAuth2(crate::error::OAuth2ErrorKind::Provider {
+            error,
+            description,
+            uri,
+            ..
+        }) = error
+        else {
+            panic!("expected a provider error, got {error:?}");
+        };
+        assert_eq!(error, "invalid_client");
+        assert_eq!(description.as_deref(), Some("unknown client"));
+        assert_eq!(uri.as_deref(), Some("https://auth.example.com/errors"));
+    }
+
+    #[test]
+    fn non_json_rejections_keep_the_raw_text() {
+        let error = rejected("<html>Bad Request</html>");
+        let crate::Error::OAuth2(crate::error::OAuth2ErrorKind::TokenEndpointError {
+            status,
+            message,
+        }) = error
+        else {
+            panic!("expected the raw token endpoint error, got {error:?}");
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("<html>Bad Request</html>"), "{message}");
+    }
+
+    #[test]
+    fn retried_rejections_are_still_provider_errors() {
+        let token_client =
+            crate::retry::Retry::new(RejectingTokenEndpoint(r#"{"error":"invalid_client"}"#), 1)
+                .retry_on_status(&[StatusCode::BAD_REQUEST])
+                .retry_non_idempotent(true)
+                .min_delay(Duration::ZERO)
+                .max_delay(Duration::ZERO);
+        let error = rejected_through(token_client);
+        assert!(
+            matches!(
+                error,
+                crate::Error::OAuth2(crate::error::OAuth2ErrorKind::Provider { ref error, .. })
+                    if error == "invalid_client"
+            ),
+            "expected a provider error, got {error:?}"
+        );
+    }
+
+    /// The token request sent for one request through `middleware`.
+    fn token_request(middleware: OAuth2ClientCredentials) -> TokenRequest {
+        let token_endpoint = MockTokenEndpoint::default();
+        let mut middleware = middleware.with_token_client(token_endpoint.clone());
+        let mut request = HttpRequest::builder()
+            .uri("https://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        smol::block_on(middleware.handle(&mut request, &mut RecordingEndpoint::default())).unwrap();
+        token_endpoint.requests().remove(0)
+    }
+
+    #[test]
+    fn body_auth_sends_the_secret_in_the_form() {
+        let (authorization, form) = token_request(
+            OAuth2ClientCredentials::new("https://auth.invalid/token", "app", "s3cret")
+                .with_extra_param("resource", "https://api.example.com"),
+        );
+        assert_eq!(authorization, None);
+        assert_eq!(
+            form,
+            "grant_type=client_credentials&client_id=app&client_secret=s3cret\
+             &resource=https%3A%2F%2Fapi.example.com"
+        );
+    }
+
+    #[test]
+    fn basic_auth_moves_the_secret_to_the_header() {
+        let (authorization, form) = token_request(
+            OAuth2ClientCredentials::new("https://auth.invalid/token", "my app", "p@ss:word")
+                .auth_method(TokenAuthMethod::BasicHeader)
+                .with_extra_param("resource", "api"),
+        );
+        let authorization = authorization.unwrap();
+        let credentials = authorization.strip_prefix("Basic ").unwrap();
+        // Both parts are form-encoded before they are joined.
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(credentials)
+                .unwrap(),
+            b"my+app:p%40ss%3Aword"
+        );
+        assert_eq!(
+            form,
+            "grant_type=client_credentials&client_id=my+app&resource=api"
+        );
+    }
+
     #[derive(Default)]
     struct RecordingEndpoint {
         calls: usize,
@@ -415,4 +1335,265 @@ mod tests {
         let _ = socket.write_all(response.as_bytes()).await;
         let _ = socket.close().await;
     }
+
+    /// Serve token requests with `handler`, which maps a form body to a
+    /// status code and JSON body.
+    async fn spawn_scripted_server(
+        handler: impl Fn(&str) -> (u16, String) + Send + Sync + 'static,
+    ) -> std::io::Result<(String, Task<()>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let handler = Arc::new(handler);
+        let server = smol::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let handler = handler.clone();
+                smol::spawn(async move {
+                    let Some(form) = read_form(&mut socket).await else {
+                        return;
+                    };
+                    let (status, body) = handler(&form);
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.close().await;
+                })
+                .detach();
+            }
+        });
+        Ok((format!("http://{addr}"), server))
+    }
+
+    /// Read one request and return its body.
+    async fn read_form(socket: &mut TcpStream) -> Option<String> {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let read = socket.read(&mut buf).await.ok()?;
+            if read == 0 {
+                return None;
+            }
+            received.extend_from_slice(&buf[..read]);
+            let text = String::from_utf8_lossy(&received);
+            let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                continue;
+            };
+            let header = |wanted: &str| {
+                head.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case(wanted)
+                        .then(|| value.trim().to_ascii_lowercase())
+                })
+            };
+            if header("transfer-encoding").as_deref() == Some("chunked") {
+                if body.ends_with("0\r\n\r\n") {
+                    return Some(dechunk(body));
+                }
+            } else {
+                let length = header("content-length").and_then(|value| value.parse().ok());
+                if body.len() >= length.unwrap_or(0) {
+                    return Some(body.to_string());
+                }
+            }
+        }
+    }
+
+    /// Join the chunks of a complete chunked body.
+    fn dechunk(mut body: &str) -> String {
+        let mut joined = String::new();
+        while let Some((size, rest)) = body.split_once("\r\n") {
+            let size = usize::from_str_radix(size.trim(), 16).unwrap_or(0);
+            if size == 0 {
+                break;
+            }
+            joined.push_str(&rest[..size]);
+            body = &rest[size + 2..];
+        }
+        joined
+    }
+
+    fn form_value(form: &str, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(form.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    fn code_flow(token_url: &str) -> OAuth2AuthorizationCode {
+        OAuth2AuthorizationCode::new(
+            "https://auth.example.com/authorize?prompt=consent",
+            token_url,
+            "app",
+            "http://localhost/callback",
+        )
+        .with_scope("read")
+    }
+
+    fn get(uri: &str) -> Request {
+        HttpRequest::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn authorization_url_carries_state_and_s256_challenge() {
+        let pending = code_flow("http://127.0.0.1:9/token")
+            .authorization_request()
+            .unwrap();
+        let url = url::Url::parse(pending.url()).unwrap();
+        let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/authorize");
+        assert_eq!(query["prompt"], "consent");
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], "app");
+        assert_eq!(query["redirect_uri"], "http://localhost/callback");
+        assert_eq!(query["scope"], "read");
+        assert_eq!(query["state"], pending.state());
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(
+            query["code_challenge"],
+            code_challenge(pending.code_verifier())
+        );
+        // 32 random bytes; RFC 7636 requires 43 to 128 characters.
+        assert_eq!(pending.code_verifier().len(), 43);
+    }
+
+    #[test]
+    fn code_exchange_round_trips_the_code_verifier() {
+        let pending = code_flow("http://127.0.0.1:9/token")
+            .authorization_request()
+            .unwrap();
+        let challenge = url::Url::parse(pending.url())
+            .unwrap()
+            .query_pairs()
+            .find(|(key, _)| key == "code_challenge")
+            .map(|(_, value)| value.into_owned())
+            .unwrap();
+
+        smol::block_on(async {
+            let server = spawn_scripted_server(move |form| {
+                let verifier = form_value(form, "code_verifier").unwrap_or_default();
+                if form_value(form, "grant_type").as_deref() == Some("authorization_code")
+                    && form_value(form, "code").as_deref() == Some("the-code")
+                    && code_challenge(&verifier) == challenge
+                {
+                    (
+                        200,
+                        r#"{"access_token":"access-1","refresh_token":"refresh-1","expires_in":3600}"#
+                            .to_string(),
+                    )
+                } else {
+                    (400, r#"{"error":"invalid_grant"}"#.to_string())
+                }
+            })
+            .await;
+            let (url, handle) = match server {
+                Ok(values) => values,
+                Err(err) => {
+                    eprintln!("skipping oauth2 code test: {err}");
+                    return;
+                }
+            };
+            let mut auth = OAuth2AuthorizationCode {
+                config: code_flow(&url).config,
+                token: Arc::default(),
+            };
+
+            // A different verifier is refused by the token endpoint.
+            let mut forged = pending.clone();
+            forged.code_verifier = "forged".to_string();
+            let error = auth
+                .exchange_code(&forged, "the-code", pending.state())
+                .await
+                .unwrap_err();
+            assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+
+            auth.exchange_code(&pending, "the-code", pending.state())
+                .await
+                .unwrap();
+            let mut endpoint = RecordingEndpoint::default();
+            auth.handle(&mut get("https://api.example.com/"), &mut endpoint)
+                .await
+                .unwrap();
+            assert_eq!(endpoint.last_auth(), Some("Bearer access-1".to_string()));
+
+            handle.cancel().await;
+        });
+    }
+
+    #[test]
+    fn expired_access_token_is_refreshed() {
+        smol::block_on(async {
+            let forms = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let seen = forms.clone();
+            let server =
+                spawn_scripted_server(move |form| {
+                    seen.lock().unwrap().push(form.to_string());
+                    match form_value(form, "grant_type").as_deref() {
+                    // Already expired, so the first request refreshes it.
+                    Some("authorization_code") => (
+                        200,
+                        r#"{"access_token":"access-1","refresh_token":"refresh-1","expires_in":0}"#
+                            .to_string(),
+                    ),
+                    Some("refresh_token")
+                        if form_value(form, "refresh_token").as_deref() == Some("refresh-1") =>
+                    {
+                        (200, r#"{"access_token":"access-2","expires_in":3600}"#.to_string())
+                    }
+                    _ => (400, r#"{"error":"invalid_grant"}"#.to_string()),
+                }
+                })
+                .await;
+            let (url, handle) = match server {
+                Ok(values) => values,
+                Err(err) => {
+                    eprintln!("skipping oauth2 refresh test: {err}");
+                    return;
+                }
+            };
+            let mut auth = code_flow(&url);
+            let pending = auth.authorization_request().unwrap();
+            auth.exchange_code(&pending, "the-code", pending.state())
+                .await
+                .unwrap();
+
+            let mut endpoint = RecordingEndpoint::default();
+            for _ in 0..2 {
+                auth.handle(&mut get("https://api.example.com/"), &mut endpoint)
+                    .await
+                    .unwrap();
+                assert_eq!(endpoint.last_auth(), Some("Bearer access-2".to_string()));
+            }
+            assert_eq!(forms.lock().unwrap().len(), 2);
+            // The refresh token was kept although the provider did not rotate it.
+            let token = auth.token.lock().await.clone().unwrap();
+            assert_eq!(token.refresh_token.as_deref(), Some("refresh-1"));
+
+            handle.cancel().await;
+        });
+    }
+
+    #[test]
+    fn code_flow_rejects_state_mismatch_and_missing_tokens() {
+        let mut auth = code_flow("http://127.0.0.1:9/token");
+        let pending = auth.authorization_request().unwrap();
+        smol::block_on(async {
+            let error = auth
+                .exchange_code(&pending, "the-code", "other-state")
+                .await
+                .unwrap_err();
+            assert!(matches!(error, OAuth2Error::StateMismatch));
+
+            let mut endpoint = RecordingEndpoint::default();
+            let error = auth
+                .handle(&mut get("https://api.example.com/"), &mut endpoint)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error,
+                MiddlewareError::Middleware(OAuth2Error::NotAuthorized)
+            ));
+            assert_eq!(endpoint.calls(), 0);
+        });
+    }
 }
@@ -0,0 +1,316 @@
+//! Watching a JSON document for changes by polling it.
+//!
+//! [`Watcher`] fetches a URL at a fixed interval and yields the parsed
+//! document whenever it changed. Requests are conditional: the `ETag` and
+//! `Last-Modified` validators of the last response are sent back as
+//! `If-None-Match` and `If-Modified-Since`, so an unchanged document costs a
+//! `304 Not Modified`. Servers without validators are handled by comparing a
+//! hash of the body instead.
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), zenwave::Error> {
+//! use std::time::Duration;
+//! use futures_util::StreamExt;
+//! use zenwave::poll::Watcher;
+//!
+//! #[derive(serde::Deserialize, Clone)]
+//! struct Config {
+//!     feature_enabled: bool,
+//! }
+//!
+//! let mut watcher: Watcher<Config> = Watcher::new(
+//!     zenwave::client(),
+//!     "https://example.com/config.json",
+//!     Duration::from_secs(30),
+//! )?;
+//! while let Some(change) = watcher.next().await {
+//!     match change {
+//!         Ok(change) => println!("enabled: {}", change.value.feature_enabled),
+//!         Err(error) => eprintln!("poll failed: {error}"),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{
+    fmt::{self, Debug, Display, Formatter},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use futures_util::{Stream, stream};
+use http_kit::{
+    Body, Method, Request, StatusCode, Uri,
+    header::{self, HeaderValue},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{Client, ResponseExt, retry};
+
+/// Longest wait between polls after consecutive failures, unless the server
+/// asks for more with `Retry-After`.
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_mins(5);
+
+/// A new version of the watched document.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Changed<T> {
+    /// The parsed document.
+    pub value: T,
+    /// The `ETag` of the response, if the server sent one.
+    pub etag: Option<String>,
+}
+
+/// A stream of the changes to a JSON document, found by polling it.
+///
+/// The first poll happens immediately and always yields the document; later
+/// polls only yield when it changed. Waits between polls are spread by up
+/// to ±10% ([`jitter`](Self::jitter)) so that many watchers do not poll in
+/// lockstep.
+///
+/// Failed polls, including documents that do not parse, are yielded as
+/// errors without ending the stream. The watcher then backs off, doubling
+/// the wait after each consecutive failure up to
+/// [`max_backoff`](Self::max_backoff), or waits as long as a `Retry-After`
+/// header asks. [`latest`](Self::latest) keeps returning the last document
+/// that parsed in the meantime.
+///
+/// The stream never ends; drop it to stop polling.
+pub struct Watcher<T> {
+    state: Option<State<T>>,
+    stream: Option<Changes<T>>,
+    latest: Arc<Mutex<Option<T>>>,
+}
+
+/// Configuration and validators of a watcher, moved into its stream on the
+/// first poll.
+struct State<T> {
+    send: Box<dyn FnMut(Request) -> SendFuture + Send>,
+    uri: Uri,
+    interval: Duration,
+    jitter: f64,
+    max_backoff: Duration,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    hash: Option<u64>,
+    /// How long to wait before the next poll; `None` before the first.
+    wait: Option<Duration>,
+    failures: u32,
+    polls: u64,
+    rng: RandomState,
+    latest: Arc<Mutex<Option<T>>>,
+}
+
+type Changes<T> = Pin<Box<dyn Stream<Item = Result<Changed<T>, crate::Error>> + Send>>;
+
+type SendFuture =
+    Pin<Box<dyn Future<Output = Result<http_kit::Response, crate::Error>> + Send + 'static>>;
+
+impl<T> Debug for Watcher<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("started", &self.stream.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Watcher<T>
+where
+    T: DeserializeOwned + Clone + Send + 'static,
+{
+    /// Watch the JSON document at `uri`, fetched with `client` every
+    /// `interval`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidUri`] when `uri` cannot be parsed.
+    pub fn new<C, U>(client: C, uri: U, interval: Duration) -> Result<Self, crate::Error>
+    where
+        C: Client + 'static,
+        C::Error: Into<crate::Error>,
+        U: TryInto<Uri>,
+        U::Error: Display,
+    {
+        let uri = uri
+            .try_into()
+            .map_err(|error| crate::Error::InvalidUri(error.to_string()))?;
+        let client = Arc::new(futures_util::lock::Mutex::new(client));
+        let send = move |mut request: Request| -> SendFuture {
+            let client = Arc::clone(&client);
+            Box::pin(async move {
+                let mut client = client.lock().await;
+                client.respond(&mut request).await.map_err(Into::into)
+            })
+        };
+        let latest = Arc::new(Mutex::new(None));
+        Ok(Self {
+            state: Some(State {
+                send: Box::new(send),
+                uri,
+                interval,
+                jitter: 0.1,
+                max_backoff: DEFAULT_MAX_BACKOFF.max(interval),
+                etag: None,
+                last_modified: None,
+                hash: None,
+                wait: None,
+                failures: 0,
+                polls: 0,
+                rng: RandomState::new(),
+                latest: Arc::clone(&latest),
+            }),
+            stream: None,
+            latest,
+        })
+    }
+
+    /// Spread each wait by up to `fraction` of it in either direction
+    /// (default 0.1). Zero polls at exactly the interval.
+    ///
+    /// Has no effect once polling started.
+    #[must_use]
+    pub const fn jitter(mut self, fraction: f64) -> Self {
+        if let Some(state) = &mut self.state {
+            state.jitter = fraction.clamp(0.0, 1.0);
+        }
+        self
+    }
+
+    /// Wait at most `max` between polls after failures (default five
+    /// minutes, or the interval if longer).
+    ///
+    /// Has no effect once polling started.
+    #[must_use]
+    pub const fn max_backoff(mut self, max: Duration) -> Self {
+        if let Some(state) = &mut self.state {
+            state.max_backoff = max;
+        }
+        self
+    }
+
+    /// The last document that was fetched and parsed, if any.
+    #[must_use]
+    pub fn latest(&self) -> Option<T> {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl<T> Stream for Watcher<T>
+where
+    T: DeserializeOwned + Clone + Send + 'static,
+{
+    type Item = Result<Changed<T>, crate::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(state) = self.state.take() {
+            self.stream = Some(Box::pin(stream::unfold(state, |mut state| async move {
+                let item = state.next().await;
+                Some((item, state))
+            })));
+        }
+        self.stream
+            .as_mut()
+            .map_or(Poll::Ready(None), |stream| stream.as_mut().poll_next(cx))
+    }
+}
+
+impl<T> State<T>
+where
+    T: DeserializeOwned + Clone + Send + 'static,
+{
+    /// Poll until the document changed or a poll failed.
+    async fn next(&mut self) -> Result<Changed<T>, crate::Error> {
+        loop {
+            if let Some(wait) = self.wait {
+                retry::sleep(wait).await;
+            }
+            match self.poll().await {
+                Ok(changed) => {
+                    self.failures = 0;
+                    self.wait = Some(self.jittered(self.interval));
+                    if let Some(changed) = changed {
+                        return Ok(changed);
+                    }
+                }
+                Err((error, retry_after)) => {
+                    self.failures = self.failures.saturating_add(1);
+                    let backoff = self
+                        .interval
+                        .saturating_mul(2u32.saturating_pow(self.failures))
+                        .min(self.max_backoff);
+                    self.wait = Some(retry_after.unwrap_or_else(|| self.jittered(backoff)));
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// `wait` moved randomly by up to `jitter` of it in either direction.
+    fn jittered(&mut self, wait: Duration) -> Duration {
+        self.polls = self.polls.wrapping_add(1);
+        #[allow(clippy::cast_precision_loss)]
+        let random = self.rng.hash_one(self.polls) as f64 / u64::MAX as f64;
+        wait.mul_f64(self.jitter.mul_add(2.0f64.mul_add(random, -1.0), 1.0))
+    }
+
+    /// One conditional request; `None` when the document did not change.
+    async fn poll(&mut self) -> Result<Option<Changed<T>>, (crate::Error, Option<Duration>)> {
+        let mut request = http::Request::builder()
+            .method(Method::GET)
+            .uri(self.uri.clone())
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .map_err(|error| (crate::Error::InvalidRequest(error.to_string()), None))?;
+        let headers = request.headers_mut();
+        if let Some(etag) = &self.etag {
+            headers.insert(header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+        }
+
+        let response = (self.send)(request).await.map_err(|error| {
+            let retry_after = error
+                .response()
+                .and_then(|response| retry::retry_after(response.headers()));
+            (error, retry_after)
+        })?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let etag = response.headers().get(header::ETAG).cloned();
+        let last_modified = response.headers().get(header::LAST_MODIFIED).cloned();
+        let bytes = response
+            .into_bytes()
+            .await
+            .map_err(|error| (error.into(), None))?;
+        let hash = self.rng.hash_one(&bytes[..]);
+        if self.hash == Some(hash) {
+            self.etag = etag;
+            self.last_modified = last_modified;
+            return Ok(None);
+        }
+        let value: T = serde_json::from_slice(&bytes)
+            .map_err(|error| (http_kit::BodyError::from(error).into(), None))?;
+
+        // Only remember the validators of a document that parsed, so that a
+        // broken one is fetched again rather than answered with a 304.
+        self.hash = Some(hash);
+        self.etag.clone_from(&etag);
+        self.last_modified = last_modified;
+        *self.latest.lock().unwrap_or_else(PoisonError::into_inner) = Some(value.clone());
+        Ok(Some(Changed {
+            value,
+            etag: etag.and_then(|etag| etag.to_str().ok().map(str::to_owned)),
+        }))
+    }
+}
@@ -2,8 +2,8 @@
 //! Proxy configuration helpers for proxy-capable backends.
 //!
 //! This simplified matcher supports HTTP/HTTPS proxies configured via
-//! environment variables or builder methods. SOCKS proxies are only used
-//! by the curl backend.
+//! environment variables or builder methods. SOCKS proxies are used by the
+//! curl backend, and by the hyper backend with the `socks` feature.
 
 use std::{collections::HashSet, env, fmt, str::FromStr, sync::Arc};
 
@@ -57,7 +57,7 @@ impl Proxy {
         self.matcher
     }
 
-    #[cfg(any(feature = "curl-backend", test))]
+    #[cfg(any(feature = "curl-backend", feature = "hyper-backend", test))]
     pub(crate) fn intercept(&self, uri: &Uri) -> Option<Intercept> {
         self.matcher.intercept(uri)
     }
@@ -176,6 +176,10 @@ impl Intercept {
         self.basic_auth.as_ref()
     }
 
+    #[cfg_attr(
+        not(any(feature = "curl-backend", feature = "socks")),
+        allow(dead_code)
+    )]
     pub(crate) fn raw_auth(&self) -> Option<(&str, &str)> {
         self.raw_auth
             .as_ref()
@@ -0,0 +1,398 @@
+//! Seekable reads over HTTP Range requests.
+//!
+//! [`RangedReader`] exposes a remote resource as `AsyncRead + AsyncSeek`, the
+//! access pattern media players and archive readers expect. Reads are served
+//! from an internal cache of fetched ranges; a miss issues one `Range` request.
+//! Sequential reads fetch extra chunks ahead of the cursor, while a seek to an
+//! uncached offset fetches a single chunk, so random access stays cheap and
+//! streaming needs few round trips.
+
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use std::{collections::VecDeque, fmt::Display, io};
+
+use futures_io::{AsyncRead, AsyncSeek};
+use http_kit::{
+    Body, HttpError, Method, Request, Response, StatusCode, Uri,
+    header::{self, HeaderMap, HeaderValue},
+    utils::Bytes,
+};
+
+use crate::{client::Client, error::captured_response};
+
+type PendingFetch<C> = Pin<Box<dyn Future<Output = (C, io::Result<Fetched>)> + Send>>;
+
+/// Reader that fetches a remote resource on demand with `Range` requests.
+///
+/// The underlying client is reused for every request, so pooled connections
+/// are kept warm. The first response's `ETag` is remembered; if a later range
+/// comes back with a different one, the read fails with
+/// [`io::ErrorKind::InvalidData`] rather than splicing two versions together.
+pub struct RangedReader<C: Client> {
+    // `None` while a fetch owns the client.
+    client: Option<C>,
+    uri: Uri,
+    chunk_size: u64,
+    readahead: u32,
+    max_cached: usize,
+    position: u64,
+    len: Option<u64>,
+    etag: Option<HeaderValue>,
+    // Fetched ranges, oldest first.
+    segments: VecDeque<Segment>,
+    cached: usize,
+    // Offset just past the last fetched range, used to detect sequential reads.
+    next_sequential: Option<u64>,
+    fetch: Option<PendingFetch<C>>,
+}
+
+// The client is never pinned: it moves by value into and out of the boxed fetch.
+impl<C: Client> Unpin for RangedReader<C> {}
+
+impl<C: Client> fmt::Debug for RangedReader<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RangedReader")
+            .field("uri", &self.uri)
+            .field("position", &self.position)
+            .field("len", &self.len)
+            .field("cached", &self.cached)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Segment {
+    start: u64,
+    data: Bytes,
+}
+
+struct Fetched {
+    start: u64,
+    data: Bytes,
+    total: Option<u64>,
+    etag: Option<HeaderValue>,
+}
+
+impl<C: Client + 'static> RangedReader<C> {
+    /// Create a reader for `uri` positioned at the start of the resource.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidUri`] when `uri` cannot be parsed.
+    pub fn new<U>(client: C, uri: U) -> Result<Self, crate::Error>
+    where
+        U: TryInto<Uri>,
+        U::Error: Display,
+    {
+        let uri = uri
+            .try_into()
+            .map_err(|error| crate::Error::InvalidUri(error.to_string()))?;
+        Ok(Self {
+            client: Some(client),
+            uri,
+            chunk_size: 64 * 1024,
+            readahead: 3,
+            max_cached: 1024 * 1024,
+            position: 0,
+            len: None,
+            etag: None,
+            segments: VecDeque::new(),
+            cached: 0,
+            next_sequential: None,
+            fetch: None,
+        })
+    }
+
+    /// Set the number of bytes requested per chunk. Defaults to 64 KiB.
+    #[must_use]
+    pub const fn chunk_size(mut self, bytes: u64) -> Self {
+        self.chunk_size = if bytes == 0 { 1 } else { bytes };
+        self
+    }
+
+    /// Set how many extra chunks a sequential read fetches. Defaults to 3.
+    ///
+    /// Reads that continue where the previous range ended request
+    /// `1 + chunks` chunks at once; reads after a seek request one.
+    #[must_use]
+    pub const fn readahead(mut self, chunks: u32) -> Self {
+        self.readahead = chunks;
+        self
+    }
+
+    /// Set the number of fetched bytes kept for re-reading. Defaults to 1 MiB.
+    ///
+    /// The oldest ranges are dropped first; the most recent one is always kept.
+    #[must_use]
+    pub const fn max_cached(mut self, bytes: usize) -> Self {
+        self.max_cached = bytes;
+        self
+    }
+
+    /// Current read offset.
+    #[must_use]
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Total size of the resource, once a response has reported it.
+    #[must_use]
+    pub const fn content_length(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Copy cached bytes at the current position into `buf`.
+    fn read_cached(&self, buf: &mut [u8]) -> Option<usize> {
+        let position = self.position;
+        let segment = self.segments.iter().rev().find(|segment| {
+            segment.start <= position && position < segment.start + segment.data.len() as u64
+        })?;
+        let offset = usize::try_from(position - segment.start).ok()?;
+        let available = &segment.data[offset..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        Some(n)
+    }
+
+    /// Drive a fetch covering `start`, starting one if none is in flight.
+    ///
+    /// An in-flight fetch for another offset (after a seek) is completed and
+    /// cached first; callers re-check the cache and call again.
+    fn poll_fetch(&mut self, cx: &mut Context<'_>, start: u64) -> Poll<io::Result<()>> {
+        if self.fetch.is_none() {
+            let chunks = if self.next_sequential == Some(start) {
+                1 + u64::from(self.readahead)
+            } else {
+                1
+            };
+            let mut end = start.saturating_add(self.chunk_size.saturating_mul(chunks)) - 1;
+            if let Some(len) = self.len {
+                end = end.min(len.saturating_sub(1));
+            }
+
+            let mut client = self
+                .client
+                .take()
+                .expect("client is present when no fetch is in flight");
+            let uri = self.uri.clone();
+            self.fetch = Some(Box::pin(async move {
+                let result = fetch(&mut client, uri, start, end).await;
+                (client, result)
+            }));
+        }
+
+        let pending = self.fetch.as_mut().expect("fetch was just started");
+        let (client, result) = ready!(pending.as_mut().poll(cx));
+        self.fetch = None;
+        self.client = Some(client);
+        Poll::Ready(result.and_then(|fetched| self.store(fetched)))
+    }
+
+    fn store(&mut self, fetched: Fetched) -> io::Result<()> {
+        match (&self.etag, fetched.etag) {
+            (Some(expected), Some(actual)) if *expected != actual => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "resource changed between range requests (ETag mismatch)",
+                ));
+            }
+            (None, Some(actual)) => self.etag = Some(actual),
+            _ => {}
+        }
+
+        match (self.len, fetched.total) {
+            (Some(len), Some(total)) if len != total => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "resource length changed between range requests",
+                ));
+            }
+            (None, Some(total)) => self.len = Some(total),
+            _ => {}
+        }
+
+        if fetched.data.is_empty() {
+            // Past the end: the server reported the range as unsatisfiable.
+            self.len.get_or_insert(fetched.start);
+            return Ok(());
+        }
+
+        self.next_sequential = Some(fetched.start + fetched.data.len() as u64);
+        self.cached += fetched.data.len();
+        self.segments.push_back(Segment {
+            start: fetched.start,
+            data: fetched.data,
+        });
+        while self.cached > self.max_cached && self.segments.len() > 1 {
+            if let Some(evicted) = self.segments.pop_front() {
+                self.cached -= evicted.data.len();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: Client + 'static> AsyncRead for RangedReader<C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if buf.is_empty() || this.len.is_some_and(|len| this.position >= len) {
+                return Poll::Ready(Ok(0));
+            }
+            if let Some(n) = this.read_cached(buf) {
+                this.position += n as u64;
+                return Poll::Ready(Ok(n));
+            }
+            ready!(this.poll_fetch(cx, this.position))?;
+        }
+    }
+}
+
+impl<C: Client + 'static> AsyncSeek for RangedReader<C> {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        let target = match pos {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::Current(delta) => this.position.checked_add_signed(delta),
+            io::SeekFrom::End(delta) => {
+                if this.len.is_none() {
+                    // Learn the length from the first chunk, which a reader
+                    // seeking from the end usually needs anyway.
+                    ready!(this.poll_fetch(cx, 0))?;
+                }
+                let Some(len) = this.len else {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "server did not report the resource length",
+                    )));
+                };
+                len.checked_add_signed(delta)
+            }
+        };
+
+        let Some(target) = target else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )));
+        };
+        this.position = target;
+        Poll::Ready(Ok(target))
+    }
+}
+
+/// Request `start..=end` and validate the server's answer.
+async fn fetch<C: Client>(client: &mut C, uri: Uri, start: u64, end: u64) -> io::Result<Fetched> {
+    let mut request: Request = http::Request::builder()
+        .method(Method::GET)
+        .uri(uri)
+        .header(header::RANGE, format!("bytes={start}-{end}"))
+        .body(Body::empty())
+        .map_err(io::Error::other)?;
+
+    let response = match client.respond(&mut request).await {
+        Ok(response) => response,
+        // Backends report 416 as an HTTP error; it only means end of file.
+        Err(error) if error.status() == StatusCode::RANGE_NOT_SATISFIABLE => {
+            let headers = captured_response(&error).map(Response::headers);
+            return Ok(past_end(start, headers));
+        }
+        Err(error) => return Err(io::Error::other(error)),
+    };
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => partial(response, start, end).await,
+        StatusCode::RANGE_NOT_SATISFIABLE => Ok(past_end(start, Some(response.headers()))),
+        StatusCode::OK => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "server ignored the Range request",
+        )),
+        status => Err(io::Error::other(format!(
+            "unexpected status {status} for a range request"
+        ))),
+    }
+}
+
+async fn partial(response: Response, start: u64, end: u64) -> io::Result<Fetched> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let (first, last, total) = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_range)
+        .ok_or_else(|| invalid("missing or malformed Content-Range"))?;
+    if first != start || last > end || last < first {
+        return Err(invalid("Content-Range does not match the requested range"));
+    }
+
+    let etag = response.headers().get(header::ETAG).cloned();
+    let data = response
+        .into_body()
+        .into_bytes()
+        .await
+        .map_err(io::Error::other)?;
+    if data.len() as u64 != last - first + 1 {
+        return Err(invalid("body length does not match Content-Range"));
+    }
+
+    Ok(Fetched {
+        start,
+        data,
+        total,
+        etag,
+    })
+}
+
+fn past_end(start: u64, headers: Option<&HeaderMap>) -> Fetched {
+    // `Content-Range: bytes */<length>` on a 416 reports the actual length.
+    let total = headers
+        .and_then(|headers| headers.get(header::CONTENT_RANGE))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bytes */"))
+        .and_then(|total| total.trim().parse().ok());
+    Fetched {
+        start,
+        data: Bytes::new(),
+        total,
+        etag: headers.and_then(|headers| headers.get(header::ETAG).cloned()),
+    }
+}
+
+/// Parse `bytes <first>-<last>/<total or *>`.
+pub(crate) fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((first.trim().parse().ok()?, last.trim().parse().ok()?, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_content_range;
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 0-99/1000"),
+            Some((0, 99, Some(1000)))
+        );
+        assert_eq!(parse_content_range("bytes 5-9/*"), Some((5, 9, None)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Client-side rate and concurrency limiting.
+//!
+//! [`RateLimit`] throttles outgoing requests with a token bucket so a client
+//! stays under an API's published request rate instead of tripping its
+//! server-side limiter. Like [`crate::timeout`], waiting relies on
+//! `async-io`'s timers (`gloo-timers` on wasm) rather than a specific runtime.
+//! [`ConcurrencyLimit`] instead caps how many requests are in flight at once,
+//! whatever their rate.
+
+use core::time::Duration;
+use std::{convert::Infallible, sync::Arc};
+
+use async_lock::{Mutex, Semaphore};
+use http_kit::{Endpoint, Middleware, Request, Response, middleware::MiddlewareError};
+
+use crate::retry::{Stopwatch, sleep};
+
+/// Middleware that limits requests to a steady rate with bursts.
+///
+/// Each request takes one token from a bucket holding at most `burst` tokens,
+/// refilled at `per_second` tokens per second; when the bucket is empty the
+/// request waits for the next token before it is sent. The bucket starts
+/// full, so the first `burst` requests go out immediately.
+///
+/// Clones share the same bucket, so every clone of a rate-limited client
+/// counts against one limit. Waiting requests are served in arrival order.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    per_second: u32,
+    burst: u32,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Stopwatch,
+}
+
+impl RateLimit {
+    /// Allow `per_second` requests per second on average and up to `burst`
+    /// requests back to back.
+    ///
+    /// A `burst` of zero is treated as one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `per_second` is zero.
+    #[must_use]
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        assert!(
+            per_second > 0,
+            "rate limit must allow at least one request per second"
+        );
+        let burst = burst.max(1);
+        Self {
+            per_second,
+            burst,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: f64::from(burst),
+                refilled: Stopwatch::start(),
+            })),
+        }
+    }
+
+    /// Wait until a token is available and take it.
+    async fn acquire(&self) {
+        // The lock is held while sleeping so waiters queue up fairly.
+        let mut bucket = self.bucket.lock().await;
+        bucket.refill(self.per_second, self.burst);
+        if bucket.tokens < 1.0 {
+            let missing = 1.0 - bucket.tokens;
+            sleep(Duration::from_secs_f64(
+                missing / f64::from(self.per_second),
+            ))
+            .await;
+            bucket.refill(self.per_second, self.burst);
+        }
+        bucket.tokens -= 1.0;
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, per_second: u32, burst: u32) {
+        let elapsed = self.refilled.elapsed();
+        self.refilled = Stopwatch::start();
+        self.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(f64::from(per_second), self.tokens)
+            .min(f64::from(burst));
+    }
+}
+
+impl Middleware for RateLimit {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        self.acquire().await;
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Middleware that caps the number of requests in flight.
+///
+/// A request waits for one of `limit` permits before it is sent and returns
+/// it once the response headers arrive; streaming the body does not hold a
+/// permit. Clones share the permits, so every clone of a limited client
+/// counts against one limit.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    permits: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimit {
+    /// Allow at most `limit` requests in flight. A `limit` of zero is treated
+    /// as one.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+}
+
+impl Middleware for ConcurrencyLimit {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let _permit = self.permits.acquire().await;
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{ConcurrencyLimit, RateLimit};
+    use crate::Client;
+    use http_kit::{Body, Endpoint, Request, Response};
+    use std::{
+        convert::Infallible,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::{Duration, Instant},
+    };
+
+    #[derive(Clone)]
+    struct Ok200;
+
+    impl Endpoint for Ok200 {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            Ok(Response::new(Body::empty()))
+        }
+    }
+
+    impl Client for Ok200 {}
+
+    fn request() -> Request {
+        http::Request::new(Body::empty())
+    }
+
+    #[test]
+    fn spaces_requests_after_the_burst() {
+        let mut client = Ok200.rate_limit(20, 2);
+        let started = Instant::now();
+        async_io::block_on(async {
+            for _ in 0..6 {
+                client.respond(&mut request()).await.unwrap();
+            }
+        });
+        // Two requests ride the burst; the other four wait 50ms each.
+        assert!(started.elapsed() >= Duration::from_millis(195));
+    }
+
+    #[test]
+    fn burst_goes_out_immediately() {
+        let mut client = Ok200.rate_limit(1, 5);
+        let started = Instant::now();
+        async_io::block_on(async {
+            for _ in 0..5 {
+                client.respond(&mut request()).await.unwrap();
+            }
+        });
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn clones_share_the_bucket() {
+        let limit = RateLimit::new(10, 1);
+        let mut first = Ok200.with(limit.clone());
+        let mut second = Ok200.with(limit);
+        let started = Instant::now();
+        async_io::block_on(async {
+            first.respond(&mut request()).await.unwrap();
+            second.respond(&mut request()).await.unwrap();
+        });
+        assert!(started.elapsed() >= Duration::from_millis(95));
+    }
+
+    /// Records the highest number of requests it served at once.
+    #[derive(Clone, Default)]
+    struct Gauge {
+        current: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Endpoint for Gauge {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            let now = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            async_io::Timer::after(Duration::from_millis(10)).await;
+            self.current.fetch_sub(1, Ordering::SeqCst);
+            Ok(Response::new(Body::empty()))
+        }
+    }
+
+    impl Client for Gauge {}
+
+    #[test]
+    fn in_flight_requests_never_exceed_the_limit() {
+        let gauge = Gauge::default();
+        let limit = ConcurrencyLimit::new(3);
+        let tasks = (0..12).map(|_| {
+            let mut client = gauge.clone().with(limit.clone());
+            async move { client.respond(&mut request()).await.unwrap() }
+        });
+        async_io::block_on(futures_util::future::join_all(tasks));
+
+        assert_eq!(gauge.peak.load(Ordering::SeqCst), 3);
+        assert_eq!(gauge.current.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,456 @@
+//! Byte-exact recording of outgoing requests for contract tests.
+//!
+//! [`Recorder`] keeps the final method, URI, headers and body of every
+//! request that passes through it. Unlike
+//! [`CaptureSentRequest`](crate::capture::CaptureSentRequest), it buffers
+//! request bodies, so it is meant for tests only. Add it before any other
+//! middleware so it sits next to the backend and sees what authentication,
+//! cookies and the like added:
+//!
+//! ```rust,no_run
+//! # async fn example(backend: impl zenwave::Client) -> Result<(), zenwave::Error> {
+//! use zenwave::{
+//!     Client,
+//!     recorder::{Recorder, RequestMatcher},
+//! };
+//!
+//! let recorder = Recorder::new();
+//! let mut client = backend
+//!     .with(recorder.clone())
+//!     .bearer_auth("token");
+//! let _ = client.get("https://example.com/items")?.await;
+//!
+//! let requests = recorder.requests();
+//! assert!(requests[0].matches(
+//!     &RequestMatcher::new().path("/items").header("authorization", "Bearer token")
+//! ));
+//! std::fs::write("requests.json", recorder.to_json())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use base64::Engine;
+use http_kit::{
+    Endpoint, Method, Middleware, Request, Response, Uri,
+    header::{HeaderMap, HeaderName},
+    middleware::MiddlewareError,
+};
+use serde_json::{Value, json};
+
+/// Default cap on the recorded bytes of each request body.
+pub(crate) const DEFAULT_BODY_LIMIT: usize = 1024 * 1024;
+
+/// A request as it was handed to the backend.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    body_len: usize,
+}
+
+impl RecordedRequest {
+    /// Record `request`, keeping at most `body_limit` bytes of its body.
+    ///
+    /// Streaming bodies are read into memory and put back, so the request
+    /// can still be sent.
+    pub(crate) async fn capture(
+        request: &mut Request,
+        body_limit: usize,
+    ) -> Result<Self, crate::Error> {
+        let bytes = if let Some(body) = request.body().try_clone() {
+            body.into_bytes().await.unwrap_or_default()
+        } else {
+            let body = request
+                .body_mut()
+                .take()
+                .map_err(|error| crate::Error::InvalidRequest(error.to_string()))?;
+            // Buffer the stream so it can be both recorded and sent.
+            let bytes = body.into_bytes().await.map_err(|error| {
+                crate::Error::InvalidRequest(format!("failed to read request body: {error}"))
+            })?;
+            *request.body_mut() = http_kit::Body::from(bytes.clone());
+            bytes
+        };
+
+        Ok(Self {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            headers: request.headers().clone(),
+            body: bytes[..bytes.len().min(body_limit)].to_vec(),
+            body_len: bytes.len(),
+        })
+    }
+
+    /// The request method.
+    #[must_use]
+    pub const fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The request URI.
+    #[must_use]
+    pub const fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The request headers after every middleware ran.
+    #[must_use]
+    pub const fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The recorded body bytes, at most [`Recorder::body_limit`] of them.
+    #[must_use]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Whether the body was longer than the recorded bytes.
+    #[must_use]
+    pub const fn is_truncated(&self) -> bool {
+        self.body_len > self.body.len()
+    }
+
+    /// Whether this request satisfies every condition of `matcher`.
+    #[must_use]
+    pub fn matches(&self, matcher: &RequestMatcher) -> bool {
+        matcher
+            .method
+            .as_ref()
+            .is_none_or(|method| method == self.method)
+            && matcher
+                .uri
+                .as_ref()
+                .is_none_or(|uri| *uri == self.uri.to_string())
+            && matcher
+                .path
+                .as_ref()
+                .is_none_or(|path| path == self.uri.path())
+            && matcher.headers.iter().all(|(name, value)| {
+                self.headers
+                    .get_all(name)
+                    .iter()
+                    .any(|sent| sent.as_bytes() == value.as_bytes())
+            })
+            && matcher.body.as_ref().is_none_or(|body| {
+                !self.is_truncated()
+                    && match body {
+                        ExpectedBody::Bytes(bytes) => *bytes == self.body,
+                        ExpectedBody::Json(json) => {
+                            serde_json::from_slice::<Value>(&self.body).is_ok_and(|v| v == *json)
+                        }
+                    }
+            })
+    }
+
+    /// This request as JSON, with headers sorted so the output does not
+    /// depend on the order middleware added them in.
+    ///
+    /// Bodies that are not UTF-8 text are written as `body_base64`.
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        let mut headers: Vec<(&str, String)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        headers.sort();
+        let mut value = json!({
+            "method": self.method.as_str(),
+            "uri": self.uri.to_string(),
+            "headers": headers,
+        });
+        match std::str::from_utf8(&self.body) {
+            Ok(text) => value["body"] = json!(text),
+            Err(_) => {
+                value["body_base64"] =
+                    json!(base64::engine::general_purpose::STANDARD.encode(&self.body));
+            }
+        }
+        if self.is_truncated() {
+            value["body_len"] = json!(self.body_len);
+        }
+        value
+    }
+}
+
+/// Conditions a [`RecordedRequest`] is checked against with
+/// [`RecordedRequest::matches`].
+///
+/// Unset conditions match anything; headers only need to be among the
+/// values sent for their name.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMatcher {
+    method: Option<Method>,
+    uri: Option<String>,
+    path: Option<String>,
+    headers: Vec<(HeaderName, String)>,
+    body: Option<ExpectedBody>,
+}
+
+#[derive(Debug, Clone)]
+enum ExpectedBody {
+    Bytes(Vec<u8>),
+    Json(Value),
+}
+
+impl RequestMatcher {
+    /// A matcher that accepts every request.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require the method `method`.
+    #[must_use]
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Require exactly the URI `uri`.
+    #[must_use]
+    pub fn uri(mut self, uri: impl Into<String>) -> Self {
+        self.uri = Some(uri.into());
+        self
+    }
+
+    /// Require the URI path `path`, whatever the host and query.
+    #[must_use]
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Require a `name` header with the value `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    #[must_use]
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        let name = HeaderName::try_from(name).expect("invalid header name");
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Require exactly the body bytes `body`.
+    #[must_use]
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(ExpectedBody::Bytes(body.into()));
+        self
+    }
+
+    /// Require a JSON body equal to `body`, whatever its formatting.
+    #[must_use]
+    pub fn json_body(mut self, body: Value) -> Self {
+        self.body = Some(ExpectedBody::Json(body));
+        self
+    }
+}
+
+/// Middleware that records every request it forwards.
+///
+/// Clones share the recording, so keep one to read it back. Streaming
+/// bodies are read into memory before they are sent on; only the first
+/// [`Recorder::body_limit`] bytes are kept.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    body_limit: usize,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    /// Create a recorder that keeps up to 1 MiB of each body.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            requests: Arc::default(),
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+
+    /// Keep at most `limit` bytes of each request body.
+    #[must_use]
+    pub const fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// The requests recorded so far, oldest first.
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().clone()
+    }
+
+    /// Forget every recorded request.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The recorded requests as a pretty-printed JSON array, for comparing
+    /// against a golden file.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let requests: Vec<Value> = self.lock().iter().map(RecordedRequest::to_json).collect();
+        format!("{:#}", Value::Array(requests))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<RecordedRequest>> {
+        self.requests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Middleware for Recorder {
+    type Error = crate::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let recorded = RecordedRequest::capture(request, self.body_limit)
+            .await
+            .map_err(MiddlewareError::Middleware)?;
+        self.lock().push(recorded);
+
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recorder, RequestMatcher};
+    use crate::Client;
+    use futures_executor::block_on;
+    use futures_util::stream;
+    use http_kit::{Body, Endpoint, Method, Request, Response, header, utils::Bytes};
+    use serde_json::json;
+    use std::convert::Infallible;
+
+    /// Sets a session cookie and echoes nothing back.
+    struct Backend;
+
+    impl Endpoint for Backend {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let body = request.body_mut().take().unwrap().into_bytes().await;
+            assert!(body.is_ok(), "the backend still gets the body");
+            let mut response = Response::new(Body::empty());
+            response.headers_mut().insert(
+                header::SET_COOKIE,
+                header::HeaderValue::from_static("session=abc"),
+            );
+            Ok(response)
+        }
+    }
+
+    impl Client for Backend {}
+
+    #[test]
+    fn records_requests_through_auth_and_cookies() {
+        let recorder = Recorder::new();
+        let mut client = Backend
+            .with(recorder.clone())
+            .enable_cookie()
+            .bearer_auth("secret");
+
+        block_on(async {
+            client
+                .post("https://api.example.com/items?draft=1")
+                .unwrap()
+                .json_body(&json!({ "name": "widget", "count": 2 }))
+                .unwrap()
+                .await
+                .unwrap();
+            client
+                .get("https://api.example.com/items")
+                .unwrap()
+                .await
+                .unwrap();
+        });
+
+        let requests = recorder.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests[0].matches(
+                &RequestMatcher::new()
+                    .method(Method::POST)
+                    .uri("https://api.example.com/items?draft=1")
+                    .header("authorization", "Bearer secret")
+                    .json_body(json!({ "count": 2, "name": "widget" }))
+            )
+        );
+        assert!(!requests[0].matches(&RequestMatcher::new().header("cookie", "session=abc")));
+        assert!(
+            requests[1].matches(
+                &RequestMatcher::new()
+                    .method(Method::GET)
+                    .path("/items")
+                    .header("cookie", "session=abc")
+                    .body(Vec::new())
+            )
+        );
+        assert!(!requests[1].matches(&RequestMatcher::new().method(Method::POST)));
+    }
+
+    #[test]
+    fn buffers_streaming_bodies_up_to_the_limit() {
+        let recorder = Recorder::new().body_limit(4);
+        let mut client = Backend.with(recorder.clone());
+        let chunks = ["abc", "def"].map(|chunk| Ok::<_, Infallible>(Bytes::from(chunk)));
+        let mut request = http::Request::put("https://example.com/upload")
+            .body(Body::from_stream(stream::iter(chunks)))
+            .unwrap();
+
+        block_on(client.respond(&mut request)).unwrap();
+
+        let upload = &recorder.requests()[0];
+        assert_eq!(upload.body(), b"abcd");
+        assert!(upload.is_truncated());
+        assert!(!upload.matches(&RequestMatcher::new().body("abcdef")));
+    }
+
+    #[test]
+    fn serializes_with_sorted_headers() {
+        let recorder = Recorder::new();
+        let mut client = Backend.with(recorder.clone());
+        let mut request = http::Request::post("https://example.com/raw")
+            .header("x-b", "2")
+            .header("x-a", "1")
+            .body(Body::from(vec![0xff, 0x00]))
+            .unwrap();
+
+        block_on(client.respond(&mut request)).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&recorder.to_json()).unwrap();
+        assert_eq!(
+            json,
+            json!([{
+                "method": "POST",
+                "uri": "https://example.com/raw",
+                "headers": [["x-a", "1"], ["x-b", "2"]],
+                "body_base64": "/wA=",
+            }])
+        );
+
+        recorder.clear();
+        assert!(recorder.requests().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Request IDs for correlating client and server logs.
+//!
+//! [`RequestId`] gives every outgoing request an identifier in a header,
+//! `X-Request-Id` unless configured otherwise, so a request can be followed
+//! through proxies and services that log it. A request that already carries
+//! the header keeps its value, which propagates an ID received from upstream.
+//! The ID that was sent is recorded in a [`SentRequestId`] response
+//! extension.
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), zenwave::Error> {
+//! use zenwave::{Client, client, request_id::{SentRequestId, X_REQUEST_ID}};
+//!
+//! let mut client = client().with_request_id(X_REQUEST_ID);
+//! if let Ok(response) = client.get("https://example.com/")?.await
+//!     && let Some(id) = response.extensions().get::<SentRequestId>()
+//! {
+//!     println!("sent as {}", id.as_str());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::any::Any;
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{HeaderName, HeaderValue},
+    middleware::MiddlewareError,
+};
+
+/// The `X-Request-Id` header, used by [`RequestId::new`].
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Response extension holding the request ID that was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentRequestId(String);
+
+impl SentRequestId {
+    /// The ID, as it appeared in the request header.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Middleware that sets a request ID header on requests that lack one.
+///
+/// Generated IDs are random (version 4) UUIDs. The ID sent, generated or
+/// not, is attached to the response as a [`SentRequestId`], and to the
+/// response of an HTTP error when the wrapped client reports
+/// [`crate::Error`].
+#[derive(Debug, Clone)]
+pub struct RequestId {
+    header: HeaderName,
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestId {
+    /// Create a middleware using the `X-Request-Id` header.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_header(X_REQUEST_ID)
+    }
+
+    /// Create a middleware using `header`, such as `X-Correlation-Id`.
+    #[must_use]
+    pub const fn with_header(header: HeaderName) -> Self {
+        Self { header }
+    }
+
+    /// The header carrying the ID.
+    #[must_use]
+    pub const fn header(&self) -> &HeaderName {
+        &self.header
+    }
+}
+
+/// A random UUID in its hyphenated form.
+fn uuid_v4() -> Result<String, crate::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|error| crate::Error::Other(Box::new(error)))?;
+    // Version 4, RFC 4122 variant.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        hex[0..4].concat(),
+        hex[4..6].concat(),
+        hex[6..8].concat(),
+        hex[8..10].concat(),
+        hex[10..16].concat()
+    ))
+}
+
+impl Middleware for RequestId {
+    type Error = crate::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let existing = request
+            .headers()
+            .get(&self.header)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+        let id = if let Some(id) = existing {
+            id
+        } else {
+            let id = uuid_v4().map_err(MiddlewareError::Middleware)?;
+            let value = HeaderValue::from_str(&id).expect("UUIDs are valid header values");
+            request.headers_mut().insert(self.header.clone(), value);
+            id
+        };
+
+        match next.respond(request).await {
+            Ok(mut response) => {
+                response.extensions_mut().insert(SentRequestId(id));
+                Ok(response)
+            }
+            Err(mut error) => {
+                if let Some(response) = (&mut error as &mut dyn Any)
+                    .downcast_mut::<crate::Error>()
+                    .and_then(crate::Error::http_response_mut)
+                {
+                    response.response.extensions_mut().insert(SentRequestId(id));
+                }
+                Err(MiddlewareError::Endpoint(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::uuid_v4;
+
+    #[test]
+    fn generates_version_4_uuids() {
+        let id = uuid_v4().unwrap();
+        let groups: Vec<_> = id.split('-').map(str::len).collect();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"), "{id}");
+        assert_ne!(id, uuid_v4().unwrap());
+    }
+}
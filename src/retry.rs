@@ -1,34 +1,200 @@
 //! Middleware for retrying failed HTTP requests.
 
-use core::time::Duration;
 #[cfg(target_arch = "wasm32")]
+use core::future::Future;
 use core::{
-    future::Future,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
+};
+use futures_util::{Stream, TryStreamExt};
+use http_kit::{
+    Body, BodyError, Endpoint, Method, Request, Response, StatusCode, Uri,
+    header::{HeaderMap, RETRY_AFTER},
+    utils::Bytes,
+};
+use httpdate::parse_http_date;
+use std::{
+    any::Any,
+    borrow::Cow,
+    fmt,
+    hash::{BuildHasher, RandomState},
+    io, mem,
+    sync::{Arc, Mutex},
+    time::SystemTime,
 };
-use http_kit::{Endpoint, Request, Response};
 
-use crate::client::Client;
+use crate::{client::Client, error::ErrorKind};
 
 /// Middleware that retries failed requests.
 ///
 /// This middleware automatically retries requests that fail with a transport error
-/// (e.g., connection timeout, DNS error). It does *not* retry requests that receive
-/// a valid HTTP response, even if the status code indicates an error (e.g., 500 or 503).
+/// (e.g., connection timeout, DNS error), and requests answered with one of the
+/// statuses configured by [`Retry::retry_on_status`] (429 and 503 by default),
+/// whether the response arrives as `Ok` or as a [`crate::Error::Http`]. Other
+/// HTTP responses are returned as-is. A `Retry-After` header on a retried
+/// response replaces the exponential backoff for that attempt.
+///
+/// Backoff uses "full jitter": each delay is drawn uniformly from zero up to
+/// `min_delay * 2^n` (capped by `max_delay`), so clients that failed together
+/// do not retry in lockstep. [`Retry::max_elapsed`] bounds the total time spent
+/// retrying, [`Retry::retry_if`] vetoes retries of errors that can never
+/// succeed, and [`Retry::on_retry`] reports each retry for logging or metrics.
 ///
-/// # Warning
+/// Only idempotent methods (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS`,
+/// `TRACE`) are retried by default, since replaying a `POST` that already took
+/// effect could apply it twice. Mark individual requests as safe to replay
+/// with the [`Idempotent`] extension, or lift the restriction entirely with
+/// [`Retry::retry_non_idempotent`].
 ///
-/// This middleware retries requests by calling the inner client's `respond` method multiple times.
-/// If the request body is a stream that is consumed by the inner client (e.g., during a partial upload),
-/// subsequent retries may send an empty or incomplete body. This is safe for requests with empty bodies
-/// (like GET) or buffered bodies that can be replayed.
+/// Buffered request bodies are sent again as they are. Streaming bodies are
+/// sent untouched on the first attempt while a copy of up to
+/// [`Retry::replay_buffer`] bytes is kept; a retry replays that copy, and
+/// fails with [`crate::Error::InvalidRequest`] when the body was larger.
+///
+/// Every response carries an [`Attempts`] extension listing the failed
+/// attempts before it, read with [`crate::ResponseExt::attempts`]. When the
+/// request was sent more than once and still failed with a [`crate::Error`],
+/// that error is returned as [`crate::Error::Retried`] with the same summary.
 #[derive(Debug, Clone)]
 pub struct Retry<C: Client> {
     client: C,
+    policy: RetryPolicy,
+    statuses: Cow<'static, [StatusCode]>,
+    non_idempotent: bool,
+    predicate: Option<RetryPredicate>,
+    replay_limit: usize,
+}
+
+/// How often and how long to retry an operation.
+///
+/// [`Retry`] is configured through its own builder methods, which set the
+/// policy it holds; other retrying operations, such as
+/// `websocket::connect_with_retry`, take a `RetryPolicy` directly. The
+/// defaults match [`Retry`]: delays from 100ms doubling up to 5s with full
+/// jitter, and no time budget.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
     max_retries: usize,
     min_delay: Duration,
     max_delay: Duration,
+    jitter: bool,
+    max_elapsed: Option<Duration>,
+    hook: Option<RetryHook>,
+    // xorshift64 state; zero until first seeded.
+    rng: u64,
+}
+
+/// Request extension marking a request as safe to retry whatever its method.
+///
+/// Set it with `RequestBuilder::idempotent`, typically on a `POST` that
+/// carries an `Idempotency-Key` the server uses to deduplicate replays.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Idempotent;
+
+type PredicateFn = dyn Fn(&crate::Error, usize) -> bool + Send + Sync;
+
+/// Caller-supplied filter set by [`Retry::retry_if`].
+#[derive(Clone)]
+struct RetryPredicate(Arc<PredicateFn>);
+
+impl fmt::Debug for RetryPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetryPredicate(..)")
+    }
+}
+
+type HookFn = dyn Fn(&RetryEvent<'_>) + Send + Sync;
+
+/// Observer set by [`Retry::on_retry`].
+#[derive(Clone)]
+struct RetryHook(Arc<HookFn>);
+
+impl fmt::Debug for RetryHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RetryHook(..)")
+    }
+}
+
+/// A retry decision reported to the hook set by [`Retry::on_retry`].
+#[derive(Debug, Clone, Copy)]
+pub struct RetryEvent<'a> {
+    kind: RetryEventKind,
+    attempt: usize,
+    cause: RetryCause<'a>,
+    delay: Duration,
+    method: &'a Method,
+    uri: &'a Uri,
+}
+
+/// Whether a [`RetryEvent`] announces another attempt or the final failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetryEventKind {
+    /// The request is sent again after [`RetryEvent::delay`].
+    Retrying,
+    /// The failure is returned to the caller: retries are exhausted or the
+    /// next one would exceed [`Retry::max_elapsed`].
+    GivingUp,
+}
+
+/// The outcome that made an attempt eligible for a retry.
+#[derive(Debug, Clone, Copy)]
+pub enum RetryCause<'a> {
+    /// The attempt failed with an error.
+    Error(&'a (dyn core::error::Error + 'static)),
+    /// The server answered with one of the retried statuses.
+    Status(StatusCode),
+}
+
+impl<'a> RetryEvent<'a> {
+    /// Whether the request is retried or given up on.
+    #[must_use]
+    pub const fn kind(&self) -> RetryEventKind {
+        self.kind
+    }
+
+    /// Number of the attempt that failed, starting at 1.
+    #[must_use]
+    pub const fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// The error or status that triggered the retry.
+    #[must_use]
+    pub const fn cause(&self) -> RetryCause<'a> {
+        self.cause
+    }
+
+    /// Delay before the next attempt; zero when giving up.
+    #[must_use]
+    pub const fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Method of the retried request.
+    #[must_use]
+    pub const fn method(&self) -> &'a Method {
+        self.method
+    }
+
+    /// URI of the retried request.
+    #[must_use]
+    pub const fn uri(&self) -> &'a Uri {
+        self.uri
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(delay: Duration) {
+    async_io::Timer::after(delay).await;
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(delay: Duration) {
+    // gloo expects milliseconds as u32; saturate for very long delays.
+    let millis = delay.as_millis().try_into().unwrap_or(u32::MAX);
+    SingleThreaded(gloo_timers::future::TimeoutFuture::new(millis)).await;
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -54,9 +220,201 @@ impl<C: Client> Retry<C> {
     pub const fn new(client: C, max_retries: usize) -> Self {
         Self {
             client,
+            policy: RetryPolicy::new(max_retries),
+            statuses: Cow::Borrowed(&[
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ]),
+            non_idempotent: false,
+            predicate: None,
+            replay_limit: 1024 * 1024,
+        }
+    }
+
+    /// Set the minimum delay between retries.
+    #[must_use]
+    pub const fn min_delay(mut self, delay: Duration) -> Self {
+        self.policy.min_delay = delay;
+        self
+    }
+
+    /// Set the maximum delay between retries.
+    ///
+    /// This also caps delays requested through `Retry-After`.
+    #[must_use]
+    pub const fn max_delay(mut self, delay: Duration) -> Self {
+        self.policy.max_delay = delay;
+        self
+    }
+
+    /// Set the response statuses that are retried.
+    ///
+    /// Defaults to `429 Too Many Requests` and `503 Service Unavailable`.
+    /// Pass an empty slice to only retry transport errors.
+    #[must_use]
+    pub fn retry_on_status(mut self, statuses: &[StatusCode]) -> Self {
+        self.statuses = Cow::Owned(statuses.to_vec());
+        self
+    }
+
+    /// Randomize backoff delays with full jitter. Enabled by default.
+    ///
+    /// When disabled, the delay is exactly `min_delay * 2^n`, capped by `max_delay`.
+    #[must_use]
+    pub const fn jitter(mut self, enabled: bool) -> Self {
+        self.policy.jitter = enabled;
+        self
+    }
+
+    /// Stop retrying once `budget` has been spent since the first attempt.
+    ///
+    /// A retry is skipped when its delay would end past the budget, and the
+    /// last outcome is returned even if attempts remain. Unlimited by default.
+    #[must_use]
+    pub const fn max_elapsed(mut self, budget: Duration) -> Self {
+        self.policy.max_elapsed = Some(budget);
+        self
+    }
+
+    /// Keep up to `limit` bytes of a streaming request body for replay.
+    /// Defaults to 1 MiB.
+    ///
+    /// The copy is taken while the first attempt streams the body, so an
+    /// upload that succeeds is never buffered beyond `limit`. When a larger
+    /// body needs a retry, the request fails with
+    /// [`crate::Error::InvalidRequest`] instead, or with the last attempt's
+    /// error if the wrapped client does not report [`crate::Error`].
+    #[must_use]
+    pub const fn replay_buffer(mut self, limit: usize) -> Self {
+        self.replay_limit = limit;
+        self
+    }
+
+    /// Retry requests whose method is not idempotent, such as `POST` and
+    /// `PATCH`. Disabled by default.
+    ///
+    /// Prefer marking individual requests with [`Idempotent`] when only some
+    /// of them are safe to replay.
+    #[must_use]
+    pub const fn retry_non_idempotent(mut self, enabled: bool) -> Self {
+        self.non_idempotent = enabled;
+        self
+    }
+
+    /// Whether `request` may be sent more than once.
+    fn replayable(&self, request: &Request) -> bool {
+        self.non_idempotent
+            || request.method().is_idempotent()
+            || request.extensions().get::<Idempotent>().is_some()
+    }
+
+    /// Only retry errors for which `predicate` returns `true`.
+    ///
+    /// The predicate is consulted before each retry of a [`crate::Error`] with
+    /// the error and the zero-based index of the attempt that produced it.
+    /// Responses returned as `Ok` and errors of other types are not passed to
+    /// it. By default every retryable error is retried.
+    ///
+    /// ```rust,no_run
+    /// # use zenwave::{Client, Error, client};
+    /// // Certificate problems will not fix themselves.
+    /// let client = client()
+    ///     .retry(3)
+    ///     .retry_if(|error, _attempt| !matches!(error, Error::Tls(_)));
+    /// ```
+    #[must_use]
+    pub fn retry_if(
+        mut self,
+        predicate: impl Fn(&crate::Error, usize) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicate = Some(RetryPredicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Call `hook` whenever an attempt fails with a retryable outcome.
+    ///
+    /// The hook runs before the backoff sleep with a [`RetryEvent`] of kind
+    /// [`RetryEventKind::Retrying`], or of kind [`RetryEventKind::GivingUp`]
+    /// when that failure is returned to the caller instead. Outcomes that are
+    /// never retried, such as a 404 or a veto from [`Retry::retry_if`], are
+    /// not reported.
+    ///
+    /// ```rust,no_run
+    /// # use zenwave::{Client, client, retry::RetryEventKind};
+    /// let client = client().retry(3).on_retry(|event| {
+    ///     if event.kind() == RetryEventKind::GivingUp {
+    ///         eprintln!("{} {} failed after {} attempts", event.method(), event.uri(), event.attempt());
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_retry(mut self, hook: impl Fn(&RetryEvent<'_>) + Send + Sync + 'static) -> Self {
+        self.policy.hook = Some(RetryHook(Arc::new(hook)));
+        self
+    }
+
+    fn notify(
+        &self,
+        kind: RetryEventKind,
+        attempt: usize,
+        outcome: &Result<Response, C::Error>,
+        delay: Duration,
+        head: &RequestHead,
+    ) {
+        let cause = match outcome {
+            Ok(response) => RetryCause::Status(response.status()),
+            Err(error) => RetryCause::Error(error),
+        };
+        self.policy
+            .notify(kind, attempt, cause, delay, &head.method, &head.uri);
+    }
+
+    /// Decide whether `outcome`, produced by attempt `attempt`, is retried.
+    fn verdict(&self, outcome: &Result<Response, C::Error>, attempt: usize) -> Verdict {
+        if let Err(error) = outcome
+            && let Some(error) = (error as &dyn Any).downcast_ref::<crate::Error>()
+            && let Some(RetryPredicate(predicate)) = &self.predicate
+            && !predicate(error, attempt)
+        {
+            return Verdict::Done;
+        }
+
+        let (status, headers) = match outcome {
+            Ok(response) => (response.status(), response.headers()),
+            Err(error) => match (error as &dyn Any).downcast_ref::<crate::Error>() {
+                Some(crate::Error::Http {
+                    status, response, ..
+                }) => (*status, response.response.headers()),
+                // Transport and other failures are always retried.
+                _ => return Verdict::Retry(None),
+            },
+        };
+        if self.statuses.contains(&status) {
+            Verdict::Retry(retry_after(headers))
+        } else {
+            Verdict::Done
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Retry up to `max_retries` times after the first attempt.
+    #[must_use]
+    pub const fn new(max_retries: usize) -> Self {
+        Self {
             max_retries,
             min_delay: Duration::from_millis(100),
             max_delay: Duration::from_secs(5),
+            jitter: true,
+            max_elapsed: None,
+            hook: None,
+            rng: 0,
         }
     }
 
@@ -73,6 +431,360 @@ impl<C: Client> Retry<C> {
         self.max_delay = delay;
         self
     }
+
+    /// Randomize backoff delays with full jitter. Enabled by default.
+    #[must_use]
+    pub const fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Stop retrying once `budget` has been spent since the first attempt.
+    #[must_use]
+    pub const fn max_elapsed(mut self, budget: Duration) -> Self {
+        self.max_elapsed = Some(budget);
+        self
+    }
+
+    /// Call `hook` whenever an attempt fails with a retryable error; see
+    /// [`Retry::on_retry`].
+    #[must_use]
+    pub fn on_retry(mut self, hook: impl Fn(&RetryEvent<'_>) + Send + Sync + 'static) -> Self {
+        self.hook = Some(RetryHook(Arc::new(hook)));
+        self
+    }
+
+    pub(crate) const fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// Whether sleeping `delay` now would end past the time budget.
+    pub(crate) fn exceeds_budget(&self, started: &Stopwatch, delay: Duration) -> bool {
+        self.max_elapsed
+            .is_some_and(|budget| started.elapsed() + delay > budget)
+    }
+
+    pub(crate) fn notify(
+        &self,
+        kind: RetryEventKind,
+        attempt: usize,
+        cause: RetryCause<'_>,
+        delay: Duration,
+        method: &Method,
+        uri: &Uri,
+    ) {
+        if let Some(RetryHook(hook)) = &self.hook {
+            hook(&RetryEvent {
+                kind,
+                attempt,
+                cause,
+                delay,
+                method,
+                uri,
+            });
+        }
+    }
+
+    /// Backoff before retry number `attempt` (starting at 1).
+    pub(crate) fn backoff(&mut self, attempt: u32) -> Duration {
+        let cap = self
+            .min_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        if !self.jitter {
+            return cap;
+        }
+        let cap_nanos = u64::try_from(cap.as_nanos()).unwrap_or(u64::MAX);
+        Duration::from_nanos(self.next_random() % cap_nanos.saturating_add(1))
+    }
+
+    fn next_random(&mut self) -> u64 {
+        if self.rng == 0 {
+            // `RandomState` is randomly keyed per process; `| 1` keeps the
+            // state non-zero, which xorshift requires.
+            self.rng = RandomState::new().hash_one(()) | 1;
+        }
+        xorshift64(&mut self.rng)
+    }
+}
+
+/// Advance the xorshift64 generator `state`, which must not be zero, and
+/// return the new value.
+pub(crate) const fn xorshift64(state: &mut u64) -> u64 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *state = x;
+    x
+}
+
+/// Monotonic clock for [`Retry::max_elapsed`] and the other timing
+/// middleware; `Instant` is unavailable on wasm.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub(crate) struct Stopwatch(std::time::Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub(crate) struct Stopwatch(f64);
+
+#[cfg(target_arch = "wasm32")]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(js_sys::Date::now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(((js_sys::Date::now() - self.0) / 1000.0).max(0.0))
+    }
+}
+
+/// How the attempts [`Retry`] made for one request went.
+///
+/// Attached to responses as an extension, see
+/// [`crate::ResponseExt::attempts`], and to errors, see
+/// [`crate::Error::attempts`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attempts {
+    total: usize,
+    errors: Vec<(ErrorKind, Duration)>,
+}
+
+impl Attempts {
+    /// Number of times the request was sent.
+    #[must_use]
+    pub const fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The failed attempts in order, with the kind of failure and how long
+    /// the wrapped client took to produce it.
+    ///
+    /// A response with a retried status counts as an [`ErrorKind::Http`]
+    /// failure. Errors of clients that do not report [`crate::Error`] are
+    /// [`ErrorKind::Other`].
+    #[must_use]
+    pub fn errors(&self) -> &[(ErrorKind, Duration)] {
+        &self.errors
+    }
+
+    /// The kind of the most recent failure.
+    #[must_use]
+    pub fn last_error(&self) -> Option<ErrorKind> {
+        self.errors.last().map(|(kind, _)| *kind)
+    }
+
+    fn record<E: 'static>(&mut self, outcome: &Result<Response, E>, latency: Duration) {
+        let kind = match outcome {
+            Ok(_) => ErrorKind::Http,
+            Err(error) => (error as &dyn Any)
+                .downcast_ref::<crate::Error>()
+                .map_or(ErrorKind::Other, crate::Error::kind),
+        };
+        self.errors.push((kind, latency));
+    }
+
+    /// Attach the summary to the final outcome.
+    fn attach<E: 'static>(self, outcome: Result<Response, E>) -> Result<Response, E> {
+        let error = match outcome {
+            Ok(mut response) => {
+                response.extensions_mut().insert(self);
+                return Ok(response);
+            }
+            Err(error) if self.total < 2 => return Err(error),
+            Err(error) => error,
+        };
+        let error: Box<dyn Any> = Box::new(error);
+        let error: Box<dyn Any> = match error.downcast::<crate::Error>() {
+            Ok(last) => Box::new(crate::Error::Retried {
+                last,
+                attempts: self,
+            }),
+            Err(error) => error,
+        };
+        Err(*error
+            .downcast::<E>()
+            .expect("the error keeps the client's error type"))
+    }
+}
+
+/// Whether an attempt's outcome is final or retried after an optional
+/// server-requested delay.
+enum Verdict {
+    Done,
+    Retry(Option<Duration>),
+}
+
+/// What a backend takes from a request besides its body, which it replaces
+/// with an empty `GET /` while sending it.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestHead {
+    method: Method,
+    uri: Uri,
+    version: http::Version,
+    headers: HeaderMap,
+    extensions: http::Extensions,
+}
+
+impl RequestHead {
+    /// Copy the head of `request` before it is sent.
+    pub(crate) fn of(request: &Request) -> Self {
+        Self {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            version: request.version(),
+            headers: request.headers().clone(),
+            extensions: request.extensions().clone(),
+        }
+    }
+
+    /// Put the copied head back on `request`, keeping its body.
+    pub(crate) fn restore(&self, request: &mut Request) {
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.uri.clone();
+        *request.version_mut() = self.version;
+        *request.headers_mut() = self.headers.clone();
+        *request.extensions_mut() = self.extensions.clone();
+    }
+}
+
+/// Restores the request body before each retry.
+enum Replay {
+    /// An in-memory body, cloned for every attempt.
+    Buffered(Body),
+    /// A streaming body, copied while the first attempt sends it.
+    Streaming(Arc<Mutex<Tee>>),
+}
+
+#[derive(Debug)]
+struct Tee {
+    copy: Vec<u8>,
+    limit: usize,
+    started: bool,
+    complete: bool,
+    overflowed: bool,
+}
+
+impl Replay {
+    fn prepare(request: &mut Request, limit: usize) -> Self {
+        if let Some(body) = request.body().try_clone() {
+            return Self::Buffered(body);
+        }
+
+        let tee = Arc::new(Mutex::new(Tee {
+            copy: Vec::new(),
+            limit,
+            started: false,
+            complete: false,
+            overflowed: false,
+        }));
+        let body = mem::replace(request.body_mut(), Body::empty());
+        let len = body.len();
+        let stream = TeeBody {
+            body,
+            tee: Arc::clone(&tee),
+        };
+        // Keep a known length so the upload is framed as before.
+        *request.body_mut() = match len {
+            Some(len) => Body::from_reader(stream.map_err(io::Error::other).into_async_read(), len),
+            None => Body::from_stream(stream),
+        };
+        Self::Streaming(tee)
+    }
+
+    /// Put a fresh copy of the body on `request`, or return `false` when the
+    /// body cannot be sent again.
+    fn rewind(&mut self, request: &mut Request) -> bool {
+        let replayed = match self {
+            Self::Buffered(body) => body.try_clone(),
+            Self::Streaming(tee) => {
+                let mut tee = tee.lock().expect("tee lock poisoned");
+                if tee.complete && !tee.overflowed {
+                    Some(Body::from(mem::take(&mut tee.copy)))
+                } else {
+                    // The attempt failed before reading any of the body, which
+                    // is then still on the request.
+                    let body = request.body();
+                    return !tee.started && !body.is_frozen() && body.is_empty() != Some(true);
+                }
+            }
+        };
+        let Some(body) = replayed else {
+            return false;
+        };
+        if let Self::Streaming(_) = self {
+            *self = Self::Buffered(body.try_clone().expect("in-memory bodies clone"));
+        }
+        *request.body_mut() = body;
+        true
+    }
+}
+
+/// Streaming request body that copies what it yields into a [`Tee`].
+struct TeeBody {
+    body: Body,
+    tee: Arc<Mutex<Tee>>,
+}
+
+impl Stream for TeeBody {
+    type Item = Result<Bytes, BodyError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = Pin::new(&mut this.body).poll_next(cx);
+        if let Poll::Ready(item) = &item {
+            let mut tee = this.tee.lock().expect("tee lock poisoned");
+            tee.started = true;
+            match item {
+                Some(Ok(chunk)) if !tee.overflowed => {
+                    if tee.copy.len() + chunk.len() <= tee.limit {
+                        tee.copy.extend_from_slice(chunk);
+                    } else {
+                        tee.overflowed = true;
+                        tee.copy = Vec::new();
+                    }
+                }
+                None => tee.complete = true,
+                _ => {}
+            }
+        }
+        item
+    }
+}
+
+/// The error for a retry whose body could not be replayed, when the wrapped
+/// client reports [`crate::Error`]; otherwise the failed attempt's outcome.
+fn not_replayable<E: 'static>(outcome: Result<Response, E>) -> Result<Response, E> {
+    let error: Box<dyn Any> = Box::new(crate::Error::InvalidRequest(
+        "body not replayable".to_string(),
+    ));
+    error.downcast::<E>().map_or(outcome, |error| Err(*error))
+}
+
+/// Parse `Retry-After` as delta-seconds or an HTTP-date (RFC 9110 §10.2.3).
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value).ok()?;
+    // A date in the past means the server is ready now.
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
 }
 
 impl<C: Client> Client for Retry<C> {}
@@ -80,32 +792,289 @@ impl<C: Client> Client for Retry<C> {}
 impl<C: Client> Endpoint for Retry<C> {
     type Error = C::Error;
 
-    #[allow(clippy::cast_possible_truncation)]
     async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        if !self.replayable(request) {
+            return self.client.respond(request).await;
+        }
+
+        let started = Stopwatch::start();
+        let head = RequestHead::of(request);
+        let mut replay = Replay::prepare(request, self.replay_limit);
         let mut attempts = 0;
+        let mut summary = Attempts::default();
         loop {
-            match self.client.respond(request).await {
-                Ok(response) => return Ok(response),
-                Err(err) => {
-                    attempts += 1;
-                    if attempts > self.max_retries {
-                        return Err(err);
-                    }
+            let attempt = Stopwatch::start();
+            let outcome = self.client.respond(request).await;
+            summary.total += 1;
+            let verdict = self.verdict(&outcome, attempts);
+            if outcome.is_err() || matches!(verdict, Verdict::Retry(_)) {
+                summary.record(&outcome, attempt.elapsed());
+            }
+            let Verdict::Retry(retry_after) = verdict else {
+                return summary.attach(outcome);
+            };
 
-                    // Simple exponential backoff
-                    let delay =
-                        (self.min_delay * 2u32.pow((attempts - 1) as u32)).min(self.max_delay);
+            attempts += 1;
+            if attempts > self.policy.max_retries() {
+                self.notify(
+                    RetryEventKind::GivingUp,
+                    attempts,
+                    &outcome,
+                    Duration::ZERO,
+                    &head,
+                );
+                return summary.attach(outcome);
+            }
 
-                    #[cfg(not(target_arch = "wasm32"))]
-                    async_io::Timer::after(delay).await;
+            // Honor the server's requested delay, otherwise back off exponentially.
+            let delay = match retry_after {
+                Some(delay) => delay.min(self.policy.max_delay),
+                None => self
+                    .policy
+                    .backoff(u32::try_from(attempts).unwrap_or(u32::MAX)),
+            };
 
-                    #[cfg(target_arch = "wasm32")]
-                    SingleThreaded(gloo_timers::future::TimeoutFuture::new(
-                        delay.as_millis() as u32
-                    ))
-                    .await;
-                }
+            if self.policy.exceeds_budget(&started, delay) {
+                self.notify(
+                    RetryEventKind::GivingUp,
+                    attempts,
+                    &outcome,
+                    Duration::ZERO,
+                    &head,
+                );
+                return summary.attach(outcome);
             }
+
+            if !replay.rewind(request) {
+                self.notify(
+                    RetryEventKind::GivingUp,
+                    attempts,
+                    &outcome,
+                    Duration::ZERO,
+                    &head,
+                );
+                return summary.attach(not_replayable(outcome));
+            }
+            head.restore(request);
+            self.notify(RetryEventKind::Retrying, attempts, &outcome, delay, &head);
+            sleep(delay).await;
         }
     }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{Retry, retry_after};
+    use crate::Client;
+    use core::time::Duration;
+    use http_kit::{
+        Body, Endpoint, Request, Response, StatusCode,
+        header::{HeaderMap, HeaderValue, RETRY_AFTER},
+    };
+    use std::time::SystemTime;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn parses_delta_seconds_and_http_dates() {
+        assert_eq!(retry_after(&headers("3")), Some(Duration::from_secs(3)));
+
+        let future = SystemTime::now() + Duration::from_secs(30);
+        let delay = retry_after(&headers(&httpdate::fmt_http_date(future))).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+
+        let past = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert_eq!(retry_after(&headers(past)), Some(Duration::ZERO));
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    /// Fails with a `crate::Error::Http` 503 until `failures` runs out.
+    struct Unavailable {
+        failures: usize,
+        attempts: usize,
+    }
+
+    impl Endpoint for Unavailable {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            use crate::ResponseExt;
+
+            self.attempts += 1;
+            let mut response = Response::new(Body::empty());
+            if self.attempts <= self.failures {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from_static("0"));
+                response.error_for_status_ref()?;
+            }
+            Ok(response)
+        }
+    }
+
+    impl Client for Unavailable {}
+
+    #[test]
+    fn retries_http_errors_with_listed_status() {
+        let mut retry = Retry::new(
+            Unavailable {
+                failures: 2,
+                attempts: 0,
+            },
+            3,
+        );
+        let mut request = http::Request::new(Body::empty());
+        let response = futures_executor::block_on(retry.respond(&mut request)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(retry.client.attempts, 3);
+
+        let mut retry = Retry::new(
+            Unavailable {
+                failures: 1,
+                attempts: 0,
+            },
+            3,
+        )
+        .retry_on_status(&[]);
+        let error = futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        assert_eq!(retry.client.attempts, 1);
+        assert!(error.is_server_error());
+    }
+
+    #[test]
+    fn exhausted_retries_embed_the_attempts() {
+        let mut retry = Retry::new(
+            Unavailable {
+                failures: 5,
+                attempts: 0,
+            },
+            2,
+        );
+        let mut request = http::Request::new(Body::empty());
+        let error = futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        let crate::Error::Retried { last, attempts } = &error else {
+            panic!("expected the attempts to be embedded, got {error:?}");
+        };
+        assert!(matches!(**last, crate::Error::Http { .. }));
+        assert_eq!(attempts.total(), 3);
+        assert_eq!(attempts.last_error(), Some(crate::error::ErrorKind::Http));
+        assert_eq!(error.attempts(), Some(attempts));
+        assert!(error.is_server_error());
+        assert!(error.to_string().ends_with("(after 3 attempts)"), "{error}");
+        // Middleware that searches the source chain finds the response.
+        assert_eq!(
+            crate::error::captured_response(&error).map(Response::status),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+
+        // A single attempt keeps the error as it is.
+        let mut retry = Retry::new(
+            Unavailable {
+                failures: 1,
+                attempts: 0,
+            },
+            3,
+        )
+        .retry_on_status(&[]);
+        let error = futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        assert!(matches!(error, crate::Error::Http { .. }));
+        assert!(error.attempts().is_none());
+    }
+
+    /// Always fails with a transport error.
+    struct Unreachable {
+        attempts: usize,
+    }
+
+    impl Endpoint for Unreachable {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            self.attempts += 1;
+            Err(crate::Error::Timeout { partial: None })
+        }
+    }
+
+    impl Client for Unreachable {}
+
+    /// Always fails certificate verification.
+    struct BadCertificate {
+        attempts: usize,
+    }
+
+    impl Endpoint for BadCertificate {
+        type Error = crate::Error;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            self.attempts += 1;
+            Err(crate::Error::Tls("certificate has expired".into()))
+        }
+    }
+
+    impl Client for BadCertificate {}
+
+    #[test]
+    fn retry_if_vetoes_unrecoverable_errors() {
+        let mut request = http::Request::new(Body::empty());
+
+        let mut retry = Retry::new(BadCertificate { attempts: 0 }, 3)
+            .min_delay(Duration::ZERO)
+            .retry_if(|error, _| !matches!(error, crate::Error::Tls(_)));
+        let error = futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        assert!(matches!(error, crate::Error::Tls(_)));
+        assert_eq!(retry.client.attempts, 1);
+
+        // The attempt index lets callers enforce their own budget.
+        let mut retry = Retry::new(Unreachable { attempts: 0 }, 10)
+            .min_delay(Duration::ZERO)
+            .retry_if(|_, attempt| attempt < 2);
+        futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        assert_eq!(retry.client.attempts, 3);
+    }
+
+    #[test]
+    fn jittered_backoff_stays_within_cap() {
+        let mut retry = Retry::new(Unreachable { attempts: 0 }, 10)
+            .min_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1));
+        retry.policy.rng = 0x9e37_79b9_7f4a_7c15;
+
+        let mut delays = Vec::new();
+        for attempt in 1..=8 {
+            let cap =
+                (Duration::from_millis(100) * 2u32.pow(attempt - 1)).min(Duration::from_secs(1));
+            let delay = retry.policy.backoff(attempt);
+            assert!(delay <= cap, "attempt {attempt}: {delay:?} > {cap:?}");
+            delays.push(delay);
+        }
+        // Jitter must actually vary the delays.
+        delays.dedup();
+        assert!(delays.len() > 1);
+
+        let mut fixed = Retry::new(Unreachable { attempts: 0 }, 10).jitter(false);
+        assert_eq!(fixed.policy.backoff(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn max_elapsed_stops_long_retry_sequences() {
+        let mut retry = Retry::new(Unreachable { attempts: 0 }, 1000)
+            .min_delay(Duration::from_millis(10))
+            .max_delay(Duration::from_millis(10))
+            .jitter(false)
+            .max_elapsed(Duration::from_millis(60));
+        let mut request = http::Request::new(Body::empty());
+
+        let started = std::time::Instant::now();
+        let error = futures_executor::block_on(retry.respond(&mut request)).unwrap_err();
+        assert!(error.is_timeout());
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(
+            (2..=7).contains(&retry.client.attempts),
+            "{}",
+            retry.client.attempts
+        );
+    }
+}
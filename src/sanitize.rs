@@ -0,0 +1,128 @@
+//! Validation of header values sent by servers.
+//!
+//! `http`'s `HeaderValue` already refuses CR, LF and NUL, but it accepts
+//! `obs-text` bytes (0x80-0xFF) and arbitrarily long values, which break
+//! consumers that expect text: the cookie store, the cache, or code that
+//! logs headers. The cookie store always applies these checks to
+//! `Set-Cookie`; [`StrictResponseHeaders`] applies them to every response
+//! header.
+
+use std::convert::Infallible;
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::MiddlewareError,
+};
+use tracing::warn;
+
+/// Longest `Set-Cookie` value kept, the minimum browsers must support
+/// (RFC 6265 §6.1).
+pub(crate) const MAX_SET_COOKIE_LEN: usize = 4096;
+
+/// Whether `value` holds only visible ASCII, spaces and tabs.
+pub(crate) fn is_clean(value: &[u8]) -> bool {
+    value
+        .iter()
+        .all(|&byte| byte == b'\t' || (0x20..0x7f).contains(&byte))
+}
+
+/// Why a header value was rejected, or `None` when it is acceptable.
+fn rejection(name: &HeaderName, value: &HeaderValue) -> Option<&'static str> {
+    if !is_clean(value.as_bytes()) {
+        Some("control or non-ASCII bytes")
+    } else if *name == header::SET_COOKIE && value.len() > MAX_SET_COOKIE_LEN {
+        Some("oversized cookie")
+    } else {
+        None
+    }
+}
+
+/// Middleware that drops response header values that are not plain text.
+///
+/// Values containing control characters or non-ASCII bytes are removed, as
+/// are `Set-Cookie` values longer than 4096 bytes. Every dropped value is
+/// logged with its header name and length, never its content. The response
+/// itself is still returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrictResponseHeaders;
+
+impl Middleware for StrictResponseHeaders {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let mut response = next
+            .respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)?;
+        strip(response.headers_mut());
+        Ok(response)
+    }
+}
+
+fn strip(headers: &mut HeaderMap) {
+    if headers
+        .iter()
+        .all(|(name, value)| rejection(name, value).is_none())
+    {
+        return;
+    }
+
+    let mut current: Option<HeaderName> = None;
+    for (name, value) in std::mem::take(headers) {
+        // `into_iter` only yields the name for the first value of each header.
+        if let Some(name) = name {
+            current = Some(name);
+        }
+        let name = current.clone().expect("first entry carries a header name");
+        if let Some(reason) = rejection(&name, &value) {
+            warn!(header = %name, len = value.len(), reason, "dropping response header");
+            continue;
+        }
+        headers.append(name, value);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::StrictResponseHeaders;
+    use crate::Client;
+    use http_kit::{Body, Endpoint, Request, Response, header};
+    use std::convert::Infallible;
+
+    struct DirtyHeaders;
+
+    impl Endpoint for DirtyHeaders {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            Ok(http::Response::builder()
+                .header("x-name", &b"caf\xe9"[..])
+                .header(header::SET_COOKIE, "ok=1")
+                .header(header::SET_COOKIE, "big=".to_string() + &"a".repeat(5000))
+                .header(header::SET_COOKIE, "tab=a\tb")
+                .body(Body::empty())
+                .unwrap())
+        }
+    }
+
+    impl Client for DirtyHeaders {}
+
+    #[test]
+    fn drops_dirty_values_and_keeps_the_rest() {
+        let mut client = DirtyHeaders.with(StrictResponseHeaders);
+        let mut request = http::Request::new(Body::empty());
+        let response = async_io::block_on(client.respond(&mut request)).unwrap();
+
+        assert!(!response.headers().contains_key("x-name"));
+        let cookies: Vec<_> = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(cookies, ["ok=1", "tab=a\tb"]);
+    }
+}
@@ -0,0 +1,663 @@
+//! AWS Signature Version 4 request signing.
+//!
+//! [`SigV4`] signs each request with an access key the way AWS services
+//! expect (<https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html>):
+//! it sets `x-amz-date` and an `Authorization` header covering the method,
+//! path, query, headers and a hash of the body.
+//!
+//! A signature is only valid for the URL it was computed for, so place the
+//! middleware *inside* [`FollowRedirect`](crate::redirect::FollowRedirect):
+//! every hop then reaches it and is signed again for its new target.
+//!
+//! ```rust,no_run
+//! # async fn example(backend: impl zenwave::Client) -> Result<(), zenwave::Error> {
+//! use zenwave::{Client, sigv4::SigV4};
+//!
+//! let signer = SigV4::new("AKIDEXAMPLE", "secret", "us-east-1", "execute-api");
+//! let mut client = backend.with(signer).follow_redirect();
+//! let _ = client.get("https://example.execute-api.us-east-1.amazonaws.com/")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::fmt::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::MiddlewareError,
+};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, percent_encode};
+use sha2::{Digest, Sha256};
+
+/// The `x-amz-date` header, the time of signing.
+pub const X_AMZ_DATE: HeaderName = HeaderName::from_static("x-amz-date");
+
+/// The `x-amz-content-sha256` header, the payload hash sent along.
+pub const X_AMZ_CONTENT_SHA256: HeaderName = HeaderName::from_static("x-amz-content-sha256");
+
+/// The `x-amz-security-token` header, carrying a session token.
+pub const X_AMZ_SECURITY_TOKEN: HeaderName = HeaderName::from_static("x-amz-security-token");
+
+/// Payload hash declaring that the body is not covered by the signature.
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Characters other than the unreserved ones of RFC 3986 are encoded.
+const ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Headers left out of the signature because proxies and backends may add,
+/// change or drop them on the way.
+const UNSIGNED_HEADERS: [HeaderName; 6] = [
+    header::AUTHORIZATION,
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::EXPECT,
+    header::TRANSFER_ENCODING,
+    header::USER_AGENT,
+];
+
+/// Middleware signing requests with AWS Signature Version 4.
+///
+/// In-memory bodies are hashed into the signature. Streaming bodies cannot
+/// be read ahead of sending, so they are signed as `UNSIGNED-PAYLOAD`, which
+/// S3 accepts; call [`unsigned_payload`](Self::unsigned_payload) to skip
+/// hashing for every body. A request that already carries
+/// `x-amz-content-sha256` is signed with that hash instead.
+#[derive(Clone)]
+pub struct SigV4 {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+    service: String,
+    unsigned_payload: bool,
+    content_sha256_header: bool,
+}
+
+impl fmt::Debug for SigV4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigV4")
+            .field("access_key", &self.access_key)
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .field("unsigned_payload", &self.unsigned_payload)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigV4 {
+    /// Sign for `service` in `region` with the given access key.
+    ///
+    /// For the `s3` service, the payload hash is also sent as
+    /// `x-amz-content-sha256` and paths are encoded once, as S3 requires.
+    pub fn new(
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+        region: impl Into<String>,
+        service: impl Into<String>,
+    ) -> Self {
+        let service = service.into();
+        Self {
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+            region: region.into(),
+            content_sha256_header: service == "s3",
+            service,
+            unsigned_payload: false,
+        }
+    }
+
+    /// Send `token`, from temporary credentials, as `x-amz-security-token`.
+    #[must_use]
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Sign every body as `UNSIGNED-PAYLOAD` instead of hashing it.
+    #[must_use]
+    pub const fn unsigned_payload(mut self, enabled: bool) -> Self {
+        self.unsigned_payload = enabled;
+        self
+    }
+
+    /// Send the payload hash as `x-amz-content-sha256`. Enabled by default
+    /// for S3; unsigned payloads always send it.
+    #[must_use]
+    pub const fn content_sha256_header(mut self, enabled: bool) -> Self {
+        self.content_sha256_header = enabled;
+        self
+    }
+
+    /// The hex SHA-256 of an in-memory body, or `UNSIGNED-PAYLOAD`.
+    async fn payload_hash(&self, request: &Request) -> Result<String, crate::Error> {
+        if let Some(hash) = request
+            .headers()
+            .get(X_AMZ_CONTENT_SHA256)
+            .and_then(|value| value.to_str().ok())
+        {
+            return Ok(hash.to_owned());
+        }
+        if self.unsigned_payload {
+            return Ok(UNSIGNED_PAYLOAD.to_owned());
+        }
+        match request.body().try_clone() {
+            Some(body) => {
+                let bytes = body
+                    .into_bytes()
+                    .await
+                    .map_err(|error| crate::Error::Other(Box::new(error)))?;
+                Ok(hex(&Sha256::digest(&bytes)))
+            }
+            None => Ok(UNSIGNED_PAYLOAD.to_owned()),
+        }
+    }
+
+    /// Set the signing headers and `Authorization` on `request` for the
+    /// time `now`.
+    fn sign(
+        &self,
+        request: &mut Request,
+        payload_hash: &str,
+        now: SystemTime,
+    ) -> Result<(), String> {
+        let (date, time) = utc(now);
+        let amz_date = format!("{date}T{time}Z");
+        let headers = request.headers_mut();
+        headers.remove(header::AUTHORIZATION);
+        headers.insert(X_AMZ_DATE, header_value(&amz_date)?);
+        if let Some(token) = &self.session_token {
+            headers.insert(X_AMZ_SECURITY_TOKEN, header_value(token)?);
+        }
+        if self.content_sha256_header || payload_hash == UNSIGNED_PAYLOAD {
+            headers.insert(X_AMZ_CONTENT_SHA256, header_value(payload_hash)?);
+        }
+
+        let (signed_headers, canonical_request) = self.canonical_request(request, payload_hash)?;
+        let scope = format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "{ALGORITHM}\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date.as_str(), &self.region, &self.service, "aws4_request"]
+            .into_iter()
+            .fold(
+                format!("AWS4{}", self.secret_key).into_bytes(),
+                |key, part| hmac_sha256(&key, part.as_bytes()).to_vec(),
+            );
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        );
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, header_value(&authorization)?);
+        Ok(())
+    }
+
+    /// The signed header list and the canonical request.
+    fn canonical_request(
+        &self,
+        request: &Request,
+        payload_hash: &str,
+    ) -> Result<(String, String), String> {
+        let uri = request.uri();
+        let host = if let Some(host) = request.headers().get(header::HOST) {
+            host.to_str()
+                .map_err(|_| "Host header is not valid text".to_owned())?
+                .to_owned()
+        } else {
+            let authority = uri.authority().ok_or("request URI has no host")?;
+            authority.port().map_or_else(
+                || authority.host().to_owned(),
+                |port| format!("{}:{port}", authority.host()),
+            )
+        };
+
+        let mut canonical = String::new();
+        canonical.push_str(request.method().as_str());
+        canonical.push('\n');
+        canonical.push_str(&self.canonical_path(uri.path()));
+        canonical.push('\n');
+        canonical.push_str(&canonical_query(uri.query().unwrap_or("")));
+        canonical.push('\n');
+
+        let headers = canonical_headers(request.headers(), &host)?;
+        let mut signed_headers = String::new();
+        for (name, value) in &headers {
+            let _ = writeln!(canonical, "{name}:{value}");
+            if !signed_headers.is_empty() {
+                signed_headers.push(';');
+            }
+            signed_headers.push_str(name);
+        }
+        canonical.push('\n');
+        canonical.push_str(&signed_headers);
+        canonical.push('\n');
+        canonical.push_str(payload_hash);
+        Ok((signed_headers, canonical))
+    }
+
+    /// Encode each path segment, which other services than S3 expect
+    /// encoded a second time after `.` and `..` segments are resolved.
+    fn canonical_path(&self, path: &str) -> String {
+        let s3 = self.service == "s3";
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in path.split('/').skip(1) {
+            match segment {
+                "" | "." if !s3 => {}
+                ".." if !s3 => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        // A trailing slash survives normalization.
+        if !s3 && path.len() > 1 && path.ends_with('/') && !segments.is_empty() {
+            segments.push("");
+        }
+
+        let mut canonical = String::new();
+        for segment in segments {
+            canonical.push('/');
+            let bytes: Vec<u8> = percent_decode_str(segment).collect();
+            let once = percent_encode(&bytes, ENCODE).to_string();
+            if s3 {
+                canonical.push_str(&once);
+            } else {
+                canonical.extend(percent_encode(once.as_bytes(), ENCODE));
+            }
+        }
+        if canonical.is_empty() {
+            canonical.push('/');
+        }
+        canonical
+    }
+}
+
+impl Middleware for SigV4 {
+    type Error = crate::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let payload_hash = self
+            .payload_hash(request)
+            .await
+            .map_err(MiddlewareError::Middleware)?;
+        self.sign(request, &payload_hash, SystemTime::now())
+            .map_err(|error| {
+                MiddlewareError::Middleware(crate::Error::InvalidRequest(format!(
+                    "cannot sign request: {error}"
+                )))
+            })?;
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Query parameters with keys and values encoded alike, sorted by key and
+/// then value.
+fn canonical_query(query: &str) -> String {
+    let encode = |text: &str| {
+        let bytes: Vec<u8> = percent_decode_str(text).collect();
+        percent_encode(&bytes, ENCODE).to_string()
+    };
+    let mut parameters: Vec<(String, String)> = query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            (encode(key), encode(value))
+        })
+        .collect();
+    parameters.sort();
+    let mut canonical = String::new();
+    for (key, value) in parameters {
+        if !canonical.is_empty() {
+            canonical.push('&');
+        }
+        canonical.push_str(&key);
+        canonical.push('=');
+        canonical.push_str(&value);
+    }
+    canonical
+}
+
+/// Lowercase names with their values trimmed, inner runs of spaces
+/// collapsed and repeated headers joined by commas, sorted by name.
+fn canonical_headers(headers: &HeaderMap, host: &str) -> Result<Vec<(String, String)>, String> {
+    let mut canonical = vec![("host".to_owned(), host.trim().to_owned())];
+    for name in headers.keys() {
+        if name == header::HOST || UNSIGNED_HEADERS.contains(name) {
+            continue;
+        }
+        let mut joined = String::new();
+        for value in headers.get_all(name) {
+            let value = value
+                .to_str()
+                .map_err(|_| format!("{name} header is not valid text"))?;
+            if !joined.is_empty() {
+                joined.push(',');
+            }
+            joined.push_str(&value.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        canonical.push((name.as_str().to_owned(), joined));
+    }
+    canonical.sort();
+    Ok(canonical)
+}
+
+fn header_value(text: &str) -> Result<HeaderValue, String> {
+    HeaderValue::try_from(text).map_err(|error| error.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|key| key ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// `YYYYMMDD` and `HHMMSS` of `time` in UTC.
+fn utc(time: SystemTime) -> (String, String) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (
+        format!("{year:04}{month:02}{day:02}"),
+        format!(
+            "{:02}{:02}{:02}",
+            of_day / 3600,
+            of_day % 3600 / 60,
+            of_day % 60
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use http_kit::{Body, Request, header};
+
+    use super::{SigV4, hex, hmac_sha256, utc};
+
+    /// Credentials and time of the AWS Signature Version 4 test suite.
+    fn suite() -> SigV4 {
+        SigV4::new(
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            "service",
+        )
+    }
+
+    /// 2015-08-30T12:36:00Z.
+    fn suite_time() -> SystemTime {
+        UNIX_EPOCH + Duration::from_mins(24_015_636)
+    }
+
+    fn signature(request: &mut Request) -> String {
+        let signer = suite();
+        let payload = futures_executor::block_on(signer.payload_hash(request)).unwrap();
+        signer.sign(request, &payload, suite_time()).unwrap();
+        request.headers()[header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    fn request(method: &str, uri: &str) -> http::request::Builder {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "example.amazonaws.com")
+    }
+
+    fn authorization(signed_headers: &str, signature: &str) -> String {
+        format!(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders={signed_headers}, Signature={signature}"
+        )
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        // Test case 2.
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn dates_are_formatted_in_utc() {
+        assert_eq!(
+            utc(suite_time()),
+            ("20150830".to_owned(), "123600".to_owned())
+        );
+        assert_eq!(
+            utc(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            ("20000229".to_owned(), "123456".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_suite_vectors() {
+        let mut get_vanilla = request("GET", "/").body(Body::empty()).unwrap();
+        assert_eq!(
+            signature(&mut get_vanilla),
+            authorization(
+                "host;x-amz-date",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+            )
+        );
+        assert_eq!(get_vanilla.headers()["x-amz-date"], "20150830T123600Z");
+
+        let mut post_vanilla = request("POST", "/").body(Body::empty()).unwrap();
+        assert_eq!(
+            signature(&mut post_vanilla),
+            authorization(
+                "host;x-amz-date",
+                "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+            )
+        );
+
+        let mut query_order = request("GET", "/?Param2=value2&Param1=value1")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            signature(&mut query_order),
+            authorization(
+                "host;x-amz-date",
+                "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+            )
+        );
+
+        let mut value_trim = request("GET", "/")
+            .header("My-Header1", " value1")
+            .header("My-Header2", " \"a   b   c\"")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            signature(&mut value_trim),
+            authorization(
+                "host;my-header1;my-header2;x-amz-date",
+                "acc3ed3afb60bb290fc8d2dd0098b9911fcaa05412b367055dee359757a9c736"
+            )
+        );
+
+        let mut form = request("POST", "/")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from_bytes("Param1=value1"))
+            .unwrap();
+        assert_eq!(
+            signature(&mut form),
+            authorization(
+                "content-type;host;x-amz-date",
+                "ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a"
+            )
+        );
+    }
+
+    #[test]
+    fn paths_are_normalized_except_for_s3() {
+        let signer = suite();
+        assert_eq!(signer.canonical_path("/./"), "/");
+        assert_eq!(signer.canonical_path("//example//"), "/example/");
+        assert_eq!(signer.canonical_path("/example/.."), "/");
+        assert_eq!(
+            signer.canonical_path("/example%20space/"),
+            "/example%2520space/"
+        );
+
+        let s3 = SigV4::new("key", "secret", "us-east-1", "s3");
+        assert_eq!(s3.canonical_path("/a/./b%20c"), "/a/./b%20c");
+    }
+
+    #[test]
+    fn streaming_bodies_are_unsigned() {
+        let mut streaming = request("PUT", "/upload")
+            .body(Body::from_stream(futures_util::stream::iter([Ok::<
+                _,
+                std::io::Error,
+            >(
+                b"chunk".as_slice(),
+            )])))
+            .unwrap();
+        let authorization = signature(&mut streaming);
+        assert!(
+            authorization.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date"),
+            "{authorization}"
+        );
+        assert_eq!(
+            streaming.headers()["x-amz-content-sha256"],
+            super::UNSIGNED_PAYLOAD
+        );
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod redirect_tests {
+    use std::sync::{Arc, Mutex};
+
+    use http::StatusCode;
+    use http_kit::{Body, Endpoint, Request, Response, header};
+
+    use super::SigV4;
+    use crate::{Client, redirect::FollowRedirect};
+
+    /// Redirects `/old` to another host and records each request's
+    /// `Authorization`.
+    #[derive(Clone, Default)]
+    struct Backend {
+        seen: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Endpoint for Backend {
+        type Error = crate::Error;
+        async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+            let authorization = request.headers()[header::AUTHORIZATION]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            self.seen
+                .lock()
+                .unwrap()
+                .push((request.uri().to_string(), authorization));
+            let response = if request.uri().path() == "/old" {
+                http::Response::builder()
+                    .status(StatusCode::TEMPORARY_REDIRECT)
+                    .header(header::LOCATION, "https://other.amazonaws.com/new")
+            } else {
+                http::Response::builder().status(StatusCode::OK)
+            };
+            Ok(response.body(Body::empty()).unwrap())
+        }
+    }
+
+    impl Client for Backend {}
+
+    #[test]
+    fn redirects_are_signed_again() {
+        let backend = Backend::default();
+        let seen = Arc::clone(&backend.seen);
+        let signer = SigV4::new("key", "secret", "us-east-1", "service");
+        let mut client = FollowRedirect::new(backend.with(signer));
+
+        futures_executor::block_on(async {
+            let response = client
+                .get("https://example.amazonaws.com/old")
+                .unwrap()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        });
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[1].0, "https://other.amazonaws.com/new");
+        let signature = |authorization: &str| {
+            authorization
+                .rsplit_once("Signature=")
+                .unwrap()
+                .1
+                .to_owned()
+        };
+        assert_ne!(signature(&seen[0].1), signature(&seen[1].1));
+    }
+}
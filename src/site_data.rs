@@ -0,0 +1,77 @@
+//! Parsing of the `Clear-Site-Data` response header.
+//!
+//! Servers send it on logout to have the client drop what it stored for
+//! their origin. The cookie and cache middleware act on the `"cookies"` and
+//! `"cache"` directives; the storage directives have no client-side state
+//! here.
+
+use http::HeaderMap;
+use url::{Origin, Url};
+
+/// The directives of the `Clear-Site-Data` headers of a response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClearSiteData {
+    pub cookies: bool,
+    pub cache: bool,
+}
+
+impl ClearSiteData {
+    /// The directives in `headers`, or `None` when there is nothing to clear.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let mut directives = Self::default();
+        let values = headers
+            .get_all("clear-site-data")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in values {
+            match directive.trim() {
+                "\"cookies\"" => directives.cookies = true,
+                "\"cache\"" => directives.cache = true,
+                "\"*\"" => {
+                    directives.cookies = true;
+                    directives.cache = true;
+                }
+                _ => {}
+            }
+        }
+        (directives != Self::default()).then_some(directives)
+    }
+}
+
+/// The origin of `uri`, or `None` when it is not absolute.
+pub fn origin(uri: &str) -> Option<Origin> {
+    Url::parse(uri).ok().map(|url| url.origin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClearSiteData;
+    use http::{HeaderMap, HeaderValue};
+
+    fn directives(value: &'static str) -> Option<ClearSiteData> {
+        let mut headers = HeaderMap::new();
+        headers.insert("clear-site-data", HeaderValue::from_static(value));
+        ClearSiteData::from_headers(&headers)
+    }
+
+    #[test]
+    fn parses_quoted_directives() {
+        let both = ClearSiteData {
+            cookies: true,
+            cache: true,
+        };
+        assert_eq!(directives(r#""cookies", "cache""#), Some(both));
+        assert_eq!(directives(r#""*""#), Some(both));
+        assert_eq!(
+            directives(r#""cookies", "storage""#),
+            Some(ClearSiteData {
+                cookies: true,
+                cache: false
+            })
+        );
+        // Unquoted and unknown directives are ignored.
+        assert_eq!(directives("cookies"), None);
+        assert_eq!(directives(r#""executionContexts""#), None);
+    }
+}
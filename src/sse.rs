@@ -0,0 +1,228 @@
+//! Sending server-sent events as a request body.
+//!
+//! Some ingestion endpoints accept a long-lived `POST` whose body is a
+//! `text/event-stream`, the same framing servers use for responses. An
+//! [`EventSink`] renders a stream of [`SseEvent`]s into that framing, one
+//! chunk per event, so each event goes out as soon as it is produced.
+//! [`RequestBuilder::sse_body`](crate::client::RequestBuilder::sse_body)
+//! attaches one to a request.
+//!
+//! ```rust,no_run
+//! # async fn example(mut client: impl zenwave::Client) -> Result<(), zenwave::Error> {
+//! use futures_util::stream;
+//! use zenwave::sse::SseEvent;
+//!
+//! let events = stream::iter([
+//!     SseEvent::new("first line\nsecond line").event("log").id("1"),
+//!     SseEvent::comment("keep-alive"),
+//! ]);
+//! client.post("https://example.com/ingest")?.sse_body(events).await;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::fmt::Write;
+
+use futures_util::Stream;
+use http_kit::utils::Bytes;
+
+/// One server-sent event, or a comment line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+    data: Option<String>,
+    comment: Option<String>,
+}
+
+impl SseEvent {
+    /// An event carrying `data`, which may span several lines.
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            event: None,
+            id: None,
+            retry: None,
+            data: Some(data.into()),
+            comment: None,
+        }
+    }
+
+    /// A comment, ignored by readers and typically used as a keep-alive.
+    pub fn comment(text: impl Into<String>) -> Self {
+        Self {
+            event: None,
+            id: None,
+            retry: None,
+            data: None,
+            comment: Some(text.into()),
+        }
+    }
+
+    /// Set the event type, sent as the `event` field.
+    #[must_use]
+    pub fn event(mut self, name: impl Into<String>) -> Self {
+        self.event = Some(name.into());
+        self
+    }
+
+    /// Set the event id, sent as the `id` field.
+    #[must_use]
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the reconnection delay, sent as the `retry` field in milliseconds.
+    #[must_use]
+    pub const fn retry(mut self, delay: Duration) -> Self {
+        self.retry = Some(delay);
+        self
+    }
+
+    /// Render the event in `text/event-stream` framing.
+    ///
+    /// Each line of the data and of a comment becomes its own field line.
+    /// Line breaks cannot be represented in the `event` and `id` fields, so
+    /// they are removed there. Events end with a blank line; comments do
+    /// not, since they dispatch nothing.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let mut encoded = String::new();
+        if let Some(comment) = &self.comment {
+            for line in lines(comment) {
+                encoded.push(':');
+                push_value(&mut encoded, line);
+            }
+        }
+        let Some(data) = &self.data else {
+            return encoded;
+        };
+        if let Some(event) = &self.event {
+            encoded.push_str("event:");
+            push_value(&mut encoded, &single_line(event));
+        }
+        if let Some(id) = &self.id {
+            encoded.push_str("id:");
+            push_value(&mut encoded, &single_line(id));
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(encoded, "retry: {}", retry.as_millis());
+        }
+        for line in lines(data) {
+            encoded.push_str("data:");
+            push_value(&mut encoded, line);
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+/// Append ` value` and a line feed. Readers strip one leading space, so it
+/// keeps values that start with a space intact.
+fn push_value(encoded: &mut String, value: &str) {
+    encoded.push(' ');
+    encoded.push_str(value);
+    encoded.push('\n');
+}
+
+/// The lines of `text`, split at `\r\n`, `\r` or `\n` as readers do.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\r\n").flat_map(|line| line.split(['\r', '\n']))
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+/// Stream of `text/event-stream` chunks rendered from a stream of events.
+///
+/// Every event becomes one chunk, so a streaming request body flushes it on
+/// its own. Pass it to
+/// [`RequestBuilder::stream_body`](crate::client::RequestBuilder::stream_body),
+/// or use [`RequestBuilder::sse_body`](crate::client::RequestBuilder::sse_body)
+/// which also sets the content type.
+pub struct EventSink<S> {
+    events: Pin<Box<S>>,
+}
+
+impl<S> core::fmt::Debug for EventSink<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EventSink").finish_non_exhaustive()
+    }
+}
+
+impl<S: Stream<Item = SseEvent>> EventSink<S> {
+    /// Render `events` as they are produced.
+    pub fn new(events: S) -> Self {
+        Self {
+            events: Box::pin(events),
+        }
+    }
+}
+
+impl<S: Stream<Item = SseEvent>> Stream for EventSink<S> {
+    type Item = Result<Bytes, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events
+            .as_mut()
+            .poll_next(cx)
+            .map(|event| event.map(|event| Ok(Bytes::from(event.encode()))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventSink, SseEvent};
+    use core::time::Duration;
+    use futures_util::{StreamExt, stream};
+
+    #[test]
+    fn multi_line_data_gets_one_field_per_line() {
+        let event = SseEvent::new("first\nsecond\r\n\nlast")
+            .event("log")
+            .id("7");
+        assert_eq!(
+            event.encode(),
+            "event: log\nid: 7\ndata: first\ndata: second\ndata: \ndata: last\n\n"
+        );
+    }
+
+    #[test]
+    fn line_breaks_are_removed_from_event_and_id() {
+        let event = SseEvent::new("x")
+            .event("a\nb")
+            .id("1\r2")
+            .retry(Duration::from_secs(3));
+        assert_eq!(
+            event.encode(),
+            "event: ab\nid: 12\nretry: 3000\ndata: x\n\n"
+        );
+    }
+
+    #[test]
+    fn sink_yields_one_chunk_per_event() {
+        let events = stream::iter([
+            SseEvent::comment("hello\nworld"),
+            SseEvent::new(""),
+            SseEvent::new(" padded"),
+        ]);
+        let chunks: Vec<_> =
+            futures_executor::block_on(EventSink::new(events).map(Result::unwrap).collect());
+        assert_eq!(
+            chunks,
+            [": hello\n: world\n", "data: \n\n", "data:  padded\n\n"]
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! The recommended order of the built-in middleware.
+//!
+//! Each call to [`Client::with`] and friends wraps what came before, so the
+//! last layer added is the first to see a request. Some orders look
+//! equivalent but are not:
+//!
+//! - A [`Timeout`] outside [`Retry`] bounds the whole exchange, retries and
+//!   backoff included. Inside it, every attempt gets the full duration and
+//!   the total is only limited by the retry count.
+//! - Authentication must sit *outside* the [`Cache`]: the cache only keeps
+//!   responses to authorized requests private when it sees the
+//!   `Authorization` header. Added below the cache, it would serve one
+//!   caller's authorized response to the next.
+//! - Authentication must also sit *outside* [`FollowRedirect`], which drops
+//!   `Authorization` on cross-origin hops. Added inside, it would send the
+//!   credentials to whatever host the server redirects to.
+//! - [`CookieStore`] must sit *inside* [`FollowRedirect`]: only then does it
+//!   see the `Set-Cookie` of a redirect, such as the session cookie set by a
+//!   login, and send it on the next hop.
+//!
+//! [`Recommended`] builds the stack in this order, outermost first:
+//!
+//! ```text
+//! Timeout → Retry → auth → FollowRedirect → CookieStore → Cache → backend
+//! ```
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), zenwave::Error> {
+//! use std::time::Duration;
+//! use zenwave::{Client, auth::BearerAuth, backend::DefaultBackend, stack::Recommended};
+//!
+//! let mut client = Recommended::new()
+//!     .timeout(Duration::from_secs(10))
+//!     .auth(BearerAuth::new("token"))
+//!     .build(DefaultBackend::default());
+//! let _ = client.get("https://example.com/")?.await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ClientBuilder`], returned by [`crate::builder`], stacks the same layers
+//! in the same order and lets each be configured or left out, along with
+//! default headers and the redirect policy.
+
+use core::{fmt::Display, time::Duration};
+use std::convert::Infallible;
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    endpoint::WithMiddleware,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::MiddlewareError,
+};
+
+use crate::{
+    Client, backend::DefaultBackend, cache::Cache, cookie::CookieStore, redirect::FollowRedirect,
+    retry::Retry, timeout::Timeout,
+};
+
+/// Builder for the recommended middleware stack; see the
+/// [module documentation](self) for the order and why it matters.
+///
+/// Requests time out after 30 seconds overall and are retried twice by
+/// default.
+#[derive(Debug, Clone)]
+pub struct Recommended<A = NoAuth> {
+    timeout: Duration,
+    retries: usize,
+    auth: A,
+}
+
+impl Default for Recommended {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recommended {
+    /// Start from the defaults, without authentication.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            retries: 2,
+            auth: NoAuth,
+        }
+    }
+}
+
+impl<A: Middleware> Recommended<A> {
+    /// Fail requests that take longer than `duration`, retries included.
+    #[must_use]
+    pub const fn timeout(mut self, duration: Duration) -> Self {
+        self.timeout = duration;
+        self
+    }
+
+    /// Retry failed requests up to `retries` times.
+    #[must_use]
+    pub const fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Authenticate requests with `auth`, such as
+    /// [`BearerAuth`](crate::auth::BearerAuth).
+    pub fn auth<B: Middleware>(self, auth: B) -> Recommended<B> {
+        Recommended {
+            timeout: self.timeout,
+            retries: self.retries,
+            auth,
+        }
+    }
+
+    /// Wrap `backend` in the stack.
+    ///
+    /// This is the stack of a [`ClientBuilder`] left at its defaults.
+    pub fn build<C>(self, backend: C) -> impl Client<Error = crate::Error>
+    where
+        C: Client,
+        C::Error: Into<crate::Error>,
+        A::Error: Into<crate::Error>,
+    {
+        ClientBuilder::new()
+            .timeout(self.timeout)
+            .retries(self.retries)
+            .auth(self.auth)
+            .stack(backend)
+    }
+}
+
+/// Placeholder for the authentication layer of a [`Recommended`] stack that
+/// has none; passes requests through unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoAuth;
+
+impl Middleware for NoAuth {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Builder for a client configured in one place, such as at application
+/// start-up; see [`crate::builder`].
+///
+/// The layers are stacked in the order of the [module documentation](self),
+/// with the default headers added just outside the cache so that it sees
+/// them:
+///
+/// ```text
+/// Timeout → Retry → auth → FollowRedirect → CookieStore → headers → Cache → backend
+/// ```
+///
+/// The defaults match [`Recommended`]: a 30 second timeout, two retries, up
+/// to ten redirects, cookies and a cache.
+///
+/// ```rust,no_run
+/// # async fn example() -> Result<(), zenwave::Error> {
+/// use std::time::Duration;
+/// use zenwave::Client;
+///
+/// let mut client = zenwave::builder()
+///     .user_agent("my-app/1.0")
+///     .default_header("x-team", "payments")
+///     .timeout(Duration::from_secs(10))
+///     // Hand 3xx responses back instead of following them.
+///     .max_redirects(0)
+///     .build()?;
+/// let _ = client.get("https://example.com/")?.await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ClientBuilder<A = NoAuth> {
+    timeout: Option<Duration>,
+    retries: usize,
+    max_redirects: u32,
+    send_referer: bool,
+    cookies: Option<CookieStore>,
+    cache: bool,
+    headers: HeaderMap,
+    auth: A,
+    /// The first default header that failed to parse.
+    error: Option<crate::Error>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    /// Start from the defaults, without authentication.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            retries: 2,
+            max_redirects: 10,
+            send_referer: false,
+            cookies: Some(CookieStore::default()),
+            cache: true,
+            headers: HeaderMap::new(),
+            auth: NoAuth,
+            error: None,
+        }
+    }
+}
+
+impl<A> ClientBuilder<A>
+where
+    A: Middleware,
+    A::Error: Into<crate::Error>,
+{
+    /// Fail requests that take longer than `duration`, retries included.
+    #[must_use]
+    pub const fn timeout(mut self, duration: Duration) -> Self {
+        self.timeout = Some(duration);
+        self
+    }
+
+    /// Let requests take as long as they need.
+    #[must_use]
+    pub const fn no_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// Retry failed requests up to `retries` times; zero disables retries.
+    #[must_use]
+    pub const fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Follow at most `max` redirects in a row.
+    ///
+    /// Zero does not follow redirects at all: the redirect response itself
+    /// is returned, rather than an error. See
+    /// [`FollowRedirect::max_redirects`].
+    #[must_use]
+    pub const fn max_redirects(mut self, max: u32) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Send a `Referer` on redirect hops; see
+    /// [`FollowRedirect::send_referer`].
+    #[must_use]
+    pub const fn send_referer(mut self, enabled: bool) -> Self {
+        self.send_referer = enabled;
+        self
+    }
+
+    /// Choose whether cookies are stored and sent back. Enabled by default,
+    /// with a store kept in memory.
+    #[must_use]
+    pub fn cookies(mut self, enabled: bool) -> Self {
+        self.cookies = enabled.then(CookieStore::default);
+        self
+    }
+
+    /// Keep cookies in `store`, such as a
+    /// [persistent](CookieStore::persistent_default) one.
+    #[must_use]
+    pub fn cookie_store(mut self, store: CookieStore) -> Self {
+        self.cookies = Some(store);
+        self
+    }
+
+    /// Choose whether responses are cached. Enabled by default.
+    #[must_use]
+    pub const fn cache(mut self, enabled: bool) -> Self {
+        self.cache = enabled;
+        self
+    }
+
+    /// Send `value` as `name` with every request that does not set `name`
+    /// itself. Calling it again for the same name adds another value.
+    ///
+    /// A name or value that cannot be parsed is reported by
+    /// [`build`](Self::build).
+    #[must_use]
+    pub fn default_header(
+        mut self,
+        name: impl TryInto<HeaderName, Error: Display>,
+        value: impl TryInto<HeaderValue, Error: Display>,
+    ) -> Self {
+        let parsed = name
+            .try_into()
+            .map_err(|error| error.to_string())
+            .and_then(|name| {
+                let value = value.try_into().map_err(|error| error.to_string())?;
+                Ok((name, value))
+            });
+        match parsed {
+            Ok((name, value)) => {
+                self.headers.append(name, value);
+            }
+            Err(error) if self.error.is_none() => {
+                self.error = Some(crate::Error::InvalidRequest(error));
+            }
+            Err(_) => {}
+        }
+        self
+    }
+
+    /// Send `User-Agent: agent` with every request that does not set one.
+    #[must_use]
+    pub fn user_agent(self, agent: impl TryInto<HeaderValue, Error: Display>) -> Self {
+        self.default_header(header::USER_AGENT, agent)
+    }
+
+    /// Authenticate requests with `auth`, such as
+    /// [`BearerAuth`](crate::auth::BearerAuth).
+    pub fn auth<B>(self, auth: B) -> ClientBuilder<B>
+    where
+        B: Middleware,
+        B::Error: Into<crate::Error>,
+    {
+        ClientBuilder {
+            timeout: self.timeout,
+            retries: self.retries,
+            max_redirects: self.max_redirects,
+            send_referer: self.send_referer,
+            cookies: self.cookies,
+            cache: self.cache,
+            headers: self.headers,
+            auth,
+            error: self.error,
+        }
+    }
+
+    /// Create the client around the [`DefaultBackend`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a default header could
+    /// not be parsed.
+    pub fn build(self) -> Result<impl Client<Error = crate::Error>, crate::Error> {
+        self.build_with(DefaultBackend::default())
+    }
+
+    /// Create the client around `backend`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::InvalidRequest`] when a default header could
+    /// not be parsed.
+    pub fn build_with<C>(
+        mut self,
+        backend: C,
+    ) -> Result<impl Client<Error = crate::Error>, crate::Error>
+    where
+        C: Client,
+        C::Error: Into<crate::Error>,
+    {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        Ok(self.stack(backend))
+    }
+
+    /// Stack the layers around `backend`, once the headers have been checked.
+    fn stack<C>(self, backend: C) -> impl Client<Error = crate::Error>
+    where
+        C: Client,
+        C::Error: Into<crate::Error>,
+    {
+        // Spelled out rather than chained so the error types stay nameable
+        // for `Unified`.
+        let stack = WithMiddleware::new(backend, Optional(self.cache.then(Cache::new)));
+        let stack = WithMiddleware::new(stack, DefaultHeaders(self.headers));
+        let stack = WithMiddleware::new(stack, Optional(self.cookies));
+        let stack = FollowRedirect::new(stack)
+            .max_redirects(self.max_redirects)
+            .send_referer(self.send_referer);
+        let stack = WithMiddleware::new(stack, self.auth);
+        let stack = Retry::new(stack, self.retries);
+        let stack = WithMiddleware::new(stack, Optional(self.timeout.map(Timeout::new)));
+        Unified(stack)
+    }
+}
+
+/// Reports the errors of a [`ClientBuilder`] stack as [`crate::Error`],
+/// whatever layer they come from.
+struct Unified<C>(C);
+
+impl<C> Endpoint for Unified<C>
+where
+    C: Client,
+    C::Error: Into<crate::Error>,
+{
+    type Error = crate::Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        self.0.respond(request).await.map_err(Into::into)
+    }
+}
+
+impl<C> Client for Unified<C>
+where
+    C: Client,
+    C::Error: Into<crate::Error>,
+{
+}
+
+/// A layer of a [`ClientBuilder`] stack that may be turned off.
+struct Optional<M>(Option<M>);
+
+impl<M: Middleware> Middleware for Optional<M> {
+    type Error = M::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        match &mut self.0 {
+            Some(layer) => layer.handle(request, next).await,
+            None => next
+                .respond(request)
+                .await
+                .map_err(MiddlewareError::Endpoint),
+        }
+    }
+}
+
+/// Adds the headers a request does not set itself.
+struct DefaultHeaders(HeaderMap);
+
+impl Middleware for DefaultHeaders {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        for name in self.0.keys() {
+            if !request.headers().contains_key(name) {
+                for value in self.0.get_all(name) {
+                    request.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+        }
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
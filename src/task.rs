@@ -0,0 +1,390 @@
+//! Lifecycle of background tasks started by backends and middleware.
+//!
+//! Some features keep work running after a request returns: the hyper
+//! backend drives each connection from a task while the body is read, and
+//! keepalives, proactive token refreshes or reconnectors run on timers.
+//! Every such task is registered with a [`TaskSet`] owned by the component
+//! that started it, so it can be stopped deliberately instead of outliving
+//! the client.
+//!
+//! ```rust,no_run
+//! # async fn example() {
+//! use std::time::Duration;
+//! use zenwave::task::TaskSet;
+//!
+//! let tasks = TaskSet::new();
+//! tasks.spawn(async {
+//!     // A keepalive loop, for example.
+//! });
+//! let drained = tasks.shutdown(Duration::from_secs(5)).await;
+//! # }
+//! ```
+
+use core::{
+    fmt,
+    future::Future,
+    task::{Poll, Waker},
+    time::Duration,
+};
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+};
+
+use executor_core::{AnyExecutor, Executor};
+use futures_util::future::{AbortHandle, Abortable, poll_fn};
+
+use crate::timeout::with_timeout;
+
+/// A set of background tasks that can be shut down together.
+///
+/// Tasks run on the configured executor, or on a dedicated thread each when
+/// there is none. [`shutdown`](Self::shutdown) stops them at their next
+/// `.await` and waits for them to be dropped. Dropping the set stops them
+/// too, without waiting, except the connection drivers of a backend: those
+/// end on their own once the response bodies they serve are read or dropped.
+pub struct TaskSet {
+    executor: Option<Arc<AnyExecutor>>,
+    // Created on the first spawn so that `new` can be const.
+    shared: OnceLock<Arc<Mutex<Tasks>>>,
+}
+
+#[derive(Default)]
+struct Tasks {
+    next_id: u64,
+    /// Each running task, and whether it outlives the set when dropped.
+    running: Vec<(u64, AbortHandle, bool)>,
+    closed: bool,
+    idle: Vec<Waker>,
+}
+
+impl Tasks {
+    fn finish(&mut self, id: u64) {
+        self.running.retain(|(task, ..)| *task != id);
+        if self.running.is_empty() {
+            self.idle.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Stop the running tasks, but those that linger when `dropped`.
+    fn abort_all(&mut self, dropped: bool) {
+        self.closed = true;
+        for (_, handle, lingers) in &self.running {
+            if !(dropped && *lingers) {
+                handle.abort();
+            }
+        }
+    }
+}
+
+/// Removes its task from the set when the task completes or is dropped.
+struct Running {
+    id: u64,
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        lock(&self.tasks).finish(self.id);
+    }
+}
+
+fn lock(tasks: &Mutex<Tasks>) -> std::sync::MutexGuard<'_, Tasks> {
+    tasks
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl Default for TaskSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TaskSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskSet")
+            .field("running", &self.running())
+            .field("closed", &self.is_shutdown())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TaskSet {
+    /// Create an empty set running each task on its own thread.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            executor: None,
+            shared: OnceLock::new(),
+        }
+    }
+
+    /// Create an empty set running tasks on `executor`.
+    #[must_use]
+    pub fn with_executor(executor: impl Executor + 'static) -> Self {
+        Self {
+            executor: Some(Arc::new(AnyExecutor::new(executor))),
+            shared: OnceLock::new(),
+        }
+    }
+
+    fn tasks(&self) -> &Arc<Mutex<Tasks>> {
+        self.shared.get_or_init(Arc::default)
+    }
+
+    /// Start `task` in the background.
+    ///
+    /// After [`shutdown`](Self::shutdown) the task is dropped without being
+    /// run.
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        self.spawner().spawn(task);
+    }
+
+    /// A handle adding tasks to this set, for code that needs an owned
+    /// executor such as hyper's HTTP/2 connections.
+    pub(crate) fn spawner(&self) -> Spawner {
+        Spawner {
+            executor: self.executor.clone(),
+            tasks: Arc::clone(self.tasks()),
+            lingers: false,
+        }
+    }
+
+    /// Like [`spawner`](Self::spawner), for tasks that end on their own
+    /// once nothing uses them, such as connection drivers: dropping the set
+    /// leaves them running so the response bodies they serve can still be
+    /// read. Only [`shutdown`](Self::shutdown) and [`drain`](Self::drain)
+    /// stop them.
+    #[cfg_attr(
+        not(all(feature = "hyper-backend", not(target_arch = "wasm32"))),
+        allow(dead_code)
+    )]
+    pub(crate) fn lingering_spawner(&self) -> Spawner {
+        Spawner {
+            lingers: true,
+            ..self.spawner()
+        }
+    }
+
+    /// The number of tasks that have not finished yet.
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.shared
+            .get()
+            .map_or(0, |tasks| lock(tasks).running.len())
+    }
+
+    /// Whether [`shutdown`](Self::shutdown) was called.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        self.shared.get().is_some_and(|tasks| lock(tasks).closed)
+    }
+
+    /// Stop every task and wait up to `deadline` for them to be dropped.
+    ///
+    /// Tasks stop at their next `.await`; a task blocking its thread is only
+    /// stopped once it yields. Tasks spawned afterwards are not run. Returns
+    /// whether every task was gone before the deadline.
+    pub async fn shutdown(&self, deadline: Duration) -> bool {
+        let tasks = Arc::clone(self.tasks());
+        lock(&tasks).abort_all(false);
+        with_timeout(deadline, idle(&tasks)).await.is_ok()
+    }
+
+    /// Wait up to `deadline` for every task to finish on its own, then stop
+    /// those still running as [`shutdown`](Self::shutdown) does.
+    ///
+    /// Tasks spawned afterwards are not run. Returns whether every task
+    /// finished before the deadline.
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        let tasks = Arc::clone(self.tasks());
+        lock(&tasks).closed = true;
+        if with_timeout(deadline, idle(&tasks)).await.is_ok() {
+            return true;
+        }
+        lock(&tasks).abort_all(false);
+        false
+    }
+}
+
+/// Resolve once no task of `tasks` is running.
+fn idle(tasks: &Mutex<Tasks>) -> impl Future<Output = ()> + '_ {
+    poll_fn(move |cx| {
+        let mut state = lock(tasks);
+        if state.running.is_empty() {
+            Poll::Ready(())
+        } else {
+            state.idle.push(cx.waker().clone());
+            Poll::Pending
+        }
+    })
+}
+
+/// Adds tasks to the [`TaskSet`] it was taken from; see
+/// [`TaskSet::spawner`].
+#[derive(Clone)]
+pub(crate) struct Spawner {
+    executor: Option<Arc<AnyExecutor>>,
+    tasks: Arc<Mutex<Tasks>>,
+    lingers: bool,
+}
+
+impl Spawner {
+    pub(crate) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let tasks = Arc::clone(&self.tasks);
+        let (handle, abort) = AbortHandle::new_pair();
+        let id = {
+            let mut state = lock(&tasks);
+            if state.closed {
+                return;
+            }
+            let id = state.next_id;
+            state.next_id += 1;
+            state.running.push((id, handle, self.lingers));
+            id
+        };
+        let running = Running { id, tasks };
+        let task = async move {
+            let _running = running;
+            let _ = Abortable::new(task, abort).await;
+        };
+        if let Some(executor) = &self.executor {
+            executor.spawn(task).detach();
+        } else {
+            thread::spawn(move || async_io::block_on(task));
+        }
+    }
+}
+
+impl Drop for TaskSet {
+    fn drop(&mut self) {
+        if let Some(tasks) = self.shared.get() {
+            lock(tasks).abort_all(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaskSet;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::{Duration, Instant},
+    };
+
+    /// Counts the tasks that ended, however they ended.
+    struct Stopped(Arc<AtomicUsize>);
+
+    impl Drop for Stopped {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Spawn a task ticking every few milliseconds until it is stopped.
+    fn keepalive(tasks: &TaskSet, ticks: &Arc<AtomicUsize>, stopped: &Arc<AtomicUsize>) {
+        let ticks = Arc::clone(ticks);
+        let stopped = Stopped(Arc::clone(stopped));
+        tasks.spawn(async move {
+            let _stopped = stopped;
+            loop {
+                ticks.fetch_add(1, Ordering::SeqCst);
+                crate::retry::sleep(Duration::from_millis(5)).await;
+            }
+        });
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn shutdown_stops_every_task() {
+        let tasks = TaskSet::new();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            keepalive(&tasks, &ticks, &stopped);
+        }
+        wait_until(|| ticks.load(Ordering::SeqCst) >= 3);
+        assert_eq!(tasks.running(), 3);
+
+        assert!(async_io::block_on(tasks.shutdown(Duration::from_secs(5))));
+        assert_eq!(stopped.load(Ordering::SeqCst), 3);
+        assert_eq!(tasks.running(), 0);
+        assert!(tasks.is_shutdown());
+    }
+
+    #[test]
+    fn tasks_spawned_after_shutdown_do_not_run() {
+        let tasks = TaskSet::new();
+        assert!(async_io::block_on(tasks.shutdown(Duration::from_secs(1))));
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        keepalive(&tasks, &ticks, &stopped);
+        assert_eq!(stopped.load(Ordering::SeqCst), 1);
+        assert_eq!(ticks.load(Ordering::SeqCst), 0);
+        assert_eq!(tasks.running(), 0);
+    }
+
+    #[test]
+    fn drain_waits_for_finishing_tasks_and_stops_the_rest() {
+        let tasks = TaskSet::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let done = Arc::clone(&finished);
+        tasks.spawn(async move {
+            crate::retry::sleep(Duration::from_millis(20)).await;
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(async_io::block_on(tasks.drain(Duration::from_secs(5))));
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+
+        let tasks = TaskSet::new();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        keepalive(&tasks, &ticks, &stopped);
+        assert!(!async_io::block_on(tasks.drain(Duration::from_millis(20))));
+        wait_until(|| stopped.load(Ordering::SeqCst) == 1);
+        assert!(tasks.is_shutdown());
+    }
+
+    #[test]
+    fn dropping_the_set_stops_its_tasks() {
+        let tasks = TaskSet::new();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        keepalive(&tasks, &ticks, &stopped);
+        keepalive(&tasks, &ticks, &stopped);
+
+        drop(tasks);
+        wait_until(|| stopped.load(Ordering::SeqCst) == 2);
+    }
+
+    #[test]
+    fn lingering_tasks_outlive_the_set_until_shutdown() {
+        let tasks = TaskSet::new();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let (done, finished) = std::sync::mpsc::channel();
+        tasks.lingering_spawner().spawn(async move {
+            crate::retry::sleep(Duration::from_millis(20)).await;
+            done.send(()).unwrap();
+        });
+        keepalive(&tasks, &ticks, &stopped);
+
+        drop(tasks);
+        wait_until(|| stopped.load(Ordering::SeqCst) == 1);
+        finished
+            .recv_timeout(Duration::from_secs(5))
+            .expect("lingering task must finish");
+    }
+}
@@ -1,25 +1,28 @@
 //! Timeout middleware backed by a runtime-agnostic timer.
 //!
-//! This middleware cancels in-flight requests when the configured duration
-//! elapses and surfaces a `504 Gateway Timeout` error. It relies on
-//! `async-io`'s timers so it works uniformly across targets without pulling
-//! in a dedicated async runtime.
+//! [`Timeout`] cancels in-flight requests when the configured duration
+//! elapses and surfaces a `504 Gateway Timeout` error. [`IdleTimeout`] only
+//! fails when the server stops sending data for too long, which suits long
+//! streaming downloads; [`ReadTimeout`] does the same for the body alone.
+//! They rely on `async-io`'s timers so they work
+//! uniformly across targets without pulling in a dedicated async runtime.
 
-use core::time::Duration;
-#[cfg(target_arch = "wasm32")]
 use core::{
     future::Future,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
+use std::{convert::Infallible, io};
 
 #[cfg(not(target_arch = "wasm32"))]
 use async_io::Timer;
-use futures_util::{future::Either, pin_mut};
+use futures_util::{Stream, TryStreamExt, future::Either, pin_mut};
 #[cfg(target_arch = "wasm32")]
 use gloo_timers::future::TimeoutFuture;
 use http_kit::{
-    Endpoint, HttpError, Middleware, Request, Response, StatusCode, middleware::MiddlewareError,
+    Body, BodyError, Endpoint, HttpError, Middleware, Request, Response, StatusCode,
+    middleware::MiddlewareError, utils::Bytes,
 };
 use thiserror::Error;
 
@@ -51,11 +54,61 @@ impl HttpError for TimeoutError {
 // Convert TimeoutError to unified zenwave::Error
 impl From<TimeoutError> for crate::Error {
     fn from(_: TimeoutError) -> Self {
-        Self::Timeout
+        Self::Timeout { partial: None }
     }
 }
 
 impl Middleware for Timeout {
+    type Error = TimeoutError;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, http_kit::middleware::MiddlewareError<E::Error, Self::Error>> {
+        match with_timeout(self.duration, next.respond(request)).await {
+            Ok(result) => Ok(result.map_err(MiddlewareError::Endpoint)?),
+            Err(error) => Err(MiddlewareError::Middleware(error)),
+        }
+    }
+}
+
+/// Run `future`, failing with [`TimeoutError`] once `duration` elapses.
+pub(crate) async fn with_timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, TimeoutError> {
+    let timeout_future = timeout_future(duration);
+    pin_mut!(future);
+    pin_mut!(timeout_future);
+
+    match futures_util::future::select(future, timeout_future).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right((_, _)) => Err(TimeoutError),
+    }
+}
+
+/// Middleware that fails requests when no data arrives for the configured duration.
+///
+/// Unlike [`Timeout`], the total duration of a request is unbounded. The
+/// response headers must arrive within the duration, or the request fails
+/// with [`TimeoutError`]. The body clock only starts when the body is first
+/// read, so time spent before reading it does not count, and restarts with
+/// every chunk; a stalled body yields an [`io::ErrorKind::TimedOut`] error
+/// from the body stream.
+#[derive(Debug, Clone, Copy)]
+pub struct IdleTimeout {
+    duration: Duration,
+}
+
+impl IdleTimeout {
+    /// Construct a middleware that allows at most `duration` between chunks.
+    #[must_use]
+    pub const fn new(duration: Duration) -> Self {
+        Self { duration }
+    }
+}
+
+impl Middleware for IdleTimeout {
     type Error = TimeoutError;
     async fn handle<E: Endpoint>(
         &mut self,
@@ -68,22 +121,138 @@ impl Middleware for Timeout {
         pin_mut!(response_future);
         pin_mut!(timeout_future);
 
-        match futures_util::future::select(response_future, timeout_future).await {
-            Either::Left((result, _)) => Ok(result.map_err(MiddlewareError::Endpoint)?),
-            Either::Right((_, _)) => Err(MiddlewareError::Middleware(TimeoutError)),
+        let response = match futures_util::future::select(response_future, timeout_future).await {
+            Either::Left((result, _)) => result.map_err(MiddlewareError::Endpoint)?,
+            Either::Right((_, _)) => return Err(MiddlewareError::Middleware(TimeoutError)),
+        };
+
+        let duration = self.duration;
+        Ok(response.map(|body| IdleBody::wrap(body, duration)))
+    }
+}
+
+/// Middleware that fails a response body when no chunk arrives for the
+/// configured duration.
+///
+/// Unlike [`IdleTimeout`], waiting for the response headers is not bounded;
+/// pair it with [`Timeout`] for that. A long download never times out as
+/// long as data keeps arriving. A stalled body yields an
+/// [`io::ErrorKind::TimedOut`] error from the body stream; helpers that read
+/// the whole body, like `string()` and `json()` on a request, report it as
+/// [`crate::Error::Timeout`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReadTimeout {
+    duration: Duration,
+}
+
+impl ReadTimeout {
+    /// Construct a middleware that allows at most `duration` between body
+    /// chunks.
+    #[must_use]
+    pub const fn new(duration: Duration) -> Self {
+        Self { duration }
+    }
+}
+
+impl Middleware for ReadTimeout {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, http_kit::middleware::MiddlewareError<E::Error, Self::Error>> {
+        let response = next
+            .respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)?;
+        let duration = self.duration;
+        Ok(response.map(|body| IdleBody::wrap(body, duration)))
+    }
+}
+
+/// Response body wrapper enforcing [`IdleTimeout`] and [`ReadTimeout`]
+/// between chunks.
+///
+/// The body is handed to the caller long after the middleware returned, so
+/// the timer cannot live on the middleware's stack. Instead it is stored next
+/// to the body and polled from `poll_next`: whenever the body is pending the
+/// timer is polled too, registering the same waker, so whichever fires first
+/// wakes the reader. Every chunk replaces the timer with a fresh one.
+///
+/// The first timer is only started when the body is first polled, so time
+/// spent before the caller begins reading does not count.
+struct IdleBody {
+    body: Body,
+    duration: Duration,
+    timer: Option<TimerFuture>,
+    expired: bool,
+}
+
+impl IdleBody {
+    /// Wrap `body`, keeping its length known.
+    fn wrap(body: Body, duration: Duration) -> Body {
+        let len = body.len();
+        let idle = Self {
+            body,
+            duration,
+            timer: None,
+            expired: false,
+        };
+        match len {
+            Some(len) => {
+                let reader = idle
+                    .map_err(|error| match error {
+                        BodyError::Io(error) => error,
+                        error => io::Error::other(error),
+                    })
+                    .into_async_read();
+                Body::from_reader(reader, len)
+            }
+            None => Body::from_stream(idle),
+        }
+    }
+}
+
+impl Stream for IdleBody {
+    type Item = Result<Bytes, BodyError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.expired {
+            return Poll::Ready(None);
+        }
+
+        if let Poll::Ready(item) = Pin::new(&mut this.body).poll_next(cx) {
+            this.timer = None;
+            return Poll::Ready(item);
+        }
+
+        let duration = this.duration;
+        let timer = this.timer.get_or_insert_with(|| timeout_future(duration));
+        if Pin::new(timer).poll(cx).is_ready() {
+            this.expired = true;
+            let error = io::Error::new(io::ErrorKind::TimedOut, TimeoutError);
+            return Poll::Ready(Some(Err(BodyError::Io(error))));
         }
+        Poll::Pending
     }
 }
 
 #[cfg(target_arch = "wasm32")]
-fn timeout_future(duration: Duration) -> SingleThreaded<TimeoutFuture> {
+type TimerFuture = SingleThreaded<TimeoutFuture>;
+
+#[cfg(not(target_arch = "wasm32"))]
+type TimerFuture = Timer;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timeout_future(duration: Duration) -> TimerFuture {
     // gloo expects milliseconds as u32; saturate to avoid overflow for long durations.
     let millis = duration.as_millis().try_into().unwrap_or(u32::MAX);
     SingleThreaded(TimeoutFuture::new(millis))
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn timeout_future(duration: Duration) -> Timer {
+pub(crate) fn timeout_future(duration: Duration) -> TimerFuture {
     Timer::after(duration)
 }
 
@@ -109,7 +278,8 @@ impl<T: Future> Future for SingleThreaded<T> {
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
-    use http_kit::{Body, HttpError, Method};
+    use futures_util::{StreamExt, stream};
+    use http_kit::{HttpError, Method};
     use std::{convert::Infallible, time::Duration};
 
     fn request() -> Request {
@@ -176,4 +346,137 @@ mod tests {
         assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
         assert!(err.to_string().contains("timed out"));
     }
+
+    /// Responds immediately with a body produced by `body`.
+    struct StreamingEndpoint<F>(F);
+
+    impl<F: FnMut() -> Body + Send + Sync> Endpoint for StreamingEndpoint<F> {
+        type Error = Infallible;
+        async fn respond(&mut self, _request: &mut Request) -> Result<Response, Self::Error> {
+            Ok(Response::new((self.0)()))
+        }
+    }
+
+    #[test]
+    fn idle_timeout_fails_stalled_body() {
+        let mut middleware = IdleTimeout::new(Duration::from_millis(20));
+        let backend = StreamingEndpoint(|| {
+            let first = stream::iter([Ok::<_, io::Error>(Bytes::from_static(b"first"))]);
+            Body::from_stream(first.chain(stream::pending()))
+        });
+        let mut req = request();
+
+        async_io::block_on(async {
+            let mut body = middleware
+                .handle(&mut req, backend)
+                .await
+                .unwrap()
+                .into_body();
+            assert_eq!(body.next().await.unwrap().unwrap().as_ref(), b"first");
+
+            let error = body.next().await.unwrap().unwrap_err();
+            assert!(
+                matches!(error, BodyError::Io(ref error) if error.kind() == io::ErrorKind::TimedOut)
+            );
+            assert!(body.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn idle_timeout_allows_slow_but_steady_body() {
+        let mut middleware = IdleTimeout::new(Duration::from_millis(40));
+        let backend = StreamingEndpoint(|| {
+            let chunks = stream::unfold(0, |sent| async move {
+                if sent == 4 {
+                    return None;
+                }
+                Timer::after(Duration::from_millis(15)).await;
+                Some((Ok::<_, io::Error>(Bytes::from_static(b"tick")), sent + 1))
+            });
+            Body::from_stream(chunks)
+        });
+        let mut req = request();
+
+        let body = async_io::block_on(async {
+            let response = middleware.handle(&mut req, backend).await.unwrap();
+            response.into_body().into_bytes().await.unwrap()
+        });
+        assert_eq!(body.as_ref(), b"ticktickticktick");
+    }
+
+    #[test]
+    fn read_timeout_reports_stalled_body_as_timeout() {
+        let mut middleware = ReadTimeout::new(Duration::from_millis(20));
+        let backend = StreamingEndpoint(|| {
+            let first = stream::iter([Ok::<_, io::Error>(Bytes::from_static(b"first"))]);
+            let stall = stream::once(async {
+                Timer::after(Duration::from_secs(5)).await;
+                Ok::<_, io::Error>(Bytes::from_static(b"late"))
+            });
+            Body::from_stream(first.chain(stall))
+        });
+        let mut req = request();
+
+        let started = std::time::Instant::now();
+        let error = async_io::block_on(async {
+            let response = middleware.handle(&mut req, backend).await.unwrap();
+            response.into_body().into_bytes().await.unwrap_err()
+        });
+
+        assert!(crate::Error::from(error).is_timeout());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn read_timeout_starts_when_the_body_is_first_read() {
+        let mut middleware = ReadTimeout::new(Duration::from_millis(40));
+        let backend = StreamingEndpoint(|| {
+            Body::from_stream(stream::once(async {
+                Timer::after(Duration::from_millis(10)).await;
+                Ok::<_, io::Error>(Bytes::from_static(b"ready"))
+            }))
+        });
+        let mut req = request();
+
+        async_io::block_on(async {
+            let body = middleware
+                .handle(&mut req, backend)
+                .await
+                .unwrap()
+                .into_body();
+            // Reading late is not a stall: nothing was waiting on the body.
+            Timer::after(Duration::from_millis(80)).await;
+            assert_eq!(body.into_bytes().await.unwrap().as_ref(), b"ready");
+        });
+    }
+
+    #[test]
+    fn read_timeout_keeps_the_body_length() {
+        let mut middleware = ReadTimeout::new(Duration::from_millis(20));
+        let backend = StreamingEndpoint(|| Body::from_bytes("ready"));
+        let mut req = request();
+
+        async_io::block_on(async {
+            let body = middleware
+                .handle(&mut req, backend)
+                .await
+                .unwrap()
+                .into_body();
+            assert_eq!(body.len(), Some(5));
+            assert_eq!(body.into_bytes().await.unwrap().as_ref(), b"ready");
+        });
+    }
+
+    #[test]
+    fn read_timeout_does_not_bound_waiting_for_headers() {
+        let mut middleware = ReadTimeout::new(Duration::from_millis(5));
+        let backend = SlowEndpoint {
+            delay: Duration::from_millis(30),
+            status: StatusCode::OK,
+        };
+        let mut req = request();
+
+        let response = async_io::block_on(middleware.handle(&mut req, backend)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
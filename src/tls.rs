@@ -0,0 +1,815 @@
+//! TLS settings shared by the HTTP backends and websockets.
+//!
+//! One [`TlsConfig`] describes which servers to trust and how to present the
+//! client: extra root certificates, a client identity, the allowed protocol
+//! versions, public key pins, and switches that turn verification off. The
+//! same value configures [`HyperBackend`](crate::backend::HyperBackend), the
+//! curl backend and [`websocket`](crate::websocket) connections, so a private
+//! CA only needs to be set up once.
+//!
+//! ```rust,no_run
+//! # #[cfg(all(feature = "hyper-backend", feature = "ws"))]
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use zenwave::{
+//!     Client,
+//!     backend::HyperBackend,
+//!     tls::{Certificate, TlsConfig},
+//!     websocket::{self, WebSocketConfig},
+//! };
+//!
+//! let ca = Certificate::from_pem(&std::fs::read("ca.pem")?)?;
+//! let tls = TlsConfig::new().with_root_certificate(ca);
+//!
+//! let mut client = HyperBackend::new().with_tls(tls.clone());
+//! let _ = client.get("https://internal.example/")?.await;
+//! let socket = websocket::connect_with_config(
+//!     "wss://internal.example/feed",
+//!     WebSocketConfig::default().with_tls(tls),
+//! )
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::fmt;
+use std::io;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use sha2::{Digest, Sha256};
+
+/// Errors raised while preparing a TLS connection from a [`TlsConfig`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TlsError {
+    /// A PEM document is malformed or lacks the expected block.
+    #[error("invalid PEM: {0}")]
+    Pem(String),
+    /// A certificate is not valid DER.
+    #[error("malformed certificate")]
+    Certificate,
+    /// No TLS version lies between the configured minimum and maximum.
+    #[error("no TLS version satisfies the configured range")]
+    NoVersions,
+    /// The TLS implementation rejected the configuration.
+    #[error("TLS setup failed: {0}")]
+    Backend(#[source] Box<dyn core::error::Error + Send + Sync>),
+    /// The TLS handshake failed.
+    #[error("TLS handshake failed: {0}")]
+    Handshake(#[source] io::Error),
+    /// The server's public key matches none of the pins.
+    #[error("server public key does not match any pin")]
+    PinMismatch,
+}
+
+impl From<TlsError> for crate::Error {
+    fn from(error: TlsError) -> Self {
+        Self::Tls(Box::new(error))
+    }
+}
+
+/// An X.509 certificate, kept in DER form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    der: Vec<u8>,
+    /// Where the `SubjectPublicKeyInfo` lies in `der`.
+    spki: core::ops::Range<usize>,
+}
+
+impl Certificate {
+    /// Wrap a DER-encoded certificate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TlsError::Certificate`] when `der` is not a certificate.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> Result<Self, TlsError> {
+        let der = der.into();
+        let spki = spki(&der).ok_or(TlsError::Certificate)?;
+        let start = spki.as_ptr() as usize - der.as_ptr() as usize;
+        let spki = start..start + spki.len();
+        Ok(Self { der, spki })
+    }
+
+    /// Read the first certificate of a PEM document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `pem` holds no valid `CERTIFICATE` block.
+    pub fn from_pem(pem: &[u8]) -> Result<Self, TlsError> {
+        Self::from_pem_bundle(pem)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TlsError::Pem("no CERTIFICATE block".into()))
+    }
+
+    /// Read every certificate of a PEM document, such as a CA bundle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a `CERTIFICATE` block is malformed.
+    pub fn from_pem_bundle(pem: &[u8]) -> Result<Vec<Self>, TlsError> {
+        pem_blocks(pem, "CERTIFICATE")?
+            .into_iter()
+            .map(Self::from_der)
+            .collect()
+    }
+
+    /// The DER encoding.
+    #[must_use]
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// SHA-256 of the certificate's public key (its DER
+    /// `SubjectPublicKeyInfo`), the value [`TlsConfig::with_pinned_public_key`]
+    /// expects.
+    #[must_use]
+    pub fn public_key_sha256(&self) -> [u8; 32] {
+        Sha256::digest(&self.der[self.spki.clone()]).into()
+    }
+}
+
+/// A client certificate chain and its private key, presented to servers
+/// that ask for one.
+#[derive(Clone)]
+pub struct Identity {
+    chain: Vec<Certificate>,
+    /// PKCS#8 DER.
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "curl-backend"))]
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Identity")
+            .field("chain", &self.chain)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Identity {
+    /// Read a PEM certificate chain, leaf first, and a PEM PKCS#8 private
+    /// key (`BEGIN PRIVATE KEY`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when either document is malformed, or `key` holds no
+    /// PKCS#8 key.
+    pub fn from_pem(chain: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        let chain = Certificate::from_pem_bundle(chain)?;
+        if chain.is_empty() {
+            return Err(TlsError::Pem("no CERTIFICATE block".into()));
+        }
+        let key = pem_blocks(key, "PRIVATE KEY")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TlsError::Pem("no PRIVATE KEY block".into()))?;
+        // Without a TLS implementation the key is only validated.
+        #[cfg(not(any(feature = "native-tls", feature = "rustls", feature = "curl-backend")))]
+        let _ = key;
+        Ok(Self {
+            chain,
+            #[cfg(any(feature = "native-tls", feature = "rustls", feature = "curl-backend"))]
+            key,
+        })
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "curl-backend"))]
+    fn chain_pem(&self) -> String {
+        self.chain
+            .iter()
+            .map(|certificate| to_pem("CERTIFICATE", &certificate.der))
+            .collect()
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "curl-backend"))]
+    fn key_pem(&self) -> String {
+        to_pem("PRIVATE KEY", &self.key)
+    }
+}
+
+/// A TLS protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum TlsVersion {
+    /// TLS 1.2.
+    Tls12,
+    /// TLS 1.3.
+    Tls13,
+}
+
+/// TLS settings for outgoing connections.
+///
+/// The default trusts the system's root certificates, sends no client
+/// certificate and lets the TLS implementation pick the versions.
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct TlsConfig {
+    roots: Vec<Certificate>,
+    system_roots: bool,
+    identity: Option<Identity>,
+    min_version: Option<TlsVersion>,
+    max_version: Option<TlsVersion>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    sni: bool,
+    pins: Vec<[u8; 32]>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsConfig {
+    /// The default configuration.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            roots: Vec::new(),
+            system_roots: true,
+            identity: None,
+            min_version: None,
+            max_version: None,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            sni: true,
+            pins: Vec::new(),
+        }
+    }
+
+    /// Trust `certificate` as a root, in addition to the system roots.
+    ///
+    /// libcurl uses the configured roots instead of its CA bundle.
+    #[must_use]
+    pub fn with_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.roots.push(certificate);
+        self
+    }
+
+    /// Choose whether the system's root certificates are trusted. Enabled by
+    /// default; disable it to trust only the configured roots.
+    #[must_use]
+    pub const fn with_system_roots(mut self, enabled: bool) -> Self {
+        self.system_roots = enabled;
+        self
+    }
+
+    /// Present `identity` to servers asking for a client certificate.
+    #[must_use]
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Refuse versions older than `version`.
+    #[must_use]
+    pub const fn with_min_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = Some(version);
+        self
+    }
+
+    /// Refuse versions newer than `version`.
+    #[must_use]
+    pub const fn with_max_version(mut self, version: TlsVersion) -> Self {
+        self.max_version = Some(version);
+        self
+    }
+
+    /// Only accept servers whose leaf certificate has a public key with this
+    /// SHA-256 hash; see [`Certificate::public_key_sha256`]. Adding several
+    /// pins accepts any of them, which allows rotating keys.
+    ///
+    /// Pins are checked in addition to the usual verification.
+    #[must_use]
+    pub fn with_pinned_public_key(mut self, sha256: [u8; 32]) -> Self {
+        self.pins.push(sha256);
+        self
+    }
+
+    /// Accept any server certificate, including expired, self-signed and
+    /// foreign ones.
+    ///
+    /// This removes the protection TLS gives against impostors. Pins are
+    /// still enforced.
+    #[must_use]
+    pub const fn danger_accept_invalid_certs(mut self, enabled: bool) -> Self {
+        self.accept_invalid_certs = enabled;
+        self
+    }
+
+    /// Accept certificates issued for another host name.
+    #[must_use]
+    pub const fn danger_accept_invalid_hostnames(mut self, enabled: bool) -> Self {
+        self.accept_invalid_hostnames = enabled;
+        self
+    }
+
+    /// Choose whether the host name is sent in the handshake (Server Name
+    /// Indication). Enabled by default; some servers behind a shared address
+    /// pick the wrong certificate without it.
+    ///
+    /// libcurl always sends it.
+    #[must_use]
+    pub const fn with_sni(mut self, enabled: bool) -> Self {
+        self.sni = enabled;
+        self
+    }
+
+    /// Whether the configuration differs from [`TlsConfig::new`].
+    #[cfg(all(
+        feature = "hyper-backend",
+        not(any(feature = "native-tls", feature = "rustls"))
+    ))]
+    pub(crate) const fn is_customized(&self) -> bool {
+        !self.roots.is_empty()
+            || !self.system_roots
+            || self.identity.is_some()
+            || self.min_version.is_some()
+            || self.max_version.is_some()
+            || self.accept_invalid_certs
+            || self.accept_invalid_hostnames
+            || !self.sni
+            || !self.pins.is_empty()
+    }
+
+    /// Check the leaf certificate `der` against the pins.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    fn check_pins(&self, der: &[u8]) -> Result<(), TlsError> {
+        if self.pins.is_empty() {
+            return Ok(());
+        }
+        let spki = spki(der).ok_or(TlsError::Certificate)?;
+        let hash: [u8; 32] = Sha256::digest(spki).into();
+        if self.pins.contains(&hash) {
+            Ok(())
+        } else {
+            Err(TlsError::PinMismatch)
+        }
+    }
+
+    /// Run a client handshake over `stream` with rustls, offering the
+    /// `alpn` protocols.
+    #[cfg(feature = "rustls")]
+    pub(crate) async fn connect_rustls<S>(
+        &self,
+        host: &str,
+        stream: S,
+        alpn: &[&[u8]],
+    ) -> Result<futures_rustls::client::TlsStream<S>, TlsError>
+    where
+        S: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin,
+    {
+        let mut config = rustls::ClientConfig::try_from(self)?;
+        config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+        let server_name = rustls::pki_types::ServerName::try_from(host.to_owned())
+            .map_err(|error| TlsError::Backend(Box::new(error)))?;
+        futures_rustls::TlsConnector::from(std::sync::Arc::new(config))
+            .connect(server_name, stream)
+            .await
+            .map_err(TlsError::Handshake)
+    }
+
+    /// Run a client handshake over `stream` with the platform's TLS.
+    #[cfg(feature = "native-tls")]
+    #[allow(dead_code)] // Unused on non-Apple platforms when rustls is enabled too
+    pub(crate) async fn connect_native<S>(
+        &self,
+        host: &str,
+        stream: S,
+    ) -> Result<async_native_tls::TlsStream<S>, TlsError>
+    where
+        S: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin,
+    {
+        let stream = async_native_tls::TlsConnector::from(native_builder(self)?)
+            .connect(host, stream)
+            .await
+            .map_err(|error| TlsError::Handshake(io::Error::other(error)))?;
+        if !self.pins.is_empty() {
+            let leaf = stream
+                .peer_certificate()
+                .map_err(|error| TlsError::Backend(Box::new(error)))?
+                .ok_or(TlsError::PinMismatch)?;
+            let der = leaf
+                .to_der()
+                .map_err(|error| TlsError::Backend(Box::new(error)))?;
+            self.check_pins(&der)?;
+        }
+        Ok(stream)
+    }
+
+    /// Map the configuration onto a curl handle.
+    #[cfg(feature = "curl-backend")]
+    pub(crate) fn apply_to_curl<H>(
+        &self,
+        easy: &mut curl::easy::Easy2<H>,
+    ) -> Result<(), curl::Error> {
+        use curl::easy::SslVersion;
+
+        let version = |version| match version {
+            Some(TlsVersion::Tls12) => SslVersion::Tlsv12,
+            Some(TlsVersion::Tls13) => SslVersion::Tlsv13,
+            None => SslVersion::Default,
+        };
+
+        if !self.roots.is_empty() {
+            let bundle: String = self
+                .roots
+                .iter()
+                .map(|root| to_pem("CERTIFICATE", &root.der))
+                .collect();
+            easy.ssl_cainfo_blob(bundle.as_bytes())?;
+        }
+        if let Some(identity) = &self.identity {
+            easy.ssl_cert_blob(identity.chain_pem().as_bytes())?;
+            easy.ssl_cert_type("PEM")?;
+            easy.ssl_key_blob(identity.key_pem().as_bytes())?;
+            easy.ssl_key_type("PEM")?;
+        }
+        if self.min_version.is_some() || self.max_version.is_some() {
+            easy.ssl_min_max_version(version(self.min_version), version(self.max_version))?;
+        }
+        if self.accept_invalid_certs {
+            easy.ssl_verify_peer(false)?;
+        }
+        if self.accept_invalid_certs || self.accept_invalid_hostnames {
+            easy.ssl_verify_host(false)?;
+        }
+        if !self.pins.is_empty() {
+            let pins: Vec<String> = self
+                .pins
+                .iter()
+                .map(|pin| format!("sha256//{}", BASE64.encode(pin)))
+                .collect();
+            easy.pinned_public_key(&pins.join(";"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl TryFrom<&TlsConfig> for rustls::ClientConfig {
+    type Error = TlsError;
+
+    fn try_from(config: &TlsConfig) -> Result<Self, TlsError> {
+        use rustls::{
+            RootCertStore,
+            pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+            version::{TLS12, TLS13},
+        };
+        use std::sync::Arc;
+
+        let backend = |error: rustls::Error| TlsError::Backend(Box::new(error));
+
+        let versions: Vec<_> = [(TlsVersion::Tls12, &TLS12), (TlsVersion::Tls13, &TLS13)]
+            .into_iter()
+            .filter(|(version, _)| {
+                config.min_version.is_none_or(|min| *version >= min)
+                    && config.max_version.is_none_or(|max| *version <= max)
+            })
+            .map(|(_, version)| version)
+            .collect();
+        if versions.is_empty() {
+            return Err(TlsError::NoVersions);
+        }
+
+        let mut roots = RootCertStore::empty();
+        if config.system_roots {
+            for certificate in rustls_native_certs::load_native_certs().certs {
+                // Skip certificates rustls cannot use rather than failing.
+                let _ = roots.add(certificate);
+            }
+            if roots.is_empty() {
+                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+        }
+        for root in &config.roots {
+            roots
+                .add(CertificateDer::from(root.der.clone()))
+                .map_err(backend)?;
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = Self::builder_with_provider(Arc::clone(&provider))
+            .with_protocol_versions(&versions)
+            .map_err(backend)?;
+        let builder = if config.accept_invalid_certs
+            || config.accept_invalid_hostnames
+            || !config.pins.is_empty()
+        {
+            let webpki = if config.accept_invalid_certs {
+                None
+            } else {
+                Some(
+                    rustls::client::WebPkiServerVerifier::builder_with_provider(
+                        Arc::new(roots),
+                        Arc::clone(&provider),
+                    )
+                    .build()
+                    .map_err(|error| TlsError::Backend(Box::new(error)))?,
+                )
+            };
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier::Verifier {
+                    webpki,
+                    accept_invalid_hostnames: config.accept_invalid_hostnames,
+                    config: config.clone(),
+                    provider,
+                }))
+        } else {
+            builder.with_root_certificates(roots)
+        };
+
+        let mut client = match &config.identity {
+            Some(identity) => builder
+                .with_client_auth_cert(
+                    identity
+                        .chain
+                        .iter()
+                        .map(|certificate| CertificateDer::from(certificate.der.clone()))
+                        .collect(),
+                    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(identity.key.clone())),
+                )
+                .map_err(backend)?,
+            None => builder.with_no_client_auth(),
+        };
+        client.enable_sni = config.sni;
+        Ok(client)
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod verifier {
+    use std::sync::Arc;
+
+    use rustls::{
+        CertificateError, DigitallySignedStruct, Error, SignatureScheme,
+        client::{
+            WebPkiServerVerifier,
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        },
+        crypto::CryptoProvider,
+        pki_types::{CertificateDer, ServerName, UnixTime},
+    };
+
+    use super::TlsConfig;
+
+    /// Verifies like webpki, minus what the danger switches turn off, then
+    /// checks the pins.
+    #[derive(Debug)]
+    pub struct Verifier {
+        /// `None` when every certificate is accepted.
+        pub webpki: Option<Arc<WebPkiServerVerifier>>,
+        pub accept_invalid_hostnames: bool,
+        pub config: TlsConfig,
+        pub provider: Arc<CryptoProvider>,
+    }
+
+    impl ServerCertVerifier for Verifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> Result<ServerCertVerified, Error> {
+            if let Some(webpki) = &self.webpki {
+                match webpki.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    ocsp_response,
+                    now,
+                ) {
+                    Ok(_) => {}
+                    Err(Error::InvalidCertificate(
+                        CertificateError::NotValidForName
+                        | CertificateError::NotValidForNameContext { .. },
+                    )) if self.accept_invalid_hostnames => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            self.config
+                .check_pins(end_entity)
+                .map_err(|error| Error::General(error.to_string()))?;
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.provider
+                .signature_verification_algorithms
+                .supported_schemes()
+        }
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl TryFrom<&TlsConfig> for native_tls::TlsConnector {
+    type Error = TlsError;
+
+    fn try_from(config: &TlsConfig) -> Result<Self, TlsError> {
+        native_builder(config)?
+            .build()
+            .map_err(|error| TlsError::Backend(Box::new(error)))
+    }
+}
+
+/// A native-tls builder set up from `config`.
+#[cfg(feature = "native-tls")]
+fn native_builder(config: &TlsConfig) -> Result<native_tls::TlsConnectorBuilder, TlsError> {
+    use native_tls::Protocol;
+
+    let backend = |error: native_tls::Error| TlsError::Backend(Box::new(error));
+    let protocol = |version| match version {
+        TlsVersion::Tls12 => Protocol::Tlsv12,
+        TlsVersion::Tls13 => Protocol::Tlsv13,
+    };
+    if let (Some(min), Some(max)) = (config.min_version, config.max_version)
+        && min > max
+    {
+        return Err(TlsError::NoVersions);
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    builder
+        .disable_built_in_roots(!config.system_roots)
+        .min_protocol_version(config.min_version.map(protocol))
+        .max_protocol_version(config.max_version.map(protocol))
+        .danger_accept_invalid_certs(config.accept_invalid_certs)
+        .danger_accept_invalid_hostnames(config.accept_invalid_hostnames)
+        .use_sni(config.sni);
+    for root in &config.roots {
+        builder
+            .add_root_certificate(native_tls::Certificate::from_der(&root.der).map_err(backend)?);
+    }
+    if let Some(identity) = &config.identity {
+        builder.identity(
+            native_tls::Identity::from_pkcs8(
+                identity.chain_pem().as_bytes(),
+                identity.key_pem().as_bytes(),
+            )
+            .map_err(backend)?,
+        );
+    }
+    Ok(builder)
+}
+
+/// Decode every `label` block of a PEM document.
+fn pem_blocks(pem: &[u8], label: &str) -> Result<Vec<Vec<u8>>, TlsError> {
+    let pem = core::str::from_utf8(pem).map_err(|error| TlsError::Pem(error.to_string()))?;
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let mut blocks = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(&begin) {
+        let body = &rest[start + begin.len()..];
+        let stop = body
+            .find(&end)
+            .ok_or_else(|| TlsError::Pem(format!("unterminated {label} block")))?;
+        let encoded: String = body[..stop].split_whitespace().collect();
+        blocks.push(
+            BASE64
+                .decode(encoded)
+                .map_err(|error| TlsError::Pem(error.to_string()))?,
+        );
+        rest = &body[stop + end.len()..];
+    }
+    Ok(blocks)
+}
+
+#[cfg(any(feature = "native-tls", feature = "curl-backend"))]
+fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = BASE64.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(core::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END ");
+    pem.push_str(label);
+    pem.push_str("-----\n");
+    pem
+}
+
+/// A DER element: its tag, its full encoding, its contents, and the bytes
+/// after it.
+struct Der<'a> {
+    tag: u8,
+    whole: &'a [u8],
+    contents: &'a [u8],
+    rest: &'a [u8],
+}
+
+fn der(input: &[u8]) -> Option<Der<'_>> {
+    let (&tag, after_tag) = input.split_first()?;
+    let (&first, after_length) = after_tag.split_first()?;
+    let (length, after_length) = if first < 0x80 {
+        (usize::from(first), after_length)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || after_length.len() < count {
+            return None;
+        }
+        let (bytes, after_length) = after_length.split_at(count);
+        let length = bytes
+            .iter()
+            .fold(0, |length, &byte| (length << 8) | usize::from(byte));
+        (length, after_length)
+    };
+    if after_length.len() < length {
+        return None;
+    }
+    let header = input.len() - after_length.len();
+    let (contents, rest) = after_length.split_at(length);
+    Some(Der {
+        tag,
+        whole: &input[..header + length],
+        contents,
+        rest,
+    })
+}
+
+/// The `SubjectPublicKeyInfo` of a DER certificate, as found in
+/// `Certificate.tbsCertificate` (RFC 5280 §4.1).
+fn spki(certificate: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let certificate = der(certificate).filter(|element| element.tag == SEQUENCE)?;
+    let tbs = der(certificate.contents).filter(|element| element.tag == SEQUENCE)?;
+    let mut fields = tbs.contents;
+    if der(fields)?.tag == VERSION {
+        fields = der(fields)?.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject.
+    for _ in 0..5 {
+        fields = der(fields)?.rest;
+    }
+    der(fields)
+        .filter(|element| element.tag == SEQUENCE)
+        .map(|element| element.whole)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Certificate, Identity, TlsError};
+    use base64::Engine;
+
+    const SERVER: &[u8] = include_bytes!("../tests/fixtures/tls/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("../tests/fixtures/tls/server.key");
+
+    #[test]
+    fn public_key_hash_matches_openssl() {
+        // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der
+        //   | openssl dgst -sha256 -binary | base64
+        let certificate = Certificate::from_pem(SERVER).unwrap();
+        assert_eq!(
+            super::BASE64.encode(certificate.public_key_sha256()),
+            "j9UPKLHdwmVg69C/c3cwWk8ryRdsPbq75dBYQnttaAA="
+        );
+    }
+
+    #[test]
+    fn malformed_pem_is_rejected() {
+        assert!(matches!(
+            Certificate::from_pem(b"no certificate here"),
+            Err(TlsError::Pem(_))
+        ));
+        assert!(matches!(
+            Certificate::from_pem(b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----"),
+            Err(TlsError::Certificate)
+        ));
+        assert!(Identity::from_pem(SERVER, SERVER).is_err());
+        assert!(Identity::from_pem(SERVER, SERVER_KEY).is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+//! W3C Trace Context propagation.
+//!
+//! Tracing systems such as OpenTelemetry join the spans of one operation
+//! across services through the `traceparent` and `tracestate` headers
+//! (<https://www.w3.org/TR/trace-context/>). [`PropagateTrace`] adds them to
+//! outgoing requests from a [`TraceContext`] describing the caller's current
+//! span, so the server's spans become its children.
+//!
+//! ```rust,no_run
+//! # async fn example(incoming: &str) -> Result<(), zenwave::Error> {
+//! use zenwave::{Client, client, trace::TraceContext};
+//!
+//! // For example the `traceparent` of the request being handled.
+//! let context = TraceContext::parse(incoming).expect("valid traceparent");
+//! let mut client = client().propagate_trace(context);
+//! let _ = client.get("https://example.com/")?.await;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::fmt::{self, Write};
+
+use http_kit::{
+    Endpoint, Middleware, Request, Response,
+    header::{HeaderName, HeaderValue},
+    middleware::MiddlewareError,
+};
+
+/// The `traceparent` header.
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// The `tracestate` header.
+pub const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// The sampled bit of the trace flags.
+const SAMPLED: u8 = 0x01;
+
+/// A span to continue in outgoing requests.
+///
+/// Attach one to a single request as an extension to override the context
+/// given to [`PropagateTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    flags: u8,
+    tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// A sampled context for span `span_id` of trace `trace_id`, or `None`
+    /// when either is all zeros, which the specification reserves as
+    /// invalid.
+    #[must_use]
+    pub fn new(trace_id: [u8; 16], span_id: [u8; 8]) -> Option<Self> {
+        (trace_id != [0; 16] && span_id != [0; 8]).then_some(Self {
+            trace_id,
+            span_id,
+            flags: SAMPLED,
+            tracestate: None,
+        })
+    }
+
+    /// Parse a `traceparent` header value, such as one received by a server.
+    ///
+    /// Versions other than `00` are read as version `00`, as the
+    /// specification asks, and sent on as `00`.
+    #[must_use]
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut fields = traceparent.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let span_id = fields.next()?;
+        let flags = fields.next()?;
+        // Version 00 has exactly four fields; later versions may add more.
+        if version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        let [flags] = hex::<1>(flags)?;
+        let _: [u8; 1] = hex(version)?;
+        let mut context = Self::new(hex(trace_id)?, hex(span_id)?)?;
+        context.flags = flags;
+        Some(context)
+    }
+
+    /// Set whether the caller recorded its span. Sampled by default.
+    #[must_use]
+    pub const fn sampled(mut self, sampled: bool) -> Self {
+        self.flags = if sampled {
+            self.flags | SAMPLED
+        } else {
+            self.flags & !SAMPLED
+        };
+        self
+    }
+
+    /// Send `tracestate`, the vendor-specific part of the context, along.
+    #[must_use]
+    pub fn with_tracestate(mut self, tracestate: impl Into<String>) -> Self {
+        self.tracestate = Some(tracestate.into());
+        self
+    }
+
+    /// The trace ID.
+    #[must_use]
+    pub const fn trace_id(&self) -> [u8; 16] {
+        self.trace_id
+    }
+
+    /// The ID of the caller's span.
+    #[must_use]
+    pub const fn span_id(&self) -> [u8; 8] {
+        self.span_id
+    }
+
+    /// Whether the sampled flag is set.
+    #[must_use]
+    pub const fn is_sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+
+    /// The `tracestate` sent along, if any.
+    #[must_use]
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// The `traceparent` header value: `00-<trace-id>-<span-id>-<flags>`.
+    #[must_use]
+    pub fn traceparent(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("00-")?;
+        write_hex(f, &self.trace_id)?;
+        f.write_char('-')?;
+        write_hex(f, &self.span_id)?;
+        write!(f, "-{:02x}", self.flags)
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+}
+
+/// Decode exactly `N` bytes of lowercase hex.
+fn hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    if text.len() != N * 2 || !text.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(text.as_bytes().chunks(2)) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Middleware adding `traceparent`, and `tracestate` when set, to requests.
+///
+/// A [`TraceContext`] extension on the request takes precedence over the
+/// configured context. Requests that already carry a `traceparent` are sent
+/// as they are.
+#[derive(Debug, Clone)]
+pub struct PropagateTrace {
+    context: TraceContext,
+}
+
+impl PropagateTrace {
+    /// Create a middleware propagating `context`.
+    #[must_use]
+    pub const fn new(context: TraceContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Middleware for PropagateTrace {
+    type Error = crate::Error;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        if !request.headers().contains_key(TRACEPARENT) {
+            let context = request
+                .extensions()
+                .get::<TraceContext>()
+                .unwrap_or(&self.context);
+            let traceparent = HeaderValue::try_from(context.traceparent())
+                .expect("traceparent is made of hex digits and dashes");
+            let tracestate = context
+                .tracestate
+                .as_deref()
+                .map(HeaderValue::try_from)
+                .transpose()
+                .map_err(|error| {
+                    MiddlewareError::Middleware(crate::Error::InvalidRequest(format!(
+                        "invalid tracestate: {error}"
+                    )))
+                })?;
+            let headers = request.headers_mut();
+            headers.insert(TRACEPARENT, traceparent);
+            if let Some(tracestate) = tracestate {
+                headers.insert(TRACESTATE, tracestate);
+            }
+        }
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraceContext;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn traceparent_round_trips() {
+        let context = TraceContext::parse(EXAMPLE).unwrap();
+        assert!(context.is_sampled());
+        assert_eq!(
+            context.span_id(),
+            [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+        );
+        assert_eq!(context.traceparent(), EXAMPLE);
+        assert_eq!(
+            context.sampled(false).traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+    }
+
+    #[test]
+    fn invalid_traceparents_are_rejected() {
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(invalid), None, "{invalid:?}");
+        }
+        // Future versions may append fields.
+        assert!(TraceContext::parse(&format!("01{}-extra", &EXAMPLE[2..])).is_some());
+    }
+}
@@ -0,0 +1,110 @@
+//! URI templates with `{name}` placeholders.
+//!
+//! [`expand_uri`] implements simple string expansion, level 1 of RFC 6570:
+//! each `{name}` is replaced by its value with everything but unreserved
+//! characters percent-encoded, so a value can never add path segments, a
+//! query or a fragment to the URI.
+//!
+//! ```rust
+//! use zenwave::uri_template::expand_uri;
+//!
+//! let uri = expand_uri("/users/{id}/posts", &[("id", "a/b c")]).unwrap();
+//! assert_eq!(uri, "/users/a%2Fb%20c/posts");
+//! ```
+
+use core::fmt::Write;
+
+/// Replace every `{name}` in `template` with the percent-encoded value of
+/// `name` in `params`.
+///
+/// Text outside the braces is copied unchanged.
+///
+/// # Errors
+///
+/// Returns [`crate::Error::InvalidUri`] when a placeholder has no value in
+/// `params`, is not closed, or is not a plain variable name (the operators
+/// of higher RFC 6570 levels, such as `{+path}`, are not supported).
+pub fn expand_uri(template: &str, params: &[(&str, &str)]) -> Result<String, crate::Error> {
+    let invalid =
+        |reason: String| crate::Error::InvalidUri(format!("URI template `{template}`: {reason}"));
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        expanded.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}') else {
+            return Err(invalid("unclosed `{`".to_string()));
+        };
+        let name = &rest[open + 1..open + close];
+        if name.is_empty() || !name.bytes().all(is_varchar) {
+            return Err(invalid(format!("unsupported expression `{{{name}}}`")));
+        }
+        let Some((_, value)) = params.iter().find(|(key, _)| *key == name) else {
+            return Err(invalid(format!("missing parameter `{name}`")));
+        };
+        encode_into(&mut expanded, value);
+        rest = &rest[open + close + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Characters of a variable name (RFC 6570 §2.3), leaving out the
+/// percent-encoded form.
+const fn is_varchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.'
+}
+
+/// Append `value` with every byte but the unreserved characters
+/// (RFC 3986 §2.3) percent-encoded.
+fn encode_into(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(char::from(byte));
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_uri;
+
+    #[test]
+    fn reserved_characters_are_encoded() {
+        let uri = expand_uri(
+            "https://api.example/search/{term}?page={page}",
+            &[("term", "a/b?c#d&e=f %"), ("page", "2")],
+        )
+        .unwrap();
+        assert_eq!(
+            uri,
+            "https://api.example/search/a%2Fb%3Fc%23d%26e%3Df%20%25?page=2"
+        );
+    }
+
+    #[test]
+    fn unreserved_and_non_ascii_values() {
+        let uri = expand_uri("/{a}/{b}", &[("a", "A-z_0.9~"), ("b", "café")]).unwrap();
+        assert_eq!(uri, "/A-z_0.9~/caf%C3%A9");
+    }
+
+    #[test]
+    fn repeated_placeholders_and_plain_templates() {
+        assert_eq!(expand_uri("/{id}/{id}", &[("id", "7")]).unwrap(), "/7/7");
+        assert_eq!(expand_uri("/plain", &[]).unwrap(), "/plain");
+    }
+
+    #[test]
+    fn malformed_templates_error_clearly() {
+        let message = |template: &str| {
+            expand_uri(template, &[("id", "1")])
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(message("/users/{name}").contains("missing parameter `name`"));
+        assert!(message("/users/{id").contains("unclosed"));
+        assert!(message("/users/{+id}").contains("unsupported expression `{+id}`"));
+        assert!(message("/users/{}").contains("unsupported expression `{}`"));
+    }
+}
@@ -1,8 +1,12 @@
 pub use http_kit::ws::*;
 
-use http_kit::{HttpError, StatusCode};
+use core::time::Duration;
+
+use http_kit::{HttpError, Method, StatusCode, Uri, header::HeaderMap};
 use serde::Serialize;
 
+use crate::retry::{RetryCause, RetryEventKind, RetryPolicy, Stopwatch, sleep};
+
 /// Errors returned by websocket operations.
 #[derive(Debug, thiserror::Error)]
 pub enum WebSocketError {
@@ -23,6 +27,23 @@ pub enum WebSocketError {
     ConnectionFailed(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl WebSocketError {
+    /// Whether the connection failed for a reason that may go away on its
+    /// own, such as a refused connection, a DNS failure, or a handshake
+    /// answered with `429` or `503`.
+    ///
+    /// Handshake rejections like `403 Forbidden`, TLS failures and invalid
+    /// URIs are not transient. Browsers do not say why a connection failed,
+    /// so on wasm every connection failure counts as transient.
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ConnectionFailed(source) => is_transient(source.as_ref()),
+            _ => false,
+        }
+    }
+}
+
 impl HttpError for WebSocketError {
     fn status(&self) -> StatusCode {
         match self {
@@ -63,6 +84,20 @@ pub struct WebSocketConfig {
     /// Maximum incoming websocket frame size in bytes.
     /// `None` means no limit.
     pub max_frame_size: Option<usize>,
+
+    /// Maximum number of 3xx responses to the upgrade request that are
+    /// followed. `0`, the default, fails on the first one.
+    pub max_redirects: usize,
+
+    /// Extra headers sent with the upgrade request, such as
+    /// `Authorization`. Browsers do not allow setting them, so they are
+    /// ignored on wasm.
+    pub headers: HeaderMap,
+
+    /// TLS settings for `wss` connections. Browsers manage TLS themselves,
+    /// so this is ignored on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tls: crate::tls::TlsConfig,
 }
 
 const DEFAULT_MAX_MESSAGE_SIZE: Option<usize> = Some(64 << 20);
@@ -73,6 +108,10 @@ impl Default for WebSocketConfig {
         Self {
             max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
             max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_redirects: 0,
+            headers: HeaderMap::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: crate::tls::TlsConfig::new(),
         }
     }
 }
@@ -99,6 +138,38 @@ impl WebSocketConfig {
         self.max_frame_size = max_frame_size;
         self
     }
+
+    /// Follow up to `max_redirects` redirects answering the upgrade request.
+    ///
+    /// A `3xx` handshake response with a `Location` is retried against the
+    /// new URL, with `http` and `https` targets mapped to `ws` and `wss`.
+    /// Like [`FollowRedirect`](crate::redirect::FollowRedirect), a hop to
+    /// another origin drops the `Authorization` header. The URLs followed are
+    /// available from [`WebSocket::redirects`]. Browsers never follow
+    /// websocket redirects, so this has no effect on wasm.
+    #[must_use]
+    pub const fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Send `headers` with the upgrade request.
+    #[must_use]
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Use `tls` for `wss` connections, such as the [`TlsConfig`] given to
+    /// the HTTP backend.
+    ///
+    /// [`TlsConfig`]: crate::tls::TlsConfig
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_tls(mut self, tls: crate::tls::TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
 }
 
 #[allow(clippy::result_large_err)]
@@ -109,6 +180,100 @@ where
     serde_json::to_string(value).map_err(WebSocketError::FailToEncodePayload)
 }
 
+/// Establish a websocket connection, retrying transient failures as
+/// `policy` allows.
+///
+/// Only errors for which [`WebSocketError::is_transient`] holds are retried;
+/// the hook set with [`RetryPolicy::on_retry`] is called before each retry
+/// and when giving up.
+///
+/// ```rust,no_run
+/// # async fn example() -> Result<(), zenwave::websocket::WebSocketError> {
+/// use std::time::Duration;
+/// use zenwave::{
+///     retry::RetryPolicy,
+///     websocket::{WebSocketConfig, connect_with_retry},
+/// };
+///
+/// let policy = RetryPolicy::new(5).max_elapsed(Duration::from_secs(30));
+/// let socket = connect_with_retry("wss://example.com/feed", WebSocketConfig::default(), policy).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns the first error that is not transient, or the last error once
+/// retries or the policy's time budget are exhausted.
+pub async fn connect_with_retry(
+    uri: impl AsRef<str>,
+    config: WebSocketConfig,
+    mut policy: RetryPolicy,
+) -> Result<WebSocket, WebSocketError> {
+    let uri = uri.as_ref();
+    // Only used to describe retries; an unparsable URI fails the first attempt.
+    let event_uri: Uri = uri.parse().unwrap_or_default();
+    let started = Stopwatch::start();
+    let mut attempts = 0;
+    loop {
+        let error = match connect_with_config(uri, config.clone()).await {
+            Ok(socket) => return Ok(socket),
+            Err(error) if error.is_transient() => error,
+            Err(error) => return Err(error),
+        };
+
+        attempts += 1;
+        let delay = policy.backoff(u32::try_from(attempts).unwrap_or(u32::MAX));
+        let cause = RetryCause::Error(&error);
+        if attempts > policy.max_retries() || policy.exceeds_budget(&started, delay) {
+            let kind = RetryEventKind::GivingUp;
+            policy.notify(
+                kind,
+                attempts,
+                cause,
+                Duration::ZERO,
+                &Method::GET,
+                &event_uri,
+            );
+            return Err(error);
+        }
+        let kind = RetryEventKind::Retrying;
+        policy.notify(kind, attempts, cause, delay, &Method::GET, &event_uri);
+        sleep(delay).await;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_transient(source: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    use async_tungstenite::tungstenite::Error as HandshakeError;
+    use std::io;
+
+    // TLS failures are wrapped in `Other`, and bad hosts or ports are
+    // reported as `InvalidInput`; neither is fixed by retrying.
+    let io_transient = |error: &io::Error| {
+        !matches!(
+            error.kind(),
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData | io::ErrorKind::Other
+        )
+    };
+    if let Some(error) = source.downcast_ref::<io::Error>() {
+        return io_transient(error);
+    }
+    match source.downcast_ref::<HandshakeError>() {
+        Some(HandshakeError::Io(error)) => io_transient(error),
+        Some(HandshakeError::Http(response)) => matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+const fn is_transient(_source: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    true
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 mod native {
     use async_lock::Mutex;
@@ -117,15 +282,17 @@ mod native {
         WebSocketReceiver as AsyncReceiver, WebSocketSender as AsyncSender, WebSocketStream,
         client_async_with_config,
         tungstenite::{
-            Message as TungsteniteMessage, Utf8Bytes,
+            Error as HandshakeError, Message as TungsteniteMessage, Utf8Bytes,
+            client::IntoClientRequest, handshake::client::Response as HandshakeResponse,
             protocol::WebSocketConfig as TungsteniteConfig,
         },
     };
     use futures_io::{AsyncRead, AsyncWrite};
     use futures_util::StreamExt;
-    use http_kit::utils::{ByteStr, Bytes};
-    #[cfg(feature = "rustls")]
-    use rustls::pki_types::ServerName;
+    use http_kit::{
+        header::{AUTHORIZATION, LOCATION},
+        utils::{ByteStr, Bytes},
+    };
     use std::{
         fmt, io,
         pin::Pin,
@@ -135,6 +302,7 @@ mod native {
     use url::Url;
 
     use super::{WebSocketConfig, WebSocketError, WebSocketMessage, serialize_payload};
+    use crate::tls::TlsConfig;
 
     type NativeSocket = WebSocketStream<MaybeTlsStream>;
     type NativeSender = AsyncSender<MaybeTlsStream>;
@@ -213,6 +381,7 @@ mod native {
     pub struct WebSocket {
         sender: WebSocketSender,
         receiver: WebSocketReceiver,
+        redirects: Vec<Url>,
     }
 
     impl fmt::Debug for WebSocket {
@@ -269,25 +438,64 @@ mod native {
         uri: impl AsRef<str>,
         websocket_config: WebSocketConfig,
     ) -> Result<WebSocket, WebSocketError> {
-        let url = Url::parse(uri.as_ref())?;
-        match url.scheme() {
-            "ws" | "wss" => {}
-            other => return Err(WebSocketError::UnsupportedScheme(other.to_string())),
-        }
-        let request: String = url.into();
+        let mut url = Url::parse(uri.as_ref())?;
         let mut config = TungsteniteConfig::default();
         config.max_message_size = websocket_config.max_message_size;
         config.max_frame_size = websocket_config.max_frame_size;
-        let stream = connect_stream(uri.as_ref()).await?;
-        let (ws_stream, _) = client_async_with_config(request, stream, Some(config))
-            .await
-            .map_err(|e| WebSocketError::ConnectionFailed(Box::new(e)))?;
+        let mut headers = websocket_config.headers;
+        let mut redirects = Vec::new();
+        loop {
+            match url.scheme() {
+                "ws" | "wss" => {}
+                other => return Err(WebSocketError::UnsupportedScheme(other.to_string())),
+            }
+            let mut request = url
+                .as_str()
+                .into_client_request()
+                .map_err(|e| WebSocketError::ConnectionFailed(Box::new(e)))?;
+            request.headers_mut().extend(headers.clone());
+            let stream = connect_stream(&url, &websocket_config.tls).await?;
+            let error = match client_async_with_config(request, stream, Some(config)).await {
+                Ok((ws_stream, _)) => return Ok(WebSocket::from_socket(ws_stream, redirects)),
+                Err(error) => error,
+            };
+
+            let target = match &error {
+                HandshakeError::Http(response)
+                    if redirects.len() < websocket_config.max_redirects =>
+                {
+                    redirect_target(&url, response)
+                }
+                _ => None,
+            };
+            let Some(target) = target else {
+                return Err(WebSocketError::ConnectionFailed(Box::new(error)));
+            };
+            if target.origin() != url.origin() {
+                headers.remove(AUTHORIZATION);
+            }
+            redirects.push(target.clone());
+            url = target;
+        }
+    }
 
-        Ok(WebSocket::from_socket(ws_stream))
+    /// The websocket URL a `3xx` handshake response points to, if any.
+    fn redirect_target(current: &Url, response: &HandshakeResponse) -> Option<Url> {
+        if !response.status().is_redirection() {
+            return None;
+        }
+        let location = response.headers().get(LOCATION)?.to_str().ok()?;
+        let mut target = current.join(location).ok()?;
+        let scheme = match target.scheme() {
+            "http" => "ws",
+            "https" => "wss",
+            _ => return Some(target),
+        };
+        target.set_scheme(scheme).ok()?;
+        Some(target)
     }
 
-    async fn connect_stream(uri: &str) -> Result<MaybeTlsStream, WebSocketError> {
-        let url = Url::parse(uri)?;
+    async fn connect_stream(url: &Url, tls: &TlsConfig) -> Result<MaybeTlsStream, WebSocketError> {
         let host = url.host_str().ok_or_else(|| {
             WebSocketError::ConnectionFailed(Box::new(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -306,7 +514,7 @@ mod native {
 
         match url.scheme() {
             "ws" => Ok(MaybeTlsStream::Plain(stream)),
-            "wss" => connect_secure(host, stream).await,
+            "wss" => connect_secure(host, stream, tls).await,
             other => Err(WebSocketError::UnsupportedScheme(other.to_string())),
         }
     }
@@ -314,74 +522,36 @@ mod native {
     async fn connect_secure(
         host: &str,
         stream: TcpStream,
+        tls: &TlsConfig,
     ) -> Result<MaybeTlsStream, WebSocketError> {
+        let failed = |error| WebSocketError::ConnectionFailed(Box::new(io::Error::other(error)));
+
         #[cfg(feature = "rustls")]
         {
-            return connect_rustls(host, stream).await;
+            let stream = tls
+                .connect_rustls(host, stream, &[])
+                .await
+                .map_err(failed)?;
+            Ok(MaybeTlsStream::Rustls(Box::new(stream)))
         }
 
         #[cfg(all(not(feature = "rustls"), feature = "native-tls"))]
         {
-            return connect_native_tls(host, stream).await;
+            let stream = tls.connect_native(host, stream).await.map_err(failed)?;
+            Ok(MaybeTlsStream::Native(stream))
         }
 
         #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
         {
-            let _ = host;
-            let _ = stream;
-            Err(WebSocketError::ConnectionFailed(Box::new(
-                io::Error::other("wss requires either the `rustls` or `native-tls` feature"),
+            let _ = (host, stream, tls);
+            Err(failed(crate::tls::TlsError::Backend(
+                "wss requires either the `rustls` or `native-tls` feature".into(),
             )))
         }
     }
 
-    #[cfg(feature = "rustls")]
-    async fn connect_rustls(
-        host: &str,
-        stream: TcpStream,
-    ) -> Result<MaybeTlsStream, WebSocketError> {
-        use std::sync::Arc as SyncArc;
-
-        use futures_rustls::TlsConnector;
-
-        let mut root_store = rustls::RootCertStore::empty();
-        let cert_result = rustls_native_certs::load_native_certs();
-        for cert in cert_result.certs {
-            let _ = root_store.add(cert);
-        }
-
-        if root_store.is_empty() {
-            root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-        }
-
-        let config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-        let connector = TlsConnector::from(SyncArc::new(config));
-        let server_name = ServerName::try_from(host.to_string())
-            .map_err(|error| WebSocketError::ConnectionFailed(Box::new(io::Error::other(error))))?;
-        let stream = connector
-            .connect(server_name, stream)
-            .await
-            .map_err(|error| WebSocketError::ConnectionFailed(Box::new(io::Error::other(error))))?;
-        Ok(MaybeTlsStream::Rustls(Box::new(stream)))
-    }
-
-    #[cfg(all(not(feature = "rustls"), feature = "native-tls"))]
-    async fn connect_native_tls(
-        host: &str,
-        stream: TcpStream,
-    ) -> Result<MaybeTlsStream, WebSocketError> {
-        let connector = async_native_tls::TlsConnector::new();
-        let stream = connector
-            .connect(host, stream)
-            .await
-            .map_err(|error| WebSocketError::ConnectionFailed(Box::new(io::Error::other(error))))?;
-        Ok(MaybeTlsStream::Native(stream))
-    }
-
     impl WebSocket {
-        fn from_socket(socket: NativeSocket) -> Self {
+        fn from_socket(socket: NativeSocket, redirects: Vec<Url>) -> Self {
             let (sender, receiver) = socket.split();
             let shared = Arc::new(SharedSocket {
                 sender: Mutex::new(sender),
@@ -393,9 +563,19 @@ mod native {
                     inner: Arc::clone(&shared),
                 },
                 receiver: WebSocketReceiver { inner: shared },
+                redirects,
             }
         }
 
+        /// The URLs of the redirects followed while connecting, in order.
+        ///
+        /// Empty unless [`WebSocketConfig::with_max_redirects`] allowed
+        /// redirects and the server sent some.
+        #[must_use]
+        pub fn redirects(&self) -> &[Url] {
+            &self.redirects
+        }
+
         /// Send a websocket message serialized as JSON.
         ///
         /// # Errors
@@ -765,6 +945,14 @@ mod wasm {
     }
 
     impl WebSocket {
+        /// The URLs of the redirects followed while connecting.
+        ///
+        /// Browsers do not follow websocket redirects, so this is always empty.
+        #[must_use]
+        pub const fn redirects(&self) -> &[url::Url] {
+            &[]
+        }
+
         /// Send a websocket message serialized as JSON.
         ///
         /// # Errors
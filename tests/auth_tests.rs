@@ -2,8 +2,22 @@
 
 mod common;
 use common::httpbin_uri;
-use zenwave::auth::{BasicAuth, BearerAuth};
-use zenwave::{Client, client};
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::Poll,
+    time::{Duration, SystemTime},
+};
+
+use futures_util::future;
+use http_kit::{Body, Endpoint, Request, Response, StatusCode, header};
+use zenwave::auth::{
+    ApiKeyAuth, BasicAuth, BearerAuth, Credential, EnvCredential, FileCredential,
+    RefreshableBearerAuth,
+};
+use zenwave::{Client, Error, ResponseExt, client};
 
 #[test_executors::async_test]
 async fn test_bearer_auth_middleware() {
@@ -62,6 +76,33 @@ async fn test_basic_auth_request_builder() {
     assert!(response.status().is_success());
 }
 
+#[test_executors::async_test]
+async fn test_basic_auth_from_uri_userinfo() {
+    let mut client = client();
+    let with_userinfo = |userinfo: &str, path: &str| {
+        httpbin_uri(path).replacen("://", &format!("://{userinfo}@"), 1)
+    };
+
+    let response = client
+        .get(with_userinfo("user", "/basic-auth/user/"))
+        .unwrap()
+        .await;
+    assert!(response.unwrap().status().is_success());
+
+    let response = client
+        .get(with_userinfo("user:pass", "/basic-auth/user/pass"))
+        .unwrap()
+        .await;
+    assert!(response.unwrap().status().is_success());
+
+    // Percent-encoded credentials are decoded before they are sent.
+    let response = client
+        .get(with_userinfo("us%40er:p%3Ass", "/basic-auth/us@er/p:ss"))
+        .unwrap()
+        .await;
+    assert!(response.unwrap().status().is_success());
+}
+
 #[test_executors::async_test]
 async fn test_basic_auth_no_password() {
     let mut client = client();
@@ -214,3 +255,337 @@ async fn test_invalid_basic_auth() {
         "error should mention 401 status: {description}"
     );
 }
+
+#[test_executors::async_test]
+async fn test_api_key_in_custom_header() {
+    let mut client = client().api_key_auth(ApiKeyAuth::header("x-api-key", "key-123").unwrap());
+
+    let body = client
+        .get(httpbin_uri("/headers"))
+        .unwrap()
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert!(body.contains("x-api-key: key-123"), "{body}");
+}
+
+#[test_executors::async_test]
+async fn test_api_key_in_query() {
+    let mut client = client().api_key_auth(ApiKeyAuth::query("api_key", "a b&c"));
+
+    let query = client
+        .get(httpbin_uri("/query"))
+        .unwrap()
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert_eq!(query.as_str(), "api_key=a+b%26c");
+
+    let query = client
+        .get(httpbin_uri("/query?page=2"))
+        .unwrap()
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert_eq!(query.as_str(), "page=2&api_key=a+b%26c");
+
+    // A key already in the URL is left alone.
+    let query = client
+        .get(httpbin_uri("/query?api_key=other"))
+        .unwrap()
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert_eq!(query.as_str(), "api_key=other");
+}
+
+#[test_executors::async_test]
+async fn test_api_key_with_custom_authorization_scheme() {
+    let mut client = client().api_key_auth(ApiKeyAuth::bearer_like("Token", "key-123").unwrap());
+
+    let body = client
+        .get(httpbin_uri("/headers"))
+        .unwrap()
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert!(body.contains("Authorization: Token key-123"), "{body}");
+
+    // An explicit Authorization header wins.
+    let body = client
+        .get(httpbin_uri("/headers"))
+        .unwrap()
+        .bearer_auth("user-token")
+        .await
+        .unwrap()
+        .into_string()
+        .await
+        .unwrap();
+    assert!(body.contains("Authorization: Bearer user-token"), "{body}");
+    assert!(!body.contains("key-123"), "{body}");
+}
+
+#[test]
+fn test_api_key_rejects_invalid_header_value() {
+    assert!(ApiKeyAuth::header("x-api-key", "line\nbreak").is_err());
+}
+
+/// Endpoint accepting only `Bearer <accepted>`, recording every
+/// `Authorization` header and body it receives. It yields once per request
+/// so concurrent requests interleave.
+#[derive(Clone)]
+struct TokenGate {
+    accepted: &'static str,
+    seen: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl TokenGate {
+    fn new(accepted: &'static str) -> Self {
+        Self {
+            accepted,
+            seen: Arc::default(),
+        }
+    }
+
+    fn seen(&self) -> Vec<(String, String)> {
+        self.seen.lock().unwrap().clone()
+    }
+}
+
+impl Endpoint for TokenGate {
+    type Error = Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let mut yielded = false;
+        future::poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await;
+
+        let auth = request.headers()[header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = request
+            .body_mut()
+            .take()
+            .unwrap()
+            .into_string()
+            .await
+            .unwrap();
+        self.seen
+            .lock()
+            .unwrap()
+            .push((auth.clone(), body.to_string()));
+        let status = if auth == format!("Bearer {}", self.accepted) {
+            StatusCode::OK
+        } else {
+            StatusCode::UNAUTHORIZED
+        };
+        Ok(http::Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .unwrap())
+    }
+}
+
+impl Client for TokenGate {}
+
+/// A refresh callback handing out `token-1`, `token-2`, ... and counting
+/// its calls.
+fn numbered_tokens(
+    calls: &Arc<AtomicUsize>,
+) -> impl Fn() -> future::Ready<Result<String, Error>> + Send + Sync + 'static {
+    let calls = Arc::clone(calls);
+    move || {
+        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        future::ready(Ok(format!("token-{n}")))
+    }
+}
+
+fn post(body: &'static str) -> Request {
+    http::Request::post("https://example.com/items")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[test_executors::async_test]
+async fn test_refreshable_bearer_auth_replays_after_refresh() {
+    let gate = TokenGate::new("token-2");
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut client = gate
+        .clone()
+        .with(RefreshableBearerAuth::new(numbered_tokens(&calls)));
+
+    let response = client.respond(&mut post("payload")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        gate.seen(),
+        [
+            ("Bearer token-1".to_string(), "payload".to_string()),
+            ("Bearer token-2".to_string(), "payload".to_string()),
+        ]
+    );
+
+    // The refreshed token is kept for later requests.
+    client.respond(&mut post("again")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+#[test_executors::async_test]
+async fn test_refreshable_bearer_auth_replays_through_a_real_backend() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut client = zenwave::backend::HyperBackend::new()
+        .with(RefreshableBearerAuth::new(numbered_tokens(&calls)));
+
+    let response = client
+        .get(httpbin_uri("/bearer/token-2"))
+        .unwrap()
+        .await
+        .unwrap();
+    let body = response.into_body().into_string().await.unwrap();
+    assert_eq!(body, "authorized");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test_executors::async_test]
+async fn test_refreshable_bearer_auth_stops_after_max_attempts() {
+    let gate = TokenGate::new("token-2");
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut client = gate
+        .clone()
+        .with(RefreshableBearerAuth::new(numbered_tokens(&calls)).with_token("stale"));
+
+    let response = client.respond(&mut post("payload")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        gate.seen(),
+        [
+            ("Bearer stale".to_string(), "payload".to_string()),
+            ("Bearer token-1".to_string(), "payload".to_string()),
+        ]
+    );
+
+    let mut client = gate.clone().with(
+        RefreshableBearerAuth::new(numbered_tokens(&calls))
+            .with_token("stale")
+            .max_refresh_attempts(2),
+    );
+    let response = client.respond(&mut post("payload")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test_executors::async_test]
+async fn test_refreshable_bearer_auth_coalesces_concurrent_refreshes() {
+    let gate = TokenGate::new("token-1");
+    let calls = Arc::new(AtomicUsize::new(0));
+    let auth = RefreshableBearerAuth::new(numbered_tokens(&calls)).with_token("stale");
+    let mut first = gate.clone().with(auth.clone());
+    let mut second = gate.clone().with(auth);
+
+    let mut first_request = post("first");
+    let mut second_request = post("second");
+    let (first, second) = future::join(
+        first.respond(&mut first_request),
+        second.respond(&mut second_request),
+    )
+    .await;
+    assert_eq!(first.unwrap().status(), StatusCode::OK);
+    assert_eq!(second.unwrap().status(), StatusCode::OK);
+    // Both were rejected with the stale token, but only one refresh happened.
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(gate.seen().len(), 4);
+}
+
+#[test_executors::async_test]
+async fn test_refreshable_bearer_auth_surfaces_refresh_errors() {
+    let gate = TokenGate::new("token-1");
+    let mut client = gate.clone().with(RefreshableBearerAuth::new(|| async {
+        Err::<String, _>(Error::InvalidRequest("refresh failed".to_string()))
+    }));
+
+    let error = client.respond(&mut post("payload")).await.unwrap_err();
+    assert!(error.to_string().contains("refresh failed"), "{error}");
+    assert!(gate.seen().is_empty());
+}
+
+#[test_executors::async_test]
+async fn test_file_credential_picks_up_rotated_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("token");
+    let rotate = |token: &str, modified: u64| {
+        std::fs::write(&path, format!("{token}\n")).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+    };
+    rotate("first", 1);
+
+    let gate = TokenGate::new("second");
+    let mut client = gate
+        .clone()
+        .with(BearerAuth::from_provider(FileCredential::new(&path)));
+    client.respond(&mut post("one")).await.unwrap();
+    client.respond(&mut post("two")).await.unwrap();
+    rotate("second", 2);
+    let response = client.respond(&mut post("three")).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        gate.seen()
+            .into_iter()
+            .map(|(auth, _)| auth)
+            .collect::<Vec<_>>(),
+        ["Bearer first", "Bearer first", "Bearer second"]
+    );
+}
+
+#[test_executors::async_test]
+async fn test_basic_auth_from_provider() {
+    let gate = TokenGate::new("unused");
+    let mut client = gate
+        .clone()
+        .with(BasicAuth::from_provider(Credential::new("user:pass")));
+    client.respond(&mut post("payload")).await.unwrap();
+
+    // base64("user:pass")
+    assert_eq!(gate.seen()[0].0, "Basic dXNlcjpwYXNz");
+}
+
+#[test_executors::async_test]
+async fn test_missing_env_credential_fails_the_request() {
+    let gate = TokenGate::new("unused");
+    let mut client = gate
+        .clone()
+        .with(BearerAuth::from_provider(EnvCredential::new(
+            "ZENWAVE_TEST_UNSET_TOKEN",
+        )));
+
+    let error = client.respond(&mut post("payload")).await.unwrap_err();
+    assert!(
+        error.to_string().contains("ZENWAVE_TEST_UNSET_TOKEN"),
+        "{error}"
+    );
+    assert!(gate.seen().is_empty());
+}
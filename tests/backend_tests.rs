@@ -1,13 +1,25 @@
 //! Tests for backend implementations
 
-#[cfg(any(feature = "hyper-backend", feature = "curl-backend"))]
+#[cfg(any(
+    feature = "hyper-backend",
+    feature = "curl-backend",
+    all(target_vendor = "apple", feature = "apple-backend")
+))]
 use http_kit::{Endpoint, Method};
 #[cfg(feature = "hyper-backend")]
 use zenwave::backend::HyperBackend;
 
-#[cfg(any(feature = "hyper-backend", feature = "curl-backend"))]
+#[cfg(any(
+    feature = "hyper-backend",
+    feature = "curl-backend",
+    all(target_vendor = "apple", feature = "apple-backend")
+))]
 mod common;
-#[cfg(any(feature = "hyper-backend", feature = "curl-backend"))]
+#[cfg(any(
+    feature = "hyper-backend",
+    feature = "curl-backend",
+    all(target_vendor = "apple", feature = "apple-backend")
+))]
 use common::httpbin_uri;
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
@@ -27,6 +39,115 @@ fn test_hyper_backend_default() {
     assert!(!format!("{backend:?}").is_empty());
 }
 
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg(feature = "hyper-backend")]
+fn test_hyper_backend_capabilities() {
+    use zenwave::backend::ClientBackend;
+
+    let capabilities = HyperBackend::new().capabilities();
+    assert!(capabilities.streaming_upload);
+    assert!(capabilities.streaming_download);
+    assert_eq!(capabilities.proxy, cfg!(feature = "proxy"));
+    // Only rustls negotiates HTTP/2, and native-tls is used on Apple
+    // platforms when both are enabled.
+    assert_eq!(
+        capabilities.http2,
+        cfg!(all(
+            feature = "rustls",
+            not(all(feature = "native-tls", target_vendor = "apple"))
+        ))
+    );
+    assert!(
+        HyperBackend::new()
+            .http2_prior_knowledge()
+            .capabilities()
+            .http2
+    );
+    assert!(
+        !HyperBackend::new()
+            .http2_prior_knowledge()
+            .with_http1_0(true)
+            .capabilities()
+            .http2
+    );
+}
+
+#[test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+fn test_curl_backend_capabilities() {
+    use zenwave::backend::{ClientBackend, CurlBackend};
+
+    let capabilities = CurlBackend::new().capabilities();
+    assert!(capabilities.proxy);
+    assert!(capabilities.streaming_download);
+}
+
+#[test_executors::async_test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+async fn test_curl_backend_streams_large_responses() {
+    use futures_util::StreamExt;
+    use zenwave::backend::CurlBackend;
+
+    let mut backend = CurlBackend::new();
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/range/4000000"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+    let response = backend.respond(&mut request).await.unwrap();
+
+    let mut body = response.into_body();
+    let (mut chunks, mut len) = (0, 0);
+    while let Some(chunk) = body.next().await {
+        chunks += 1;
+        len += chunk.unwrap().len();
+    }
+    assert_eq!(len, 4_000_000);
+    assert!(chunks > 1, "the body arrived in {chunks} chunk");
+}
+
+#[test_executors::async_test]
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    feature = "hyper-backend",
+    feature = "curl-backend"
+))]
+async fn test_any_backend_variants_send_the_same_request() {
+    use zenwave::{Client, backend::AnyBackend};
+
+    for mut backend in [AnyBackend::hyper(), AnyBackend::curl()] {
+        let response = backend
+            .get(httpbin_uri("/get"))
+            .unwrap()
+            .await
+            .unwrap_or_else(|error| panic!("{backend:?}: {error}"));
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let body = response.into_body().into_string().await.unwrap();
+        assert!(body.contains("\"url\""), "{backend:?}: {body}");
+
+        let error = backend
+            .get(httpbin_uri("/status/404"))
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, zenwave::Error::Http { status, .. } if status == 404));
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+fn test_any_backend_defaults_to_the_default_backend() {
+    use zenwave::backend::{AnyBackend, ClientBackend};
+
+    let backend = AnyBackend::default();
+    assert!(matches!(backend, AnyBackend::Hyper(_)));
+    assert_eq!(
+        backend.capabilities(),
+        zenwave::backend::HyperBackend::new().capabilities()
+    );
+}
+
 #[test_executors::async_test]
 #[cfg(feature = "hyper-backend")]
 async fn test_hyper_backend_get_request() {
@@ -42,6 +163,40 @@ async fn test_hyper_backend_get_request() {
     assert!(response.status().is_success());
 }
 
+/// The address the local test server listens on.
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "hyper-backend", feature = "curl-backend")
+))]
+fn test_server_address() -> std::net::SocketAddr {
+    common::httpbin_base()
+        .trim_start_matches("http://")
+        .parse()
+        .expect("the test server listens on an IP address")
+}
+
+#[test_executors::async_test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+async fn test_hyper_backend_reports_connection_info() {
+    use zenwave::ResponseExt;
+
+    let mut backend = HyperBackend::new();
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/get"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+    let response = backend.respond(&mut request).await.unwrap();
+
+    let info = response
+        .connection_info()
+        .expect("hyper reports connections");
+    assert_eq!(info.peer_addr, Some(test_server_address()));
+    assert!(info.local_addr.is_some_and(|addr| addr.ip().is_loopback()));
+    assert_eq!((info.tls_version, info.alpn.as_deref()), (None, None));
+    assert!(!info.reused);
+}
+
 #[test_executors::async_test]
 #[cfg(feature = "hyper-backend")]
 async fn test_hyper_backend_post_request() {
@@ -102,6 +257,26 @@ async fn test_hyper_backend_http_error_returns_err() {
     );
 }
 
+#[test_executors::async_test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+async fn test_curl_backend_reports_connection_info() {
+    use zenwave::{ResponseExt, backend::CurlBackend};
+
+    let mut backend = CurlBackend::new();
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/get"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+    let response = backend.respond(&mut request).await.unwrap();
+
+    let info = response
+        .connection_info()
+        .expect("curl reports connections");
+    assert_eq!(info.peer_addr, Some(test_server_address()));
+    assert!(info.local_addr.is_some_and(|addr| addr.ip().is_loopback()));
+}
+
 #[test_executors::async_test]
 #[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
 async fn test_curl_backend_http_error_returns_err() {
@@ -121,6 +296,96 @@ async fn test_curl_backend_http_error_returns_err() {
     );
 }
 
+#[test_executors::async_test]
+#[cfg(feature = "hyper-backend")]
+async fn test_hyper_backend_truncates_captured_error_body() {
+    let mut backend = HyperBackend::new().with_error_body_limit(6);
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/status/500"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+
+    let error = backend.respond(&mut request).await.unwrap_err();
+    assert_eq!(error.response_body(), Some("status…"));
+    let zenwave::Error::Http { response, .. } = error else {
+        panic!("expected an HTTP error, got {error:?}");
+    };
+    let body = response.response.into_body().into_string().await.unwrap();
+    assert_eq!(body, "status 500");
+}
+
+#[test_executors::async_test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+async fn test_curl_backend_truncates_captured_error_body() {
+    use zenwave::backend::CurlBackend;
+
+    let mut backend = CurlBackend::new().with_error_body_limit(6);
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/status/500"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+
+    let error = backend.respond(&mut request).await.unwrap_err();
+    assert_eq!(error.response_body(), Some("status…"));
+}
+
+#[test_executors::async_test]
+#[cfg(feature = "hyper-backend")]
+async fn test_hyper_backend_http_error_returns_ok_when_disabled() {
+    let mut backend = HyperBackend::new().with_error_for_status(false);
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/status/404"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+
+    let response = backend
+        .respond(&mut request)
+        .await
+        .expect("404 should be returned as a response");
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[test_executors::async_test]
+#[cfg(all(not(target_arch = "wasm32"), feature = "curl-backend"))]
+async fn test_curl_backend_http_error_returns_ok_when_disabled() {
+    use zenwave::backend::CurlBackend;
+
+    let mut backend = CurlBackend::new().with_error_for_status(false);
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/status/404"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+
+    let response = backend
+        .respond(&mut request)
+        .await
+        .expect("404 should be returned as a response");
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[test_executors::async_test]
+#[cfg(all(target_vendor = "apple", feature = "apple-backend"))]
+async fn test_apple_backend_http_error_returns_ok_when_disabled() {
+    use zenwave::backend::AppleBackend;
+
+    let mut backend = AppleBackend::new().with_error_for_status(false);
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri(httpbin_uri("/status/404"))
+        .body(http_kit::Body::empty())
+        .unwrap();
+
+    let response = backend
+        .respond(&mut request)
+        .await
+        .expect("404 should be returned as a response");
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
 #[cfg_attr(not(target_arch = "wasm32"), test)]
 #[cfg(feature = "hyper-backend")]
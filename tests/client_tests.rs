@@ -65,6 +65,24 @@ async fn test_request_builder_string() {
     assert!(string.contains("httpbin"));
 }
 
+#[test_executors::async_test]
+async fn test_request_builder_append_header() {
+    let mut client = client();
+    let body = client
+        .get(httpbin_uri("/headers"))
+        .unwrap()
+        .header("accept", "text/plain")
+        .unwrap()
+        .append_header("accept", "application/json")
+        .unwrap()
+        .string()
+        .await
+        .unwrap()
+        .to_ascii_lowercase();
+    assert!(body.contains("accept: text/plain"), "{body}");
+    assert!(body.contains("accept: application/json"), "{body}");
+}
+
 #[test_executors::async_test]
 async fn test_request_builder_bytes() {
     let mut client = client();
@@ -74,6 +92,30 @@ async fn test_request_builder_bytes() {
     assert!(!bytes.is_empty());
 }
 
+#[test_executors::async_test]
+async fn test_request_builder_bytes_stream() {
+    use futures_util::StreamExt;
+
+    let mut client = client();
+    let expected = client
+        .get(httpbin_uri("/json"))
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let mut stream = client
+        .get(httpbin_uri("/json"))
+        .unwrap()
+        .bytes_stream()
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        received.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(received, expected);
+}
+
 #[test_executors::async_test]
 async fn test_request_builder_json() {
     use serde_json::Value;
@@ -85,6 +127,37 @@ async fn test_request_builder_json() {
     assert!(json.is_object());
 }
 
+#[test_executors::async_test]
+async fn test_request_builder_json_on_raw_backend() {
+    use serde_json::Value;
+
+    // The backend's own error type converts into `zenwave::Error`, so the
+    // consuming helpers work without the redirect wrapper as well.
+    let mut client = zenwave::raw_client();
+    let json: Value = client
+        .get(httpbin_uri("/json"))
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(json.is_object());
+}
+
+#[test_executors::async_test]
+async fn test_request_builder_json_reports_http_error_with_body() {
+    use serde_json::Value;
+
+    let mut client = client();
+    let error = client
+        .get(httpbin_uri("/status/404"))
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, zenwave::Error::Http { status, .. } if status == 404));
+    assert_eq!(error.response_body(), Some("status 404"));
+}
+
 #[test_executors::async_test]
 async fn test_client_with_middleware() {
     let mut client = client().enable_cookie();
@@ -115,3 +188,84 @@ fn test_invalid_uri() {
     let response = client.get("");
     assert!(response.is_err());
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_try_bearer_auth_rejects_invalid_token() {
+    let mut client = client();
+    let result = client
+        .get(httpbin_uri("/get"))
+        .unwrap()
+        .try_bearer_auth("token\r\nX-Injected: yes");
+    assert!(matches!(result, Err(zenwave::Error::InvalidRequest(_))));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_header_rejects_invalid_value() {
+    let mut client = client();
+    let result = client
+        .get(httpbin_uri("/get"))
+        .unwrap()
+        .header("x-trace", "line\nbreak");
+    assert!(matches!(result, Err(zenwave::Error::InvalidRequest(_))));
+}
+
+#[test_executors::async_test]
+async fn test_if_none_match_gets_not_modified() {
+    use zenwave::ResponseExt;
+
+    let mut client = client();
+    let response = client.get(httpbin_uri("/etag/v1")).unwrap().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let etag = response.etag().unwrap();
+    assert_eq!(etag, "\"v1\"");
+
+    let response = client
+        .get(httpbin_uri("/etag/v1"))
+        .unwrap()
+        .if_none_match(&etag)
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+
+    // A bare tag is quoted before it is sent.
+    let response = client
+        .get(httpbin_uri("/etag/v1"))
+        .unwrap()
+        .if_none_match("v1")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = client
+        .get(httpbin_uri("/etag/v1"))
+        .unwrap()
+        .if_none_match("\"v0\"")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+#[test_executors::async_test]
+async fn test_batch_through_a_real_backend() {
+    let client = zenwave::backend::HyperBackend::new();
+    let requests = (0..3)
+        .map(|page| {
+            http::Request::get(httpbin_uri(&format!("/query?page={page}")))
+                .body(zenwave::Body::empty())
+                .unwrap()
+        })
+        .collect();
+
+    let mut bodies = Vec::new();
+    for result in client.batch(requests).await {
+        let body = result.unwrap().into_body().into_string().await.unwrap();
+        bodies.push(body.to_string());
+    }
+    assert_eq!(bodies, ["page=0", "page=1", "page=2"]);
+}
@@ -7,7 +7,13 @@
 
 #[cfg(not(target_arch = "wasm32"))]
 mod local {
-    use std::{fmt::Write, io::Cursor, thread, time::Duration};
+    use std::{
+        fmt::Write,
+        io::Cursor,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
 
     use base64::Engine as _;
     use base64::engine::general_purpose::STANDARD as BASE64;
@@ -103,6 +109,7 @@ mod local {
                 }
                 text_response(StatusCode(200), body)
             }
+            "/query" => text_response(StatusCode(200), url.query().unwrap_or_default()),
             "/cookies" => {
                 let cookie_header = header_value(request, "cookie").unwrap_or_default();
                 text_response(StatusCode(200), format!("cookies: {cookie_header}"))
@@ -132,15 +139,27 @@ mod local {
             }
             "/html" => text_response(StatusCode(200), "<html><body>not json</body></html>"),
             _ => {
-                if let Some(stripped) = path.strip_prefix("/basic-auth/") {
-                    return handle_basic_auth(request, stripped);
+                if let Some(response) = handle_auth(request, &path) {
+                    return response;
+                }
+                if let Some(response) = handle_site_data(&path) {
+                    return response;
                 }
                 if let Some(stripped) = path.strip_prefix("/cookies/set/") {
                     return handle_set_cookie(stripped);
                 }
+                if let Some(stripped) = path.strip_prefix("/cookies/unauthorized/") {
+                    return handle_set_cookie(stripped).with_status_code(StatusCode(401));
+                }
                 if let Some(stripped) = path.strip_prefix("/status/") {
                     return handle_status(stripped);
                 }
+                if let Some(response) = handle_ranges(request, &path) {
+                    return response;
+                }
+                if let Some(stripped) = path.strip_prefix("/etag/") {
+                    return handle_etag(request, stripped);
+                }
                 if let Some(stripped) = path.strip_prefix("/base64/") {
                     return handle_base64(stripped);
                 }
@@ -155,6 +174,38 @@ mod local {
         }
     }
 
+    /// `/cached` answers with a fresh hit count that may be cached for a
+    /// minute; `/clear-site-data/{directive}` asks to clear that data.
+    fn handle_site_data(path: &str) -> Option<Response<Cursor<Vec<u8>>>> {
+        static HITS: AtomicUsize = AtomicUsize::new(0);
+
+        if path == "/cached" {
+            let hit = HITS.fetch_add(1, Ordering::SeqCst) + 1;
+            let header = Header::from_bytes("Cache-Control", "max-age=60").unwrap();
+            return Some(text_response(StatusCode(200), format!("hit-{hit}")).with_header(header));
+        }
+        let directive = path.strip_prefix("/clear-site-data/")?;
+        let header = Header::from_bytes("Clear-Site-Data", format!("\"{directive}\"")).unwrap();
+        Some(text_response(StatusCode(200), "cleared").with_header(header))
+    }
+
+    fn handle_auth(request: &Request, path: &str) -> Option<Response<Cursor<Vec<u8>>>> {
+        if let Some(credentials) = path.strip_prefix("/basic-auth/") {
+            return Some(handle_basic_auth(request, credentials));
+        }
+        let token = path.strip_prefix("/bearer/")?;
+        Some(handle_bearer(request, token))
+    }
+
+    fn handle_bearer(request: &Request, token: &str) -> Response<Cursor<Vec<u8>>> {
+        if header_value(request, "authorization")
+            .is_some_and(|auth| auth == format!("Bearer {token}"))
+        {
+            return text_response(StatusCode(200), "authorized");
+        }
+        text_response(StatusCode(401), "unauthorized")
+    }
+
     fn handle_basic_auth(request: &Request, path: &str) -> Response<Cursor<Vec<u8>>> {
         let mut parts = path.split('/');
         let user = parts.next().unwrap_or_default();
@@ -191,6 +242,87 @@ mod local {
         text_response(StatusCode(status), format!("status {status}"))
     }
 
+    /// `/range/{len}` honors the requested range; `/skewed-range/{len}`
+    /// ignores where it starts.
+    fn handle_ranges(request: &Request, path: &str) -> Option<Response<Cursor<Vec<u8>>>> {
+        if let Some(len) = path.strip_prefix("/range/") {
+            return Some(handle_range(request, len));
+        }
+        path.strip_prefix("/skewed-range/")
+            .map(|len| handle_skewed_range(request, len))
+    }
+
+    /// Serve `len` deterministic bytes, honoring a single `Range: bytes=a-b`.
+    fn handle_range(request: &Request, len: &str) -> Response<Cursor<Vec<u8>>> {
+        let Ok(len) = len.parse::<usize>() else {
+            return text_response(StatusCode(400), "invalid length");
+        };
+        let body: Vec<u8> = (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect();
+        let etag = Header::from_bytes("ETag", format!("\"range{len}\"")).unwrap();
+        let accept = Header::from_bytes("Accept-Ranges", "bytes").unwrap();
+
+        let Some(range) = header_value(request, "range") else {
+            return bytes_response(StatusCode(200), body)
+                .with_header(etag)
+                .with_header(accept);
+        };
+        let bounds = range.strip_prefix("bytes=").and_then(|spec| {
+            let (start, end) = spec.split_once('-')?;
+            let start: usize = start.parse().ok()?;
+            let end: usize = end.parse().unwrap_or(usize::MAX);
+            Some((start, end.min(len.saturating_sub(1))))
+        });
+        match bounds {
+            Some((start, end)) if start < len && start <= end => {
+                let content_range =
+                    Header::from_bytes("Content-Range", format!("bytes {start}-{end}/{len}"))
+                        .unwrap();
+                bytes_response(StatusCode(206), body[start..=end].to_vec())
+                    .with_header(content_range)
+                    .with_header(etag)
+                    .with_header(accept)
+            }
+            _ => {
+                let content_range =
+                    Header::from_bytes("Content-Range", format!("bytes */{len}")).unwrap();
+                text_response(StatusCode(416), "range not satisfiable").with_header(content_range)
+            }
+        }
+    }
+
+    /// Serve `len` deterministic bytes, but answer any `Range` request with a
+    /// `206` for the whole body, whatever offset was asked for.
+    fn handle_skewed_range(request: &Request, len: &str) -> Response<Cursor<Vec<u8>>> {
+        let Ok(len) = len.parse::<usize>() else {
+            return text_response(StatusCode(400), "invalid length");
+        };
+        let body: Vec<u8> = (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect();
+        if header_value(request, "range").is_none() {
+            return bytes_response(StatusCode(200), body);
+        }
+        let content_range = Header::from_bytes(
+            "Content-Range",
+            format!("bytes 0-{}/{len}", len.saturating_sub(1)),
+        )
+        .unwrap();
+        bytes_response(StatusCode(206), body).with_header(content_range)
+    }
+
+    /// Like httpbin: `304` when `If-None-Match` lists the tag, else `200`.
+    fn handle_etag(request: &Request, etag: &str) -> Response<Cursor<Vec<u8>>> {
+        let quoted = format!("\"{etag}\"");
+        let header = Header::from_bytes("ETag", quoted.as_str()).unwrap();
+        let matches = header_value(request, "if-none-match").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == "*" || tag.trim().trim_start_matches("W/") == quoted)
+        });
+        if matches {
+            return text_response(StatusCode(304), "").with_header(header);
+        }
+        json_response(StatusCode(200), r#"{"etag":"ok"}"#).with_header(header)
+    }
+
     fn handle_base64(data: &str) -> Response<Cursor<Vec<u8>>> {
         BASE64.decode(data).map_or_else(
             |_| text_response(StatusCode(400), "invalid base64"),
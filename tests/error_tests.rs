@@ -96,3 +96,51 @@ async fn test_empty_response_handling() {
     let body_str = body.unwrap();
     assert!(body_str.is_empty());
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_bearer_challenge_on_401() {
+    use zenwave::{Body, Response, ResponseExt, StatusCode, header};
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        header::HeaderValue::from_static(
+            r#"Bearer realm="example", error="invalid_token", error_description="The access token \"abc\" expired""#,
+        ),
+    );
+
+    let error = response.error_for_status_ref().unwrap_err();
+    let challenge = error.auth_challenge().expect("401 carries a challenge");
+    assert_eq!(challenge.scheme(), "Bearer");
+    assert_eq!(challenge.error(), Some("invalid_token"));
+    assert_eq!(
+        challenge.error_description(),
+        Some(r#"The access token "abc" expired"#)
+    );
+    assert_eq!(challenge.param("REALM"), Some("example"));
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn test_auth_challenge_picks_first_of_several() {
+    use zenwave::{Body, Response, ResponseExt, StatusCode, header};
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        header::HeaderValue::from_static(
+            r#"Negotiate YII=, Bearer error="insufficient_scope", scope="read""#,
+        ),
+    );
+    let error = response.error_for_status_ref().unwrap_err();
+    let challenge = error.auth_challenge().unwrap();
+    assert_eq!(challenge.scheme(), "Negotiate");
+    assert_eq!(challenge.error(), None);
+
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    let error = response.error_for_status_ref().unwrap_err();
+    assert!(error.auth_challenge().is_none());
+}
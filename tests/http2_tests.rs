@@ -0,0 +1,375 @@
+#![allow(missing_docs)]
+#![cfg(all(
+    not(target_arch = "wasm32"),
+    feature = "hyper-backend",
+    feature = "rustls"
+))]
+//! HTTP/2 between `HyperBackend` and a local hyper server, negotiated over
+//! TLS or with prior knowledge over cleartext.
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_net::TcpListener;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_rustls::{
+    TlsAcceptor,
+    rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+};
+use futures_util::{StreamExt, stream};
+use http::Version;
+use http_body_util::BodyExt;
+use smol::{Task, spawn};
+use zenwave::{
+    Body, Client, ResponseExt,
+    backend::HyperBackend,
+    cancel::CancelToken,
+    tls::{Certificate, TlsConfig, TlsVersion},
+};
+
+const CA: &[u8] = include_bytes!("fixtures/tls/ca.pem");
+const SERVER: &[u8] = include_bytes!("fixtures/tls/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("fixtures/tls/server.key");
+
+/// Adapts a `futures-io` stream to hyper's I/O traits.
+struct Io<S>(S);
+
+impl<S: AsyncRead + Unpin> hyper::rt::Read for Io<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut chunk = [0; 8192];
+        let len = chunk.len().min(buf.remaining());
+        let read = match Pin::new(&mut self.0).poll_read(cx, &mut chunk[..len]) {
+            Poll::Ready(Ok(read)) => read,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        buf.put_slice(&chunk[..read]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> hyper::rt::Write for Io<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SmolExecutor;
+
+impl<F: Future<Output = ()> + Send + 'static> hyper::rt::Executor<F> for SmolExecutor {
+    fn execute(&self, future: F) {
+        spawn(future).detach();
+    }
+}
+
+/// A server answering every request with the HTTP version it arrived over,
+/// and the trailers of its body in an `x-request-trailers` header.
+///
+/// `/stalled` instead answers with a body that never ends; `dropped` counts
+/// such bodies given up because the client reset their stream.
+struct Server {
+    address: SocketAddr,
+    connections: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+    _task: Task<()>,
+}
+
+/// Counts a stalled body once it is dropped.
+struct DropCounter(Arc<AtomicUsize>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    async fn start(tls: Option<TlsAcceptor>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::clone(&connections);
+        let counter = Arc::clone(&dropped);
+        let task = spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let tls = tls.clone();
+                let dropped = Arc::clone(&counter);
+                spawn(async move {
+                    match tls {
+                        Some(acceptor) => {
+                            let stream = acceptor.accept(stream).await.unwrap();
+                            serve(Io(stream), dropped).await;
+                        }
+                        None => serve(Io(stream), dropped).await,
+                    }
+                })
+                .detach();
+            }
+        });
+        Self {
+            address,
+            connections,
+            dropped,
+            _task: task,
+        }
+    }
+}
+
+async fn serve<S>(io: Io<S>, dropped: Arc<AtomicUsize>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service =
+        hyper::service::service_fn(move |request: http::Request<hyper::body::Incoming>| {
+            let dropped = Arc::clone(&dropped);
+            async move {
+                if request.uri().path() == "/stalled" {
+                    let counter = DropCounter(dropped);
+                    let body = stream::once(async { Ok::<_, Infallible>(b"partial".to_vec()) })
+                        .chain(stream::pending())
+                        .map(move |chunk| {
+                            let _ = &counter;
+                            chunk
+                        });
+                    return Ok(http::Response::new(Body::from_stream(body)));
+                }
+                let version = format!("{:?}", request.version());
+                let collected = request.into_body().collect().await.unwrap();
+                let trailers = collected
+                    .trailers()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, value)| format!("{name}: {}", value.to_str().unwrap()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let response = http::Response::builder()
+                    .header("x-request-trailers", trailers)
+                    .body(Body::from(version))
+                    .unwrap();
+                Ok::<_, Infallible>(response)
+            }
+        });
+    let _ = hyper::server::conn::http2::Builder::new(SmolExecutor)
+        .serve_connection(io, service)
+        .await;
+}
+
+fn acceptor() -> TlsAcceptor {
+    let chain = CertificateDer::pem_slice_iter(SERVER)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+    let mut config = ServerConfig::builder_with_provider(Arc::new(
+        futures_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(chain, key)
+    .unwrap();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    TlsAcceptor::from(Arc::new(config))
+}
+
+#[test]
+fn https_negotiates_http2_and_multiplexes_one_connection() {
+    smol::block_on(async {
+        let server = Server::start(Some(acceptor())).await;
+        let tls = TlsConfig::new()
+            .with_system_roots(false)
+            .with_root_certificate(Certificate::from_pem(CA).unwrap());
+        let mut client = HyperBackend::new().with_tls(tls);
+        let uri = format!("https://{}/", server.address);
+
+        // The first body is still unread while the second request is sent.
+        let first = client.get(&uri).unwrap().await.unwrap();
+        let second = client.get(&uri).unwrap().await.unwrap();
+        assert_eq!(first.version(), Version::HTTP_2);
+        let info = first.connection_info().unwrap().clone();
+        assert_eq!(info.peer_addr, Some(server.address));
+        assert_eq!(info.tls_version, Some(TlsVersion::Tls13));
+        assert_eq!(info.alpn.as_deref(), Some("h2"));
+        assert!(!info.reused);
+        assert!(second.connection_info().unwrap().reused);
+        assert_eq!(second.into_string().await.unwrap(), "HTTP/2.0");
+        assert_eq!(first.into_string().await.unwrap(), "HTTP/2.0");
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn prior_knowledge_speaks_http2_over_cleartext() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        let mut client = HyperBackend::new().http2_prior_knowledge();
+        let uri = format!("http://{}/", server.address);
+
+        for _ in 0..3 {
+            let body = client.get(&uri).unwrap().string().await.unwrap();
+            assert_eq!(body, "HTTP/2.0");
+        }
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn request_trailers_arrive_over_http2() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        let mut client = HyperBackend::new().http2_prior_knowledge();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+
+        let response = client
+            .post(format!("http://{}/", server.address))
+            .unwrap()
+            .bytes_body(b"payload".to_vec())
+            .trailers(trailers)
+            .await
+            .unwrap();
+        assert_eq!(response.version(), Version::HTTP_2);
+        assert_eq!(response.headers()["x-request-trailers"], "grpc-status: 0");
+    });
+}
+
+#[test]
+fn fresh_connections_bypass_the_shared_http2_connection() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        let mut client = HyperBackend::new().http2_prior_knowledge();
+        let uri = format!("http://{}/", server.address);
+
+        let shared = client.get(&uri).unwrap().await.unwrap();
+        let fresh = client.get(&uri).unwrap().fresh_connection().await.unwrap();
+        assert!(!fresh.connection_info().unwrap().reused);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+
+        // The fresh connection is not handed to later requests.
+        let later = client.get(&uri).unwrap().await.unwrap();
+        let info = later.connection_info().unwrap();
+        assert!(info.reused);
+        assert_eq!(
+            info.local_addr,
+            shared.connection_info().unwrap().local_addr
+        );
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+        for response in [shared, fresh, later] {
+            assert_eq!(response.into_string().await.unwrap(), "HTTP/2.0");
+        }
+    });
+}
+
+#[test]
+fn shared_http2_connections_leave_the_host_slot_free() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        // The timeout turns a request stuck waiting for the slot into a
+        // failure instead of a hang.
+        let mut client = HyperBackend::new()
+            .http2_prior_knowledge()
+            .max_connections_per_host(1)
+            .pool_acquire_timeout(Duration::from_secs(5));
+        let uri = format!("http://{}/", server.address);
+
+        // The shared connection stays open while the fresh one is made.
+        let shared = client.get(&uri).unwrap().await.unwrap();
+        let fresh = client.get(&uri).unwrap().fresh_connection().await.unwrap();
+        assert!(!fresh.connection_info().unwrap().reused);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+        for response in [shared, fresh] {
+            assert_eq!(response.into_string().await.unwrap(), "HTTP/2.0");
+        }
+    });
+}
+
+#[test]
+fn cancelling_resets_the_http2_stream_and_keeps_the_connection() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        let mut client = HyperBackend::new().http2_prior_knowledge();
+        let token = CancelToken::new();
+
+        let mut response = client
+            .get(format!("http://{}/stalled", server.address))
+            .unwrap()
+            .with_cancel(token.clone())
+            .await
+            .unwrap();
+        // The response is held, not dropped, so only the token can end it.
+        token.cancel();
+        let read = async { Some(response.body_mut().as_bytes().await) };
+        let timeout = async {
+            smol::Timer::after(Duration::from_secs(5)).await;
+            None
+        };
+        let error = smol::future::or(read, timeout)
+            .await
+            .expect("the body must end once cancelled")
+            .unwrap_err();
+        assert!(error.to_string().contains("cancelled"), "{error}");
+        for _ in 0..100 {
+            if server.dropped.load(Ordering::SeqCst) == 1 {
+                break;
+            }
+            smol::Timer::after(Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.dropped.load(Ordering::SeqCst), 1);
+
+        let later = client
+            .get(format!("http://{}/", server.address))
+            .unwrap()
+            .await
+            .unwrap();
+        assert!(later.connection_info().unwrap().reused);
+        assert_eq!(later.into_string().await.unwrap(), "HTTP/2.0");
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn cleartext_stays_on_http1_by_default() {
+    smol::block_on(async {
+        let server = Server::start(None).await;
+        let mut client = HyperBackend::new();
+        let error = client
+            .get(format!("http://{}/", server.address))
+            .unwrap()
+            .await
+            .unwrap_err();
+        // An HTTP/2-only server cannot answer an HTTP/1.1 request.
+        assert!(matches!(error, zenwave::Error::Transport(_)), "{error:?}");
+    });
+}
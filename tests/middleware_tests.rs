@@ -39,6 +39,93 @@ async fn test_cookie_store_middleware() {
     assert!(body.contains("value"));
 }
 
+#[test_executors::async_test]
+async fn test_cookie_store_keeps_cookies_from_error_responses() {
+    let mut client = client().enable_cookie();
+
+    // A 401 that hands out a CSRF token still fails the request.
+    let error = client
+        .get(httpbin_uri("/cookies/unauthorized/csrf/token123"))
+        .unwrap()
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.get(httpbin_uri("/cookies")).unwrap().await.unwrap();
+    let body = response.into_body().into_string().await.unwrap();
+    assert!(body.contains("csrf=token123"), "{body}");
+}
+
+#[test_executors::async_test]
+async fn test_clear_site_data_empties_the_cookie_jar() {
+    let mut client = client().enable_cookie();
+
+    client
+        .get(httpbin_uri("/cookies/set/session/abc"))
+        .unwrap()
+        .await
+        .unwrap();
+    client
+        .get(httpbin_uri("/clear-site-data/cookies"))
+        .unwrap()
+        .await
+        .unwrap();
+
+    let response = client.get(httpbin_uri("/cookies")).unwrap().await.unwrap();
+    let body = response.into_body().into_string().await.unwrap();
+    assert!(!body.contains("session=abc"), "{body}");
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+#[test_executors::async_test]
+async fn test_clear_site_data_keeps_host_only_cookies_of_other_hosts() {
+    let server: std::net::SocketAddr = common::httpbin_base()
+        .trim_start_matches("http://")
+        .parse()
+        .unwrap();
+    let port = server.port();
+    let mut client = zenwave::backend::HyperBackend::new()
+        .resolve("site-a.test", server)
+        .resolve("site-b.test", server)
+        .enable_cookie();
+    let mut get = async |uri: String| {
+        let response = client.get(uri).unwrap().await.unwrap();
+        response
+            .into_body()
+            .into_string()
+            .await
+            .unwrap()
+            .to_string()
+    };
+
+    get(format!("http://site-a.test:{port}/cookies/set/a/1")).await;
+    get(format!("http://site-b.test:{port}/cookies/set/b/2")).await;
+    get(format!("http://site-b.test:{port}/clear-site-data/cookies")).await;
+
+    let body = get(format!("http://site-a.test:{port}/cookies")).await;
+    assert!(body.contains("a=1"), "{body}");
+    assert!(!body.contains("b=2"), "{body}");
+}
+
+#[test_executors::async_test]
+async fn test_clear_site_data_evicts_cached_responses() {
+    let mut client = client().enable_cache();
+    let mut fetch = async |path: &str| {
+        let response = client.get(httpbin_uri(path)).unwrap().await.unwrap();
+        response
+            .into_body()
+            .into_string()
+            .await
+            .unwrap()
+            .to_string()
+    };
+
+    let first = fetch("/cached").await;
+    assert_eq!(fetch("/cached").await, first);
+    fetch("/clear-site-data/cache").await;
+    assert_ne!(fetch("/cached").await, first);
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
 #[cfg_attr(not(target_arch = "wasm32"), test)]
 fn test_cookie_store_creation() {
@@ -247,3 +334,28 @@ async fn test_enable_cache_serves_cached_response() {
     assert_eq!(first_body.as_str(), "hit-1");
     assert_eq!(hits.load(Ordering::SeqCst), 1, "backend should be hit once");
 }
+
+#[test_executors::async_test]
+async fn test_propagate_trace_sends_traceparent() {
+    use zenwave::trace::TraceContext;
+
+    let context = TraceContext::new([0x4b; 16], [0x01; 8])
+        .unwrap()
+        .with_tracestate("vendor=value");
+    let mut client = client().propagate_trace(context);
+
+    let response = client.get(httpbin_uri("/headers")).unwrap().await.unwrap();
+    let body = response
+        .into_body()
+        .into_string()
+        .await
+        .unwrap()
+        .to_string();
+    let traceparent = body
+        .lines()
+        .find_map(|line| line.strip_prefix("traceparent: "))
+        .expect("traceparent reaches the server");
+    let fields: Vec<&str> = traceparent.split('-').collect();
+    assert_eq!(fields, ["00", &"4b".repeat(16), &"01".repeat(8), "01"]);
+    assert!(body.contains("tracestate: vendor=value"), "{body}");
+}
@@ -0,0 +1,164 @@
+//! Tests for polling a document with `poll::Watcher`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use http::StatusCode;
+use http_kit::{Body, Endpoint, HttpError, Request, Response, header};
+use serde::Deserialize;
+use zenwave::{Client, Error, ResponseExt, poll::Watcher};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct Config {
+    version: u32,
+}
+
+/// Serves `{"version": n}` from a script of responses, one per request; the
+/// last entry repeats. Records the `If-None-Match` of every request.
+#[derive(Clone)]
+struct ConfigServer {
+    script: Arc<Mutex<Vec<Reply>>>,
+    conditions: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+#[derive(Clone, Copy)]
+enum Reply {
+    /// The document with this version, tagged with it as `ETag` if `true`.
+    Version(u32, bool),
+    /// A `503` asking to retry immediately.
+    Unavailable,
+    /// A body that is not a `Config`.
+    Garbage,
+}
+
+impl ConfigServer {
+    fn new(script: &[Reply]) -> Self {
+        Self {
+            script: Arc::new(Mutex::new(script.iter().rev().copied().collect())),
+            conditions: Arc::default(),
+        }
+    }
+
+    fn conditions(&self) -> Vec<Option<String>> {
+        self.conditions.lock().unwrap().clone()
+    }
+}
+
+impl Endpoint for ConfigServer {
+    type Error = Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let condition = request
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .map(|value| value.to_str().unwrap().to_string());
+        self.conditions.lock().unwrap().push(condition.clone());
+        let reply = {
+            let mut script = self.script.lock().unwrap();
+            if script.len() > 1 {
+                script.pop().unwrap()
+            } else {
+                script[0]
+            }
+        };
+
+        let response = match reply {
+            Reply::Version(version, tagged) => {
+                let etag = format!("\"v{version}\"");
+                if tagged && condition.as_deref() == Some(etag.as_str()) {
+                    http::Response::builder().status(StatusCode::NOT_MODIFIED)
+                } else if tagged {
+                    http::Response::builder().header(header::ETAG, etag)
+                } else {
+                    http::Response::builder()
+                }
+                .body(Body::from(format!("{{\"version\": {version}}}")))
+            }
+            Reply::Unavailable => http::Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(header::RETRY_AFTER, "0")
+                .body(Body::empty()),
+            Reply::Garbage => http::Response::builder().body(Body::from("not json")),
+        };
+        let response = response.unwrap();
+        // Like the built-in backends, report 4xx and 5xx responses as errors.
+        if response.status().is_client_error() || response.status().is_server_error() {
+            return response.error_for_status().await;
+        }
+        Ok(response)
+    }
+}
+
+impl Client for ConfigServer {}
+
+fn watch(server: &ConfigServer) -> Watcher<Config> {
+    Watcher::new(
+        server.clone(),
+        "https://config.example/app.json",
+        Duration::from_millis(5),
+    )
+    .unwrap()
+    .jitter(0.0)
+}
+
+#[test_executors::async_test]
+async fn test_watcher_yields_only_changes_using_conditional_requests() {
+    let server = ConfigServer::new(&[
+        Reply::Version(1, true),
+        Reply::Version(1, true),
+        Reply::Version(1, true),
+        Reply::Version(2, true),
+    ]);
+    let mut watcher = watch(&server);
+    assert_eq!(watcher.latest(), None);
+
+    let first = watcher.next().await.unwrap().unwrap();
+    assert_eq!(first.value, Config { version: 1 });
+    assert_eq!(first.etag.as_deref(), Some("\"v1\""));
+    let second = watcher.next().await.unwrap().unwrap();
+    assert_eq!(second.value, Config { version: 2 });
+    assert_eq!(watcher.latest(), Some(Config { version: 2 }));
+
+    let tag = |version: u32| Some(format!("\"v{version}\""));
+    assert_eq!(server.conditions(), [None, tag(1), tag(1), tag(1)]);
+}
+
+#[test_executors::async_test]
+async fn test_watcher_compares_bodies_without_validators() {
+    let server = ConfigServer::new(&[
+        Reply::Version(1, false),
+        Reply::Version(1, false),
+        Reply::Version(1, false),
+        Reply::Version(2, false),
+    ]);
+    let mut watcher = watch(&server);
+
+    assert_eq!(watcher.next().await.unwrap().unwrap().value.version, 1);
+    assert_eq!(watcher.next().await.unwrap().unwrap().value.version, 2);
+    assert_eq!(server.conditions(), [None, None, None, None]);
+}
+
+#[test_executors::async_test]
+async fn test_watcher_survives_errors_and_keeps_the_last_good_value() {
+    let server = ConfigServer::new(&[
+        Reply::Version(1, true),
+        Reply::Unavailable,
+        Reply::Garbage,
+        Reply::Version(1, true),
+        Reply::Version(3, true),
+    ]);
+    let mut watcher = watch(&server).max_backoff(Duration::from_millis(20));
+
+    assert_eq!(watcher.next().await.unwrap().unwrap().value.version, 1);
+    let unavailable = watcher.next().await.unwrap().unwrap_err();
+    assert_eq!(unavailable.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(watcher.next().await.unwrap().is_err());
+    assert_eq!(watcher.latest(), Some(Config { version: 1 }));
+
+    // The unparsable document did not replace the validators of version 1,
+    // so version 1 coming back is a 304 rather than a change.
+    assert_eq!(watcher.next().await.unwrap().unwrap().value.version, 3);
+    assert_eq!(server.conditions().len(), 5);
+}
@@ -0,0 +1,329 @@
+#![allow(missing_docs)]
+#![cfg(all(
+    not(target_arch = "wasm32"),
+    feature = "hyper-backend",
+    feature = "proxy"
+))]
+//! Requests sent by the hyper backend through a tiny local proxy.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use async_net::{TcpListener, TcpStream};
+use futures_util::{AsyncReadExt, AsyncWriteExt, future, io};
+use smol::{Task, spawn};
+use zenwave::{Client, Proxy, backend::HyperBackend};
+
+/// First lines and `Proxy-Authorization` of the requests a proxy received.
+type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// `user:pass`, as sent in `Proxy-Authorization`.
+const CREDENTIALS: &str = "Basic dXNlcjpwYXNz";
+
+/// Read a request or response head, byte by byte so that nothing after it
+/// is consumed.
+async fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await.unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+/// Copy between `client` and `upstream` until both directions ended.
+async fn pipe(client: TcpStream, upstream: TcpStream) {
+    let (mut client_writer, mut upstream_writer) = (client.clone(), upstream.clone());
+    future::join(
+        async {
+            let _ = io::copy(client, &mut upstream_writer).await;
+            let _ = upstream_writer.close().await;
+        },
+        async {
+            let _ = io::copy(upstream, &mut client_writer).await;
+            let _ = client_writer.close().await;
+        },
+    )
+    .await;
+}
+
+/// An HTTP proxy that answers forwarded requests itself with their request
+/// line and tunnels `CONNECT`s. With `auth`, it wants [`CREDENTIALS`].
+async fn http_proxy(auth: bool) -> (SocketAddr, Seen, Task<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let seen = Seen::default();
+    let recorded = Arc::clone(&seen);
+    let task = spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let seen = Arc::clone(&recorded);
+            spawn(async move {
+                let head = read_head(&mut stream).await;
+                let line = head.lines().next().unwrap_or_default().to_string();
+                let authorization = head.lines().find_map(|line| {
+                    let (name, value) = line.split_once(": ")?;
+                    name.eq_ignore_ascii_case("proxy-authorization")
+                        .then(|| value.to_string())
+                });
+                seen.lock()
+                    .unwrap()
+                    .push((line.clone(), authorization.clone()));
+
+                if auth && authorization.as_deref() != Some(CREDENTIALS) {
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n")
+                        .await;
+                    return;
+                }
+                if let Some(target) = line.strip_prefix("CONNECT ") {
+                    let target = target.split(' ').next().unwrap();
+                    let upstream = TcpStream::connect(target).await.unwrap();
+                    stream
+                        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                        .await
+                        .unwrap();
+                    pipe(stream, upstream).await;
+                } else {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{line}",
+                        line.len()
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            })
+            .detach();
+        }
+    });
+    (address, seen, task)
+}
+
+#[test]
+fn plain_http_is_forwarded_in_absolute_form() {
+    smol::block_on(async {
+        let (proxy, seen, _task) = http_proxy(true).await;
+        let proxy = Proxy::builder()
+            .http(format!("http://user:pass@{proxy}"))
+            .build();
+
+        let mut client = zenwave::client_with_proxy(proxy);
+        let body = client
+            .get("http://origin.test/path?q=1")
+            .unwrap()
+            .string()
+            .await
+            .unwrap();
+        assert_eq!(body, "GET http://origin.test/path?q=1 HTTP/1.1");
+        assert_eq!(seen.lock().unwrap()[0].1.as_deref(), Some(CREDENTIALS));
+    });
+}
+
+#[test]
+fn rejected_proxy_credentials_fail_the_tunnel() {
+    smol::block_on(async {
+        let (proxy, seen, _task) = http_proxy(true).await;
+        let proxy = Proxy::builder().https(format!("http://{proxy}")).build();
+
+        let mut client = HyperBackend::with_proxy(proxy);
+        let error = client
+            .get("https://origin.test:8443/")
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("407"), "{error}");
+        assert_eq!(
+            seen.lock().unwrap().clone(),
+            [("CONNECT origin.test:8443 HTTP/1.1".to_string(), None)]
+        );
+    });
+}
+
+#[test]
+fn unsupported_proxy_schemes_are_reported() {
+    smol::block_on(async {
+        let proxy = Proxy::builder().http("ftp://proxy.test:21").build();
+        let mut client = HyperBackend::with_proxy(proxy);
+        let error = client
+            .get("http://origin.test/")
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("unsupported proxy scheme"),
+            "{error}"
+        );
+    });
+}
+
+#[cfg(feature = "rustls")]
+mod tls {
+    use std::sync::Arc;
+
+    use async_net::TcpListener;
+    use futures_rustls::{
+        TlsAcceptor,
+        rustls::{
+            ServerConfig,
+            pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+        },
+    };
+    use futures_util::{AsyncReadExt, AsyncWriteExt};
+    use smol::spawn;
+    use zenwave::{
+        Client, Proxy,
+        backend::HyperBackend,
+        tls::{Certificate, TlsConfig},
+    };
+
+    use super::{CREDENTIALS, http_proxy};
+
+    const CA: &[u8] = include_bytes!("fixtures/tls/ca.pem");
+    const SERVER: &[u8] = include_bytes!("fixtures/tls/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("fixtures/tls/server.key");
+
+    fn acceptor() -> TlsAcceptor {
+        let provider = Arc::new(futures_rustls::rustls::crypto::ring::default_provider());
+        let chain = CertificateDer::pem_slice_iter(SERVER)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap();
+        TlsAcceptor::from(Arc::new(config))
+    }
+
+    #[test]
+    fn https_tunnels_through_connect_and_verifies_the_origin() {
+        smol::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let origin = listener.local_addr().unwrap();
+            let _origin_task = spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = acceptor().accept(stream).await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                    )
+                    .await
+                    .unwrap();
+                stream.close().await.unwrap();
+            });
+            let (proxy, seen, _proxy_task) = http_proxy(true).await;
+            let proxy = Proxy::builder()
+                .https(format!("http://user:pass@{proxy}"))
+                .build();
+            let tls = TlsConfig::new()
+                .with_system_roots(false)
+                .with_root_certificate(Certificate::from_pem(CA).unwrap());
+
+            let mut client = HyperBackend::with_proxy(proxy).with_tls(tls);
+            let body = client
+                .get(format!("https://{origin}/"))
+                .unwrap()
+                .string()
+                .await
+                .unwrap();
+            assert_eq!(body, "hello");
+            assert_eq!(
+                seen.lock().unwrap().clone(),
+                [(
+                    format!("CONNECT {origin} HTTP/1.1"),
+                    Some(CREDENTIALS.to_string())
+                )]
+            );
+        });
+    }
+}
+
+#[cfg(feature = "socks")]
+#[test]
+fn socks5h_leaves_resolution_to_the_proxy() {
+    smol::block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin = listener.local_addr().unwrap();
+        let _origin_task = spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_head(&mut stream).await;
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                )
+                .await
+                .unwrap();
+        });
+
+        // A SOCKS5 proxy that wants `user:pass` and sends every host name
+        // to the loopback interface.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&names);
+        let _proxy_task = spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0; 2];
+            stream.read_exact(&mut greeting).await.unwrap();
+            let mut methods = vec![0; usize::from(greeting[1])];
+            stream.read_exact(&mut methods).await.unwrap();
+            assert!(methods.contains(&2));
+            stream.write_all(&[5, 2]).await.unwrap();
+
+            let mut auth = [0; 2];
+            stream.read_exact(&mut auth).await.unwrap();
+            let mut user = vec![0; usize::from(auth[1])];
+            stream.read_exact(&mut user).await.unwrap();
+            let mut length = [0];
+            stream.read_exact(&mut length).await.unwrap();
+            let mut pass = vec![0; usize::from(length[0])];
+            stream.read_exact(&mut pass).await.unwrap();
+            assert_eq!((&user[..], &pass[..]), (&b"user"[..], &b"pass"[..]));
+            stream.write_all(&[1, 0]).await.unwrap();
+
+            let mut request = [0; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 3]);
+            let mut name = vec![0; usize::from(request[4])];
+            stream.read_exact(&mut name).await.unwrap();
+            let mut port = [0; 2];
+            stream.read_exact(&mut port).await.unwrap();
+            recorded
+                .lock()
+                .unwrap()
+                .push(String::from_utf8(name).unwrap());
+            let upstream = TcpStream::connect(("127.0.0.1", u16::from_be_bytes(port)))
+                .await
+                .unwrap();
+            stream
+                .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            pipe(stream, upstream).await;
+        });
+
+        let proxy = Proxy::builder()
+            .all(format!("socks5h://user:pass@{proxy}"))
+            .build();
+        let mut client = HyperBackend::with_proxy(proxy);
+        let body = client
+            .get(format!("http://origin.test:{}/", origin.port()))
+            .unwrap()
+            .string()
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+        assert_eq!(names.lock().unwrap().clone(), ["origin.test"]);
+    });
+}
@@ -0,0 +1,157 @@
+//! Tests for seekable ranged reads and resumed downloads.
+
+use std::{
+    convert::Infallible,
+    io::SeekFrom,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures_util::io::{AsyncReadExt, AsyncSeekExt};
+use http_kit::{Endpoint, Middleware, Request, Response, middleware::MiddlewareError};
+use zenwave::{Client, client, range::RangedReader};
+
+mod common;
+use common::httpbin_uri;
+
+const LEN: usize = 10_000;
+
+/// Counts the requests that reach the backend.
+#[derive(Clone, Default)]
+struct CountRequests(Arc<AtomicUsize>);
+
+impl Middleware for CountRequests {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+/// Path and `Range` header of a request.
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+type Sent = (String, Option<String>);
+
+/// Records every request sent.
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+#[derive(Clone, Default)]
+struct RecordRequests(Arc<std::sync::Mutex<Vec<Sent>>>);
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+impl Middleware for RecordRequests {
+    type Error = Infallible;
+    async fn handle<E: Endpoint>(
+        &mut self,
+        request: &mut Request,
+        mut next: E,
+    ) -> Result<Response, MiddlewareError<E::Error, Self::Error>> {
+        let range = request
+            .headers()
+            .get(http::header::RANGE)
+            .map(|value| value.to_str().unwrap().to_string());
+        self.0
+            .lock()
+            .unwrap()
+            .push((request.uri().path().to_string(), range));
+        next.respond(request)
+            .await
+            .map_err(MiddlewareError::Endpoint)
+    }
+}
+
+fn expected() -> Vec<u8> {
+    (0..LEN).map(|i| u8::try_from(i % 251).unwrap()).collect()
+}
+
+fn reader(counter: &CountRequests) -> RangedReader<impl Client + use<>> {
+    RangedReader::new(
+        client().with(counter.clone()),
+        httpbin_uri(&format!("/range/{LEN}")),
+    )
+    .unwrap()
+    .chunk_size(1000)
+    .readahead(3)
+}
+
+#[test_executors::async_test]
+async fn sequential_reads_use_readahead() {
+    let counter = CountRequests::default();
+    let mut reader = reader(&counter);
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await.unwrap();
+
+    assert_eq!(data, expected());
+    assert_eq!(reader.content_length(), Some(LEN as u64));
+    // One chunk to start, then 4-chunk readahead: 0-999, 1000-4999, 5000-8999, 9000-9999.
+    assert_eq!(counter.0.load(Ordering::SeqCst), 4);
+}
+
+#[test_executors::async_test]
+async fn seeks_fetch_single_chunks_and_reuse_the_cache() {
+    let counter = CountRequests::default();
+    let mut reader = reader(&counter);
+    let expected = expected();
+
+    reader.seek(SeekFrom::Start(7000)).await.unwrap();
+    let mut buf = vec![0; 500];
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, expected[7000..7500]);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    // Rewinding inside the fetched chunk is served from the cache.
+    reader.seek(SeekFrom::Current(-200)).await.unwrap();
+    reader.read_exact(&mut buf[..200]).await.unwrap();
+    assert_eq!(buf[..200], expected[7300..7500]);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    let end = reader.seek(SeekFrom::End(-100)).await.unwrap();
+    assert_eq!(end, LEN as u64 - 100);
+    let mut tail = Vec::new();
+    reader.read_to_end(&mut tail).await.unwrap();
+    assert_eq!(tail, expected[LEN - 100..]);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+}
+
+#[test_executors::async_test]
+async fn reading_past_the_end_returns_eof() {
+    let counter = CountRequests::default();
+    let mut reader = reader(&counter);
+
+    reader.seek(SeekFrom::Start(LEN as u64 + 50)).await.unwrap();
+    let mut buf = [0; 16];
+    assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+    assert_eq!(reader.content_length(), Some(LEN as u64));
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+#[test_executors::async_test]
+async fn download_restarts_with_the_original_request_after_a_skewed_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("download.bin");
+    std::fs::write(&path, b"stale").unwrap();
+
+    let recorder = RecordRequests::default();
+    let mut client = zenwave::backend::HyperBackend::new().with(recorder.clone());
+    let download = client
+        .get(httpbin_uri(&format!("/skewed-range/{LEN}")))
+        .unwrap()
+        .download_to_path(&path);
+    let report = Box::pin(download).await.unwrap();
+
+    assert_eq!(report.resumed_from, 0);
+    assert_eq!(std::fs::read(&path).unwrap(), expected());
+    let path = format!("/skewed-range/{LEN}");
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        [(path.clone(), Some("bytes=5-".to_string())), (path, None),]
+    );
+}
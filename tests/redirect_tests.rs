@@ -11,7 +11,7 @@ use http_kit::{
     header::{HeaderValue, LOCATION},
 };
 use zenwave::Client;
-use zenwave::redirect::FollowRedirect;
+use zenwave::redirect::{EffectiveUrl, FollowRedirect, FollowRedirectError};
 
 #[derive(Clone, Debug)]
 struct SeenRequest {
@@ -162,3 +162,77 @@ async fn follow_redirect_strips_sensitive_headers_on_host_change() {
     assert_eq!(state.seen[1].uri, "https://example.net/next");
     drop(state);
 }
+
+#[test_executors::async_test]
+async fn follow_redirect_detects_loops_before_the_hop_limit() {
+    let mock = MockClient::with_responses(vec![
+        redirect_response(StatusCode::FOUND, "https://example.com/b"),
+        redirect_response(StatusCode::FOUND, "https://EXAMPLE.com:443/a#again"),
+        ok_response(),
+    ]);
+    let state = mock.state();
+    let mut client = FollowRedirect::new(mock);
+
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri("https://example.com/a")
+        .body(Body::empty())
+        .unwrap();
+
+    let error = client.respond(&mut request).await.unwrap_err();
+    assert!(
+        matches!(error, FollowRedirectError::RedirectLoop { ref url } if url.starts_with("https://example.com/a")),
+        "expected a redirect loop error, got {error:?}"
+    );
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.seen.len(), 2, "loop should be detected on the repeat");
+    drop(state);
+}
+
+#[test_executors::async_test]
+async fn follow_redirect_stops_at_the_configured_limit() {
+    let mock = MockClient::with_responses(vec![
+        redirect_response(StatusCode::FOUND, "https://example.com/b"),
+        redirect_response(StatusCode::FOUND, "https://example.com/c"),
+        ok_response(),
+    ]);
+    let state = mock.state();
+    let mut client = FollowRedirect::new(mock).max_redirects(1);
+
+    let mut request = http::Request::builder()
+        .method(Method::GET)
+        .uri("https://example.com/a")
+        .body(Body::empty())
+        .unwrap();
+
+    let error = client.respond(&mut request).await.unwrap_err();
+    assert!(
+        matches!(error, FollowRedirectError::TooManyRedirects { max: 1 }),
+        "expected the redirect limit, got {error:?}"
+    );
+    assert_eq!(state.lock().unwrap().seen.len(), 2);
+}
+
+#[test_executors::async_test]
+async fn follow_redirect_keeps_the_fragment_of_the_original_url() {
+    let mock = MockClient::with_responses(vec![
+        redirect_response(StatusCode::FOUND, "/landing"),
+        ok_response(),
+    ]);
+    let state = mock.state();
+    let mut client = FollowRedirect::new(mock);
+
+    let response = client
+        .get("https://example.com/start#top")
+        .unwrap()
+        .await
+        .unwrap();
+
+    let effective = response.extensions().get::<EffectiveUrl>().unwrap();
+    assert_eq!(effective.0.as_str(), "https://example.com/landing#top");
+    let state = state.lock().unwrap();
+    assert_eq!(state.seen[0].uri, "https://example.com/start");
+    assert_eq!(state.seen[1].uri, "https://example.com/landing");
+    drop(state);
+}
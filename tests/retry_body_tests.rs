@@ -0,0 +1,157 @@
+//! Tests for how the retry middleware replays request bodies.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    convert::Infallible,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures_util::{StreamExt, stream};
+use http_kit::{Body, Endpoint, Request, Response, utils::Bytes};
+use zenwave::{Client, Error};
+
+/// Allocator that tracks the bytes currently allocated and their peak.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const MIB: usize = 1024 * 1024;
+static CHUNK: [u8; 64 * 1024] = [7; 64 * 1024];
+
+fn streaming_body(total: usize) -> Body {
+    let chunks = total / CHUNK.len();
+    Body::from_stream(
+        stream::repeat_with(|| Ok::<_, Infallible>(Bytes::from_static(&CHUNK))).take(chunks),
+    )
+}
+
+/// Endpoint that reads the whole body, records its length, and fails the
+/// first `failures` attempts with a transport error.
+#[derive(Clone, Default)]
+struct Upload {
+    received: Arc<Mutex<Vec<usize>>>,
+    failures: usize,
+}
+
+impl Endpoint for Upload {
+    type Error = Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let mut body = request.body_mut().take().unwrap();
+        let mut len = 0;
+        while let Some(chunk) = body.next().await {
+            len += chunk.unwrap().len();
+        }
+        let attempt = {
+            let mut received = self.received.lock().unwrap();
+            received.push(len);
+            received.len()
+        };
+        if attempt <= self.failures {
+            return Err(Error::Transport("connection reset".into()));
+        }
+        Ok(Response::new(Body::empty()))
+    }
+}
+
+impl Client for Upload {}
+
+fn put(body: Body) -> Request {
+    http::Request::put("https://example.com/upload")
+        .body(body)
+        .unwrap()
+}
+
+#[test_executors::async_test]
+async fn successful_streaming_upload_is_not_buffered() {
+    let upload = Upload::default();
+    let mut client = upload.clone().retry(3);
+
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    client
+        .respond(&mut put(streaming_body(100 * MIB)))
+        .await
+        .unwrap();
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+
+    assert_eq!(*upload.received.lock().unwrap(), [100 * MIB]);
+    // The replay copy stops at the 1 MiB default.
+    assert!(peak < 8 * MIB, "peak allocation was {peak} bytes");
+}
+
+#[test_executors::async_test]
+async fn small_streaming_body_is_replayed() {
+    let upload = Upload {
+        failures: 2,
+        ..Upload::default()
+    };
+    let mut client = upload.clone().retry(3).min_delay(Duration::from_millis(1));
+
+    client
+        .respond(&mut put(streaming_body(MIB / 2)))
+        .await
+        .unwrap();
+    assert_eq!(*upload.received.lock().unwrap(), [MIB / 2; 3]);
+}
+
+#[test_executors::async_test]
+async fn oversized_streaming_body_is_not_replayed() {
+    let upload = Upload {
+        failures: 1,
+        ..Upload::default()
+    };
+    let mut client = upload
+        .clone()
+        .retry(3)
+        .min_delay(Duration::from_millis(1))
+        .replay_buffer(MIB);
+
+    let error = client
+        .respond(&mut put(streaming_body(2 * MIB)))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::InvalidRequest(message) if message == "body not replayable"),
+        "{error:?}"
+    );
+    assert_eq!(*upload.received.lock().unwrap(), [2 * MIB]);
+}
+
+#[test_executors::async_test]
+async fn buffered_body_is_sent_again() {
+    let upload = Upload {
+        failures: 1,
+        ..Upload::default()
+    };
+    let mut client = upload.clone().retry(3).min_delay(Duration::from_millis(1));
+
+    client
+        .respond(&mut put(Body::from(vec![1; 1000])))
+        .await
+        .unwrap();
+    assert_eq!(*upload.received.lock().unwrap(), [1000, 1000]);
+}
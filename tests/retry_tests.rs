@@ -8,7 +8,11 @@ use std::{
 
 use http::StatusCode;
 use http_kit::{Body, Endpoint, HttpError, Request, Response};
-use zenwave::Client;
+use zenwave::{
+    Client, ResponseExt,
+    error::ErrorKind,
+    retry::{Retry, RetryCause, RetryEventKind},
+};
 
 #[derive(Default)]
 struct MockState {
@@ -101,6 +105,55 @@ async fn retry_middleware_retries_on_error() {
     assert_eq!(attempts, 3);
 }
 
+#[test_executors::async_test]
+async fn retry_records_failed_attempts_on_the_response() {
+    let mock = MockClient::with_results(vec![
+        Err(MockError::NetworkError),
+        Ok(status_response(StatusCode::SERVICE_UNAVAILABLE, Some("0"))),
+        Ok(ok_response()),
+    ]);
+    let mut client = mock.retry(3).min_delay(Duration::from_millis(1));
+
+    let mut request = http::Request::builder()
+        .uri("https://example.com/")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = client.respond(&mut request).await.unwrap();
+    let attempts = response.attempts().expect("retry attaches its attempts");
+    assert_eq!(attempts.total(), 3);
+    let kinds: Vec<_> = attempts.errors().iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [ErrorKind::Other, ErrorKind::Http]);
+    assert_eq!(attempts.last_error(), Some(ErrorKind::Http));
+}
+
+#[test_executors::async_test]
+async fn retry_records_every_attempt_when_retries_run_out() {
+    let mock = MockClient::with_results(vec![
+        Ok(status_response(StatusCode::TOO_MANY_REQUESTS, Some("0"))),
+        Ok(status_response(StatusCode::SERVICE_UNAVAILABLE, Some("0"))),
+        Ok(status_response(StatusCode::SERVICE_UNAVAILABLE, Some("0"))),
+    ]);
+    let mut client = mock.retry(2).min_delay(Duration::from_millis(1));
+
+    let mut request = http::Request::builder()
+        .uri("https://example.com/")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = client.respond(&mut request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let attempts = response.attempts().unwrap();
+    assert_eq!(attempts.total(), 3);
+    assert_eq!(attempts.errors().len(), 3);
+    assert!(
+        attempts
+            .errors()
+            .iter()
+            .all(|(kind, _)| *kind == ErrorKind::Http)
+    );
+}
+
 #[test_executors::async_test]
 async fn retry_middleware_gives_up_after_max_retries() {
     let mock = MockClient::with_results(vec![
@@ -125,3 +178,353 @@ async fn retry_middleware_gives_up_after_max_retries() {
 
     assert_eq!(state.lock().unwrap().attempts, 3); // Initial + 2 retries
 }
+
+fn status_response(status: StatusCode, retry_after: Option<&str>) -> Response {
+    let mut builder = http::Response::builder().status(status);
+    if let Some(value) = retry_after {
+        builder = builder.header(http::header::RETRY_AFTER, value);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[test_executors::async_test]
+async fn retry_middleware_honors_retry_after_on_503() {
+    let mock = MockClient::with_results(vec![
+        Ok(status_response(StatusCode::SERVICE_UNAVAILABLE, Some("1"))),
+        Ok(ok_response()),
+    ]);
+    let state = mock.state();
+
+    let mut client = mock.retry(2).min_delay(Duration::from_millis(1));
+
+    let mut request = http::Request::builder()
+        .uri("https://example.com/")
+        .body(Body::empty())
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    let response = client.respond(&mut request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(state.lock().unwrap().attempts, 2);
+}
+
+#[test_executors::async_test]
+async fn retry_middleware_caps_retry_after_with_max_delay() {
+    let mock = MockClient::with_results(vec![
+        Ok(status_response(StatusCode::TOO_MANY_REQUESTS, Some("120"))),
+        Ok(ok_response()),
+    ]);
+
+    let mut client = mock.retry(1).max_delay(Duration::from_millis(10));
+
+    let mut request = http::Request::builder()
+        .uri("https://example.com/")
+        .body(Body::empty())
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    let response = client.respond(&mut request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test_executors::async_test]
+async fn retry_middleware_returns_unlisted_statuses_immediately() {
+    let mock = MockClient::with_results(vec![
+        Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR, None)),
+        Ok(ok_response()),
+    ]);
+    let state = mock.state();
+
+    let mut client = mock.retry(3).retry_on_status(&[StatusCode::BAD_GATEWAY]);
+
+    let mut request = http::Request::builder()
+        .uri("https://example.com/")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = client.respond(&mut request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(state.lock().unwrap().attempts, 1);
+}
+
+#[test_executors::async_test]
+async fn retry_skips_non_idempotent_requests_by_default() {
+    let mock = MockClient::with_results(vec![Err(MockError::NetworkError), Ok(ok_response())]);
+    let state = mock.state();
+    let mut client = mock.retry(3).min_delay(Duration::from_millis(1));
+
+    let result = client.post("https://example.com/orders").unwrap().await;
+    assert!(matches!(result, Err(MockError::NetworkError)));
+    assert_eq!(state.lock().unwrap().attempts, 1);
+}
+
+#[test_executors::async_test]
+async fn retry_replays_posts_marked_idempotent() {
+    let mock = MockClient::with_results(vec![Err(MockError::NetworkError), Ok(ok_response())]);
+    let state = mock.state();
+    let mut client = mock.retry(3).min_delay(Duration::from_millis(1));
+
+    let response = client
+        .post("https://example.com/orders")
+        .unwrap()
+        .header("idempotency-key", "order-42")
+        .unwrap()
+        .idempotent()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(state.lock().unwrap().attempts, 2);
+}
+
+#[test_executors::async_test]
+async fn retry_non_idempotent_replays_every_method() {
+    let mock = MockClient::with_results(vec![Err(MockError::NetworkError), Ok(ok_response())]);
+    let state = mock.state();
+    let mut client = mock
+        .retry(3)
+        .min_delay(Duration::from_millis(1))
+        .retry_non_idempotent(true);
+
+    let response = client
+        .method(http::Method::PATCH, "https://example.com/orders/42")
+        .unwrap()
+        .await;
+    assert!(response.is_ok());
+    assert_eq!(state.lock().unwrap().attempts, 2);
+}
+
+/// `(kind, attempt, delay)` of every event the retry hook reported.
+type Events = Arc<Mutex<Vec<(RetryEventKind, usize, Duration)>>>;
+
+fn record_events(client: Retry<MockClient>) -> (Retry<MockClient>, Events) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let client = client.on_retry(move |event| {
+        assert_eq!(event.method(), http::Method::GET);
+        assert_eq!(event.uri(), "https://example.com/");
+        sink.lock()
+            .unwrap()
+            .push((event.kind(), event.attempt(), event.delay()));
+    });
+    (client, events)
+}
+
+#[test_executors::async_test]
+async fn on_retry_reports_each_retry_before_sleeping() {
+    let mock = MockClient::with_results(vec![
+        Err(MockError::NetworkError),
+        Ok(status_response(StatusCode::SERVICE_UNAVAILABLE, None)),
+        Ok(ok_response()),
+    ]);
+    let (mut client, events) = record_events(
+        mock.retry(3)
+            .jitter(false)
+            .min_delay(Duration::from_millis(2)),
+    );
+
+    let response = client.get("https://example.com/").unwrap().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (RetryEventKind::Retrying, 1, Duration::from_millis(2)),
+            (RetryEventKind::Retrying, 2, Duration::from_millis(4)),
+        ]
+    );
+}
+
+#[test_executors::async_test]
+async fn on_retry_carries_the_triggering_outcome() {
+    let mock = MockClient::with_results(vec![
+        Err(MockError::NetworkError),
+        Ok(status_response(StatusCode::TOO_MANY_REQUESTS, None)),
+        Ok(ok_response()),
+    ]);
+    let causes = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&causes);
+    let mut client = mock
+        .retry(3)
+        .min_delay(Duration::from_millis(1))
+        .on_retry(move |event| {
+            let cause = match event.cause() {
+                RetryCause::Error(error) => error.to_string(),
+                RetryCause::Status(status) => status.to_string(),
+            };
+            sink.lock().unwrap().push(cause);
+        });
+
+    client.get("https://example.com/").unwrap().await.unwrap();
+    assert_eq!(
+        *causes.lock().unwrap(),
+        ["mock network error", "429 Too Many Requests"]
+    );
+}
+
+#[test_executors::async_test]
+async fn on_retry_marks_the_final_failure() {
+    let mock = MockClient::with_results(vec![
+        Err(MockError::NetworkError),
+        Err(MockError::NetworkError),
+        Err(MockError::NetworkError),
+    ]);
+    let (mut client, events) = record_events(
+        mock.retry(2)
+            .jitter(false)
+            .min_delay(Duration::from_millis(1)),
+    );
+
+    let result = client.get("https://example.com/").unwrap().await;
+    assert!(matches!(result, Err(MockError::NetworkError)));
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (RetryEventKind::Retrying, 1, Duration::from_millis(1)),
+            (RetryEventKind::Retrying, 2, Duration::from_millis(2)),
+            (RetryEventKind::GivingUp, 3, Duration::ZERO),
+        ]
+    );
+}
+
+/// Retries through a real backend, which takes the request apart while
+/// sending it.
+#[cfg(all(not(target_arch = "wasm32"), feature = "hyper-backend"))]
+mod hyper_backend {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener},
+        thread,
+    };
+
+    use std::sync::{Arc, Mutex};
+
+    use http::StatusCode;
+    use http_kit::HttpError;
+    use zenwave::{Client, backend::HyperBackend, retry::RetryEventKind};
+
+    /// Answer one connection per response in `responses`, returning the
+    /// requests that arrived, head and body.
+    fn serve(responses: &[&'static str]) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let responses = responses.to_vec();
+        let server = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut socket, _) = listener.accept().unwrap();
+                    let request = read_request(&mut socket);
+                    socket.write_all(response.as_bytes()).unwrap();
+                    request
+                })
+                .collect()
+        });
+        (address, server)
+    }
+
+    fn read_request(socket: &mut std::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0_u8; 1024];
+        loop {
+            let read = socket.read(&mut buffer).unwrap();
+            assert_ne!(read, 0, "request ended early");
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    return text;
+                }
+            }
+        }
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+
+    #[test_executors::async_test]
+    async fn retries_resend_the_whole_request() {
+        let (address, server) = serve(&[UNAVAILABLE, OK]);
+
+        let mut client = HyperBackend::new().retry(3);
+        let body = client
+            .put(format!("http://{address}/items?page=2"))
+            .unwrap()
+            .header("x-a", "1")
+            .unwrap()
+            .bytes_body(b"payload".to_vec())
+            .string()
+            .await
+            .unwrap();
+        assert_eq!(body, "ok");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert!(
+                request.starts_with("PUT /items?page=2 HTTP/1.1\r\n"),
+                "{request}"
+            );
+            assert!(request.contains("x-a: 1\r\n"), "{request}");
+            assert!(request.ends_with("\r\n\r\npayload"), "{request}");
+        }
+    }
+
+    #[test_executors::async_test]
+    async fn retry_after_statuses_are_retried_until_success() {
+        const TOO_MANY: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (address, server) = serve(&[UNAVAILABLE, TOO_MANY, OK]);
+
+        let mut client = HyperBackend::new().retry(3);
+        let response = client
+            .get(format!("http://{address}/status"))
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body().into_string().await.unwrap(), "ok");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.starts_with("GET /status HTTP/1.1\r\n"))
+        );
+    }
+
+    #[test_executors::async_test]
+    async fn retry_events_report_the_request_sent() {
+        let (address, server) = serve(&[UNAVAILABLE, UNAVAILABLE]);
+        let uri = format!("http://{address}/items");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+
+        let mut client = HyperBackend::new().retry(1).on_retry(move |event| {
+            sink.lock().unwrap().push((
+                event.kind(),
+                event.method().clone(),
+                event.uri().to_string(),
+            ));
+        });
+        let error = client.put(&uri).unwrap().await.unwrap_err();
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+        server.join().unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (RetryEventKind::Retrying, http::Method::PUT, uri.clone()),
+                (RetryEventKind::GivingUp, http::Method::PUT, uri),
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Tests for sharing one client between tasks with `SharedClient`.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use futures_util::future::join_all;
+use http_kit::{Body, Endpoint, Request, Response};
+use zenwave::{Client, Error};
+
+/// Answers every request after a short delay, recording how many were in
+/// flight at once.
+#[derive(Clone, Default)]
+struct Site {
+    hits: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+impl Endpoint for Site {
+    type Error = Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        self.hits.fetch_add(1, Ordering::SeqCst);
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+        async_io::Timer::after(Duration::from_millis(50)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        Ok(Response::new(Body::from(request.uri().path().to_string())))
+    }
+}
+
+impl Client for Site {}
+
+#[test]
+fn clones_send_requests_concurrently_across_tasks_and_threads() {
+    let site = Site::default();
+    let client = site.clone().follow_redirect().into_shared();
+
+    let replies = smol::block_on(join_all((0..8).map(|index| {
+        let client = client.clone();
+        async move {
+            client
+                .get(format!("https://site.example/{index}"))
+                .unwrap()
+                .string()
+                .await
+                .unwrap()
+        }
+    })));
+    assert_eq!(replies[3], "/3");
+    assert_eq!(site.peak.load(Ordering::SeqCst), 8);
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let client = client.clone();
+            scope.spawn(move || {
+                let reply = smol::block_on(async {
+                    client
+                        .head("https://site.example/whoami")
+                        .unwrap()
+                        .string()
+                        .await
+                        .unwrap()
+                });
+                assert_eq!(reply, "/whoami");
+            });
+        }
+    });
+    assert_eq!(site.hits.load(Ordering::SeqCst), 12);
+}
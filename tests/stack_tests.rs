@@ -0,0 +1,326 @@
+//! Pin the behavior of the recommended middleware stack in the scenarios
+//! where the order of the layers matters.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::future;
+use http::StatusCode;
+use http_kit::{
+    Body, Endpoint, HttpError, Request, Response,
+    header::{self, HeaderName, HeaderValue},
+};
+use zenwave::{Client, Error, ResponseExt, auth::RefreshableBearerAuth, stack::Recommended};
+
+const TEAM: HeaderName = HeaderName::from_static("x-team");
+
+/// `(uri, authorization)` of a request that reached the site.
+type Seen = (String, Option<String>);
+
+/// A site whose routes exercise retries, caching, cookies and auth. Like
+/// the built-in backends, it reports 4xx and 5xx responses as errors.
+#[derive(Clone, Default)]
+struct Site {
+    seen: Arc<Mutex<Vec<Seen>>>,
+    flaky_calls: Arc<AtomicUsize>,
+}
+
+impl Site {
+    fn seen(&self) -> Vec<Seen> {
+        self.seen.lock().unwrap().clone()
+    }
+
+    fn hits(&self, path: &str) -> usize {
+        self.seen()
+            .iter()
+            .filter(|(uri, _)| uri.ends_with(path))
+            .count()
+    }
+}
+
+fn response(status: StatusCode) -> http::response::Builder {
+    http::Response::builder().status(status)
+}
+
+impl Endpoint for Site {
+    type Error = Error;
+    async fn respond(&mut self, request: &mut Request) -> Result<Response, Self::Error> {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let authorization = header(header::AUTHORIZATION);
+        let cookie = header(header::COOKIE).unwrap_or_default();
+        let echo = format!(
+            "{} {}",
+            header(header::USER_AGENT).unwrap_or_default(),
+            header(TEAM).unwrap_or_default()
+        );
+        self.seen
+            .lock()
+            .unwrap()
+            .push((request.uri().to_string(), authorization.clone()));
+
+        let response = match (request.uri().host(), request.uri().path()) {
+            (Some("other.example"), _) | (_, "/echo") => response(StatusCode::OK),
+            (_, "/flaky") => {
+                if self.flaky_calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    response(StatusCode::SERVICE_UNAVAILABLE)
+                } else {
+                    response(StatusCode::OK).header(header::CACHE_CONTROL, "max-age=60")
+                }
+            }
+            (_, "/login") => response(StatusCode::FOUND)
+                .header(header::LOCATION, "/home")
+                .header(header::SET_COOKIE, "session=abc"),
+            (_, "/home") if cookie.contains("session=abc") => response(StatusCode::OK),
+            (_, "/private") if authorization.as_deref() == Some("Bearer fresh") => {
+                response(StatusCode::OK).header(header::CACHE_CONTROL, "max-age=60")
+            }
+            (_, "/leave") => {
+                response(StatusCode::FOUND).header(header::LOCATION, "https://other.example/")
+            }
+            (_, "/hang") => future::pending().await,
+            _ => response(StatusCode::UNAUTHORIZED),
+        };
+        let body = if request.uri().path() == "/echo" {
+            echo
+        } else {
+            "body".to_string()
+        };
+        let response = response.body(Body::from(body)).unwrap();
+        if response.status().is_client_error() || response.status().is_server_error() {
+            return Err(response.error_for_status().await.unwrap_err());
+        }
+        Ok(response)
+    }
+}
+
+impl Client for Site {}
+
+/// Hands out `fresh`, counting its calls.
+fn refresh(
+    calls: &Arc<AtomicUsize>,
+) -> impl Fn() -> future::Ready<Result<String, Error>> + Send + Sync + 'static {
+    let calls = Arc::clone(calls);
+    move || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        future::ready(Ok("fresh".to_string()))
+    }
+}
+
+#[test_executors::async_test]
+async fn flaky_backend_is_retried_and_the_recovery_cached() {
+    let site = Site::default();
+    let mut client = Recommended::new().build(site.clone());
+
+    for _ in 0..2 {
+        let response = client
+            .get("https://site.example/flaky")
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    // The 503 was retried, then the 200 served from the cache.
+    assert_eq!(site.hits("/flaky"), 2);
+}
+
+#[test_executors::async_test]
+async fn cookies_set_by_a_redirect_reach_its_target() {
+    let site = Site::default();
+    let mut client = Recommended::new().build(site.clone());
+
+    let response = client
+        .get("https://site.example/login")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(site.hits("/home"), 1);
+}
+
+#[test_executors::async_test]
+async fn rejected_tokens_are_refreshed_and_private_responses_not_cached() {
+    let site = Site::default();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut client = Recommended::new()
+        .auth(RefreshableBearerAuth::new(refresh(&calls)).with_token("stale"))
+        .build(site.clone());
+
+    for _ in 0..2 {
+        let response = client
+            .get("https://site.example/private")
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    // The 401 was not retried, and the authorized response not cached.
+    assert_eq!(
+        site.seen(),
+        [
+            (
+                "https://site.example/private".to_string(),
+                Some("Bearer stale".to_string())
+            ),
+            (
+                "https://site.example/private".to_string(),
+                Some("Bearer fresh".to_string())
+            ),
+            (
+                "https://site.example/private".to_string(),
+                Some("Bearer fresh".to_string())
+            ),
+        ]
+    );
+}
+
+#[test_executors::async_test]
+async fn credentials_stay_on_the_original_origin() {
+    let site = Site::default();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut client = Recommended::new()
+        .auth(RefreshableBearerAuth::new(refresh(&calls)))
+        .build(site.clone());
+
+    client
+        .get("https://site.example/leave")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(
+        site.seen(),
+        [
+            (
+                "https://site.example/leave".to_string(),
+                Some("Bearer fresh".to_string())
+            ),
+            ("https://other.example/".to_string(), None),
+        ]
+    );
+}
+
+#[test_executors::async_test]
+async fn timeout_bounds_the_retries() {
+    let site = Site::default();
+    let mut client = Recommended::new()
+        .timeout(Duration::from_millis(100))
+        .retries(5)
+        .build(site.clone());
+
+    let started = Instant::now();
+    let error = client
+        .get("https://site.example/hang")
+        .unwrap()
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(site.hits("/hang"), 1);
+}
+
+#[test_executors::async_test]
+async fn builder_sends_default_headers_unless_the_request_sets_them() {
+    let mut client = zenwave::builder()
+        .user_agent("app/1.0")
+        .default_header(TEAM, "core")
+        .build_with(Site::default())
+        .unwrap();
+
+    let body = client
+        .get("https://site.example/echo")
+        .unwrap()
+        .string()
+        .await
+        .unwrap();
+    assert_eq!(body, "app/1.0 core");
+    let body = client
+        .get("https://site.example/echo")
+        .unwrap()
+        .header(header::USER_AGENT, "override")
+        .unwrap()
+        .string()
+        .await
+        .unwrap();
+    assert_eq!(body, "override core");
+}
+
+#[test_executors::async_test]
+async fn builder_composes_retries_redirects_cookies_and_timeout() {
+    let site = Site::default();
+    let mut client = zenwave::builder()
+        .timeout(Duration::from_millis(100))
+        .retries(1)
+        .build_with(site.clone())
+        .unwrap();
+
+    let response = client
+        .get("https://site.example/flaky")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(site.hits("/flaky"), 2);
+
+    let response = client
+        .get("https://site.example/login")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(site.hits("/home"), 1);
+
+    let error = client
+        .get("https://site.example/hang")
+        .unwrap()
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[test_executors::async_test]
+async fn builder_can_leave_layers_out() {
+    let site = Site::default();
+    let mut client = zenwave::builder()
+        .max_redirects(0)
+        .retries(0)
+        .cookies(false)
+        .build_with(site.clone())
+        .unwrap();
+
+    let response = client
+        .get("https://site.example/login")
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(site.hits("/home"), 0);
+
+    let error = client
+        .get("https://site.example/flaky")
+        .unwrap()
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(site.hits("/flaky"), 1);
+}
+
+#[test]
+fn builder_reports_invalid_default_headers() {
+    let error = zenwave::builder()
+        .default_header("bad header", "value")
+        .build_with(Site::default())
+        .err()
+        .unwrap();
+    assert!(matches!(error, Error::InvalidRequest(_)), "{error}");
+}
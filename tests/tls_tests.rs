@@ -0,0 +1,288 @@
+#![allow(missing_docs)]
+#![cfg(all(
+    not(target_arch = "wasm32"),
+    feature = "hyper-backend",
+    feature = "rustls",
+    feature = "ws"
+))]
+//! A server with a certificate from a private CA, reached over HTTPS and WSS
+//! with one [`TlsConfig`].
+
+use std::{net::SocketAddr, sync::Arc};
+
+use async_net::TcpListener;
+use async_tungstenite::accept_async;
+use futures_rustls::{
+    TlsAcceptor,
+    rustls::{
+        RootCertStore, ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+        server::WebPkiClientVerifier,
+    },
+};
+use futures_util::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use smol::{Task, spawn};
+use zenwave::{
+    Client,
+    backend::HyperBackend,
+    tls::{Certificate, TlsConfig, TlsError, TlsVersion},
+    websocket::{self, WebSocketConfig},
+};
+
+const CA: &[u8] = include_bytes!("fixtures/tls/ca.pem");
+const SERVER: &[u8] = include_bytes!("fixtures/tls/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("fixtures/tls/server.key");
+
+fn acceptor() -> TlsAcceptor {
+    server_config(false)
+}
+
+/// Like [`acceptor`], but requiring a client certificate issued by the CA.
+fn mutual_acceptor() -> TlsAcceptor {
+    server_config(true)
+}
+
+fn server_config(client_auth: bool) -> TlsAcceptor {
+    let provider = Arc::new(futures_rustls::rustls::crypto::ring::default_provider());
+    let chain = CertificateDer::pem_slice_iter(SERVER)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if client_auth {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(CA).unwrap())
+            .unwrap();
+        builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .unwrap(),
+        )
+    } else {
+        builder.with_no_client_auth()
+    };
+    let config = builder.with_single_cert(chain, key).unwrap();
+    TlsAcceptor::from(Arc::new(config))
+}
+
+/// Answer every HTTPS request with `hello`.
+async fn https_server() -> (SocketAddr, Task<()>) {
+    serve_https(acceptor()).await
+}
+
+async fn serve_https(acceptor: TlsAcceptor) -> (SocketAddr, Task<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let task = spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            // Handshakes rejected by the client end here.
+            let Ok(mut stream) = acceptor.accept(stream).await else {
+                continue;
+            };
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).await.unwrap();
+                assert_ne!(read, 0, "connection closed mid-request");
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                )
+                .await
+                .unwrap();
+            stream.close().await.unwrap();
+        }
+    });
+    (address, task)
+}
+
+/// Echo the first websocket message back.
+async fn wss_server() -> (SocketAddr, Task<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let acceptor = acceptor();
+    let task = spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(stream).await.unwrap();
+        let mut socket = accept_async(stream).await.unwrap();
+        if let Some(Ok(message)) = socket.next().await {
+            socket.send(message).await.unwrap();
+        }
+        let _ = socket.close(None).await;
+    });
+    (address, task)
+}
+
+fn private_ca() -> TlsConfig {
+    TlsConfig::new()
+        .with_system_roots(false)
+        .with_root_certificate(Certificate::from_pem(CA).unwrap())
+}
+
+#[test]
+fn private_ca_is_trusted_over_https_and_wss() {
+    smol::block_on(async {
+        let tls = private_ca();
+        let (https, _https_task) = https_server().await;
+        let (wss, wss_task) = wss_server().await;
+
+        let mut client = HyperBackend::new().with_tls(tls.clone());
+        let body = client
+            .get(format!("https://{https}/"))
+            .unwrap()
+            .string()
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+
+        let socket = websocket::connect_with_config(
+            format!("wss://{wss}/"),
+            WebSocketConfig::default().with_tls(tls),
+        )
+        .await
+        .unwrap();
+        socket.send_text("over tls").await.unwrap();
+        let echoed = socket.recv().await.unwrap().unwrap();
+        assert_eq!(echoed.as_text(), Some("over tls"));
+        let _ = socket.close().await;
+        wss_task.await;
+    });
+}
+
+#[test]
+fn unknown_ca_and_wrong_pins_are_rejected() {
+    smol::block_on(async {
+        let (https, _task) = https_server().await;
+        let url = format!("https://{https}/");
+
+        let error = HyperBackend::new()
+            .with_tls(TlsConfig::new().with_system_roots(false))
+            .get(&url)
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("UnknownIssuer"), "{error}");
+
+        let error = HyperBackend::new()
+            .with_tls(private_ca().with_pinned_public_key([0; 32]))
+            .get(&url)
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("pin"), "{error}");
+
+        let pin = Certificate::from_pem(SERVER).unwrap().public_key_sha256();
+        let response = HyperBackend::new()
+            .with_tls(private_ca().with_pinned_public_key(pin))
+            .get(&url)
+            .unwrap()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+
+        // Without verification only the pin protects the connection.
+        let response = HyperBackend::new()
+            .with_tls(
+                TlsConfig::new()
+                    .with_system_roots(false)
+                    .danger_accept_invalid_certs(true)
+                    .with_pinned_public_key(pin),
+            )
+            .get(&url)
+            .unwrap()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    });
+}
+
+#[test]
+fn builder_applies_pem_roots_and_client_identity() {
+    smol::block_on(async {
+        let (https, _task) = serve_https(mutual_acceptor()).await;
+        let url = format!("https://{https}/");
+        let identity = [SERVER, SERVER_KEY].concat();
+
+        let mut client = HyperBackend::builder()
+            .tls(TlsConfig::new().with_system_roots(false))
+            .add_root_certificate(CA)
+            .identity(&identity)
+            .min_tls_version(TlsVersion::Tls12)
+            .build()
+            .unwrap();
+        let body = client.get(&url).unwrap().string().await.unwrap();
+        assert_eq!(body, "hello");
+
+        // The server turns away clients without a certificate.
+        let mut client = HyperBackend::builder()
+            .add_root_certificate(CA)
+            .build()
+            .unwrap();
+        assert!(client.get(&url).unwrap().await.is_err());
+
+        // Without verification and SNI the identity is still presented.
+        let mut client = HyperBackend::builder()
+            .danger_accept_invalid_certs(true)
+            .tls_sni(false)
+            .identity(&identity)
+            .build()
+            .unwrap();
+        let body = client.get(&url).unwrap().string().await.unwrap();
+        assert_eq!(body, "hello");
+    });
+}
+
+#[test]
+fn builder_reports_misconfiguration() {
+    let error = HyperBackend::builder()
+        .add_root_certificate(b"not a certificate")
+        .build()
+        .unwrap_err();
+    assert!(matches!(error, TlsError::Pem(_)), "{error}");
+
+    // A certificate without its private key is no identity.
+    let error = HyperBackend::builder()
+        .identity(SERVER)
+        .build()
+        .unwrap_err();
+    assert!(error.to_string().contains("PRIVATE KEY"), "{error}");
+
+    let error = HyperBackend::builder()
+        .min_tls_version(TlsVersion::Tls13)
+        .max_tls_version(TlsVersion::Tls12)
+        .build()
+        .unwrap_err();
+    assert!(matches!(error, TlsError::NoVersions), "{error}");
+}
+
+#[test]
+fn resolve_overrides_keep_the_hostname_for_tls() {
+    smol::block_on(async {
+        let (https, _task) = https_server().await;
+        let mut client = HyperBackend::new()
+            .with_tls(private_ca())
+            .resolve("localhost", https)
+            .resolve("elsewhere.test", https);
+
+        let body = client
+            .get("https://localhost/")
+            .unwrap()
+            .string()
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+
+        // The certificate is checked against the URL's host, not the address.
+        let error = client
+            .get("https://elsewhere.test/")
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not valid for name"), "{error}");
+    });
+}
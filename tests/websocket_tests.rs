@@ -382,3 +382,183 @@ async fn attempt_public_echo(url: &str, payload: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+/// A port with nothing listening on it, for now.
+fn unused_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test_executors::async_test]
+async fn connect_with_retry_waits_for_a_late_server() {
+    use std::sync::{Arc, Mutex};
+    use zenwave::retry::{RetryEventKind, RetryPolicy};
+
+    let port = unused_port();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let policy = RetryPolicy::new(5)
+        .min_delay(Duration::from_millis(20))
+        .jitter(false)
+        .on_retry(move |event| {
+            sink.lock().unwrap().push((event.kind(), event.attempt()));
+            // Start listening only once the second attempt has failed.
+            if event.attempt() == 2 {
+                let listener = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+                let listener = TcpListener::try_from(listener).unwrap();
+                spawn(async move {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut ws = accept_async(stream).await.unwrap();
+                    let _ = ws.next().await;
+                })
+                .detach();
+            }
+        });
+
+    let client = zenwave::websocket::connect_with_retry(
+        format!("ws://127.0.0.1:{port}"),
+        WebSocketConfig::default(),
+        policy,
+    )
+    .await
+    .unwrap();
+    client.close().await.unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [(RetryEventKind::Retrying, 1), (RetryEventKind::Retrying, 2)]
+    );
+}
+
+#[test_executors::async_test]
+async fn connect_with_retry_does_not_retry_rejected_handshakes() {
+    use futures_util::AsyncWriteExt;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use zenwave::retry::RetryPolicy;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream
+            .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap();
+    });
+
+    let retries = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&retries);
+    let policy = RetryPolicy::new(3)
+        .min_delay(Duration::from_millis(1))
+        .on_retry(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+    let error = zenwave::websocket::connect_with_retry(
+        format!("ws://{addr}"),
+        WebSocketConfig::default(),
+        policy,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(!error.is_transient(), "{error:?}");
+    assert_eq!(retries.load(Ordering::SeqCst), 0);
+    server.await;
+}
+
+/// Answer the next connection on `listener` with a 302 to `location`.
+async fn redirect_once(listener: TcpListener, location: String) {
+    use futures_util::{AsyncReadExt, AsyncWriteExt};
+
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await.unwrap();
+        assert_ne!(read, 0, "client closed before sending the upgrade request");
+        request.extend_from_slice(&buf[..read]);
+    }
+    let response =
+        format!("HTTP/1.1 302 Found\r\nlocation: {location}\r\ncontent-length: 0\r\n\r\n");
+    stream.write_all(response.as_bytes()).await.unwrap();
+}
+
+// The header callback's error type is tungstenite's.
+#[allow(clippy::result_large_err)]
+#[test_executors::async_test]
+async fn websocket_follows_handshake_redirect() {
+    use async_tungstenite::{
+        accept_hdr_async,
+        tungstenite::handshake::server::{Request, Response},
+    };
+    use http::{HeaderMap, HeaderValue, header::AUTHORIZATION};
+
+    let gateway = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let gateway_addr = gateway.local_addr().unwrap();
+    let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target = format!("ws://{}/feed", backend.local_addr().unwrap());
+
+    let redirect = spawn(redirect_once(gateway, target.clone()));
+    let server = spawn(async move {
+        let (stream, _) = backend.accept().await.unwrap();
+        let mut authorization = None;
+        let mut ws = accept_hdr_async(stream, |request: &Request, response: Response| {
+            authorization = request.headers().get(AUTHORIZATION).cloned();
+            Ok(response)
+        })
+        .await
+        .unwrap();
+        if let Some(Ok(message)) = ws.next().await {
+            ws.send(message).await.unwrap();
+        }
+        authorization
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+    let config = WebSocketConfig::default()
+        .with_max_redirects(1)
+        .with_headers(headers);
+    let client = zenwave::websocket::connect_with_config(format!("ws://{gateway_addr}"), config)
+        .await
+        .unwrap();
+    assert_eq!(
+        client
+            .redirects()
+            .iter()
+            .map(url::Url::as_str)
+            .collect::<Vec<_>>(),
+        [target.as_str()]
+    );
+
+    client.send_text("hello").await.unwrap();
+    let echoed = client.recv().await.unwrap().unwrap();
+    assert_eq!(echoed.as_text(), Some("hello"));
+    client.close().await.unwrap();
+
+    redirect.await;
+    // The redirect crossed to another port, so the credentials were dropped.
+    assert_eq!(server.await, None);
+}
+
+#[test_executors::async_test]
+async fn websocket_does_not_follow_redirects_by_default() {
+    let gateway = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let gateway_addr = gateway.local_addr().unwrap();
+    let redirect = spawn(redirect_once(gateway, "ws://127.0.0.1:1/".to_string()));
+
+    let error = zenwave::websocket::connect(format!("ws://{gateway_addr}"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, WebSocketError::ConnectionFailed(_)),
+        "{error:?}"
+    );
+    redirect.await;
+}